@@ -0,0 +1,26 @@
+//! Stamps the binary with the commit it was built from
+//! (`RASPIBOT_GIT_HASH`), reported in `robot_info`.
+
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let text = text.trim();
+    (output.status.success() && !text.is_empty()).then(|| text.to_string())
+}
+
+fn main() {
+    let hash = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some();
+    println!(
+        "cargo:rustc-env=RASPIBOT_GIT_HASH={}{}",
+        hash,
+        if dirty { "-dirty" } else { "" }
+    );
+    // Rebuilt when HEAD moves or the index changes, not on every build
+    if let Some(dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", dir);
+        println!("cargo:rerun-if-changed={}/index", dir);
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Tuning for the white-tape boundary detector. Distances are fractions of
+/// the frame height, measured up from the bottom edge (the robot's bumper).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundaryConfig {
+    pub enabled: bool,
+    /// Grayscale level above which a pixel counts as tape.
+    pub white_threshold: u8,
+    /// Only the floor below this fraction of the frame height is searched.
+    pub roi_top: f32,
+    /// Width of the forward corridor, as a fraction of the frame width.
+    pub corridor_width: f32,
+    /// Forward motion stops when tape in the corridor is closer than this.
+    pub stop_margin: f32,
+    /// Forward motion is scaled down linearly below this distance.
+    pub slow_margin: f32,
+    /// Estimates older than this block forward motion for autonomous modes.
+    pub max_age_ms: u64,
+}
+
+impl Default for BoundaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            white_threshold: 200,
+            roi_top: 0.4,
+            corridor_width: 0.5,
+            stop_margin: 0.15,
+            slow_margin: 0.35,
+            max_age_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoundaryEstimate {
+    /// Detected tape segments `[x1, y1, x2, y2]` in normalized frame coordinates.
+    pub lines: Vec<[f32; 4]>,
+    /// Distance to the closest tape inside the forward corridor, if any.
+    pub nearest_ahead: Option<f32>,
+    pub taken_unix_ms: u64,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Tuning for the virtual bumper: detections that fill a large part of the
+/// frame are taken to be close, and forward motion backs off from them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BumperConfig {
+    pub enabled: bool,
+    /// Classes that count as obstacles; empty means every class.
+    #[serde(default)]
+    pub classes: Vec<String>,
+    /// Only boxes overlapping this centered fraction of the frame width
+    /// are in the robot's path.
+    pub corridor_width: f32,
+    /// Forward motion is scaled down linearly once a box covers this
+    /// fraction of the frame area.
+    pub slow_fraction: f32,
+    /// Forward motion stops at this fraction.
+    pub stop_fraction: f32,
+}
+
+impl Default for BumperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            classes: Vec::new(),
+            corridor_width: 0.6,
+            slow_fraction: 0.15,
+            stop_fraction: 0.35,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BumperStatus {
+    /// Closest obstacle in the corridor, if any.
+    pub nearest_class: Option<String>,
+    /// Its box area as a fraction of the frame.
+    pub nearest_fraction: f32,
+    /// Multiplier currently applied to forward motion (0.0..=1.0).
+    pub forward_scale: f32,
+}
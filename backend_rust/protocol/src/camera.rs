@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureLockRequest {
+    pub locked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureStatus {
+    /// Auto-exposure and auto-white-balance are frozen at their current values.
+    pub locked: bool,
+    /// False while playing back a recording, which has no exposure to lock.
+    pub supported: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowLightRequest {
+    pub enabled: bool,
+    /// Process frames as grayscale while low light is on.
+    #[serde(default)]
+    pub grayscale: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowLightStatus {
+    pub enabled: bool,
+    pub grayscale: bool,
+    /// `None` when no IR illuminator is wired.
+    pub ir_illuminator: Option<bool>,
+}
+
+/// Manual image controls; one left at `None` stays automatic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageControls {
+    /// Exposure time in microseconds.
+    pub exposure_us: Option<u32>,
+    /// Analogue gain (1.0 = none) on the CSI camera, the driver's gain units
+    /// on V4L2 devices.
+    pub gain: Option<f32>,
+    /// White balance as a colour temperature in kelvin.
+    pub white_balance_k: Option<u32>,
+}
+
+/// `POST /camera/settings`; what is left out keeps its current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettingsRequest {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub fps: Option<i32>,
+    /// Replaces every image control, so one left out goes back to automatic.
+    pub controls: Option<ImageControls>,
+}
+
+/// The video mode and image controls asked for, next to what the camera
+/// delivers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraSettingsStatus {
+    pub width: i32,
+    pub height: i32,
+    pub fps: i32,
+    pub controls: ImageControls,
+    /// Size of the frames coming in; `None` before the first.
+    pub frame_size: Option<[i32; 2]>,
+    pub measured_fps: f64,
+    /// Controls as read back from a V4L2 device; `None` for the CSI camera,
+    /// whose pipeline doesn't report them.
+    pub device_controls: Option<ImageControls>,
+    /// Whether changes reach the open device directly; otherwise the
+    /// GStreamer pipeline is rebuilt, dropping frames for a moment.
+    pub direct_controls: bool,
+}
+
+/// One camera frame, as streamed over Socket.IO.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Frame {
+    pub seq: u64,
+    pub width: i32,
+    pub height: i32,
+    pub captured_unix_ms: u64,
+    /// Base64-encoded JPEG.
+    pub jpeg: String,
+}
+
+/// Query of the MJPEG stream route.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamQuery {
+    /// Frame rate for this client; capped by the server's maximum.
+    #[serde(default)]
+    pub fps: Option<f32>,
+    /// Draw detections, rates and the overlay onto the frames; the server's
+    /// default when unset.
+    #[serde(default)]
+    pub annotate: Option<bool>,
+    /// Language of the class names drawn on annotated frames (`en`, `id`,
+    /// `ja`); English when unset.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+/// Query of the single-frame snapshot route.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotQuery {
+    /// Draw detections, rates and the overlay onto the frame, as on the
+    /// stream; the server's default when unset.
+    #[serde(default)]
+    pub annotated: Option<bool>,
+    /// JPEG quality (1-100); the stream's quality when unset.
+    #[serde(default)]
+    pub quality: Option<i32>,
+    /// Language of the class names drawn on it, as on the stream.
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+/// Pairing quality of the stereo rig.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StereoStats {
+    /// Pairs delivered since start.
+    pub pairs: u64,
+    /// Frames dropped because no frame of the other camera was close enough.
+    pub unpaired: u64,
+    /// Largest skew a pair may have.
+    pub max_skew_ms: f32,
+    /// Over the most recent pairs; `None` before the first.
+    pub mean_skew_ms: Option<f32>,
+    pub worst_skew_ms: Option<f32>,
+}
+
+/// Thresholds of the image quality monitor. Sharpness is measured on a
+/// 320 px wide grayscale copy, so it doesn't depend on the capture size.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageQualityConfig {
+    pub enabled: bool,
+    /// Variance of the Laplacian below which the image counts as blurred.
+    pub min_sharpness: f32,
+    /// Gray-level spread below which an image that isn't dark counts as
+    /// fogged.
+    pub min_contrast: f32,
+    /// Mean gray level (0..=255) below which the image is under-exposed.
+    pub min_brightness: f32,
+    /// Share of blown-out pixels above which it is over-exposed.
+    pub max_clipped: f32,
+    /// Consecutive samples a condition must hold before it is raised, and
+    /// be gone before it is cleared.
+    pub hold_samples: u32,
+}
+
+impl Default for ImageQualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_sharpness: 60.0,
+            min_contrast: 20.0,
+            min_brightness: 40.0,
+            max_clipped: 0.25,
+            hold_samples: 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum QualityIssue {
+    /// Out of focus, smudged lens or motion blur.
+    Blur,
+    /// Washed out: little contrast although there is light.
+    Fog,
+    UnderExposed,
+    OverExposed,
+}
+
+/// Metrics of one sampled frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ImageQuality {
+    /// Variance of the Laplacian; drops as edges soften.
+    pub sharpness: f32,
+    /// Mean gray level, 0..=255.
+    pub brightness: f32,
+    /// Standard deviation of the gray levels.
+    pub contrast: f32,
+    /// Share of pixels at or near full white.
+    pub clipped: f32,
+    /// Brightness histogram in 16 equal bins, as shares of the pixels.
+    pub histogram: Vec<f32>,
+    pub taken_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageQualityStatus {
+    pub enabled: bool,
+    /// The latest sample; `None` before the first.
+    pub latest: Option<ImageQuality>,
+    /// Conditions currently raised.
+    pub issues: Vec<QualityIssue>,
+}
+
+/// A quality condition being raised or cleared, as broadcast to dashboards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct QualityChange {
+    pub issue: QualityIssue,
+    pub active: bool,
+    /// The sample that settled it.
+    pub quality: ImageQuality,
+}
+
+/// What the capture thread is doing with the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureState {
+    /// Opening the camera for the first time.
+    Starting,
+    /// Frames are coming in.
+    Streaming,
+    /// Reads kept failing; the camera is being released and reopened.
+    Reconnecting,
+    /// No camera could be opened; waiting for one to be plugged in, or a
+    /// playback source reached its end.
+    Offline,
+    /// Released on purpose while the robot idles.
+    Suspended,
+}
+
+/// Camera health, served at `/camera/health` and broadcast whenever the
+/// state changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CameraHealth {
+    pub state: CaptureState,
+    /// When the current state was entered.
+    pub since_unix_ms: u64,
+    /// Failed reads since the last good frame.
+    pub consecutive_failures: u32,
+    /// Times the camera was reopened after failing.
+    pub reconnects: u64,
+    pub last_frame_unix_ms: Option<u64>,
+    /// Why the camera last failed, if it ever did.
+    pub last_error: Option<String>,
+    /// The last time the capture pipeline was rebuilt, and why.
+    pub last_restart: Option<CameraRestart>,
+}
+
+/// What made the capture thread rebuild a camera's pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum RestartCause {
+    /// The GStreamer pipeline ended, as it does when the libcamera or
+    /// PipeWire daemon underneath restarts.
+    EndOfStream,
+    /// The pipeline stopped delivering frames without ending.
+    Stalled,
+    /// Reads kept failing.
+    ReadErrors,
+}
+
+/// A camera's pipeline being rebuilt, as broadcast to dashboards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CameraRestart {
+    pub camera: String,
+    pub cause: RestartCause,
+    /// What was seen, for the log.
+    pub detail: String,
+    pub unix_ms: u64,
+}
+
+/// One of the robot's cameras, as listed by `GET /cameras`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CameraSummary {
+    pub id: String,
+    /// The camera behind the unprefixed endpoints (`/stream`, `/snapshot`, ...).
+    pub primary: bool,
+    pub frame_size: Option<[i32; 2]>,
+    pub capture_fps: f64,
+    pub state: CaptureState,
+}
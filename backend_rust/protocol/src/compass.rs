@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Calibration {
+    /// Hard-iron offset subtracted from raw readings.
+    pub offset: [f32; 3],
+    /// Per-axis soft-iron scale applied after the offset.
+    pub scale: [f32; 3],
+}
+
+impl Calibration {
+    pub fn apply(&self, raw: [f32; 3]) -> [f32; 3] {
+        [
+            (raw[0] - self.offset[0]) * self.scale[0],
+            (raw[1] - self.offset[1]) * self.scale[1],
+            (raw[2] - self.offset[2]) * self.scale[2],
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CompassReading {
+    pub raw: [f32; 3],
+    pub heading_deg: f32,
+    pub calibrated: bool,
+    pub calibrating: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrateRequest {
+    #[serde(default = "default_duration")]
+    pub duration_s: f32,
+}
+
+fn default_duration() -> f32 {
+    20.0
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    Camera,
+    Gamepad,
+    Serial,
+}
+
+/// A plugged-in device the robot can use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Device {
+    pub kind: DeviceKind,
+    /// Device node, e.g. `/dev/video2` or `/dev/ttyACM0`.
+    pub path: String,
+    /// Model name reported by the device, when it has one.
+    pub name: Option<String>,
+    /// USB vendor/product ids as hex strings.
+    pub vendor_id: Option<String>,
+    pub product_id: Option<String>,
+    pub connected_unix_ms: u64,
+}
+
+/// A device appearing or going away, as broadcast to dashboards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DeviceChange {
+    pub connected: bool,
+    pub device: Device,
+}
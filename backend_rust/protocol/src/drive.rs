@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+/// Differential-drive command, each side normalized to `-1.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DriveCommand {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl DriveCommand {
+    pub fn new(left: f32, right: f32) -> Self {
+        Self { left, right }
+    }
+
+    /// Splits into (forward, turn) components.
+    pub fn forward_turn(self) -> (f32, f32) {
+        (
+            (self.left + self.right) / 2.0,
+            (self.left - self.right) / 2.0,
+        )
+    }
+
+    pub fn from_forward_turn(forward: f32, turn: f32) -> Self {
+        Self {
+            left: forward + turn,
+            right: forward - turn,
+        }
+    }
+}
+
+/// Joystick or keyboard input on the control namespace, each axis in
+/// `-1.0..=1.0`: positive `forward` drives ahead, positive `turn` turns
+/// clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TeleopInput {
+    pub forward: f32,
+    pub turn: f32,
+}
+
+impl TeleopInput {
+    /// Mixed onto the two sides, scaled back so that neither goes past
+    /// full speed and a full turn at full speed still turns.
+    pub fn to_command(self) -> DriveCommand {
+        let command = DriveCommand::from_forward_turn(
+            self.forward.clamp(-1.0, 1.0),
+            self.turn.clamp(-1.0, 1.0),
+        );
+        let peak = command.left.abs().max(command.right.abs());
+        if peak > 1.0 {
+            DriveCommand::new(command.left / peak, command.right / peak)
+        } else {
+            command
+        }
+    }
+}
+
+/// Answer to a [`TeleopInput`]: what reached the motors after the safety
+/// constraints, or why nothing did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TeleopAck {
+    pub sent: Option<DriveCommand>,
+    /// E.g. another source owns the drive, or the robot is charging.
+    pub refused: Option<String>,
+}
+
+/// The control namespace's deadman switch stopped the robot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DeadmanTrip {
+    /// How long the driving client had been silent.
+    pub silent_ms: u64,
+    pub timeout_ms: u64,
+}
+
+/// The emergency stop, served at `/estop` and broadcast as `estop_state`
+/// whenever it is engaged or cleared.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EStopStatus {
+    /// Outputs are dead and drive commands refused until it is cleared.
+    pub engaged: bool,
+    /// What engaged it: `api`, `socket` or `watchdog`.
+    pub source: Option<String>,
+    pub reason: Option<String>,
+    pub since_unix_ms: Option<u64>,
+}
+
+/// Engages the emergency stop (`POST /estop`, the `estop` event).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EStopRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Velocity-scaled steering mixing: the faster the robot drives, the less
+/// it may turn, and the harder it turns, the less it may drive, so speed
+/// runs don't spin out. Curves are `[input, limit]` points in the normalized
+/// units of [`DriveCommand::forward_turn`], sorted by input; the limit is
+/// interpolated between points and held beyond the last.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SteeringMixConfig {
+    pub enabled: bool,
+    /// Largest turn allowed at a forward speed.
+    pub turn_limit: Vec<[f32; 2]>,
+    /// Largest forward speed allowed at a turn rate.
+    pub forward_limit: Vec<[f32; 2]>,
+}
+
+impl Default for SteeringMixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            turn_limit: vec![[0.0, 1.0], [0.5, 0.6], [1.0, 0.3]],
+            forward_limit: vec![[0.0, 1.0], [0.5, 0.7], [1.0, 0.4]],
+        }
+    }
+}
+
+/// A small relative move for fine positioning (`POST /drive/nudge`). The
+/// parts run one after another: rotate, then sideways, then forward.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct NudgeRequest {
+    /// Positive is forward.
+    #[serde(default)]
+    pub forward_m: f32,
+    /// Positive is right. The drive can't strafe, so this turns a quarter
+    /// turn, drives and turns back, which ends up beside the start.
+    #[serde(default)]
+    pub right_m: f32,
+    /// Positive is clockwise.
+    #[serde(default)]
+    pub rotate_deg: f32,
+}
+
+/// How far a nudge went, by the same estimates that stopped it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NudgeResult {
+    pub requested: NudgeRequest,
+    pub moved: NudgeRequest,
+    /// Rotations were measured with the compass rather than estimated
+    /// from wheel speeds.
+    pub compass: bool,
+    /// Rotations were measured with the IMU's heading.
+    #[serde(default)]
+    pub imu: bool,
+    pub elapsed_ms: u64,
+}
+
+/// `POST /drive/velocity`: speeds for the closed-loop drive to hold. Like
+/// raw drive commands, they have to be resent continuously.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct VelocityCommand {
+    /// Forward speed in m/s.
+    pub linear: f32,
+    /// Turn rate in degrees per second, positive clockwise.
+    #[serde(default)]
+    pub angular: f32,
+}
+
+/// What the closed-loop drive is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VelocityStatus {
+    /// `None` when not driving, or the last setpoint expired.
+    pub setpoint: Option<VelocityCommand>,
+    /// Wheel speeds `[left, right]` from the encoders, in m/s; `None`
+    /// without current encoder counts, when duty follows the speed model
+    /// alone.
+    pub measured_mps: Option<[f32; 2]>,
+    /// Heading held while the setpoint has no turn, from the IMU.
+    pub holding_heading_deg: Option<f32>,
+    /// What reached the motors on the last control step.
+    pub sent: Option<DriveCommand>,
+}
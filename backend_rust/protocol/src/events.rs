@@ -0,0 +1,93 @@
+//! Socket.IO event names.
+
+/// Server -> client: full [`StateSnapshot`](crate::state::StateSnapshot), sent first on connect.
+pub const STATE_SNAPSHOT: &str = "state_snapshot";
+/// Client -> server: request a fresh snapshot, answered via ack.
+pub const SYNC: &str = "sync";
+/// Client -> server: replace all overlay primitives (`Vec<OverlayPrimitive>`).
+pub const OVERLAY_SET: &str = "overlay_set";
+/// Client -> server: add or replace (by id) one `OverlayPrimitive`.
+pub const OVERLAY_ADD: &str = "overlay_add";
+/// Client -> server: remove the primitive with this id.
+pub const OVERLAY_REMOVE: &str = "overlay_remove";
+/// Client -> server: remove every primitive.
+pub const OVERLAY_CLEAR: &str = "overlay_clear";
+/// Client -> server: operator dashboard is present (send every ~1 s).
+pub const HEARTBEAT: &str = "heartbeat";
+/// Server -> client: [`PresenceStatus`](crate::presence::PresenceStatus), once per second.
+pub const PRESENCE: &str = "presence";
+/// Server -> client: [`MissionState`](crate::mission::MissionState) after every mode change.
+pub const MISSION_STATE: &str = "mission_state";
+/// Server -> client: [`DetectionSet`](crate::inference::DetectionSet) for every inferred frame.
+pub const DETECTIONS: &str = "detections";
+/// Server -> client: [`VersionOffer`](crate::version::VersionOffer), right after the snapshot.
+pub const API_VERSIONS: &str = "api_versions";
+/// Client -> server: [`VersionRequest`](crate::version::VersionRequest), answered via ack
+/// with a [`VersionSelection`](crate::version::VersionSelection).
+pub const SELECT_API_VERSION: &str = "select_api_version";
+/// Server -> client: [`TelemetryAggregate`](crate::telemetry::TelemetryAggregate), downsampled per stream.
+pub const TELEMETRY: &str = "telemetry";
+/// Server -> client: rate/jitter per loop (`BTreeMap<String, RateStats>`), once per second.
+pub const RATES: &str = "rates";
+/// Server -> client: [`TargetChange`](crate::target::TargetChange) when a target is designated or released.
+pub const TARGET: &str = "target";
+/// Server -> client: [`PowerStatus`](crate::power::PowerStatus) when the robot idles or wakes.
+pub const POWER: &str = "power";
+/// Client -> server: operator input (e.g. gamepad) that should wake the robot from idle.
+pub const ACTIVITY: &str = "activity";
+/// Namespace that only carries [`FRAME`] events, so clients that don't show
+/// video never receive them.
+pub const FRAMES_NAMESPACE: &str = "/frames";
+/// Server -> client on [`FRAMES_NAMESPACE`]: [`Frame`](crate::camera::Frame), the camera image as JPEG.
+pub const FRAME: &str = "frame";
+/// Namespace that only carries [`DETECTIONS`] events, for clients that want
+/// the boxes without the rest of the dashboard traffic.
+pub const DETECTIONS_NAMESPACE: &str = "/detections";
+/// Client -> server: [`DriveCommand`](crate::drive::DriveCommand) from the operator; send it
+/// continuously while driving, the motors stop once commands stop arriving.
+pub const DRIVE: &str = "drive";
+/// Server -> client: [`DeviceChange`](crate::devices::DeviceChange) when a device is plugged or unplugged.
+pub const DEVICE: &str = "device";
+/// Server -> client: [`QualityChange`](crate::camera::QualityChange) when the feed degrades or recovers.
+pub const IMAGE_QUALITY: &str = "image_quality";
+/// Server -> client: [`ThermalFrame`](crate::thermal::ThermalFrame) for every frame of the thermal camera.
+pub const THERMAL: &str = "thermal";
+/// Server -> client: [`CameraHealth`](crate::camera::CameraHealth) when the camera stalls, reconnects or recovers.
+pub const CAMERA_HEALTH: &str = "camera_health";
+/// Server -> client: [`CameraRestart`](crate::camera::CameraRestart) when a camera's pipeline ends
+/// or stalls, or reads keep failing, and it is rebuilt.
+pub const CAMERA_RESTART: &str = "camera_restart";
+/// Server -> client on every namespace: [`RobotInfo`](crate::health::RobotInfo) at startup, on
+/// connect and after an [`ANNOUNCE`].
+pub const ROBOT_INFO: &str = "robot_info";
+/// Client -> server: broadcast [`ROBOT_INFO`] to every client; answered via ack with the same.
+pub const ANNOUNCE: &str = "announce";
+/// Namespace for teleoperation: [`TELEOP`] in, [`DEADMAN`] out. The robot
+/// stops once the client driving goes quiet for the deadman timeout.
+pub const CONTROL_NAMESPACE: &str = "/control";
+/// Client -> server on [`CONTROL_NAMESPACE`]: [`TeleopInput`](crate::drive::TeleopInput), sent at
+/// about 20 Hz while driving; answered via ack with [`TeleopAck`](crate::drive::TeleopAck).
+pub const TELEOP: &str = "teleop";
+/// Server -> client on [`CONTROL_NAMESPACE`]: [`DeadmanTrip`](crate::drive::DeadmanTrip) when the
+/// robot was stopped for lack of input.
+pub const DEADMAN: &str = "deadman";
+/// Client -> server: [`EStopRequest`](crate::drive::EStopRequest), engages the emergency stop;
+/// answered via ack with [`EStopStatus`](crate::drive::EStopStatus).
+pub const ESTOP: &str = "estop";
+/// Client -> server: clears the emergency stop; answered via ack with
+/// [`EStopStatus`](crate::drive::EStopStatus).
+pub const ESTOP_CLEAR: &str = "estop_clear";
+/// Server -> client: [`EStopStatus`](crate::drive::EStopStatus) on connect and whenever the
+/// emergency stop is engaged or cleared.
+pub const ESTOP_STATE: &str = "estop_state";
+/// Server -> client: [`RunTimerStatus`](crate::mission::RunTimerStatus), once per second during an
+/// autonomous run and whenever its phase changes.
+pub const RUN_TIMER: &str = "run_timer";
+/// Client -> server: [`ApprovalDecision`](crate::mission::ApprovalDecision), decides a step a
+/// mission waits at (see [`MISSION_STATE`]); answered via ack with the
+/// [`ApprovalResolution`](crate::mission::ApprovalResolution), or `null` when nothing waits
+/// under that id.
+pub const MISSION_APPROVE: &str = "mission_approve";
+/// Server -> client: [`PlanProgress`](crate::mission::PlanProgress) whenever a mission plan
+/// moves to another step or phase, and a few times a second within a step.
+pub const MISSION_PROGRESS: &str = "mission_progress";
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedObject {
+    pub track_id: u64,
+    pub class: String,
+    pub best_confidence: f32,
+    pub first_seen_unix_ms: u64,
+    pub last_seen_unix_ms: u64,
+    /// URL of the crop taken at the best confidence, if one was saved.
+    pub crop_url: Option<String>,
+}
+
+/// Unique objects of one class, the way the competition scores identification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassEvidence {
+    pub class: String,
+    pub unique_objects: usize,
+    pub best_confidence: f32,
+    pub objects: Vec<TrackedObject>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceQuery {
+    /// Only objects seen within the last `window_s` seconds; all when absent.
+    pub window_s: Option<f64>,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Faults injected into a `faults` test build. Each field is independent;
+/// the default injects nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FaultConfig {
+    /// Drop one of every N captured frames before consumers see it (0 = off).
+    #[serde(default)]
+    pub drop_frame_every: u32,
+    /// The inference worker sleeps this long after every pass, as if the
+    /// model had stalled.
+    #[serde(default)]
+    pub inference_delay_ms: u64,
+    /// The next N compass readings are mirrored (heading off by 180°);
+    /// counts down as they are injected.
+    #[serde(default)]
+    pub compass_outliers: u32,
+    /// Tasks to stop: `camera`, `boundary`, `compass`, `presence`. A killed
+    /// task stays dead until the backend restarts.
+    #[serde(default)]
+    pub kill: Vec<String>,
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GpsFix {
+    pub valid: bool,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude_m: f64,
+    pub satellites: u32,
+    pub hdop: f64,
+    pub speed_mps: f64,
+    /// Course over ground; only meaningful while moving.
+    pub heading_deg: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Geofence {
+    Circle {
+        name: String,
+        lat: f64,
+        lon: f64,
+        radius_m: f64,
+    },
+    /// Vertices as `[lat, lon]` pairs.
+    Polygon { name: String, points: Vec<[f64; 2]> },
+}
+
+impl Geofence {
+    pub fn name(&self) -> &str {
+        match self {
+            Geofence::Circle { name, .. } | Geofence::Polygon { name, .. } => name,
+        }
+    }
+
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match self {
+            Geofence::Circle {
+                lat: c_lat,
+                lon: c_lon,
+                radius_m,
+                ..
+            } => haversine_m(lat, lon, *c_lat, *c_lon) <= *radius_m,
+            Geofence::Polygon { points, .. } => {
+                // Ray casting; fine at arena scale where lat/lon is locally planar
+                let mut inside = false;
+                let mut j = points.len().wrapping_sub(1);
+                for i in 0..points.len() {
+                    let [yi, xi] = points[i];
+                    let [yj, xj] = points[j];
+                    if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GeofenceEvent {
+    Enter { fence: String, lat: f64, lon: f64 },
+    Exit { fence: String, lat: f64, lon: f64 },
+}
+
+pub fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (p1, p2) = (lat1.to_radians(), lat2.to_radians());
+    let dp = (lat2 - lat1).to_radians();
+    let dl = (lon2 - lon1).to_radians();
+    let a = (dp / 2.0).sin().powi(2) + p1.cos() * p2.cos() * (dl / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
@@ -0,0 +1,84 @@
+use crate::camera::CaptureState;
+use serde::{Deserialize, Serialize};
+
+/// Defaults selected by the active build/runtime profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileSettings {
+    pub log_level: String,
+    /// Upper bound on normalized drive speed (0.0..=1.0).
+    pub max_drive_speed: f32,
+    /// Motors stop if no drive command arrives within this window.
+    pub command_timeout_ms: u64,
+    pub stream_jpeg_quality: i32,
+    pub stream_max_fps: u32,
+    /// Whether simulated/replay hardware backends may be used.
+    pub allow_simulation: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+    pub uptime_s: f64,
+    pub profile: String,
+    pub settings: ProfileSettings,
+    pub camera: CaptureState,
+    pub robot: RobotInfo,
+    /// Where the CPU went over the last sampling window; `None` until two
+    /// samples were taken, or where `/proc` has no per-thread times.
+    pub cpu: Option<CpuReport>,
+}
+
+/// CPU use per subsystem and per thread. 100 % is one core kept busy, as in
+/// `top`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuReport {
+    pub window_s: f32,
+    pub cores: usize,
+    /// The whole process.
+    pub total_percent: f32,
+    /// Busiest first.
+    pub subsystems: Vec<SubsystemCpu>,
+    /// Busiest first.
+    pub threads: Vec<ThreadCpu>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsystemCpu {
+    /// `inference`, `capture`, `jpeg`, ...; `runtime` is the async runtime's
+    /// threads, `main` every thread nothing named.
+    pub name: String,
+    pub percent: f32,
+    /// Threads that ran for it in the window.
+    pub threads: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadCpu {
+    pub tid: u32,
+    /// The kernel's name for the thread, as `top -H` shows it.
+    pub name: String,
+    pub subsystem: String,
+    /// Not counting work charged to another subsystem, such as JPEG
+    /// encoding done on a runtime thread.
+    pub percent: f32,
+}
+
+/// Which robot and build the client reached, broadcast as `robot_info`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RobotInfo {
+    pub name: String,
+    pub team: Option<String>,
+    /// Backend version.
+    pub version: String,
+    /// Commit the backend was built from; `unknown` outside a git checkout.
+    pub git_hash: String,
+    pub profile: String,
+    /// Hardware found and optional features built in, e.g. `compass`,
+    /// `thermal` or `sqlite`.
+    pub capabilities: Vec<String>,
+    /// Latest battery reading from the MCU.
+    pub battery_v: Option<f32>,
+    pub uptime_s: f64,
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// One IMU sample, in the chip's axes as mounted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ImuReading {
+    /// Acceleration in m/s², gravity included.
+    pub accel_mps2: [f32; 3],
+    /// Angular rate in degrees per second.
+    pub gyro_dps: [f32; 3],
+    pub roll_deg: f32,
+    pub pitch_deg: f32,
+    /// Clockwise from where the heading was last zeroed, 0..360; not from
+    /// north, which is the compass's job.
+    pub heading_deg: f32,
+    /// Clockwise from magnetic north, 0..360, tilt-compensated; only with a
+    /// calibrated compass.
+    #[serde(default)]
+    pub magnetic_heading_deg: Option<f32>,
+    /// Orientation was fused by the chip itself (BNO055) rather than by the
+    /// backend from the raw rates.
+    pub fused: bool,
+    pub taken_unix_ms: u64,
+}
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+/// ONNX Runtime session tuning, traded off against the control loop's CPU needs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionOptions {
+    /// Threads used inside a single operator.
+    pub intra_threads: usize,
+    /// Threads used across independent graph branches (parallel mode only).
+    pub inter_threads: usize,
+    /// Run independent branches concurrently instead of sequentially.
+    pub parallel_execution: bool,
+    /// Use ONNX Runtime's arena allocator for CPU memory.
+    pub memory_arena: bool,
+    /// Pre-plan allocations from the first run's shapes.
+    pub memory_pattern: bool,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            intra_threads: 4,
+            inter_threads: 1,
+            parallel_execution: false,
+            memory_arena: true,
+            memory_pattern: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InferenceSessionInfo {
+    pub model_path: String,
+    pub loaded: bool,
+    pub options: SessionOptions,
+}
+
+/// The classes the detector reports out of the model's vocabulary, for
+/// open-vocabulary (YOLO-World) models.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionClasses {
+    /// Prompted classes; `None` while the full vocabulary is active.
+    pub classes: Option<Vec<String>>,
+    /// Every class the loaded model can score; empty while it is unloaded.
+    pub vocabulary: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassPrompt {
+    pub classes: Vec<String>,
+}
+
+/// How far behind the camera a detection set is allowed to be before the
+/// controllers acting on it back off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StalenessLimits {
+    /// Up to this age results are used at full gain.
+    pub fresh_ms: u64,
+    /// Gain falls linearly to zero between `fresh_ms` and this age; older
+    /// results stop the controller.
+    pub stale_ms: u64,
+}
+
+impl Default for StalenessLimits {
+    fn default() -> Self {
+        Self {
+            fresh_ms: 150,
+            stale_ms: 600,
+        }
+    }
+}
+
+impl StalenessLimits {
+    pub fn freshness(&self, age_ms: u64) -> Freshness {
+        if age_ms <= self.fresh_ms {
+            Freshness::Fresh
+        } else if age_ms < self.stale_ms {
+            Freshness::Decaying
+        } else {
+            Freshness::Stale
+        }
+    }
+
+    /// Multiplier (0.0..=1.0) for control output driven by results this old.
+    pub fn gain_scale(&self, age_ms: u64) -> f32 {
+        match self.freshness(age_ms) {
+            Freshness::Fresh => 1.0,
+            Freshness::Decaying => {
+                let span = (self.stale_ms - self.fresh_ms) as f32;
+                1.0 - (age_ms - self.fresh_ms) as f32 / span
+            }
+            Freshness::Stale => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Freshness {
+    Fresh,
+    Decaying,
+    Stale,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DetectedObject {
+    pub class: String,
+    /// `class` in the language the client asked for; `None` when no name
+    /// is set for it, so `class` is what to show.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    pub confidence: f32,
+    /// `[x, y, w, h]` in frame pixels.
+    pub bbox: [i32; 4],
+    /// Stable id of the object across sets; `None` for detections too weak
+    /// to start a track of their own.
+    pub track_id: Option<u64>,
+}
+
+/// One frame's detections as published to consumers, tagged with how old
+/// they were when sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DetectionSet {
+    pub seq: u64,
+    /// When the frame the detections came from was captured.
+    pub captured_unix_ms: u64,
+    pub age_ms: u64,
+    pub freshness: Freshness,
+    pub objects: Vec<DetectedObject>,
+}
+
+/// Which crop of a tracked object to serve.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CropPick {
+    /// From the most recent set the object was in.
+    #[default]
+    Latest,
+    /// From the set it was seen in with the highest confidence.
+    Best,
+}
+
+/// Query of the crop route.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CropQuery {
+    pub pick: CropPick,
+}
+
+/// Filters of a detection history query; unset ones match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionQuery {
+    /// Run id; detections outside any run have none.
+    pub session: Option<String>,
+    pub class: Option<String>,
+    /// Zone the box center fell in.
+    pub zone: Option<String>,
+    pub track_id: Option<u64>,
+    /// Capture time range in Unix milliseconds, both ends included.
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    /// At most this many records, newest first.
+    pub limit: Option<u32>,
+}
+
+/// One stored detection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    pub session: Option<String>,
+    /// Detection set the object was published in.
+    pub seq: u64,
+    pub captured_unix_ms: u64,
+    pub class: String,
+    pub confidence: f32,
+    pub track_id: Option<u64>,
+    /// `[x, y, w, h]` in frame pixels.
+    pub bbox: [i32; 4],
+    pub zone: Option<String>,
+}
+
+/// Per-class totals over the detections matching a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassHistory {
+    pub class: String,
+    pub detections: u64,
+    /// Distinct objects, by track id.
+    pub tracks: u64,
+    pub max_confidence: f32,
+    pub first_unix_ms: u64,
+    pub last_unix_ms: u64,
+}
+
+/// Closed-loop confidence threshold: detections that show up in a single set
+/// and never again are most likely false positives, so the threshold rises
+/// while they are frequent and relaxes once they are rare.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveThresholdConfig {
+    pub enabled: bool,
+    /// Bounds the working threshold never leaves.
+    pub min_confidence: f32,
+    pub max_confidence: f32,
+    /// Detection sets between adjustments.
+    pub window: usize,
+    /// Share of transient detections to aim for.
+    pub target_transient: f32,
+    /// No adjustment while the share is within this of the target.
+    pub tolerance: f32,
+    /// Threshold change per adjustment.
+    pub step: f32,
+}
+
+impl Default for AdaptiveThresholdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_confidence: 0.2,
+            max_confidence: 0.6,
+            window: 30,
+            target_transient: 0.1,
+            tolerance: 0.05,
+            step: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveThresholdStatus {
+    pub enabled: bool,
+    /// Threshold the detector currently decodes with.
+    pub confidence: f32,
+    /// Transient share over the last complete window; `None` before the
+    /// first one, or when it held no detections.
+    pub transient_ratio: Option<f32>,
+    /// Detection sets seen in the window in progress.
+    pub window_progress: usize,
+}
@@ -0,0 +1,49 @@
+//! Wire types shared by the Rust backend and Rust clients (ground station).
+//!
+//! Everything the backend sends or accepts over HTTP and Socket.IO is defined
+//! here, so both sides agree on field names at compile time.
+//!
+//! The crate depends on serde alone and builds for `wasm32-unknown-unknown`;
+//! the `wasm` feature exports decoding to the browser. Keep it that way:
+//! anything needing OpenCV, tokio or the filesystem belongs in the backend.
+
+pub mod boundary;
+pub mod bumper;
+pub mod camera;
+pub mod compass;
+pub mod devices;
+pub mod drive;
+pub mod events;
+pub mod evidence;
+pub mod faults;
+pub mod gps;
+pub mod health;
+pub mod imu;
+pub mod inference;
+pub mod locale;
+pub mod logging;
+pub mod mission;
+pub mod network;
+pub mod overlay;
+pub mod pose;
+pub mod power;
+pub mod presence;
+pub mod privacy;
+pub mod range;
+pub mod rates;
+pub mod recording;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod servo;
+pub mod session;
+pub mod settings;
+pub mod state;
+pub mod target;
+pub mod telemetry;
+pub mod thermal;
+pub mod units;
+pub mod version;
+pub mod viewers;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zones;
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Languages class names can be shown in. The judges' interface and the
+/// team don't read the same one.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    En,
+    Id,
+    Ja,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::Id, Locale::Ja];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Id => "id",
+            Locale::Ja => "ja",
+        }
+    }
+
+    /// Reads a language tag such as `ja` or `id-ID`; only the language
+    /// counts. `in` is the old code for Indonesian some platforms still send.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "id" | "in" => Some(Locale::Id),
+            "ja" => Some(Locale::Ja),
+            _ => None,
+        }
+    }
+}
+
+/// A class's display name per language; unset ones fall back to English,
+/// then to the class as the model names it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalizedName {
+    pub en: Option<String>,
+    pub id: Option<String>,
+    pub ja: Option<String>,
+}
+
+impl LocalizedName {
+    pub fn get(&self, locale: Locale) -> Option<&str> {
+        let name = match locale {
+            Locale::En => &self.en,
+            Locale::Id => &self.id,
+            Locale::Ja => &self.ja,
+        };
+        name.as_deref().or(self.en.as_deref())
+    }
+}
+
+/// Display names by model class.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassLabels {
+    pub classes: BTreeMap<String, LocalizedName>,
+}
+
+impl ClassLabels {
+    /// Name of `class` in `locale`; `None` when the class has no names set.
+    pub fn name(&self, class: &str, locale: Locale) -> Option<&str> {
+        self.classes.get(class)?.get(locale)
+    }
+}
+
+/// Query selecting the language of class names in an HTTP response or
+/// stream; English when unset or unknown.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocaleQuery {
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+impl LocaleQuery {
+    pub fn locale(&self) -> Locale {
+        self.locale
+            .as_deref()
+            .and_then(Locale::from_tag)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// Plain-text log lines streamed to a listener on the ground station
+/// (e.g. `nc -lu 9000`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkSink {
+    /// `host:port`
+    pub addr: String,
+    #[serde(default = "default_transport")]
+    pub transport: Transport,
+}
+
+fn default_transport() -> Transport {
+    Transport::Udp
+}
+
+/// Where log events go besides stdout. Changes apply immediately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogSinkConfig {
+    /// Daily rolling files in `file_dir`, the last `file_keep` days kept.
+    #[serde(default = "default_true")]
+    pub file: bool,
+    #[serde(default = "default_file_dir")]
+    pub file_dir: String,
+    #[serde(default = "default_file_keep")]
+    pub file_keep: usize,
+    #[serde(default)]
+    pub journald: bool,
+    #[serde(default)]
+    pub network: Option<NetworkSink>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_file_dir() -> String {
+    "data/logs".to_string()
+}
+
+fn default_file_keep() -> usize {
+    7
+}
+
+impl Default for LogSinkConfig {
+    fn default() -> Self {
+        Self {
+            file: true,
+            file_dir: default_file_dir(),
+            file_keep: default_file_keep(),
+            journald: false,
+            network: None,
+        }
+    }
+}
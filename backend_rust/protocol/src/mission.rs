@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+/// Drive priority of the foreground mode. Background missions above it
+/// preempt the drive; at or below it they only drive while the foreground
+/// doesn't.
+pub const FOREGROUND_PRIORITY: u8 = 100;
+
+/// Foreground operating mode; exactly one runs at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum MissionMode {
+    Idle,
+    Teleop,
+    Autonomous {
+        mission: String,
+    },
+    /// Holding still after a restart; the operator resumes by requesting
+    /// `resume` (or any other mode) explicitly.
+    Paused {
+        resume: Box<MissionMode>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MissionState {
+    #[serde(flatten)]
+    pub mode: MissionMode,
+    pub since_unix_ms: u64,
+    /// Why the last transition happened (operator request, auto-idle, ...).
+    pub reason: String,
+    /// Missions running alongside the foreground mode, highest priority first.
+    #[serde(default)]
+    pub background: Vec<BackgroundMission>,
+    /// Who currently gets drive commands through: `teleop`, `auto:<name>`
+    /// or `background:<name>`; `None` when nothing claims the drive.
+    #[serde(default)]
+    pub drive_owner: Option<String>,
+    /// Critical steps missions are paused at, waiting for the operator;
+    /// oldest first.
+    #[serde(default)]
+    pub approvals: Vec<PendingApproval>,
+}
+
+/// What a mission does at a step that needed approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalAction {
+    /// Carry the step out.
+    #[default]
+    Proceed,
+    /// Leave the step out and carry on with the rest of the mission.
+    Skip,
+    /// End the mission.
+    Abort,
+}
+
+/// A step a mission waits at until the operator decides, or the deadline
+/// passes and `on_timeout` applies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PendingApproval {
+    pub id: u64,
+    pub mission: String,
+    /// The configured step, e.g. `grasp`.
+    pub step: String,
+    /// What the mission is about to do, for the operator to judge.
+    pub detail: String,
+    pub requested_unix_ms: u64,
+    pub deadline_unix_ms: u64,
+    pub on_timeout: ApprovalAction,
+}
+
+/// `POST /mission/approve` or the `mission_approve` event: the operator's
+/// decision on a pending step; just the `id` approves it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApprovalDecision {
+    pub id: u64,
+    #[serde(default)]
+    pub action: ApprovalAction,
+}
+
+/// `POST /mission/approval`: a behavior running outside the backend asks
+/// to carry out `step`; answered with the [`ApprovalAction`] once decided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub mission: String,
+    pub step: String,
+    #[serde(default)]
+    pub detail: String,
+}
+
+/// How a pending step was decided.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApprovalResolution {
+    pub id: u64,
+    pub mission: String,
+    pub step: String,
+    pub action: ApprovalAction,
+    /// `operator`, `timeout` or why the mission ended.
+    pub reason: String,
+}
+
+/// A mission running next to the foreground one, e.g. sentry detection
+/// or extra logging while the robot navigates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BackgroundMission {
+    pub name: String,
+    pub priority: u8,
+    /// Whether the mission moves the robot; only then does it compete for
+    /// the drive.
+    pub drive: bool,
+    pub since_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundRequest {
+    pub name: String,
+    #[serde(default = "default_background_priority")]
+    pub priority: u8,
+    #[serde(default)]
+    pub drive: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+fn default_background_priority() -> u8 {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModeRequest {
+    #[serde(flatten)]
+    pub mode: MissionMode,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Where the autonomous run stands against the competition's time limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum RunPhase {
+    /// Not in autonomous mode.
+    Stopped,
+    Running,
+    /// Inside the warning window before the limit.
+    Warning,
+    /// The limit was hit and the robot sent to idle; stays until the next
+    /// autonomous run starts.
+    Expired,
+}
+
+/// Clock of the current autonomous run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RunTimerStatus {
+    pub phase: RunPhase,
+    /// Mission of the current (or expired) run.
+    pub mission: Option<String>,
+    pub elapsed_s: Option<f32>,
+    /// `None` outside a run or without a limit.
+    pub remaining_s: Option<f32>,
+    /// Longest autonomous run allowed; `None` when unlimited.
+    pub limit_s: Option<f32>,
+    pub warn_before_s: f32,
+}
+
+/// One step of a mission plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum MissionStep {
+    /// Straight ahead, or back for a negative distance.
+    Drive {
+        distance_m: f32,
+        #[serde(default = "default_step_speed")]
+        speed_mps: f32,
+    },
+    /// Turn on the spot to a heading, clockwise. By default the IMU's
+    /// gyro heading, relative to where it was zeroed (the magnetometer
+    /// plays no part); with `magnetic`, from magnetic north, which needs a
+    /// calibrated compass.
+    TurnTo {
+        heading_deg: f32,
+        #[serde(default)]
+        magnetic: bool,
+    },
+    /// Hold still until the detector sees `class`; the step fails after
+    /// `timeout_s`.
+    WaitFor {
+        class: String,
+        #[serde(default)]
+        min_confidence: f32,
+        #[serde(default = "default_wait_timeout")]
+        timeout_s: f32,
+    },
+    /// Point the gimbal, in degrees from center.
+    Servo { pan_deg: f32, tilt_deg: f32 },
+    /// Hold still for a while.
+    Wait { seconds: f32 },
+}
+
+fn default_step_speed() -> f32 {
+    0.2
+}
+
+fn default_wait_timeout() -> f32 {
+    30.0
+}
+
+/// `POST /mission/plan`: steps run in order as autonomous mission `name`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissionPlan {
+    pub name: String,
+    pub steps: Vec<MissionStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PlanPhase {
+    Running,
+    /// Held still mid-step until resumed.
+    Paused,
+    Completed,
+    /// Stopped by the operator, or by leaving the mission's mode.
+    Aborted,
+    /// A step couldn't be carried out; `detail` says why.
+    Failed,
+}
+
+/// Where the running (or last) mission plan is, served at
+/// `GET /mission/plan` and sent as `mission_progress`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PlanProgress {
+    pub mission: String,
+    pub phase: PlanPhase,
+    /// Index of the current step; the number of steps once completed.
+    pub step: usize,
+    pub steps: usize,
+    pub current: Option<MissionStep>,
+    /// How much of the current step is done, for steps that can tell.
+    pub fraction: Option<f32>,
+    /// Why the plan stopped, or what the current step is waiting on.
+    pub detail: Option<String>,
+    pub started_unix_ms: u64,
+    pub updated_unix_ms: u64,
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// A URL the dashboard can be reached at, and the interface it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReachableUrl {
+    pub interface: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    /// Addresses the HTTP server is bound to, e.g. `0.0.0.0:8080`, `[::]:8080`.
+    pub listeners: Vec<String>,
+    /// Resolved against the current interface addresses, so DHCP changes show up.
+    pub urls: Vec<ReachableUrl>,
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// Positions are normalized to the frame (0.0..=1.0 on both axes), so
+/// primitives land in the same place at any stream resolution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OverlayShape {
+    /// Open polyline, e.g. a planned path.
+    Line {
+        points: Vec<[f32; 2]>,
+        #[serde(default = "default_thickness")]
+        thickness: i32,
+    },
+    /// Circle with a crosshair.
+    Reticle { center: [f32; 2], radius: f32 },
+    Text {
+        pos: [f32; 2],
+        text: String,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct OverlayPrimitive {
+    /// Adding a primitive with an existing id replaces it.
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub shape: OverlayShape,
+    /// RGB.
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    /// Removed automatically after this many seconds; kept until cleared if absent.
+    pub ttl_s: Option<f32>,
+}
+
+impl OverlayShape {
+    /// The shape in pixels of a `width` x `height` frame: points scaled and
+    /// rounded, a reticle's radius relative to the shorter side (at least
+    /// one pixel). Every renderer lays shapes out through this.
+    pub fn in_pixels(&self, width: f32, height: f32) -> OverlayShape {
+        let to_px = |p: [f32; 2]| [(p[0] * width).round(), (p[1] * height).round()];
+        match self {
+            OverlayShape::Line { points, thickness } => OverlayShape::Line {
+                points: points.iter().map(|p| to_px(*p)).collect(),
+                thickness: *thickness,
+            },
+            OverlayShape::Reticle { center, radius } => OverlayShape::Reticle {
+                center: to_px(*center),
+                radius: (radius * width.min(height)).round().max(1.0),
+            },
+            OverlayShape::Text { pos, text, scale } => OverlayShape::Text {
+                pos: to_px(*pos),
+                text: text.clone(),
+                scale: *scale,
+            },
+        }
+    }
+}
+
+fn default_thickness() -> i32 {
+    2
+}
+
+fn default_scale() -> f32 {
+    0.6
+}
+
+fn default_color() -> [u8; 3] {
+    [0, 255, 0]
+}
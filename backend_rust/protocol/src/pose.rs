@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Where the heading of a pose comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum HeadingSource {
+    /// The difference between the wheels' travel.
+    Encoders,
+    /// The IMU's heading, which wheel slip doesn't fool.
+    Imu,
+}
+
+/// The robot's 2D pose, dead-reckoned from where it was last reset: `x`
+/// forward and `y` to the left of the robot as it stood then, `theta`
+/// counter-clockwise from `x`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Pose {
+    pub x_m: f32,
+    pub y_m: f32,
+    /// -180..180.
+    pub theta_deg: f32,
+    /// Distance driven since the reset, reversing included.
+    pub travelled_m: f32,
+    pub heading_source: HeadingSource,
+    pub updated_unix_ms: u64,
+}
+
+/// `POST /pose/reset`: where the robot is now; the origin facing `x` when
+/// left out.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoseResetRequest {
+    pub x_m: f32,
+    pub y_m: f32,
+    pub theta_deg: f32,
+}
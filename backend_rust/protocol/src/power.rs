@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PowerStatus {
+    /// Camera stopped, model unloaded and CPU on the powersave governor.
+    pub idle: bool,
+    /// When the current idle period started.
+    pub idle_since_unix_ms: Option<u64>,
+    /// Why the last transition happened (operator request, inactivity, ...).
+    pub reason: String,
+    /// Inactivity after which the robot idles by itself; `None` if it never does.
+    pub idle_after_s: Option<f32>,
+    pub last_activity_age_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerRequest {
+    pub idle: bool,
+}
+
+/// What showed the charger is plugged in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeSense {
+    /// The charger's GPIO sense line.
+    SenseLine,
+    /// Current flowing into the battery.
+    Current,
+}
+
+/// Whether the robot is on the charger, served at `/power/charging` and
+/// recorded as the `charging` telemetry stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChargingStatus {
+    pub charging: bool,
+    pub detected_by: Option<ChargeSense>,
+    /// Battery current, positive while charging, if it is measured.
+    pub current_a: Option<f32>,
+    /// Drive commands are refused until the charger is unplugged.
+    pub drive_blocked: bool,
+    /// When the charging state last changed.
+    pub since_unix_ms: u64,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Operator dashboards that sent a heartbeat recently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PresenceStatus {
+    pub operators: usize,
+    /// Age of the newest heartbeat from any client; `None` if none yet.
+    pub last_heartbeat_age_ms: Option<u64>,
+    /// Teleop drops to idle after this long without a heartbeat.
+    pub timeout_ms: u64,
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceBlurRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceBlurStatus {
+    pub enabled: bool,
+    /// False when the face detector model could not be loaded.
+    pub available: bool,
+}
+
+/// Static region blacked out of every frame before detection, streaming and
+/// recording (e.g. the pit area behind the arena). The polygon is in
+/// normalized frame coordinates (0.0..=1.0, origin top-left), like zones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrivacyMask {
+    pub name: String,
+    pub polygon: Vec<[f32; 2]>,
+}
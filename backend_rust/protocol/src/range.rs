@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// The latest distance one sensor measured.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeReading {
+    pub sensor: String,
+    /// `None` when nothing was in range or the read failed.
+    pub distance_m: Option<f32>,
+    /// Looks where the robot drives forward.
+    pub forward: bool,
+    pub taken_unix_ms: u64,
+}
+
+/// Every sensor's latest reading and what the obstacle-stop guard makes
+/// of them, served at `/range`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeStatus {
+    pub readings: Vec<RangeReading>,
+    /// `None` when the guard is off.
+    pub stop_distance_m: Option<f32>,
+    /// A forward sensor reads closer than `stop_distance_m`, so forward
+    /// drive is blocked.
+    pub blocked: bool,
+}
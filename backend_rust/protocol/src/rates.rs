@@ -0,0 +1,65 @@
+use crate::camera::CaptureState;
+use serde::{Deserialize, Serialize};
+
+/// Tick rate of one loop or stream over a sliding window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RateStats {
+    pub hz: f64,
+    pub mean_interval_ms: f64,
+    /// Standard deviation of the interval.
+    pub jitter_ms: f64,
+    pub max_interval_ms: f64,
+    /// Ticks inside the window.
+    pub samples: usize,
+}
+
+/// What a node of the processing graph does with what flows through it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// Produces frames: a camera, or the stereo pairer.
+    Source,
+    /// Takes frames or sets in and publishes something else.
+    Stage,
+    /// Takes frames or sets and publishes nothing the graph knows of.
+    Consumer,
+}
+
+/// One node of `GET /pipeline`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineNode {
+    /// `camera.<id>` for a camera, `camera.<id>.<consumer>` for what
+    /// subscribes to its frames, `detections.<id>` for its results.
+    pub id: String,
+    pub kind: NodeKind,
+    /// What the node puts out; for a consumer, what it is handed.
+    pub rate: RateStats,
+    /// Items waiting for the node to take them. A frame consumer holds at
+    /// most the newest frame; a detection channel keeps up to `capacity`
+    /// sets for its slowest receiver.
+    pub queued: usize,
+    pub capacity: usize,
+    /// Receivers of a channel, when it doesn't know them by name.
+    #[serde(default)]
+    pub receivers: Option<usize>,
+    /// What the capture thread is doing, for a camera.
+    #[serde(default)]
+    pub capture: Option<CaptureState>,
+}
+
+/// Items flow from `from` to `to`, thinned by `decimation` if set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineEdge {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub decimation: Option<String>,
+}
+
+/// The processing graph as currently wired, served at `GET /pipeline`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineGraph {
+    pub nodes: Vec<PipelineNode>,
+    pub edges: Vec<PipelineEdge>,
+}
@@ -0,0 +1,43 @@
+use crate::inference::DetectedObject;
+use serde::{Deserialize, Serialize};
+
+/// Body of `POST /record/start`; everything is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingRequest {
+    /// Prefix of the recording's directory name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Video frame rate; the server's default when unset.
+    #[serde(default)]
+    pub fps: Option<f32>,
+    /// Draw detections and the operator overlay into the video; on unless
+    /// set to false.
+    #[serde(default)]
+    pub annotate: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingStatus {
+    pub recording: bool,
+    /// Directory holding `video.mp4` and `detections.jsonl`.
+    pub dir: Option<String>,
+    /// Run the recording was started in, if any.
+    pub session: Option<String>,
+    pub started_unix_ms: Option<u64>,
+    pub fps: f32,
+    pub frames: u64,
+    pub detection_sets: u64,
+}
+
+/// One line of a recording's `detections.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedDetections {
+    /// Index of the video frame showing when the set was published; seek to
+    /// `frame / fps` seconds to see it.
+    pub frame: u64,
+    /// Since the recording started.
+    pub offset_ms: u64,
+    pub seq: u64,
+    pub captured_unix_ms: u64,
+    pub objects: Vec<DetectedObject>,
+}
@@ -0,0 +1,194 @@
+//! JSON Schemas of every Socket.IO payload, for clients that validate what
+//! they receive (and to catch drift between the backend and the dashboard).
+//! Schemas describe the current API version.
+
+use crate::camera::{CameraHealth, CameraRestart, Frame, QualityChange};
+use crate::devices::DeviceChange;
+use crate::drive::{DeadmanTrip, DriveCommand, EStopRequest, EStopStatus, TeleopAck, TeleopInput};
+use crate::events;
+use crate::health::RobotInfo;
+use crate::inference::DetectionSet;
+use crate::mission::{
+    ApprovalDecision, ApprovalResolution, MissionState, PlanProgress, RunTimerStatus,
+};
+use crate::overlay::OverlayPrimitive;
+use crate::power::PowerStatus;
+use crate::presence::PresenceStatus;
+use crate::rates::RateStats;
+use crate::state::StateSnapshot;
+use crate::target::TargetChange;
+use crate::telemetry::TelemetryAggregate;
+use crate::thermal::ThermalFrame;
+use crate::version::{VersionOffer, VersionRequest, VersionSelection};
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    ServerToClient,
+    ClientToServer,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSchema {
+    pub direction: Direction,
+    /// `None` for events without a payload.
+    pub payload: Option<RootSchema>,
+    /// What the server answers through the ack, if it does.
+    pub ack: Option<RootSchema>,
+}
+
+impl EventSchema {
+    fn new(direction: Direction, payload: Option<RootSchema>) -> Self {
+        Self {
+            direction,
+            payload,
+            ack: None,
+        }
+    }
+
+    fn with_ack(mut self, ack: RootSchema) -> Self {
+        self.ack = Some(ack);
+        self
+    }
+}
+
+/// Every event in [`events`], by name.
+pub fn event_schemas() -> BTreeMap<&'static str, EventSchema> {
+    use Direction::{ClientToServer as In, ServerToClient as Out};
+    BTreeMap::from([
+        (
+            events::STATE_SNAPSHOT,
+            EventSchema::new(Out, Some(schema_for!(StateSnapshot))),
+        ),
+        (
+            events::SYNC,
+            EventSchema::new(In, None).with_ack(schema_for!(StateSnapshot)),
+        ),
+        (
+            events::OVERLAY_SET,
+            EventSchema::new(In, Some(schema_for!(Vec<OverlayPrimitive>))),
+        ),
+        (
+            events::OVERLAY_ADD,
+            EventSchema::new(In, Some(schema_for!(OverlayPrimitive))),
+        ),
+        (
+            events::OVERLAY_REMOVE,
+            EventSchema::new(In, Some(schema_for!(String))),
+        ),
+        (events::OVERLAY_CLEAR, EventSchema::new(In, None)),
+        (events::HEARTBEAT, EventSchema::new(In, None)),
+        (
+            events::PRESENCE,
+            EventSchema::new(Out, Some(schema_for!(PresenceStatus))),
+        ),
+        (
+            events::MISSION_STATE,
+            EventSchema::new(Out, Some(schema_for!(MissionState))),
+        ),
+        (
+            events::DETECTIONS,
+            EventSchema::new(Out, Some(schema_for!(DetectionSet))),
+        ),
+        (
+            events::API_VERSIONS,
+            EventSchema::new(Out, Some(schema_for!(VersionOffer))),
+        ),
+        (
+            events::SELECT_API_VERSION,
+            EventSchema::new(In, Some(schema_for!(VersionRequest)))
+                .with_ack(schema_for!(VersionSelection)),
+        ),
+        (
+            events::TELEMETRY,
+            EventSchema::new(Out, Some(schema_for!(TelemetryAggregate))),
+        ),
+        (
+            events::RATES,
+            EventSchema::new(Out, Some(schema_for!(BTreeMap<String, RateStats>))),
+        ),
+        (
+            events::TARGET,
+            EventSchema::new(Out, Some(schema_for!(TargetChange))),
+        ),
+        (
+            events::POWER,
+            EventSchema::new(Out, Some(schema_for!(PowerStatus))),
+        ),
+        (events::ACTIVITY, EventSchema::new(In, None)),
+        (
+            events::DRIVE,
+            EventSchema::new(In, Some(schema_for!(DriveCommand))),
+        ),
+        (
+            events::DEVICE,
+            EventSchema::new(Out, Some(schema_for!(DeviceChange))),
+        ),
+        (
+            events::IMAGE_QUALITY,
+            EventSchema::new(Out, Some(schema_for!(QualityChange))),
+        ),
+        (
+            events::FRAME,
+            EventSchema::new(Out, Some(schema_for!(Frame))),
+        ),
+        (
+            events::THERMAL,
+            EventSchema::new(Out, Some(schema_for!(ThermalFrame))),
+        ),
+        (
+            events::CAMERA_HEALTH,
+            EventSchema::new(Out, Some(schema_for!(CameraHealth))),
+        ),
+        (
+            events::CAMERA_RESTART,
+            EventSchema::new(Out, Some(schema_for!(CameraRestart))),
+        ),
+        (
+            events::ROBOT_INFO,
+            EventSchema::new(Out, Some(schema_for!(RobotInfo))),
+        ),
+        (
+            events::ANNOUNCE,
+            EventSchema::new(In, None).with_ack(schema_for!(RobotInfo)),
+        ),
+        (
+            events::RUN_TIMER,
+            EventSchema::new(Out, Some(schema_for!(RunTimerStatus))),
+        ),
+        (
+            events::MISSION_APPROVE,
+            EventSchema::new(In, Some(schema_for!(ApprovalDecision)))
+                .with_ack(schema_for!(Option<ApprovalResolution>)),
+        ),
+        (
+            events::MISSION_PROGRESS,
+            EventSchema::new(Out, Some(schema_for!(PlanProgress))),
+        ),
+        (
+            events::TELEOP,
+            EventSchema::new(In, Some(schema_for!(TeleopInput))).with_ack(schema_for!(TeleopAck)),
+        ),
+        (
+            events::DEADMAN,
+            EventSchema::new(Out, Some(schema_for!(DeadmanTrip))),
+        ),
+        (
+            events::ESTOP,
+            EventSchema::new(In, Some(schema_for!(EStopRequest)))
+                .with_ack(schema_for!(EStopStatus)),
+        ),
+        (
+            events::ESTOP_CLEAR,
+            EventSchema::new(In, None).with_ack(schema_for!(EStopStatus)),
+        ),
+        (
+            events::ESTOP_STATE,
+            EventSchema::new(Out, Some(schema_for!(EStopStatus))),
+        ),
+    ])
+}
@@ -0,0 +1,74 @@
+use crate::inference::{DetectedObject, StalenessLimits};
+use serde::{Deserialize, Serialize};
+
+/// Gains for one image axis of the visual servoing controller. The error is
+/// the target's offset from the image center, normalized to -1.0..=1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AxisGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Errors smaller than this are treated as centered (no output, no windup).
+    pub deadband: f32,
+    /// Output clamp, in the actuator's rate units (e.g. normalized turn rate, deg/s).
+    pub max_rate: f32,
+}
+
+impl Default for AxisGains {
+    fn default() -> Self {
+        Self {
+            kp: 0.8,
+            ki: 0.0,
+            kd: 0.05,
+            deadband: 0.03,
+            max_rate: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ServoGains {
+    /// Horizontal: drives turn rate / gimbal pan.
+    pub x: AxisGains,
+    /// Vertical: drives gimbal tilt / arm height.
+    pub y: AxisGains,
+    /// Output decays, then stops, as the detections it follows age.
+    #[serde(default)]
+    pub staleness: StalenessLimits,
+}
+
+/// Gimbal angles in degrees from center: pan positive to the right, tilt
+/// positive up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GimbalCommand {
+    pub pan: f32,
+    pub tilt: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GimbalMode {
+    /// Holds the angles last asked for.
+    #[default]
+    Manual,
+    /// Keeps a detection centered in the primary camera's view.
+    AutoTrack,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GimbalModeRequest {
+    pub mode: GimbalMode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GimbalStatus {
+    pub mode: GimbalMode,
+    /// Where the servos are now, in degrees from center.
+    pub pan: f32,
+    pub tilt: f32,
+    /// Where they are slewing to.
+    pub target_pan: f32,
+    pub target_tilt: f32,
+    /// The detection auto-track is centering, if it sees one.
+    pub tracking: Option<DetectedObject>,
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassSummary {
+    pub count: u64,
+    pub best_confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fault {
+    pub t_s: f32,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Split {
+    pub label: String,
+    pub t_s: f32,
+    pub distance_m: f32,
+}
+
+/// One stretch in autonomous mode during the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutonomousRun {
+    pub mission: String,
+    /// Start, relative to the run start.
+    pub t_s: f32,
+    pub duration_s: f32,
+    /// Ended by the autonomous time limit rather than a mode change.
+    pub timed_out: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    pub id: String,
+    pub name: String,
+    pub started_unix: u64,
+    pub duration_s: f32,
+    pub distance_m: f32,
+    pub detections: BTreeMap<String, ClassSummary>,
+    pub faults: Vec<Fault>,
+    pub battery_start_v: Option<f32>,
+    pub battery_end_v: Option<f32>,
+    pub battery_delta_v: Option<f32>,
+    pub splits: Vec<Split>,
+    #[serde(default)]
+    pub autonomous: Vec<AutonomousRun>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartSessionRequest {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartSessionResponse {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitRequest {
+    pub label: String,
+}
+
+/// One entry of a run's export bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub name: String,
+    pub bytes: u64,
+    /// Where this file's own clock starts relative to the run start (the
+    /// blackbox's `t_s = 0`); `None` for files without a timeline.
+    pub offset_s: Option<f64>,
+}
+
+/// `manifest.json` inside an export bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub session_id: String,
+    pub run_started_unix_ms: Option<u64>,
+    pub created_unix_ms: u64,
+    pub files: Vec<BundleFile>,
+}
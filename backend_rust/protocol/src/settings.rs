@@ -0,0 +1,57 @@
+use crate::boundary::BoundaryConfig;
+use crate::bumper::BumperConfig;
+use crate::camera::ImageQualityConfig;
+use crate::drive::SteeringMixConfig;
+use crate::inference::AdaptiveThresholdConfig;
+use crate::locale::ClassLabels;
+use crate::privacy::PrivacyMask;
+use crate::servo::ServoGains;
+use crate::units::UnitCalibration;
+use crate::viewers::ViewerLimits;
+use crate::zones::ZoneConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Every persisted tuning setting in one document, for cloning a known-good
+/// setup onto the other robot. On import, absent sections are left as they
+/// are and servo gains are replaced per controller.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SettingsDocument {
+    #[serde(default)]
+    pub servo_gains: Option<BTreeMap<String, ServoGains>>,
+    #[serde(default)]
+    pub zones: Option<ZoneConfig>,
+    #[serde(default)]
+    pub boundary: Option<BoundaryConfig>,
+    #[serde(default)]
+    pub bumper: Option<BumperConfig>,
+    #[serde(default)]
+    pub steering: Option<SteeringMixConfig>,
+    #[serde(default)]
+    pub adaptive_threshold: Option<AdaptiveThresholdConfig>,
+    #[serde(default)]
+    pub image_quality: Option<ImageQualityConfig>,
+    #[serde(default)]
+    pub viewer_limits: Option<ViewerLimits>,
+    #[serde(default)]
+    pub privacy_masks: Option<Vec<PrivacyMask>>,
+    #[serde(default)]
+    pub units: Option<UnitCalibration>,
+    #[serde(default)]
+    pub class_labels: Option<ClassLabels>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportQuery {
+    /// Validate and report the changes without applying them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Settings that differ from the current ones, e.g. `zones` or
+    /// `servo_gains.follow`.
+    pub changed: Vec<String>,
+}
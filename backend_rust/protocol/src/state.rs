@@ -0,0 +1,33 @@
+use crate::compass::CompassReading;
+use crate::gps::GpsFix;
+use crate::imu::ImuReading;
+use crate::mission::MissionState;
+use crate::pose::Pose;
+use crate::presence::PresenceStatus;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CameraState {
+    pub streaming: bool,
+    pub width: i32,
+    pub height: i32,
+    pub exposure_locked: bool,
+    pub low_light: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StateSnapshot {
+    /// Increases with every snapshot so clients can tell which one is newest.
+    pub revision: u64,
+    pub taken_unix_ms: u64,
+    pub camera: CameraState,
+    pub active_session: Option<String>,
+    pub gps: Option<GpsFix>,
+    pub compass: Option<CompassReading>,
+    pub imu: Option<ImuReading>,
+    pub pose: Option<Pose>,
+    pub mission: MissionState,
+    pub presence: PresenceStatus,
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+/// A click on the dashboard stream, in the coordinates of the element it
+/// was shown in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesignateRequest {
+    pub x: f32,
+    pub y: f32,
+    /// Size the stream was displayed at; clicks are scaled from it.
+    pub view_width: f32,
+    pub view_height: f32,
+    /// Clockwise rotation (0, 90, 180 or 270) the dashboard applied to the
+    /// stream before showing it.
+    #[serde(default)]
+    pub rotation_deg: u16,
+    /// Set when the view is mirrored horizontally (after rotation).
+    #[serde(default)]
+    pub mirrored: bool,
+    /// Visual-servo controller the target is for.
+    #[serde(default = "default_controller")]
+    pub controller: String,
+}
+
+fn default_controller() -> String {
+    "follow".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TargetKind {
+    /// The click landed on (or near) a detection.
+    Object {
+        class: String,
+        confidence: f32,
+        /// Detection set the object was picked from.
+        seq: u64,
+        /// Track to follow, when the object had one.
+        track_id: Option<u64>,
+    },
+    /// Nothing detected there: a patch around the clicked point.
+    Point,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Target {
+    pub controller: String,
+    #[serde(flatten)]
+    pub kind: TargetKind,
+    /// Clicked point in frame pixels.
+    pub point: [i32; 2],
+    /// `[x, y, w, h]` in frame pixels.
+    pub bbox: [i32; 4],
+    pub designated_unix_ms: u64,
+}
+
+/// A designation or release, as broadcast to dashboards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TargetChange {
+    pub controller: String,
+    /// `None` when the target was released.
+    pub target: Option<Target>,
+}
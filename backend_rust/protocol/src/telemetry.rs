@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How often each telemetry stream is sent to remote clients. Streams are
+/// recorded at full rate on the robot either way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownsampleConfig {
+    /// 0 sends every sample.
+    pub default_hz: f32,
+    /// Per-stream overrides, e.g. `{"imu": 5.0}`.
+    #[serde(default)]
+    pub streams: BTreeMap<String, f32>,
+}
+
+impl Default for DownsampleConfig {
+    fn default() -> Self {
+        Self {
+            default_hz: 10.0,
+            streams: BTreeMap::new(),
+        }
+    }
+}
+
+/// One numeric field over an aggregation window; booleans count as 0/1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FieldAggregate {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    pub last: f64,
+}
+
+/// Everything one stream recorded since the previous aggregate. Extremes
+/// are kept, so a spike between two sends is still visible.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TelemetryAggregate {
+    pub stream: String,
+    pub samples: usize,
+    pub window_ms: u64,
+    pub fields: BTreeMap<String, FieldAggregate>,
+}
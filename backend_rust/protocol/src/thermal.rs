@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings of the thermal camera, persisted in `data/thermal.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalConfig {
+    /// Pixels at or above this temperature (°C) make up hot objects.
+    pub hot_threshold_c: f32,
+    /// Smallest blob of hot pixels, in sensor pixels, reported as an object.
+    pub min_pixels: u32,
+    /// Emissivity assumed for every surface in view.
+    pub emissivity: f32,
+    /// Field of view of the sensor; 55 x 35 for the MLX90640BAB, 110 x 75
+    /// for the BAA. Sensor and camera are assumed to point the same way.
+    pub hfov_deg: f32,
+    pub vfov_deg: f32,
+    /// Blend the heat image into annotated frames.
+    pub overlay: bool,
+    /// Weight of the heat image in the blend (0..=1).
+    pub overlay_opacity: f32,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        Self {
+            hot_threshold_c: 60.0,
+            min_pixels: 2,
+            emissivity: 0.95,
+            hfov_deg: 55.0,
+            vfov_deg: 35.0,
+            overlay: false,
+            overlay_opacity: 0.4,
+        }
+    }
+}
+
+/// One full frame of the thermal sensor, streamed as the `thermal` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ThermalFrame {
+    pub seq: u64,
+    pub captured_unix_ms: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Temperatures in °C, row by row from the top left, as the RGB camera
+    /// sees the scene (not mirrored).
+    pub pixels: Vec<f32>,
+    /// Temperature of the sensor itself.
+    pub ambient_c: f32,
+    pub min_c: f32,
+    pub max_c: f32,
+}
@@ -0,0 +1,339 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Calibration constants behind every conversion between raw hardware units
+/// (PWM duty, pixels, ADC counts) and physical ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnitCalibration {
+    #[serde(default)]
+    pub wheels: WheelCalibration,
+    #[serde(default)]
+    pub camera: CameraOptics,
+    /// ADC channels by MCU sensor id; uncalibrated ids stay raw counts.
+    #[serde(default)]
+    pub adc: BTreeMap<u8, AdcChannel>,
+    /// Wheel speed by battery voltage, lowest first, for chassis that
+    /// estimate distance from the commanded duty; empty uses `wheels` as is.
+    #[serde(default)]
+    pub speed_curve: Vec<SpeedPoint>,
+}
+
+impl UnitCalibration {
+    /// `wheels` with the full-duty speed and deadband at `battery_v`,
+    /// interpolated between the speed curve's points and held at its ends.
+    pub fn wheels_at(&self, battery_v: Option<f32>) -> WheelCalibration {
+        let mut wheels = self.wheels;
+        let (Some(volts), Some(first), Some(last)) =
+            (battery_v, self.speed_curve.first(), self.speed_curve.last())
+        else {
+            return wheels;
+        };
+        let point = if volts <= first.battery_v {
+            *first
+        } else if volts >= last.battery_v {
+            *last
+        } else {
+            let upper = self
+                .speed_curve
+                .iter()
+                .position(|p| p.battery_v >= volts)
+                .unwrap_or(self.speed_curve.len() - 1);
+            let (a, b) = (self.speed_curve[upper - 1], self.speed_curve[upper]);
+            let t = (volts - a.battery_v) / (b.battery_v - a.battery_v);
+            SpeedPoint {
+                battery_v: volts,
+                max_speed_mps: a.max_speed_mps + (b.max_speed_mps - a.max_speed_mps) * t,
+                deadband: a.deadband + (b.deadband - a.deadband) * t,
+            }
+        };
+        wheels.max_speed_mps = point.max_speed_mps;
+        wheels.deadband = point.deadband;
+        wheels
+    }
+}
+
+/// Full-duty speed and deadband measured at one battery voltage: motors
+/// turn slower as the battery runs down.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedPoint {
+    pub battery_v: f32,
+    pub max_speed_mps: f32,
+    pub deadband: f32,
+}
+
+/// `POST /calibration/speed/run`: drive straight ahead at `duty` for
+/// `seconds`, then stop for the distance to be measured.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SpeedRunRequest {
+    pub duty: f32,
+    #[serde(default = "default_run_seconds")]
+    pub seconds: f32,
+}
+
+fn default_run_seconds() -> f32 {
+    2.0
+}
+
+/// One run of the speed calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedRun {
+    /// Average duty that reached the motors, after the safety constraints.
+    pub duty: f32,
+    pub seconds: f32,
+    /// `None` when no battery voltage is reported.
+    pub battery_v: Option<f32>,
+    /// Measured by the operator (`POST /calibration/speed/measure`).
+    pub distance_m: Option<f32>,
+}
+
+/// `POST /calibration/speed/measure`: how far the last run went.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SpeedMeasurement {
+    pub distance_m: f32,
+}
+
+/// Line through the measured runs of the speed calibration.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedFit {
+    pub max_speed_mps: f32,
+    pub deadband: f32,
+    /// Average over the runs; `None` when no battery voltage is reported.
+    pub battery_v: Option<f32>,
+}
+
+/// Where the guided speed calibration stands.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedCalibrationStatus {
+    pub runs: Vec<SpeedRun>,
+    /// Speed and deadband fitted to the measured runs, once there are
+    /// enough.
+    pub fit: Option<SpeedFit>,
+    /// What the operator should do next.
+    pub next_step: String,
+    pub curve: Vec<SpeedPoint>,
+}
+
+/// Maps normalized drive duty (`-1.0..=1.0`) to ground speed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WheelCalibration {
+    /// Ground speed at full duty, measured on the floor the robot runs on.
+    pub max_speed_mps: f32,
+    /// Duty below which the wheels do not turn at all.
+    pub deadband: f32,
+    /// Distance between the left and right wheels' contact points, which
+    /// turns wheel speeds into a rotation rate.
+    #[serde(default = "default_track_width")]
+    pub track_width_m: f32,
+    /// Encoder counts per metre of wheel travel; measured by driving a
+    /// known distance straight.
+    #[serde(default = "default_ticks_per_m")]
+    pub ticks_per_m: f32,
+}
+
+fn default_track_width() -> f32 {
+    0.15
+}
+
+fn default_ticks_per_m() -> f32 {
+    1000.0
+}
+
+impl Default for WheelCalibration {
+    fn default() -> Self {
+        Self {
+            max_speed_mps: 0.8,
+            deadband: 0.08,
+            track_width_m: default_track_width(),
+            ticks_per_m: default_ticks_per_m(),
+        }
+    }
+}
+
+impl WheelCalibration {
+    /// Duty needed for `mps`, clamped to full duty.
+    pub fn duty_for(&self, mps: f32) -> f32 {
+        if mps == 0.0 || self.max_speed_mps <= 0.0 {
+            return 0.0;
+        }
+        let fraction = (mps.abs() / self.max_speed_mps).min(1.0);
+        (self.deadband + (1.0 - self.deadband) * fraction).copysign(mps)
+    }
+
+    /// Ground speed produced by `duty`.
+    pub fn speed_for(&self, duty: f32) -> f32 {
+        let duty = duty.clamp(-1.0, 1.0);
+        if duty.abs() <= self.deadband {
+            return 0.0;
+        }
+        let fraction = (duty.abs() - self.deadband) / (1.0 - self.deadband);
+        (fraction * self.max_speed_mps).copysign(duty)
+    }
+
+    /// Rotation rate in degrees per second (clockwise positive) at `left`
+    /// and `right` duty.
+    pub fn turn_rate_deg(&self, left: f32, right: f32) -> f32 {
+        if self.track_width_m <= 0.0 {
+            return 0.0;
+        }
+        ((self.speed_for(left) - self.speed_for(right)) / self.track_width_m).to_degrees()
+    }
+}
+
+/// Camera field of view, for converting pixel offsets to angles.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraOptics {
+    pub hfov_deg: f32,
+    pub vfov_deg: f32,
+    /// Distortion of a wide-angle lens; `None` treats the camera as a pinhole.
+    #[serde(default)]
+    pub lens: Option<LensDistortion>,
+}
+
+impl Default for CameraOptics {
+    /// Raspberry Pi Camera Module 3 (standard lens).
+    fn default() -> Self {
+        Self {
+            hfov_deg: 66.0,
+            vfov_deg: 41.0,
+            lens: None,
+        }
+    }
+}
+
+/// OpenCV's lens model, as produced by a chessboard calibration. Frames are
+/// never remapped (too slow on the Pi); instead the points geometric checks
+/// rely on are undistorted one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LensDistortion {
+    /// Resolution the intrinsics were measured at; they are scaled to the
+    /// frame size in use.
+    pub width: i32,
+    pub height: i32,
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+    /// `[k1, k2, p1, p2, k3]`: radial and tangential coefficients in
+    /// OpenCV's order.
+    pub coefficients: [f32; 5],
+}
+
+/// Fixed-point iterations when inverting the model; plenty for the mild
+/// distortion of CSI lenses.
+const UNDISTORT_ITERATIONS: usize = 5;
+
+impl LensDistortion {
+    /// Where pixel `(x, y)` of a `width` x `height` frame would be without
+    /// distortion, in the same frame's pixels.
+    pub fn undistort_point(&self, x: f32, y: f32, width: i32, height: i32) -> [f32; 2] {
+        let sx = width as f32 / self.width.max(1) as f32;
+        let sy = height as f32 / self.height.max(1) as f32;
+        let (fx, fy) = (self.fx * sx, self.fy * sy);
+        let (cx, cy) = (self.cx * sx, self.cy * sy);
+        let [k1, k2, p1, p2, k3] = self.coefficients;
+
+        let (xd, yd) = ((x - cx) / fx, (y - cy) / fy);
+        let (mut xu, mut yu) = (xd, yd);
+        for _ in 0..UNDISTORT_ITERATIONS {
+            let r2 = xu * xu + yu * yu;
+            let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+            let dx = 2.0 * p1 * xu * yu + p2 * (r2 + 2.0 * xu * xu);
+            let dy = p1 * (r2 + 2.0 * yu * yu) + 2.0 * p2 * xu * yu;
+            xu = (xd - dx) / radial;
+            yu = (yd - dy) / radial;
+        }
+        [xu * fx + cx, yu * fy + cy]
+    }
+}
+
+impl CameraOptics {
+    /// Bearing of pixel column `x` in a `width`-wide frame, in degrees;
+    /// positive is right of center.
+    pub fn bearing_deg(&self, x: f32, width: i32) -> f32 {
+        pixel_to_angle(x, width, self.hfov_deg)
+    }
+
+    /// Elevation of pixel row `y` in a `height`-tall frame, in degrees;
+    /// positive is above center.
+    pub fn elevation_deg(&self, y: f32, height: i32) -> f32 {
+        -pixel_to_angle(y, height, self.vfov_deg)
+    }
+
+    /// Undistorts pixel `(x, y)` with the lens model, if there is one.
+    pub fn undistort_point(&self, x: f32, y: f32, width: i32, height: i32) -> [f32; 2] {
+        match &self.lens {
+            Some(lens) => lens.undistort_point(x, y, width, height),
+            None => [x, y],
+        }
+    }
+
+    /// Undistorted `[x, y, w, h]` box: the bounds of its corners and edge
+    /// midpoints, since barrel distortion bows the edges.
+    pub fn undistort_box(&self, bbox: [f32; 4], width: i32, height: i32) -> [f32; 4] {
+        if self.lens.is_none() {
+            return bbox;
+        }
+        let [x, y, w, h] = bbox;
+        let mut min = [f32::MAX; 2];
+        let mut max = [f32::MIN; 2];
+        for (fx, fy) in [
+            (0.0, 0.0),
+            (0.5, 0.0),
+            (1.0, 0.0),
+            (1.0, 0.5),
+            (1.0, 1.0),
+            (0.5, 1.0),
+            (0.0, 1.0),
+            (0.0, 0.5),
+        ] {
+            let [px, py] = self.undistort_point(x + fx * w, y + fy * h, width, height);
+            min = [min[0].min(px), min[1].min(py)];
+            max = [max[0].max(px), max[1].max(py)];
+        }
+        [min[0], min[1], max[0] - min[0], max[1] - min[1]]
+    }
+
+    /// Bearing and elevation of pixel `(x, y)`, in degrees, corrected for
+    /// lens distortion.
+    pub fn direction_deg(&self, x: f32, y: f32, width: i32, height: i32) -> (f32, f32) {
+        let [x, y] = self.undistort_point(x, y, width, height);
+        (self.bearing_deg(x, width), self.elevation_deg(y, height))
+    }
+
+    /// Pixel column at `bearing_deg`; inverse of [`bearing_deg`](Self::bearing_deg).
+    pub fn column_at(&self, bearing_deg: f32, width: i32) -> f32 {
+        let half = width as f32 / 2.0;
+        half + focal_px(width, self.hfov_deg) * bearing_deg.to_radians().tan()
+    }
+}
+
+// Pinhole model: offsets grow with the tangent of the angle, not linearly
+fn focal_px(size: i32, fov_deg: f32) -> f32 {
+    size as f32 / 2.0 / (fov_deg.to_radians() / 2.0).tan()
+}
+
+fn pixel_to_angle(pos: f32, size: i32, fov_deg: f32) -> f32 {
+    let offset = pos - size as f32 / 2.0;
+    offset.atan2(focal_px(size, fov_deg)).to_degrees()
+}
+
+/// Linear ADC channel: `volts = raw * volts_per_count + offset_v`, with the
+/// divider ratio folded into `volts_per_count`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdcChannel {
+    /// Telemetry field name; `battery` also feeds the run's battery figures.
+    pub name: String,
+    pub volts_per_count: f32,
+    #[serde(default)]
+    pub offset_v: f32,
+}
+
+impl AdcChannel {
+    pub fn volts(&self, raw: i32) -> f32 {
+        raw as f32 * self.volts_per_count + self.offset_v
+    }
+
+    pub fn counts(&self, volts: f32) -> i32 {
+        ((volts - self.offset_v) / self.volts_per_count).round() as i32
+    }
+}
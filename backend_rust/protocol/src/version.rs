@@ -0,0 +1,40 @@
+//! API schema versions.
+//!
+//! HTTP routes live under `/api/v{N}`. The bare `/api` prefix keeps serving
+//! version 0, the schema from before versioning. Socket.IO clients receive a
+//! [`VersionOffer`] on connect and answer with a [`VersionRequest`]; clients
+//! that never answer are treated as version 0.
+//!
+//! | Version | Changes |
+//! |---------|---------|
+//! | 0       | Unversioned schema |
+//! | 1       | Mission mode `paused` (version 0 sees `idle`) |
+
+use serde::{Deserialize, Serialize};
+
+pub const CURRENT: u32 = 1;
+/// Oldest first.
+pub const SUPPORTED: [u32; 2] = [0, 1];
+
+/// Server -> client on connect.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionOffer {
+    pub supported: Vec<u32>,
+    pub current: u32,
+}
+
+/// Client -> server: the version the client speaks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionRequest {
+    pub version: u32,
+}
+
+/// Ack to a [`VersionRequest`]; a rejected request keeps the previous version.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct VersionSelection {
+    pub version: u32,
+    pub accepted: bool,
+}
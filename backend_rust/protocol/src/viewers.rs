@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Maximum concurrent viewers per endpoint (e.g. `"stream"`, `"socket.io"`).
+pub type ViewerLimits = BTreeMap<String, usize>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewerInfo {
+    pub id: u64,
+    pub endpoint: String,
+    pub addr: Option<String>,
+    pub connected_s: f64,
+    pub bytes_sent: u64,
+    /// Average outgoing rate since the client connected.
+    pub kbps: f64,
+}
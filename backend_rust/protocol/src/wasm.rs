@@ -0,0 +1,84 @@
+//! WebAssembly bindings (`wasm` feature), so in-browser tools decode what
+//! the backend sends and lay out overlays with these very types instead of
+//! hand-kept TypeScript copies. Build with
+//! `wasm-pack build protocol --target web --features wasm`.
+//!
+//! Payloads are taken and returned as plain JS values; 64-bit integers come
+//! back as numbers.
+
+use crate::camera::{CameraHealth, CameraRestart, Frame, QualityChange};
+use crate::devices::DeviceChange;
+use crate::drive::{DeadmanTrip, EStopStatus, TeleopAck};
+use crate::events;
+use crate::health::RobotInfo;
+use crate::inference::DetectionSet;
+use crate::mission::{ApprovalResolution, MissionState, PlanProgress, RunTimerStatus};
+use crate::overlay::OverlayPrimitive;
+use crate::power::PowerStatus;
+use crate::presence::PresenceStatus;
+use crate::rates::RateStats;
+use crate::state::StateSnapshot;
+use crate::target::TargetChange;
+use crate::telemetry::TelemetryAggregate;
+use crate::thermal::ThermalFrame;
+use crate::version::{VersionOffer, VersionSelection};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    Ok(serde_wasm_bindgen::from_value(value)?)
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+fn decode<T: DeserializeOwned + Serialize>(payload: JsValue) -> Result<JsValue, JsError> {
+    to_js(&from_js::<T>(payload)?)
+}
+
+/// Checks what the server sends for `event` (its payload, or its ack answer)
+/// against the type of the current API version and returns it with defaults
+/// filled in. Throws on a payload the type rejects, or an event without one.
+#[wasm_bindgen(js_name = decodeEvent)]
+pub fn decode_event(event: &str, payload: JsValue) -> Result<JsValue, JsError> {
+    match event {
+        events::STATE_SNAPSHOT | events::SYNC => decode::<StateSnapshot>(payload),
+        events::PRESENCE => decode::<PresenceStatus>(payload),
+        events::MISSION_STATE => decode::<MissionState>(payload),
+        events::DETECTIONS => decode::<DetectionSet>(payload),
+        events::API_VERSIONS => decode::<VersionOffer>(payload),
+        events::SELECT_API_VERSION => decode::<VersionSelection>(payload),
+        events::TELEMETRY => decode::<TelemetryAggregate>(payload),
+        events::RATES => decode::<BTreeMap<String, RateStats>>(payload),
+        events::TARGET => decode::<TargetChange>(payload),
+        events::POWER => decode::<PowerStatus>(payload),
+        events::DEVICE => decode::<DeviceChange>(payload),
+        events::IMAGE_QUALITY => decode::<QualityChange>(payload),
+        events::FRAME => decode::<Frame>(payload),
+        events::THERMAL => decode::<ThermalFrame>(payload),
+        events::CAMERA_HEALTH => decode::<CameraHealth>(payload),
+        events::CAMERA_RESTART => decode::<CameraRestart>(payload),
+        events::ROBOT_INFO | events::ANNOUNCE => decode::<RobotInfo>(payload),
+        events::RUN_TIMER => decode::<RunTimerStatus>(payload),
+        events::MISSION_APPROVE => decode::<Option<ApprovalResolution>>(payload),
+        events::MISSION_PROGRESS => decode::<PlanProgress>(payload),
+        events::TELEOP => decode::<TeleopAck>(payload),
+        events::DEADMAN => decode::<DeadmanTrip>(payload),
+        events::ESTOP | events::ESTOP_CLEAR | events::ESTOP_STATE => decode::<EStopStatus>(payload),
+        _ => Err(JsError::new(&format!(
+            "no server payload for event {:?}",
+            event
+        ))),
+    }
+}
+
+/// An overlay primitive laid out on a `width` x `height` canvas, exactly as
+/// the backend draws it onto annotated frames.
+#[wasm_bindgen(js_name = overlayInPixels)]
+pub fn overlay_in_pixels(primitive: JsValue, width: f32, height: f32) -> Result<JsValue, JsError> {
+    let mut primitive: OverlayPrimitive = from_js(primitive)?;
+    primitive.shape = primitive.shape.in_pixels(width, height);
+    to_js(&primitive)
+}
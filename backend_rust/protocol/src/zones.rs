@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// Region of the camera image with its own detection rules. The polygon is
+/// in normalized frame coordinates (0.0..=1.0, origin top-left).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub polygon: Vec<[f32; 2]>,
+    /// Overrides the default confidence threshold inside this zone.
+    #[serde(default)]
+    pub min_confidence: Option<f32>,
+    /// Only these class names are reported inside the zone; all when absent.
+    #[serde(default)]
+    pub classes: Option<Vec<String>>,
+}
+
+/// Zones are matched in order against a detection's box center, so list the
+/// more specific ones first; detections outside every zone use the default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneConfig {
+    #[serde(default = "default_min_confidence")]
+    pub default_min_confidence: f32,
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+}
+
+fn default_min_confidence() -> f32 {
+    0.25
+}
+
+impl Default for ZoneConfig {
+    fn default() -> Self {
+        Self {
+            default_min_confidence: default_min_confidence(),
+            zones: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Adaptive confidence threshold.
+//!
+//! A real object stays in view for more than one inference pass; a box that
+//! shows up in a single set and is gone in the next (and wasn't there in the
+//! one before) is almost always noise. When lighting changes, the share of
+//! such transient detections moves with it, so the controller nudges the
+//! working threshold up while they are frequent and back down once they are
+//! rare, never leaving the configured bounds.
+
+use crate::nms::iou;
+use crate::state::AppState;
+use crate::transform::BoxF;
+use crate::yolo::Thresholds;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::inference::{
+    AdaptiveThresholdConfig, AdaptiveThresholdStatus, DetectedObject,
+};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+pub const CONFIG_PATH: &str = "data/adaptive_threshold.json";
+/// Overlap at which a detection counts as the same object in a neighbouring set.
+const PERSIST_IOU: f32 = 0.3;
+
+#[derive(Default)]
+struct Window {
+    /// The two sets before the latest one; the older one's neighbours are
+    /// both known once a new set arrives.
+    before: Option<Vec<DetectedObject>>,
+    middle: Option<Vec<DetectedObject>>,
+    sets: usize,
+    judged: usize,
+    transient: usize,
+    last_ratio: Option<f32>,
+}
+
+pub struct AdaptiveThreshold {
+    path: PathBuf,
+    config: Mutex<AdaptiveThresholdConfig>,
+    window: Mutex<Window>,
+    /// The configured confidence, where the controller starts from.
+    base: Mutex<f32>,
+    confidence: Mutex<f32>,
+}
+
+impl AdaptiveThreshold {
+    pub fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config: AdaptiveThresholdConfig = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let base = Thresholds::default().confidence;
+        let confidence = start_confidence(&config, base);
+        Self {
+            path,
+            config: Mutex::new(config),
+            window: Mutex::new(Window::default()),
+            base: Mutex::new(base),
+            confidence: Mutex::new(confidence),
+        }
+    }
+
+    pub fn config(&self) -> AdaptiveThresholdConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Sets the configured confidence and restarts the controller from it.
+    pub fn set_base(&self, base: f32) {
+        *self.base.lock().unwrap() = base;
+        *self.confidence.lock().unwrap() = start_confidence(&self.config(), base);
+    }
+
+    /// Saves the config and restarts the controller from the configured
+    /// confidence (clamped to the new bounds).
+    pub fn set_config(&self, config: AdaptiveThresholdConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        let base = *self.base.lock().unwrap();
+        *self.confidence.lock().unwrap() = start_confidence(&config, base);
+        *self.window.lock().unwrap() = Window::default();
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// The threshold the detector should run at: `None` while disabled,
+    /// which leaves the configured confidence in charge.
+    pub fn confidence(&self) -> Option<f32> {
+        if self.config.lock().unwrap().enabled {
+            Some(*self.confidence.lock().unwrap())
+        } else {
+            None
+        }
+    }
+
+    pub fn status(&self) -> AdaptiveThresholdStatus {
+        let config = self.config();
+        let window = self.window.lock().unwrap();
+        AdaptiveThresholdStatus {
+            enabled: config.enabled,
+            confidence: match config.enabled {
+                true => *self.confidence.lock().unwrap(),
+                false => *self.base.lock().unwrap(),
+            },
+            transient_ratio: window.last_ratio,
+            window_progress: window.sets,
+        }
+    }
+
+    /// Feeds one detection set; returns the new threshold when it moved.
+    pub fn observe(&self, objects: &[DetectedObject]) -> Option<f32> {
+        let config = self.config();
+        if !config.enabled {
+            return None;
+        }
+        let mut window = self.window.lock().unwrap();
+        if let Some(middle) = window.middle.take() {
+            if let Some(before) = &window.before {
+                let transient = middle
+                    .iter()
+                    .filter(|o| !persists(o, before) && !persists(o, objects))
+                    .count();
+                window.judged += middle.len();
+                window.transient += transient;
+            }
+            window.before = Some(middle);
+        }
+        window.middle = Some(objects.to_vec());
+        window.sets += 1;
+        if window.sets < config.window {
+            return None;
+        }
+
+        let ratio = (window.judged > 0).then(|| window.transient as f32 / window.judged as f32);
+        window.sets = 0;
+        window.judged = 0;
+        window.transient = 0;
+        window.last_ratio = ratio;
+        // An empty window says nothing about precision
+        let ratio = ratio?;
+
+        let mut confidence = self.confidence.lock().unwrap();
+        let target = if ratio > config.target_transient + config.tolerance {
+            *confidence + config.step
+        } else if ratio < config.target_transient - config.tolerance {
+            *confidence - config.step
+        } else {
+            return None;
+        };
+        let target = target.clamp(config.min_confidence, config.max_confidence);
+        if target == *confidence {
+            return None;
+        }
+        *confidence = target;
+        Some(target)
+    }
+}
+
+fn start_confidence(config: &AdaptiveThresholdConfig, base: f32) -> f32 {
+    base.clamp(config.min_confidence, config.max_confidence)
+}
+
+fn to_box(bbox: [i32; 4]) -> BoxF {
+    let [x, y, w, h] = bbox;
+    BoxF {
+        x: x as f32,
+        y: y as f32,
+        w: w as f32,
+        h: h as f32,
+    }
+}
+
+/// Whether `object` overlaps a same-class detection of `set`.
+fn persists(object: &DetectedObject, set: &[DetectedObject]) -> bool {
+    let bbox = to_box(object.bbox);
+    set.iter()
+        .any(|o| o.class == object.class && iou(&to_box(o.bbox), &bbox) >= PERSIST_IOU)
+}
+
+pub fn validate(config: &AdaptiveThresholdConfig) -> Result<(), String> {
+    let unit = [
+        config.min_confidence,
+        config.max_confidence,
+        config.target_transient,
+        config.tolerance,
+    ];
+    if unit.iter().any(|v| !(0.0..=1.0).contains(v)) {
+        return Err("confidences, target_transient and tolerance must be within 0..=1".to_string());
+    }
+    if config.min_confidence > config.max_confidence {
+        return Err("min_confidence is above max_confidence".to_string());
+    }
+    if !(config.step > 0.0 && config.step <= 1.0) {
+        return Err("step must be above 0 and at most 1".to_string());
+    }
+    if config.window == 0 {
+        return Err("window must be at least one detection set".to_string());
+    }
+    Ok(())
+}
+
+/// Runs every published set through the controller and retunes the
+/// detector when the threshold moves.
+pub fn start_adaptive_threshold(state: AppState) {
+    if let Some(model) = &state.model {
+        state.adaptive.set_base(model.base_thresholds().confidence);
+        model.set_confidence(state.adaptive.confidence());
+    }
+    let mut sets = state.detections.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let published = match sets.recv().await {
+                Ok(published) => published,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Some(confidence) = state.adaptive.observe(&published.objects) else {
+                continue;
+            };
+            if let Some(model) = &state.model {
+                model.set_confidence(Some(confidence));
+            }
+            println!(
+                "[INFO] Adaptive threshold moved to {:.2} (transient ratio {:.2})",
+                confidence,
+                state.adaptive.status().transient_ratio.unwrap_or(0.0)
+            );
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/inference/adaptive-threshold", get(get_status))
+        .route(
+            "/inference/adaptive-threshold/config",
+            get(get_config).put(set_config),
+        )
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<AdaptiveThresholdStatus> {
+    Json(state.adaptive.status())
+}
+
+async fn get_config(State(state): State<AppState>) -> Json<AdaptiveThresholdConfig> {
+    Json(state.adaptive.config())
+}
+
+async fn set_config(
+    State(state): State<AppState>,
+    Json(config): Json<AdaptiveThresholdConfig>,
+) -> Result<Json<AdaptiveThresholdConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    apply_config(&state, config.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Adaptive threshold {}",
+        if config.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    Ok(Json(config))
+}
+
+/// Saves `config` and hands the detector the threshold it implies, so a
+/// disabled controller gives the configured confidence back right away.
+pub fn apply_config(state: &AppState, config: AdaptiveThresholdConfig) -> std::io::Result<()> {
+    state.adaptive.set_config(config)?;
+    if let Some(model) = &state.model {
+        model.set_confidence(state.adaptive.confidence());
+    }
+    Ok(())
+}
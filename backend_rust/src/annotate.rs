@@ -0,0 +1,183 @@
+//! Annotated frames for viewing: the latest detections (boxes, labels, track
+//! ids), capture and inference rates, and the operator overlay, drawn onto a
+//! copy of the frame the way the Python backend's stream showed them.
+//!
+//! Per MJPEG client with `/stream?annotate=true`; `stream.annotate` in the
+//! config makes it the default for the MJPEG and Socket.IO streams. Only what is sent to
+//! viewers is drawn on, consumers that analyse frames always get raw ones.
+//! Each camera shows its own detections and rates; the thermal blend and the
+//! overlay belong to the primary camera's view.
+//!
+//! Class names are drawn in the viewer's language (`?locale=`) where
+//! OpenCV's Hershey fonts can draw them, which is ASCII only: Japanese
+//! names fall back to the class, the dashboard shows them from the
+//! detection payloads instead.
+//!
+//! Everything sent off the robot goes through [`publish`], which blurs
+//! faces first when face blur is on (see `privacy`).
+
+use crate::camera::{self, Camera};
+use crate::state::AppState;
+use crate::threads;
+use opencv::{
+    core::{Mat, Point, Rect, Scalar},
+    imgproc,
+    prelude::*,
+};
+use raspibot_protocol::inference::Freshness;
+use raspibot_protocol::locale::Locale;
+
+/// Box colors (BGR), picked per class so a class keeps its color.
+const PALETTE: [(f64, f64, f64); 8] = [
+    (56.0, 56.0, 255.0),
+    (151.0, 157.0, 255.0),
+    (31.0, 112.0, 255.0),
+    (29.0, 178.0, 255.0),
+    (49.0, 210.0, 207.0),
+    (10.0, 249.0, 72.0),
+    (23.0, 204.0, 146.0),
+    (255.0, 194.0, 0.0),
+];
+const FONT: i32 = imgproc::FONT_HERSHEY_SIMPLEX;
+const FONT_SCALE: f64 = 0.5;
+
+fn class_color(class: &str) -> Scalar {
+    let hash = class
+        .bytes()
+        .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    let (b, g, r) = PALETTE[hash % PALETTE.len()];
+    Scalar::new(b, g, r, 0.0)
+}
+
+/// `text` on a filled background, its bottom-left corner at `origin`.
+fn label(frame: &mut Mat, text: &str, origin: Point, background: Scalar) -> opencv::Result<()> {
+    let mut baseline = 0;
+    let size = imgproc::get_text_size(text, FONT, FONT_SCALE, 1, &mut baseline)?;
+    imgproc::rectangle(
+        frame,
+        Rect::new(
+            origin.x,
+            origin.y - size.height - baseline,
+            size.width + 4,
+            size.height + baseline + 2,
+        ),
+        background,
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )?;
+    imgproc::put_text(
+        frame,
+        text,
+        Point::new(origin.x + 2, origin.y - baseline / 2),
+        FONT,
+        FONT_SCALE,
+        Scalar::all(255.0),
+        1,
+        imgproc::LINE_AA,
+        false,
+    )
+}
+
+/// A copy of `frame` with everything drawn on; grayscale frames come back
+/// as BGR so the annotations keep their colors.
+pub fn render(
+    state: &AppState,
+    camera: &Camera,
+    frame: &Mat,
+    locale: Locale,
+) -> opencv::Result<Mat> {
+    let primary = state.cameras.is_primary(camera);
+    let mut out = Mat::default();
+    if frame.channels() == 1 {
+        imgproc::cvt_color_def(frame, &mut out, imgproc::COLOR_GRAY2BGR)?;
+    } else {
+        frame.copy_to(&mut out)?;
+    }
+
+    if let Some(thermal) = state
+        .thermal
+        .as_ref()
+        .filter(|t| primary && t.config().overlay)
+    {
+        thermal.blend(&mut out, &state.units.camera())?;
+    }
+
+    // Stale boxes would point at where things were, not where they are
+    let limits = camera.detections.limits();
+    let objects = camera
+        .detections
+        .latest()
+        .map(|published| published.to_set(&limits))
+        .filter(|set| set.freshness != Freshness::Stale)
+        .map(|set| set.objects)
+        .unwrap_or_default();
+    for object in &objects {
+        let [x, y, w, h] = object.bbox;
+        let color = class_color(&object.class);
+        imgproc::rectangle(
+            &mut out,
+            Rect::new(x, y, w, h),
+            color,
+            2,
+            imgproc::LINE_8,
+            0,
+        )?;
+        let name = Some(state.labels.display(&object.class, locale))
+            .filter(|name| name.is_ascii())
+            .unwrap_or_else(|| object.class.clone());
+        let text = match object.track_id {
+            Some(id) => format!("{} {:.2} #{}", name, object.confidence, id),
+            None => format!("{} {:.2}", name, object.confidence),
+        };
+        label(&mut out, &text, Point::new(x, y.max(16)), color)?;
+    }
+
+    let stats = format!(
+        "camera {:.1} fps | inference {:.1} fps",
+        camera.frames.capture_rate().hz,
+        camera.detections.rate().hz
+    );
+    label(
+        &mut out,
+        &stats,
+        Point::new(4, 20),
+        Scalar::new(0.0, 0.0, 0.0, 0.0),
+    )?;
+
+    if primary {
+        state.overlay.render(&mut out)?;
+    }
+    Ok(out)
+}
+
+/// `frame` as it may leave the robot: faces blurred when face blur is on,
+/// then annotated when `annotate` has the camera it came from and the
+/// language to label in. A frame that can't be drawn on goes out
+/// unannotated.
+pub fn publish(state: &AppState, frame: &Mat, annotate: Option<(&Camera, Locale)>) -> Mat {
+    let blurred = state.face_blur.process(frame);
+    let Some((camera, locale)) = annotate else {
+        return blurred;
+    };
+    match render(state, camera, &blurred, locale) {
+        Ok(annotated) => annotated,
+        Err(e) => {
+            println!("[WARN] Could not annotate frame: {}", e);
+            blurred
+        }
+    }
+}
+
+/// JPEG of `frame` as [`publish`] makes it.
+pub fn publish_jpeg(
+    state: &AppState,
+    frame: &Mat,
+    quality: i32,
+    annotate: Option<(&Camera, Locale)>,
+) -> opencv::Result<Vec<u8>> {
+    // Viewers' frames are encoded on shared threads; keep their CPU apart
+    threads::attribute("jpeg", || {
+        camera::encode_jpeg(&publish(state, frame, annotate), quality)
+    })
+}
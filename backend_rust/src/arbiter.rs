@@ -0,0 +1,186 @@
+//! Single owner of the drive motors. Teleop and autonomous behaviours submit
+//! commands here, and every registered safety [`Constraint`] gets the last
+//! word before anything reaches the motor driver.
+
+use crate::motors::MotorDriver;
+use crate::rate::RateMeter;
+use anyhow::{bail, Result};
+use raspibot_protocol::drive::DriveCommand;
+use raspibot_protocol::rates::RateStats;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandSource {
+    Teleop,
+    /// An autonomous mode or mission, by name.
+    Autonomous(String),
+    /// A background mission, by name.
+    Background(String),
+}
+
+impl CommandSource {
+    pub fn is_autonomous(&self) -> bool {
+        matches!(
+            self,
+            CommandSource::Autonomous(_) | CommandSource::Background(_)
+        )
+    }
+}
+
+impl fmt::Display for CommandSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandSource::Teleop => f.write_str("teleop"),
+            CommandSource::Autonomous(name) => write!(f, "auto:{}", name),
+            CommandSource::Background(name) => write!(f, "background:{}", name),
+        }
+    }
+}
+
+/// A safety layer that may scale down or zero a command before it is sent.
+pub trait Constraint: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, source: &CommandSource, command: DriveCommand) -> DriveCommand;
+}
+
+pub struct CommandArbiter {
+    /// Held while a command is checked and sent, so an interlock or owner
+    /// change lands entirely before or after it.
+    driver: Mutex<Option<Box<dyn MotorDriver>>>,
+    max_speed: f32,
+    constraints: Mutex<Vec<Arc<dyn Constraint>>>,
+    last: Mutex<Option<(CommandSource, DriveCommand)>>,
+    /// When the last command arrived, for the drive watchdog.
+    last_submit: Mutex<Option<Instant>>,
+    /// Only this source's commands go through; anyone may drive when unset.
+    owner: Mutex<Option<CommandSource>>,
+    /// Why no one may drive at all, by what set it (the emergency stop,
+    /// the charger, ...).
+    interlocks: Mutex<BTreeMap<&'static str, String>>,
+    /// Drive commands submitted, i.e. the control-loop rate.
+    rate: RateMeter,
+}
+
+impl CommandArbiter {
+    /// `driver` is `None` on chassis without drive motors; commands are then
+    /// still arbitrated (and reported) but go nowhere.
+    pub fn new(driver: Option<Box<dyn MotorDriver>>, max_speed: f32) -> Self {
+        Self {
+            driver: Mutex::new(driver),
+            max_speed,
+            constraints: Mutex::new(Vec::new()),
+            last: Mutex::new(None),
+            last_submit: Mutex::new(None),
+            owner: Mutex::new(None),
+            interlocks: Mutex::new(BTreeMap::new()),
+            rate: RateMeter::new(),
+        }
+    }
+
+    pub fn add_constraint(&self, constraint: Arc<dyn Constraint>) {
+        println!("[INFO] Drive constraint '{}' registered", constraint.name());
+        self.constraints.lock().unwrap().push(constraint);
+    }
+
+    /// Set by the mission controller whenever drive ownership changes.
+    pub fn set_owner(&self, owner: Option<CommandSource>) {
+        let _driver = self.driver.lock().unwrap();
+        *self.owner.lock().unwrap() = owner;
+    }
+
+    /// Refuses every command for `reason` until `name` clears it with
+    /// `None`, and stops the motors when set. Interlocks are independent:
+    /// drive resumes once none is left.
+    pub fn set_interlock(&self, name: &'static str, reason: Option<String>) -> Result<()> {
+        let mut driver = self.driver.lock().unwrap();
+        let engaged = reason.is_some();
+        {
+            let mut interlocks = self.interlocks.lock().unwrap();
+            match reason {
+                Some(reason) => interlocks.insert(name, reason),
+                None => interlocks.remove(name),
+            };
+        }
+        if engaged {
+            self.stop_driver(&mut driver)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `command` through every constraint and the profile speed limit,
+    /// then sends it; returns what was actually sent. Fails without touching
+    /// the motors if another source owns the drive or an interlock is set.
+    pub fn submit(&self, source: CommandSource, command: DriveCommand) -> Result<DriveCommand> {
+        // Constraints run before taking the driver; they may be slow, and
+        // their verdict doesn't depend on who owns the drive
+        let constraints = self.constraints.lock().unwrap().clone();
+        let mut command = constraints
+            .iter()
+            .fold(command, |cmd, c| c.apply(&source, cmd));
+        command.left = command.left.clamp(-self.max_speed, self.max_speed);
+        command.right = command.right.clamp(-self.max_speed, self.max_speed);
+
+        let mut driver = self.driver.lock().unwrap();
+        if let Some(reason) = self.interlocks.lock().unwrap().values().next() {
+            bail!("drive is disabled: {}", reason);
+        }
+        if let Some(owner) = self.owner.lock().unwrap().as_ref() {
+            if *owner != source {
+                bail!("drive is owned by {}", owner);
+            }
+        }
+        self.rate.tick();
+        if let Some(driver) = driver.as_mut() {
+            driver.set_speeds(command.left, command.right)?;
+        }
+        *self.last.lock().unwrap() = Some((source, command));
+        *self.last_submit.lock().unwrap() = Some(Instant::now());
+        Ok(command)
+    }
+
+    /// Stops the motors if they are moving and no command has arrived for
+    /// `timeout`, i.e. whoever was driving went quiet. Returns whether it did.
+    pub fn stop_if_stale(&self, timeout: Duration) -> Result<bool> {
+        let stale = self
+            .last_submit
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() >= timeout);
+        let moving = self
+            .last()
+            .is_some_and(|(_, command)| command != DriveCommand::default());
+        if !(stale && moving) {
+            return Ok(false);
+        }
+        self.stop()?;
+        Ok(true)
+    }
+
+    /// Stops the motors regardless of constraints.
+    pub fn stop(&self) -> Result<()> {
+        self.stop_driver(&mut self.driver.lock().unwrap())
+    }
+
+    fn stop_driver(&self, driver: &mut Option<Box<dyn MotorDriver>>) -> Result<()> {
+        if let Some(driver) = driver.as_mut() {
+            driver.stop()?;
+        }
+        if let Some((_, command)) = self.last.lock().unwrap().as_mut() {
+            *command = DriveCommand::default();
+        }
+        Ok(())
+    }
+
+    pub fn rate(&self) -> RateStats {
+        self.rate.stats()
+    }
+
+    /// Who sent the last command, and what went to the motors.
+    pub fn last(&self) -> Option<(CommandSource, DriveCommand)> {
+        self.last.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,166 @@
+//! Precision manipulator built from Dynamixel smart servos.
+
+use crate::dynamixel::{
+    DynamixelBus, OperatingMode, ServoStatus, TICKS_PER_REV, VELOCITY_UNIT_RPM,
+};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const DEFAULT_PORT: &str = "/dev/ttyUSB0";
+pub const DEFAULT_BAUD: u32 = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: &'static str,
+    pub id: u8,
+    pub min_deg: f32,
+    pub max_deg: f32,
+}
+
+/// Joint layout of the precision arm build, base to gripper.
+pub const JOINTS: &[Joint] = &[
+    Joint {
+        name: "base",
+        id: 1,
+        min_deg: -150.0,
+        max_deg: 150.0,
+    },
+    Joint {
+        name: "shoulder",
+        id: 2,
+        min_deg: -90.0,
+        max_deg: 90.0,
+    },
+    Joint {
+        name: "elbow",
+        id: 3,
+        min_deg: -120.0,
+        max_deg: 120.0,
+    },
+    Joint {
+        name: "wrist",
+        id: 4,
+        min_deg: -100.0,
+        max_deg: 100.0,
+    },
+    Joint {
+        name: "gripper",
+        id: 5,
+        min_deg: 0.0,
+        max_deg: 80.0,
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JointState {
+    pub name: &'static str,
+    pub angle_deg: f32,
+    pub velocity_rpm: f32,
+    #[serde(flatten)]
+    pub raw: ServoStatus,
+}
+
+// Servo position 2048 is the mechanical center of every joint
+fn deg_to_ticks(deg: f32) -> i32 {
+    2048 + (deg / 360.0 * TICKS_PER_REV).round() as i32
+}
+
+fn ticks_to_deg(ticks: i32) -> f32 {
+    (ticks - 2048) as f32 * 360.0 / TICKS_PER_REV
+}
+
+struct ArmBus {
+    bus: DynamixelBus,
+    // Switching modes drops torque, so only do it when actually needed
+    modes: HashMap<u8, OperatingMode>,
+}
+
+impl ArmBus {
+    fn ensure_mode(&mut self, id: u8, mode: OperatingMode) -> Result<()> {
+        if self.modes.get(&id) != Some(&mode) {
+            self.bus.set_operating_mode(id, mode)?;
+            self.modes.insert(id, mode);
+        }
+        self.bus.set_torque(id, true)
+    }
+}
+
+pub struct Arm {
+    bus: Mutex<ArmBus>,
+    joints: Vec<Joint>,
+}
+
+impl Arm {
+    pub fn open(path: &str, baud: u32) -> Result<Self> {
+        let mut bus = DynamixelBus::open(path, baud)?;
+        let mut modes = HashMap::new();
+        for joint in JOINTS {
+            bus.ping(joint.id).map_err(|e| {
+                anyhow!(
+                    "joint '{}' (id {}) not responding: {}",
+                    joint.name,
+                    joint.id,
+                    e
+                )
+            })?;
+            bus.set_operating_mode(joint.id, OperatingMode::Position)?;
+            modes.insert(joint.id, OperatingMode::Position);
+        }
+        println!("[OK] Arm ready with {} joints", JOINTS.len());
+        Ok(Self {
+            bus: Mutex::new(ArmBus { bus, modes }),
+            joints: JOINTS.to_vec(),
+        })
+    }
+
+    fn joint(&self, name: &str) -> Result<&Joint> {
+        self.joints
+            .iter()
+            .find(|j| j.name == name)
+            .ok_or_else(|| anyhow!("unknown joint '{}'", name))
+    }
+
+    pub fn set_torque(&self, enabled: bool) -> Result<()> {
+        let mut bus = self.bus.lock().unwrap();
+        for joint in &self.joints {
+            bus.bus.set_torque(joint.id, enabled)?;
+        }
+        Ok(())
+    }
+
+    /// Moves a joint to an absolute angle, clamped to its limits.
+    pub fn set_angle(&self, name: &str, deg: f32) -> Result<()> {
+        let joint = self.joint(name)?;
+        let deg = deg.clamp(joint.min_deg, joint.max_deg);
+        let mut bus = self.bus.lock().unwrap();
+        bus.ensure_mode(joint.id, OperatingMode::Position)?;
+        bus.bus.set_goal_position(joint.id, deg_to_ticks(deg))
+    }
+
+    /// Spins a joint continuously; only meaningful for the base and gripper.
+    pub fn set_velocity(&self, name: &str, rpm: f32) -> Result<()> {
+        let joint = self.joint(name)?;
+        let mut bus = self.bus.lock().unwrap();
+        bus.ensure_mode(joint.id, OperatingMode::Velocity)?;
+        bus.bus
+            .set_goal_velocity(joint.id, (rpm / VELOCITY_UNIT_RPM).round() as i32)
+    }
+
+    pub fn state(&self) -> Result<Vec<JointState>> {
+        let mut bus = self.bus.lock().unwrap();
+        self.joints
+            .iter()
+            .map(|joint| {
+                let raw = bus.bus.status(joint.id)?;
+                Ok(JointState {
+                    name: joint.name,
+                    angle_deg: ticks_to_deg(raw.position),
+                    velocity_rpm: raw.velocity as f32 * VELOCITY_UNIT_RPM,
+                    raw,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,99 @@
+//! Actions run once at startup, so the robot is match-ready the moment
+//! systemd starts it. Configured in `data/autostart.json`, e.g.
+//!
+//! ```json
+//! [
+//!   { "action": "start_recording", "name": "qualifier" },
+//!   { "action": "enter_mode", "mode": "autonomous", "mission": "patrol" }
+//! ]
+//! ```
+//!
+//! Nothing runs until the startup self-test passes (the camera delivers a
+//! frame). A mission restored paused after a crash is left alone. A
+//! recording started here ends with the first autonomous run, whose report
+//! it becomes; without one it runs until `POST /sessions/stop`.
+
+use crate::state::AppState;
+use raspibot_protocol::mission::MissionMode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const AUTOSTART_PATH: &str = "data/autostart.json";
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AutostartAction {
+    StartRecording {
+        #[serde(default)]
+        name: Option<String>,
+    },
+    EnterMode {
+        #[serde(flatten)]
+        mode: MissionMode,
+    },
+}
+
+fn load() -> Vec<AutostartAction> {
+    let Ok(text) = std::fs::read_to_string(AUTOSTART_PATH) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        println!("[WARN] Ignoring invalid {}: {}", AUTOSTART_PATH, e);
+        Vec::new()
+    })
+}
+
+async fn self_test(state: &AppState) -> Result<(), String> {
+    let mut frames = state.frames.watch();
+    match tokio::time::timeout(SELF_TEST_TIMEOUT, frames.wait_for(Option::is_some)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(_)) => Err("capture stopped".to_string()),
+        Err(_) => Err(format!(
+            "no camera frame within {}s",
+            SELF_TEST_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+fn run(state: &AppState, action: AutostartAction) {
+    match action {
+        AutostartAction::StartRecording { name } => {
+            match state.sessions.start_for_run(name, state.config_snapshot()) {
+                Ok(_) => state.frames.set_exposure_lock(true),
+                Err(e) => println!("[WARN] Autostart could not start recording: {}", e),
+            }
+        }
+        AutostartAction::EnterMode { mode } => {
+            if state.mission.mode() != MissionMode::Idle {
+                println!(
+                    "[INFO] Autostart leaves mission in {:?}",
+                    state.mission.mode()
+                );
+                return;
+            }
+            state.mission.set_mode(mode, "autostart");
+        }
+    }
+}
+
+/// Runs the configured actions in order once the self-test passes.
+pub fn start_autostart(state: AppState) {
+    let actions = load();
+    if actions.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        if let Err(e) = self_test(&state).await {
+            println!("[WARN] Self-test failed ({}), skipping autostart", e);
+            return;
+        }
+        println!(
+            "[INFO] Self-test passed, running {} autostart action(s)",
+            actions.len()
+        );
+        for action in actions {
+            run(&state, action);
+        }
+    });
+}
@@ -0,0 +1,256 @@
+//! `bench-capture` subcommand: runs every capture backend we can open for a
+//! fixed time and reports achieved FPS, CPU usage and frame-interval jitter,
+//! to pick the default pipeline per camera model:
+//!
+//! ```text
+//! backend_rust bench-capture [--seconds 10] [--resolution 1280x720] [--fps 30] [--json report.json]
+//! ```
+
+use crate::camera::{parse_resolution, CaptureSettings};
+use crate::config::CameraConfig;
+use opencv::{core::Mat, prelude::*, videoio};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Frames read before measuring, while the sensor and AE settle.
+const WARMUP_FRAMES: usize = 10;
+/// `/proc/self/stat` times are in clock ticks; USER_HZ is 100 on every
+/// kernel we deploy on.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+pub struct BenchArgs {
+    pub seconds: f64,
+    pub settings: CaptureSettings,
+    /// Supplies the GStreamer pipeline.
+    pub camera: CameraConfig,
+    pub json: Option<PathBuf>,
+}
+
+impl BenchArgs {
+    /// Parses the arguments after `bench-capture`; the capture defaults come
+    /// from the camera configuration, like for the normal camera thread.
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        camera: CameraConfig,
+    ) -> Result<Self, String> {
+        let mut parsed = Self {
+            seconds: 10.0,
+            settings: camera.video,
+            camera,
+            json: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seconds" => {
+                    parsed.seconds = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|s: &f64| *s > 0.0)
+                        .ok_or("--seconds needs a positive number")?;
+                }
+                "--resolution" => {
+                    let (width, height) = args
+                        .next()
+                        .as_deref()
+                        .and_then(parse_resolution)
+                        .ok_or("--resolution needs WxH")?;
+                    parsed.settings.width = width;
+                    parsed.settings.height = height;
+                }
+                "--fps" => {
+                    parsed.settings.fps = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or("--fps needs a number")?;
+                }
+                "--json" => {
+                    parsed.json = Some(args.next().ok_or("--json needs a path")?.into());
+                }
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BackendResult {
+    backend: &'static str,
+    frames: usize,
+    /// Resolution actually delivered, which may differ from the request.
+    width: i32,
+    height: i32,
+    fps: f64,
+    /// Process CPU time over wall time; 100 = one full core.
+    cpu_percent: f64,
+    mean_interval_ms: f64,
+    /// Standard deviation of the frame interval.
+    jitter_ms: f64,
+    p99_interval_ms: f64,
+    max_interval_ms: f64,
+    dropped_reads: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Report {
+    requested_width: i32,
+    requested_height: i32,
+    requested_fps: i32,
+    seconds: f64,
+    results: Vec<BackendResult>,
+}
+
+fn open_backend(
+    name: &str,
+    camera: &CameraConfig,
+    settings: &CaptureSettings,
+) -> Option<videoio::VideoCapture> {
+    let cap = match name {
+        "gstreamer" => videoio::VideoCapture::from_file(
+            &camera.pipeline(settings, false),
+            videoio::CAP_GSTREAMER,
+        ),
+        "v4l2" => videoio::VideoCapture::new(0, videoio::CAP_V4L2),
+        _ => videoio::VideoCapture::new(0, videoio::CAP_ANY),
+    };
+    let mut cap = cap.ok()?;
+    if !cap.is_opened().unwrap_or(false) {
+        return None;
+    }
+    if name != "gstreamer" {
+        let _ = cap.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64);
+        let _ = cap.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64);
+        let _ = cap.set(videoio::CAP_PROP_FPS, settings.fps as f64);
+    }
+    Some(cap)
+}
+
+/// User + system CPU time of this process, in seconds.
+fn process_cpu_seconds() -> Option<f64> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so split after its closing paren
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: f64 = fields.get(11)?.parse().ok()?;
+    let stime: f64 = fields.get(12)?.parse().ok()?;
+    Some((utime + stime) / CLOCK_TICKS_PER_SEC)
+}
+
+fn bench_backend(
+    name: &'static str,
+    cap: &mut videoio::VideoCapture,
+    seconds: f64,
+) -> BackendResult {
+    let mut frame = Mat::default();
+    for _ in 0..WARMUP_FRAMES {
+        let _ = cap.read(&mut frame);
+    }
+
+    let duration = Duration::from_secs_f64(seconds);
+    let mut frames = 0;
+    let mut intervals = Vec::new();
+    let mut dropped_reads = 0;
+    let cpu_start = process_cpu_seconds();
+    let start = Instant::now();
+    let mut last = start;
+    while start.elapsed() < duration {
+        if !cap.read(&mut frame).unwrap_or(false) || frame.empty() {
+            dropped_reads += 1;
+            continue;
+        }
+        let now = Instant::now();
+        // The first interval includes the wait for the first frame
+        if frames > 0 {
+            intervals.push(now.duration_since(last).as_secs_f64() * 1000.0);
+        }
+        frames += 1;
+        last = now;
+    }
+    let wall = start.elapsed().as_secs_f64();
+    let cpu_percent = match (cpu_start, process_cpu_seconds()) {
+        (Some(a), Some(b)) => (b - a) / wall * 100.0,
+        _ => f64::NAN,
+    };
+
+    let n = intervals.len().max(1) as f64;
+    let mean = intervals.iter().sum::<f64>() / n;
+    let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+    let mut sorted = intervals.clone();
+    sorted.sort_by(f64::total_cmp);
+    let p99 = sorted
+        .get(((sorted.len() as f64 * 0.99) as usize).min(sorted.len().saturating_sub(1)))
+        .copied()
+        .unwrap_or(0.0);
+
+    BackendResult {
+        backend: name,
+        frames,
+        width: frame.cols(),
+        height: frame.rows(),
+        fps: frames as f64 / wall,
+        cpu_percent,
+        mean_interval_ms: mean,
+        jitter_ms: variance.sqrt(),
+        p99_interval_ms: p99,
+        max_interval_ms: sorted.last().copied().unwrap_or(0.0),
+        dropped_reads,
+    }
+}
+
+pub fn run(args: &BenchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = &args.settings;
+    println!(
+        "[INFO] Benchmarking capture at {}x{}@{} for {:.0}s per backend",
+        settings.width, settings.height, settings.fps, args.seconds
+    );
+
+    let mut results = Vec::new();
+    // One at a time: the sensor can only be opened by one backend at once
+    for name in ["gstreamer", "v4l2", "opencv"] {
+        let Some(mut cap) = open_backend(name, &args.camera, settings) else {
+            println!("[WARN] Backend '{}' unavailable, skipping", name);
+            continue;
+        };
+        println!("[INFO] Running '{}'...", name);
+        results.push(bench_backend(name, &mut cap, args.seconds));
+        let _ = cap.release();
+    }
+    if results.is_empty() {
+        return Err("no capture backend could be opened".into());
+    }
+
+    println!(
+        "{:<10} {:>10} {:>7} {:>6} {:>9} {:>9} {:>9} {:>8}",
+        "backend", "size", "fps", "cpu%", "mean ms", "jitter", "p99 ms", "dropped"
+    );
+    for r in &results {
+        println!(
+            "{:<10} {:>10} {:>7.1} {:>6.1} {:>9.2} {:>9.2} {:>9.2} {:>8}",
+            r.backend,
+            format!("{}x{}", r.width, r.height),
+            r.fps,
+            r.cpu_percent,
+            r.mean_interval_ms,
+            r.jitter_ms,
+            r.p99_interval_ms,
+            r.dropped_reads
+        );
+    }
+
+    if let Some(path) = &args.json {
+        let report = Report {
+            requested_width: settings.width,
+            requested_height: settings.height,
+            requested_fps: settings.fps,
+            seconds: args.seconds,
+            results,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("[OK] Report written to {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+//! `bench-postprocess` subcommand: times YOLO output decoding and NMS,
+//! sequential against parallel, on synthetic heads of every common input
+//! size, and suggests the `YOLO_PARALLEL_MIN_*` cutoffs for this machine:
+//!
+//! ```text
+//! backend_rust bench-postprocess [--classes 80] [--iterations 200] [--json report.json]
+//! ```
+
+use crate::nms::{nms, Detection};
+use crate::yolo::decode_output;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Square input sizes benchmarked; anchors are summed over strides 8/16/32.
+const INPUT_SIZES: [usize; 5] = [256, 320, 416, 480, 640];
+const STRIDES: [usize; 3] = [8, 16, 32];
+const CONFIDENCE: f32 = 0.25;
+const IOU: f32 = 0.45;
+/// Share of anchors that fire on one of the synthetic objects.
+const HOT_FRACTION: f32 = 0.05;
+/// Objects in the synthetic scene; hot anchors cluster around them, so NMS
+/// has real overlaps to suppress.
+const OBJECTS: usize = 12;
+
+pub struct PostprocessBenchArgs {
+    pub classes: usize,
+    pub iterations: usize,
+    pub json: Option<PathBuf>,
+}
+
+impl PostprocessBenchArgs {
+    /// Parses the arguments after `bench-postprocess`; anything else is an
+    /// error, so a mistyped flag doesn't silently bench the defaults.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self {
+            classes: 80,
+            iterations: 200,
+            json: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--classes" => {
+                    parsed.classes = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|c: &usize| *c > 0)
+                        .ok_or("--classes needs a positive number")?;
+                }
+                "--iterations" => {
+                    parsed.iterations = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .filter(|i: &usize| *i > 0)
+                        .ok_or("--iterations needs a positive number")?;
+                }
+                "--json" => {
+                    parsed.json = Some(args.next().ok_or("--json needs a path")?.into());
+                }
+                other => return Err(format!("bench-postprocess: unknown argument '{}'", other)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SizeResult {
+    input_size: usize,
+    anchors: usize,
+    /// Candidates left after decoding, i.e. NMS input.
+    boxes: usize,
+    decode_seq_us: f64,
+    decode_par_us: f64,
+    nms_seq_us: f64,
+    nms_par_us: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Report {
+    classes: usize,
+    iterations: usize,
+    threads: usize,
+    results: Vec<SizeResult>,
+    /// Suggested cutoffs; `None` when parallel never won.
+    min_anchors: Option<usize>,
+    min_boxes: Option<usize>,
+}
+
+/// xorshift64*, seeded, so every run scores the same head.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, lo: f32, hi: f32) -> f32 {
+        lo + (hi - lo) * self.next_f32()
+    }
+}
+
+/// A `[1, 4 + classes, anchors]` head with mostly background anchors and a
+/// few clusters of confident ones.
+fn synthetic_head(size: usize, anchors: usize, classes: usize) -> Vec<f32> {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15 ^ anchors as u64);
+    let objects: Vec<[f32; 5]> = (0..OBJECTS)
+        .map(|i| {
+            let s = size as f32;
+            [
+                rng.range(0.1, 0.9) * s,
+                rng.range(0.1, 0.9) * s,
+                rng.range(0.05, 0.3) * s,
+                rng.range(0.05, 0.3) * s,
+                (i % classes) as f32,
+            ]
+        })
+        .collect();
+
+    let channels = 4 + classes;
+    let mut data = vec![0.0f32; channels * anchors];
+    for anchor in 0..anchors {
+        let hot = rng.next_f32() < HOT_FRACTION;
+        let (cx, cy, w, h, class) = if hot {
+            let [cx, cy, w, h, class] = objects[anchor % OBJECTS];
+            let jitter = 0.1 * w.min(h);
+            (
+                cx + rng.range(-jitter, jitter),
+                cy + rng.range(-jitter, jitter),
+                w * rng.range(0.9, 1.1),
+                h * rng.range(0.9, 1.1),
+                Some(class as usize),
+            )
+        } else {
+            let s = size as f32;
+            (
+                rng.range(0.0, s),
+                rng.range(0.0, s),
+                rng.range(4.0, 40.0),
+                rng.range(4.0, 40.0),
+                None,
+            )
+        };
+        for (channel, value) in [cx, cy, w, h].into_iter().enumerate() {
+            data[channel * anchors + anchor] = value;
+        }
+        for c in 0..classes {
+            data[(4 + c) * anchors + anchor] = rng.range(0.0, 0.1);
+        }
+        if let Some(class) = class {
+            data[(4 + class) * anchors + anchor] = rng.range(0.3, 1.0);
+        }
+    }
+    data
+}
+
+/// Median wall time of `f` over `iterations` runs, in microseconds.
+fn median_us(iterations: usize, mut f: impl FnMut()) -> f64 {
+    let mut times: Vec<f64> = (0..iterations)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed().as_secs_f64() * 1e6
+        })
+        .collect();
+    times.sort_by(f64::total_cmp);
+    times[times.len() / 2]
+}
+
+fn bench_size(size: usize, args: &PostprocessBenchArgs) -> Result<SizeResult, String> {
+    let anchors: usize = STRIDES.iter().map(|s| (size / s).pow(2)).sum();
+    let data = synthetic_head(size, anchors, args.classes);
+    let shape = [1, (4 + args.classes) as i64, anchors as i64];
+
+    let decode = |min_anchors: usize| -> Result<Vec<Detection>, String> {
+        let mut out = Vec::new();
+        decode_output(&shape, &data, CONFIDENCE, None, min_anchors, &mut out)
+            .map_err(|e| e.to_string())?;
+        Ok(out)
+    };
+    let sequential = decode(usize::MAX)?;
+    if decode(0)? != sequential {
+        return Err(format!("parallel decode differs at {} px", size));
+    }
+    let decode_seq_us = median_us(args.iterations, || {
+        let _ = decode(usize::MAX);
+    });
+    let decode_par_us = median_us(args.iterations, || {
+        let _ = decode(0);
+    });
+
+    let (mut seq, mut par) = (sequential.clone(), sequential.clone());
+    nms(&mut seq, IOU, false);
+    nms(&mut par, IOU, true);
+    if seq != par {
+        return Err(format!("parallel NMS differs at {} px", size));
+    }
+    let mut scratch = Vec::with_capacity(sequential.len());
+    let mut run_nms = |parallel: bool| {
+        scratch.clear();
+        scratch.extend_from_slice(&sequential);
+        nms(&mut scratch, IOU, parallel);
+    };
+    let nms_seq_us = median_us(args.iterations, || run_nms(false));
+    let nms_par_us = median_us(args.iterations, || run_nms(true));
+
+    Ok(SizeResult {
+        input_size: size,
+        anchors,
+        boxes: sequential.len(),
+        decode_seq_us,
+        decode_par_us,
+        nms_seq_us,
+        nms_par_us,
+    })
+}
+
+/// Smallest size from which parallel wins at every larger size too.
+fn crossover(
+    results: &[SizeResult],
+    key: impl Fn(&SizeResult) -> (usize, f64, f64),
+) -> Option<usize> {
+    let mut sorted: Vec<(usize, f64, f64)> = results.iter().map(key).collect();
+    sorted.sort_by_key(|r| r.0);
+    let losing = sorted.iter().rposition(|r| r.2 >= r.1);
+    match losing {
+        None => sorted.first().map(|r| r.0),
+        Some(i) => sorted.get(i + 1).map(|r| r.0),
+    }
+}
+
+pub fn run(args: &PostprocessBenchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let threads = rayon::current_num_threads();
+    println!(
+        "[INFO] Benchmarking postprocessing: {} classes, {} iterations, {} threads",
+        args.classes, args.iterations, threads
+    );
+
+    let mut results = Vec::new();
+    for size in INPUT_SIZES {
+        results.push(bench_size(size, args)?);
+    }
+
+    println!(
+        "{:>6} {:>8} {:>6} {:>11} {:>11} {:>9} {:>9}",
+        "input", "anchors", "boxes", "decode us", "parallel", "nms us", "parallel"
+    );
+    for r in &results {
+        println!(
+            "{:>6} {:>8} {:>6} {:>11.1} {:>11.1} {:>9.1} {:>9.1}",
+            r.input_size,
+            r.anchors,
+            r.boxes,
+            r.decode_seq_us,
+            r.decode_par_us,
+            r.nms_seq_us,
+            r.nms_par_us
+        );
+    }
+
+    let min_anchors = crossover(&results, |r| (r.anchors, r.decode_seq_us, r.decode_par_us));
+    let min_boxes = crossover(&results, |r| (r.boxes, r.nms_seq_us, r.nms_par_us));
+    let suggest = |name: &str, value: Option<usize>| match value {
+        Some(v) => println!("[OK] {}={}", name, v),
+        None => println!("[OK] {}={} (parallel never won)", name, usize::MAX),
+    };
+    suggest("YOLO_PARALLEL_MIN_ANCHORS", min_anchors);
+    suggest("YOLO_PARALLEL_MIN_BOXES", min_boxes);
+
+    if let Some(path) = &args.json {
+        let report = Report {
+            classes: args.classes,
+            iterations: args.iterations,
+            threads,
+            results,
+            min_anchors,
+            min_boxes,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        println!("[OK] Report written to {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+//! Per-run telemetry blackbox: samples tagged with the stream they belong
+//! to (`imu`, `drive`, `detections`, ...), written to
+//! `data/sessions/<id>/telemetry.bbx`.
+//!
+//! The file is a run of zstd-compressed chunks, each a little-endian `u32`
+//! byte count followed by the frame; inside, every sample is a `u32` length
+//! and its JSON. A chunk closes at `CHUNK_BYTES` of samples or after
+//! `CHUNK_INTERVAL`, which bounds what a power cut loses. Every closed
+//! chunk gets a 16-byte entry in the sparse index `telemetry.bbx.idx`, its
+//! offset and latest `t_s`, so [`read_range`] skips to a time without
+//! decompressing what comes before it, stops once past the end of the range
+//! and picks up again after a corrupt chunk.
+//!
+//! Runs recorded before as one JSON line per sample (`telemetry.jsonl`)
+//! are still read; `logdump` converts a run back to that.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub const FILE_NAME: &str = "telemetry.bbx";
+pub const INDEX_FILE_NAME: &str = "telemetry.bbx.idx";
+/// One JSON line per sample, as runs were recorded before `FILE_NAME`.
+pub const LEGACY_FILE_NAME: &str = "telemetry.jsonl";
+
+/// Uncompressed samples per chunk, about.
+const CHUNK_BYTES: usize = 64 * 1024;
+const CHUNK_INTERVAL: Duration = Duration::from_secs(1);
+const ZSTD_LEVEL: i32 = 3;
+/// A chunk claiming more than this is taken as torn rather than read.
+const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+/// Offset (`u64`) and latest `t_s` (`f64`) of a chunk, little-endian.
+const INDEX_ENTRY_BYTES: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Seconds since the run started.
+    pub t_s: f64,
+    pub stream: String,
+    pub values: Map<String, Value>,
+}
+
+pub struct BlackboxWriter {
+    out: BufWriter<File>,
+    index: File,
+    /// Bytes written to `out`, where the next chunk starts.
+    offset: u64,
+    /// Samples of the open chunk, length-prefixed.
+    chunk: Vec<u8>,
+    chunk_max_t_s: f64,
+    chunk_opened: Instant,
+}
+
+impl BlackboxWriter {
+    pub fn create(session_dir: &Path) -> std::io::Result<Self> {
+        let file = File::create(session_dir.join(FILE_NAME))?;
+        let index = File::create(session_dir.join(INDEX_FILE_NAME))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+            index,
+            offset: 0,
+            chunk: Vec::new(),
+            chunk_max_t_s: f64::NEG_INFINITY,
+            chunk_opened: Instant::now(),
+        })
+    }
+
+    pub fn write(&mut self, sample: &Sample) -> std::io::Result<()> {
+        let json = serde_json::to_vec(sample)?;
+        if self.chunk.is_empty() {
+            self.chunk_opened = Instant::now();
+        }
+        self.chunk
+            .extend_from_slice(&(json.len() as u32).to_le_bytes());
+        self.chunk.extend_from_slice(&json);
+        self.chunk_max_t_s = self.chunk_max_t_s.max(sample.t_s);
+        if self.chunk.len() >= CHUNK_BYTES || self.chunk_opened.elapsed() >= CHUNK_INTERVAL {
+            self.close_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Compresses the open chunk into the file and indexes it.
+    fn close_chunk(&mut self) -> std::io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let frame = zstd::bulk::compress(&self.chunk, ZSTD_LEVEL)?;
+        self.out.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.out.write_all(&frame)?;
+        // The chunk is in the file before its index entry, so the index
+        // never points past the end
+        self.out.flush()?;
+        let mut entry = [0u8; INDEX_ENTRY_BYTES];
+        entry[..8].copy_from_slice(&self.offset.to_le_bytes());
+        entry[8..].copy_from_slice(&self.chunk_max_t_s.to_le_bytes());
+        self.index.write_all(&entry)?;
+        self.offset += 4 + frame.len() as u64;
+        self.chunk.clear();
+        self.chunk_max_t_s = f64::NEG_INFINITY;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.close_chunk()?;
+        self.out.flush()
+    }
+}
+
+impl Drop for BlackboxWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<usize> {
+    let bytes: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(bytes) as usize)
+}
+
+/// The samples of the chunk at the reader's position, `None` at the end of
+/// the file; fails on a torn or corrupt chunk. Malformed samples are
+/// skipped.
+fn read_chunk(file: &mut impl Read) -> std::io::Result<Option<Vec<Sample>>> {
+    let mut len = [0u8; 4];
+    match file.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_CHUNK_BYTES {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("chunk of {} bytes", len),
+        ));
+    }
+    let mut frame = vec![0u8; len];
+    file.read_exact(&mut frame)?;
+    let data = zstd::stream::decode_all(frame.as_slice())?;
+    let mut samples = Vec::new();
+    let mut rest = data.as_slice();
+    while let Some(len) = read_u32(rest) {
+        let Some(json) = rest.get(4..4 + len) else {
+            break;
+        };
+        if let Ok(sample) = serde_json::from_slice(json) {
+            samples.push(sample);
+        }
+        rest = &rest[4 + len..];
+    }
+    Ok(Some(samples))
+}
+
+/// The offset and latest `t_s` of every indexed chunk, in file order; empty
+/// without an index.
+fn read_index(session_dir: &Path) -> Vec<(u64, f64)> {
+    let Ok(index) = std::fs::read(session_dir.join(INDEX_FILE_NAME)) else {
+        return Vec::new();
+    };
+    index
+        .chunks_exact(INDEX_ENTRY_BYTES)
+        .filter_map(|entry| {
+            let offset = u64::from_le_bytes(entry[..8].try_into().ok()?);
+            let max_t_s = f64::from_le_bytes(entry[8..].try_into().ok()?);
+            Some((offset, max_t_s))
+        })
+        .collect()
+}
+
+/// Where to start reading for samples at or after `from_s`: the first
+/// indexed chunk ending there or later, else the last indexed one (later
+/// chunks may not be indexed yet). The start of the file without an index.
+fn seek_offset(index: &[(u64, f64)], from_s: f64) -> u64 {
+    index
+        .iter()
+        .find(|(_, max_t_s)| *max_t_s >= from_s)
+        .or(index.last())
+        .map_or(0, |(offset, _)| *offset)
+}
+
+fn read_legacy(session_dir: &Path) -> std::io::Result<Vec<Sample>> {
+    let file = File::open(session_dir.join(LEGACY_FILE_NAME))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Reads every sample of a run; a torn last chunk or line (e.g. after a
+/// power cut) is skipped, and so is a corrupt chunk the index can step
+/// over.
+pub fn read(session_dir: &Path) -> std::io::Result<Vec<Sample>> {
+    read_range(session_dir, f64::NEG_INFINITY, f64::INFINITY)
+}
+
+/// Samples with `from_s <= t_s < to_s`, in the order recorded, from the
+/// first chunk that can hold any to the first one reaching `to_s`.
+pub fn read_range(session_dir: &Path, from_s: f64, to_s: f64) -> std::io::Result<Vec<Sample>> {
+    let in_range = |sample: &Sample| sample.t_s >= from_s && sample.t_s < to_s;
+    let file = match File::open(session_dir.join(FILE_NAME)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let mut samples = read_legacy(session_dir)?;
+            samples.retain(in_range);
+            return Ok(samples);
+        }
+        Err(e) => return Err(e),
+    };
+    let index = read_index(session_dir);
+    let mut file = BufReader::new(file);
+    let mut offset = seek_offset(&index, from_s);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut samples = Vec::new();
+    loop {
+        let chunk = match read_chunk(&mut file) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                // Picks up at the next chunk the index knows of; a torn
+                // last chunk has none
+                let Some(&(next, _)) = index.iter().find(|(o, _)| *o > offset) else {
+                    break;
+                };
+                eprintln!(
+                    "[ERR] Skipping blackbox chunk at byte {} of {}: {}",
+                    offset,
+                    session_dir.display(),
+                    e
+                );
+                offset = next;
+                file.seek(SeekFrom::Start(offset))?;
+                continue;
+            }
+        };
+        // Chunks not indexed yet go by their samples
+        let max_t_s = match index.iter().find(|(o, _)| *o == offset) {
+            Some(&(_, max_t_s)) => max_t_s,
+            None => chunk
+                .iter()
+                .map(|s| s.t_s)
+                .fold(f64::NEG_INFINITY, f64::max),
+        };
+        samples.extend(chunk.into_iter().filter(in_range));
+        if max_t_s >= to_s {
+            break;
+        }
+        offset = file.stream_position()?;
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("raspibot-blackbox-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Records samples at `t_s` 0..30, ten to a chunk.
+    fn record(dir: &Path) {
+        let mut writer = BlackboxWriter::create(dir).unwrap();
+        for t in 0..30 {
+            let mut values = Map::new();
+            values.insert("n".to_string(), Value::from(t));
+            let sample = Sample {
+                t_s: t as f64,
+                stream: if t % 2 == 0 { "imu" } else { "drive" }.to_string(),
+                values,
+            };
+            writer.write(&sample).unwrap();
+            if t % 10 == 9 {
+                writer.close_chunk().unwrap();
+            }
+        }
+    }
+
+    fn times(samples: &[Sample]) -> Vec<f64> {
+        samples.iter().map(|s| s.t_s).collect()
+    }
+
+    #[test]
+    fn reads_back_a_range() {
+        let dir = session_dir("range");
+        record(&dir);
+        assert_eq!(read_index(&dir).len(), 3);
+        let samples = read_range(&dir, 5.0, 25.0).unwrap();
+        assert_eq!(times(&samples), (5..25).map(f64::from).collect::<Vec<_>>());
+        assert_eq!(samples[0].stream, "drive");
+        assert_eq!(samples[0].values["n"], Value::from(5));
+        assert_eq!(read(&dir).unwrap().len(), 30);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_a_corrupt_chunk() {
+        let dir = session_dir("corrupt");
+        record(&dir);
+        let path = dir.join(FILE_NAME);
+        let mut bytes = std::fs::read(&path).unwrap();
+        // Garbles the frame of the middle chunk, leaving its length
+        let (middle, _) = read_index(&dir)[1];
+        for byte in &mut bytes[middle as usize + 4..middle as usize + 12] {
+            *byte = 0xff;
+        }
+        std::fs::write(&path, bytes).unwrap();
+        let expected: Vec<f64> = (0..10).chain(20..30).map(f64::from).collect();
+        assert_eq!(times(&read(&dir).unwrap()), expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
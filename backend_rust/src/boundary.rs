@@ -0,0 +1,278 @@
+//! Arena boundary from white tape on the dark floor, and the containment
+//! constraint that keeps autonomous modes from driving across it.
+//!
+//! Detection runs on the bottom part of the camera image only: threshold the
+//! bright tape, clean it up, and fit line segments. The constraint then looks
+//! at the closest tape inside a forward corridor and scales forward motion
+//! down to zero as it approaches; turning and reversing stay allowed.
+
+use crate::arbiter::{CommandSource, Constraint};
+use crate::camera::FrameManager;
+use crate::dispatch::Decimation;
+use crate::faults;
+use crate::threads;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use opencv::{
+    core::{self, Mat, Size, Vec4i, Vector},
+    imgproc,
+    prelude::*,
+};
+use raspibot_protocol::boundary::{BoundaryConfig, BoundaryEstimate};
+use raspibot_protocol::drive::DriveCommand;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const CONFIG_PATH: &str = "data/boundary.json";
+const DETECT_FPS: f32 = 15.0;
+
+pub struct BoundaryMonitor {
+    path: PathBuf,
+    config: Mutex<BoundaryConfig>,
+    estimate: Mutex<Option<(Instant, BoundaryEstimate)>>,
+}
+
+impl BoundaryMonitor {
+    fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            config: Mutex::new(config),
+            estimate: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> BoundaryConfig {
+        *self.config.lock().unwrap()
+    }
+
+    pub fn set_config(&self, config: BoundaryConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config;
+        if !config.enabled {
+            *self.estimate.lock().unwrap() = None;
+        }
+        Ok(())
+    }
+
+    pub fn estimate(&self) -> Option<BoundaryEstimate> {
+        self.estimate
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(_, e)| e.clone())
+    }
+
+    /// The latest estimate, unless it is older than `max_age_ms`.
+    fn fresh_estimate(&self, max_age_ms: u64) -> Option<BoundaryEstimate> {
+        let estimate = self.estimate.lock().unwrap();
+        let (at, estimate) = estimate.as_ref()?;
+        (at.elapsed() <= Duration::from_millis(max_age_ms)).then(|| estimate.clone())
+    }
+
+    fn update(&self, lines: Vec<[f32; 4]>, nearest_ahead: Option<f32>) {
+        let estimate = BoundaryEstimate {
+            lines,
+            nearest_ahead,
+            taken_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        *self.estimate.lock().unwrap() = Some((Instant::now(), estimate));
+    }
+}
+
+impl Constraint for BoundaryMonitor {
+    fn name(&self) -> &'static str {
+        "boundary"
+    }
+
+    fn apply(&self, source: &CommandSource, command: DriveCommand) -> DriveCommand {
+        let config = self.config();
+        if !config.enabled || !source.is_autonomous() {
+            return command;
+        }
+        let (forward, turn) = command.forward_turn();
+        if forward <= 0.0 {
+            return command;
+        }
+        // No recent view of the floor: don't drive forward blind
+        let Some(estimate) = self.fresh_estimate(config.max_age_ms) else {
+            return DriveCommand::from_forward_turn(0.0, turn);
+        };
+        let Some(distance) = estimate.nearest_ahead else {
+            return command;
+        };
+        let scale = if config.slow_margin > config.stop_margin {
+            ((distance - config.stop_margin) / (config.slow_margin - config.stop_margin))
+                .clamp(0.0, 1.0)
+        } else if distance > config.stop_margin {
+            1.0
+        } else {
+            0.0
+        };
+        DriveCommand::from_forward_turn(forward * scale, turn)
+    }
+}
+
+/// Finds tape segments in the floor region of `frame` and the distance to
+/// the closest one inside the forward corridor.
+fn detect(frame: &Mat, config: &BoundaryConfig) -> opencv::Result<(Vec<[f32; 4]>, Option<f32>)> {
+    let (w, h) = (frame.cols(), frame.rows());
+    let top = ((h as f32 * config.roi_top.clamp(0.0, 0.9)) as i32).min(h - 1);
+    let floor = Mat::roi(frame, core::Rect::new(0, top, w, h - top))?;
+
+    let mut gray = Mat::default();
+    if floor.channels() == 1 {
+        floor.copy_to(&mut gray)?;
+    } else {
+        imgproc::cvt_color_def(&*floor, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+    }
+    let mut mask = Mat::default();
+    imgproc::threshold(
+        &gray,
+        &mut mask,
+        config.white_threshold as f64,
+        255.0,
+        imgproc::THRESH_BINARY,
+    )?;
+    // Opening drops specular highlights and other small bright speckles
+    let kernel = imgproc::get_structuring_element_def(imgproc::MORPH_RECT, Size::new(5, 5))?;
+    let mut cleaned = Mat::default();
+    imgproc::morphology_ex_def(&mask, &mut cleaned, imgproc::MORPH_OPEN, &kernel)?;
+
+    let mut segments = Vector::<Vec4i>::new();
+    imgproc::hough_lines_p(
+        &cleaned,
+        &mut segments,
+        1.0,
+        std::f64::consts::PI / 180.0,
+        40,
+        w as f64 / 8.0,
+        10.0,
+    )?;
+
+    let corridor = (
+        0.5 - config.corridor_width / 2.0,
+        0.5 + config.corridor_width / 2.0,
+    );
+    let lines: Vec<[f32; 4]> = segments
+        .iter()
+        .map(|s| {
+            [
+                s[0] as f32 / w as f32,
+                (s[1] + top) as f32 / h as f32,
+                s[2] as f32 / w as f32,
+                (s[3] + top) as f32 / h as f32,
+            ]
+        })
+        .collect();
+    let nearest_ahead = lines
+        .iter()
+        .filter_map(|l| corridor_distance(*l, corridor))
+        .reduce(f32::min);
+    Ok((lines, nearest_ahead))
+}
+
+/// Distance from the bottom edge to the lowest point of `line` within the
+/// corridor's x range, or `None` if the line misses the corridor.
+fn corridor_distance(line: [f32; 4], corridor: (f32, f32)) -> Option<f32> {
+    let [x1, y1, x2, y2] = line;
+    let lo = x1.min(x2).max(corridor.0);
+    let hi = x1.max(x2).min(corridor.1);
+    if lo > hi {
+        return None;
+    }
+    let y_at = |x: f32| {
+        if (x2 - x1).abs() < f32::EPSILON {
+            y1.max(y2)
+        } else {
+            y1 + (y2 - y1) * (x - x1) / (x2 - x1)
+        }
+    };
+    Some((1.0 - y_at(lo).max(y_at(hi))).max(0.0))
+}
+
+pub fn start_boundary_thread(frames: &FrameManager) -> Arc<BoundaryMonitor> {
+    let monitor = Arc::new(BoundaryMonitor::load());
+    let subscription = frames.subscribe("boundary", Decimation::MaxFps(DETECT_FPS));
+    let worker = Arc::clone(&monitor);
+    thread::spawn(move || {
+        threads::label("boundary");
+        loop {
+            if faults::killed("boundary") {
+                return;
+            }
+            let Some(frame) = subscription.recv_timeout(Duration::from_secs(1)) else {
+                continue;
+            };
+            let config = worker.config();
+            if !config.enabled {
+                continue;
+            }
+            match detect(&frame, &config) {
+                Ok((lines, nearest_ahead)) => worker.update(lines, nearest_ahead),
+                Err(e) => eprintln!("[ERR] Boundary detection failed: {}", e),
+            }
+        }
+    });
+    monitor
+}
+
+pub fn validate(config: &BoundaryConfig) -> Result<(), String> {
+    let fractions = [
+        config.roi_top,
+        config.corridor_width,
+        config.stop_margin,
+        config.slow_margin,
+    ];
+    if fractions.iter().any(|f| !(0.0..=1.0).contains(f)) {
+        return Err("roi_top, corridor_width and margins must be within 0..=1".to_string());
+    }
+    Ok(())
+}
+
+pub fn routes(monitor: Arc<BoundaryMonitor>) -> Router {
+    Router::new()
+        .route("/boundary", get(get_estimate))
+        .route("/boundary/config", get(get_config).put(set_config))
+        .with_state(monitor)
+}
+
+async fn get_estimate(
+    State(monitor): State<Arc<BoundaryMonitor>>,
+) -> Json<Option<BoundaryEstimate>> {
+    Json(monitor.estimate())
+}
+
+async fn get_config(State(monitor): State<Arc<BoundaryMonitor>>) -> Json<BoundaryConfig> {
+    Json(monitor.config())
+}
+
+async fn set_config(
+    State(monitor): State<Arc<BoundaryMonitor>>,
+    Json(config): Json<BoundaryConfig>,
+) -> Result<Json<BoundaryConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    monitor
+        .set_config(config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Boundary containment {}",
+        if config.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    Ok(Json(config))
+}
@@ -0,0 +1,169 @@
+//! Virtual bumper: obstacle safety from detections alone, for chassis
+//! without range sensors.
+//!
+//! A detection that covers a large share of the frame is close. The
+//! constraint finds the biggest box of an obstacle class inside a forward
+//! corridor and scales forward motion down to zero as it grows; turning and
+//! reversing stay allowed so the robot can get away.
+
+use crate::arbiter::{CommandSource, Constraint};
+use crate::camera::FrameManager;
+use crate::detections::DetectionHub;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::bumper::{BumperConfig, BumperStatus};
+use raspibot_protocol::drive::DriveCommand;
+use raspibot_protocol::inference::Freshness;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub const CONFIG_PATH: &str = "data/bumper.json";
+
+pub struct VirtualBumper {
+    path: PathBuf,
+    config: Mutex<BumperConfig>,
+    detections: Arc<DetectionHub>,
+    frames: Arc<FrameManager>,
+}
+
+impl VirtualBumper {
+    pub fn load(detections: Arc<DetectionHub>, frames: Arc<FrameManager>) -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            config: Mutex::new(config),
+            detections,
+            frames,
+        }
+    }
+
+    pub fn config(&self) -> BumperConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: BumperConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// The closest obstacle in the corridor and the forward scale it calls
+    /// for; `None` when there are no usable detections to judge by.
+    pub fn status(&self) -> Option<BumperStatus> {
+        let config = self.config();
+        let (width, height) = self.frames.frame_size()?;
+        let published = self.detections.latest()?;
+        let age_ms = published.age().as_millis() as u64;
+        if self.detections.limits().freshness(age_ms) == Freshness::Stale {
+            return None;
+        }
+
+        let frame_area = (width as f32) * (height as f32);
+        let corridor_left = width as f32 * (1.0 - config.corridor_width) / 2.0;
+        let corridor_right = width as f32 - corridor_left;
+        let nearest = published
+            .objects
+            .iter()
+            .filter(|o| config.classes.is_empty() || config.classes.contains(&o.class))
+            .filter(|o| {
+                let [x, _, w, _] = o.bbox;
+                (x + w) as f32 > corridor_left && (x as f32) < corridor_right
+            })
+            .map(|o| (o, (o.bbox[2] as f32 * o.bbox[3] as f32) / frame_area))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let Some((object, fraction)) = nearest else {
+            return Some(BumperStatus {
+                nearest_class: None,
+                nearest_fraction: 0.0,
+                forward_scale: 1.0,
+            });
+        };
+        let forward_scale = if config.stop_fraction > config.slow_fraction {
+            ((config.stop_fraction - fraction) / (config.stop_fraction - config.slow_fraction))
+                .clamp(0.0, 1.0)
+        } else if fraction < config.stop_fraction {
+            1.0
+        } else {
+            0.0
+        };
+        Some(BumperStatus {
+            nearest_class: Some(object.class.clone()),
+            nearest_fraction: fraction,
+            forward_scale,
+        })
+    }
+}
+
+impl Constraint for VirtualBumper {
+    fn name(&self) -> &'static str {
+        "bumper"
+    }
+
+    fn apply(&self, source: &CommandSource, command: DriveCommand) -> DriveCommand {
+        if !self.config.lock().unwrap().enabled {
+            return command;
+        }
+        let (forward, turn) = command.forward_turn();
+        if forward <= 0.0 {
+            return command;
+        }
+        let scale = match self.status() {
+            Some(status) => status.forward_scale,
+            // Autonomous modes don't drive forward blind; the operator
+            // still can, watching the stream
+            None if source.is_autonomous() => 0.0,
+            None => 1.0,
+        };
+        DriveCommand::from_forward_turn(forward * scale, turn)
+    }
+}
+
+pub fn validate(config: &BumperConfig) -> Result<(), String> {
+    let fractions = [
+        config.corridor_width,
+        config.slow_fraction,
+        config.stop_fraction,
+    ];
+    if fractions.iter().any(|f| !(0.0..=1.0).contains(f)) {
+        return Err("corridor_width and fractions must be within 0..=1".to_string());
+    }
+    Ok(())
+}
+
+pub fn routes(bumper: Arc<VirtualBumper>) -> Router {
+    Router::new()
+        .route("/bumper", get(get_status))
+        .route("/bumper/config", get(get_config).put(set_config))
+        .with_state(bumper)
+}
+
+async fn get_status(State(bumper): State<Arc<VirtualBumper>>) -> Json<Option<BumperStatus>> {
+    Json(bumper.status())
+}
+
+async fn get_config(State(bumper): State<Arc<VirtualBumper>>) -> Json<BumperConfig> {
+    Json(bumper.config())
+}
+
+async fn set_config(
+    State(bumper): State<Arc<VirtualBumper>>,
+    Json(config): Json<BumperConfig>,
+) -> Result<Json<BumperConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let enabled = config.enabled;
+    bumper
+        .set_config(config.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Virtual bumper {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(Json(config))
+}
@@ -1,90 +1,1309 @@
-use opencv::{
-    core,
-    prelude::*,
-    videoio,
+use crate::annotate;
+use crate::config::CameraConfig;
+use crate::detections::DetectionHub;
+use crate::dispatch::{ConsumerInfo, Decimation, FrameDispatcher, FrameSubscription};
+use crate::faults;
+use crate::pipeline::PipelineBarrier;
+use crate::privacy::MaskStore;
+use crate::rate::RateMeter;
+use crate::source::{self, FrameSource};
+use crate::state::AppState;
+use crate::threads;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
 };
+use opencv::{core, imgcodecs, imgproc, prelude::*, videoio};
+use raspibot_protocol::camera::{
+    CameraHealth, CameraRestart, CameraSettingsRequest, CameraSettingsStatus, CameraSummary,
+    CaptureState, ExposureLockRequest, ExposureStatus, ImageControls, LowLightRequest,
+    LowLightStatus, RestartCause,
+};
+use raspibot_protocol::rates::RateStats;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, oneshot, watch};
+
+/// Exposure/low-light state requested through the API, applied by the
+/// capture thread between reads.
+#[derive(Debug, Clone, Copy, Default)]
+struct Controls {
+    exposure_locked: bool,
+    low_light: bool,
+    grayscale: bool,
+}
+
+struct PendingControls {
+    current: Controls,
+    dirty: bool,
+}
+
+/// Video mode and image controls set through the API, taken up by the
+/// capture thread like `Controls`.
+struct PendingVideo {
+    current: CaptureSettings,
+    dirty: bool,
+}
+
+/// Manual exposure for low light, in V4L2 `exposure_absolute` units (100 us).
+const LOW_LIGHT_EXPOSURE: f64 = 300.0;
+const LOW_LIGHT_GAIN: f64 = 200.0;
+/// Analogue gain for low light on the libcamera pipeline, whose exposure is
+/// stretched to the whole frame interval instead.
+const LIBCAMERA_LOW_LIGHT_GAIN: f32 = 8.0;
+const STILL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Stills are sent in pieces so a multi-megabyte JPEG doesn't sit in one buffer.
+const STILL_CHUNK_SIZE: usize = 64 * 1024;
+/// How often a suspended capture thread checks whether to resume.
+const SUSPEND_POLL: Duration = Duration::from_millis(200);
+/// Failed reads in a row (50 ms apart) before the camera is reopened.
+const MAX_READ_FAILURES: u32 = 40;
+/// Empty reads in a row from the GStreamer pipeline before it counts as
+/// ended rather than hiccuping.
+const END_OF_STREAM_READS: u32 = 3;
+/// A read from the GStreamer pipeline waits this many frame intervals
+/// before it counts as stalled, and never less than `MIN_READ_TIMEOUT`,
+/// which covers libcamera bringing the sensor up.
+const READ_TIMEOUT_FRAMES: u32 = 3;
+const MIN_READ_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+/// How often image controls are read back from a V4L2 device, which
+/// changes them itself while on auto.
+const READBACK_INTERVAL: Duration = Duration::from_secs(1);
+/// Colour temperatures white balance can be set to, in kelvin.
+const WHITE_BALANCE_RANGE: std::ops::RangeInclusive<u32> = 1500..=15000;
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
 
 pub struct FrameManager {
-    raw_frame: Arc<Mutex<Option<core::Mat>>>,
+    /// Newest frame, shared by `Arc`: readers never wait on the capture
+    /// thread and never copy pixels unless they need to draw on them.
+    latest: watch::Sender<Option<Arc<core::Mat>>>,
+    dispatcher: FrameDispatcher<core::Mat>,
+    capture_rate: RateMeter,
+    controls: Mutex<PendingControls>,
+    controls_supported: AtomicBool,
+    video: Mutex<PendingVideo>,
+    /// Image controls last read back from a V4L2 device.
+    device_controls: Mutex<Option<ImageControls>>,
+    playback: AtomicBool,
+    stills: Mutex<Vec<oneshot::Sender<Result<Arc<core::Mat>, String>>>>,
+    masks: Arc<MaskStore>,
+    suspended: AtomicBool,
+    pipeline: PipelineBarrier,
+    /// Hot-plugged V4L2 camera standing in for the configured one.
+    hotplugged: Mutex<Option<String>>,
+    reattach: AtomicBool,
+    health: Mutex<CameraHealth>,
+    health_changes: broadcast::Sender<CameraHealth>,
+    restarts: broadcast::Sender<CameraRestart>,
 }
 
 impl FrameManager {
-    pub fn new() -> Self {
+    pub fn new(masks: Arc<MaskStore>) -> Self {
         Self {
-            raw_frame: Arc::new(Mutex::new(None)),
+            latest: watch::Sender::new(None),
+            dispatcher: FrameDispatcher::new(),
+            capture_rate: RateMeter::new(),
+            controls: Mutex::new(PendingControls {
+                current: Controls::default(),
+                dirty: false,
+            }),
+            controls_supported: AtomicBool::new(false),
+            video: Mutex::new(PendingVideo {
+                current: CaptureSettings::default(),
+                dirty: false,
+            }),
+            device_controls: Mutex::new(None),
+            playback: AtomicBool::new(false),
+            stills: Mutex::new(Vec::new()),
+            masks,
+            suspended: AtomicBool::new(false),
+            pipeline: PipelineBarrier::new(),
+            hotplugged: Mutex::new(None),
+            reattach: AtomicBool::new(false),
+            health: Mutex::new(CameraHealth {
+                state: CaptureState::Starting,
+                since_unix_ms: unix_ms(),
+                consecutive_failures: 0,
+                reconnects: 0,
+                last_frame_unix_ms: None,
+                last_error: None,
+                last_restart: None,
+            }),
+            health_changes: broadcast::channel(16).0,
+            restarts: broadcast::channel(8).0,
+        }
+    }
+
+    pub fn health(&self) -> CameraHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Health updates, one per state change.
+    pub fn subscribe_health(&self) -> broadcast::Receiver<CameraHealth> {
+        self.health_changes.subscribe()
+    }
+
+    /// Pipeline rebuilds, one per restart, with the cause.
+    pub fn subscribe_restarts(&self) -> broadcast::Receiver<CameraRestart> {
+        self.restarts.subscribe()
+    }
+
+    /// Moves to `state`, telling subscribers if that's a change.
+    fn set_capture_state(&self, state: CaptureState) {
+        let mut health = self.health.lock().unwrap();
+        if health.state == state {
+            return;
+        }
+        health.state = state;
+        health.since_unix_ms = unix_ms();
+        let _ = self.health_changes.send(health.clone());
+    }
+
+    fn read_succeeded(&self) {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures = 0;
+        health.last_frame_unix_ms = Some(unix_ms());
+        if health.state != CaptureState::Streaming {
+            health.state = CaptureState::Streaming;
+            health.since_unix_ms = unix_ms();
+            let _ = self.health_changes.send(health.clone());
+        }
+    }
+
+    /// Counts a failed read; returns how many failed in a row.
+    fn read_failed(&self, error: String) -> u32 {
+        let mut health = self.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        health.last_error = Some(error);
+        health.consecutive_failures
+    }
+
+    /// Privacy masks painted into every frame by the capture thread.
+    pub fn masks(&self) -> &Arc<MaskStore> {
+        &self.masks
+    }
+
+    /// Barrier every stage passes through per frame, for changing several
+    /// settings at once.
+    pub fn pipeline(&self) -> &PipelineBarrier {
+        &self.pipeline
+    }
+
+    /// Switches capture to a newly plugged camera at `path`, unless the
+    /// current one is delivering frames. Returns whether it was taken.
+    pub fn attach_camera(&self, path: &str) -> bool {
+        if self.capture_rate().samples > 0 || self.suspended() {
+            return false;
+        }
+        *self.hotplugged.lock().unwrap() = Some(path.to_string());
+        self.reattach.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Goes back to the configured camera when the attached one at `path`
+    /// is unplugged.
+    pub fn detach_camera(&self, path: &str) {
+        let mut hotplugged = self.hotplugged.lock().unwrap();
+        if hotplugged.as_deref() == Some(path) {
+            *hotplugged = None;
+            self.reattach.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn take_reattach(&self) -> bool {
+        self.reattach.swap(false, Ordering::Relaxed)
+    }
+
+    /// Releases the sensor (or reopens it) from the capture thread; frames
+    /// stop arriving while suspended.
+    pub fn set_suspended(&self, suspended: bool) {
+        self.suspended.store(suspended, Ordering::Relaxed);
+    }
+
+    pub fn suspended(&self) -> bool {
+        self.suspended.load(Ordering::Relaxed)
+    }
+
+    fn change_controls(&self, f: impl FnOnce(&mut Controls)) {
+        let mut pending = self.controls.lock().unwrap();
+        f(&mut pending.current);
+        pending.dirty = true;
+    }
+
+    /// Freezes (or releases) auto-exposure and auto-white-balance, so AE
+    /// does not hunt when the robot turns toward a window. V4L2 devices hold
+    /// their current values; the CSI camera's pipeline is rebuilt with AE
+    /// and AWB off, holding what the sensor starts with.
+    pub fn set_exposure_lock(&self, locked: bool) {
+        self.change_controls(|c| c.exposure_locked = locked);
+    }
+
+    pub fn exposure(&self) -> ExposureStatus {
+        ExposureStatus {
+            locked: self.controls.lock().unwrap().current.exposure_locked,
+            supported: !self.playback.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Raises gain/exposure for dim areas; `grayscale` additionally drops
+    /// color (mostly noise under IR) before frames reach consumers.
+    pub fn set_low_light(&self, enabled: bool, grayscale: bool) {
+        self.change_controls(|c| {
+            c.low_light = enabled;
+            c.grayscale = enabled && grayscale;
+        });
+    }
+
+    pub fn low_light(&self) -> (bool, bool) {
+        let current = self.controls.lock().unwrap().current;
+        (current.low_light, current.grayscale)
+    }
+
+    /// Changes the video mode and image controls, applied by the capture
+    /// thread between reads: on the open device where V4L2 allows,
+    /// otherwise by reopening the camera.
+    pub fn change_settings(
+        &self,
+        request: CameraSettingsRequest,
+    ) -> Result<CameraSettingsStatus, String> {
+        if self.playback.load(Ordering::Relaxed) {
+            return Err("playing back a recording, there is no camera to set".to_string());
         }
+        let mut pending = self.video.lock().unwrap();
+        let mut next = pending.current;
+        next.width = request.width.unwrap_or(next.width);
+        next.height = request.height.unwrap_or(next.height);
+        next.fps = request.fps.unwrap_or(next.fps);
+        next.controls = request.controls.unwrap_or(next.controls);
+        validate_settings(&next)?;
+        pending.current = next;
+        pending.dirty = true;
+        drop(pending);
+        Ok(self.settings())
+    }
+
+    pub fn settings(&self) -> CameraSettingsStatus {
+        let current = self.video.lock().unwrap().current;
+        let direct_controls = self.controls_supported.load(Ordering::Relaxed);
+        CameraSettingsStatus {
+            width: current.width,
+            height: current.height,
+            fps: current.fps,
+            controls: current.controls,
+            frame_size: self.frame_size().map(|(w, h)| [w, h]),
+            measured_fps: self.capture_rate.stats().hz,
+            device_controls: if direct_controls {
+                *self.device_controls.lock().unwrap()
+            } else {
+                None
+            },
+            direct_controls,
+        }
+    }
+
+    fn take_settings(&self) -> Option<CaptureSettings> {
+        let mut pending = self.video.lock().unwrap();
+        std::mem::take(&mut pending.dirty).then_some(pending.current)
+    }
+
+    /// Goes back to `previous` when the camera wouldn't open with the new
+    /// settings, unless newer ones are already waiting.
+    fn restore_settings(&self, previous: CaptureSettings) {
+        let mut pending = self.video.lock().unwrap();
+        if !pending.dirty {
+            pending.current = previous;
+        }
+    }
+
+    /// Requests a full-resolution frame, privacy masks applied. The capture
+    /// thread switches to the still mode for one frame, so the video feed
+    /// pauses for a moment.
+    pub async fn capture_still(&self) -> Result<Arc<core::Mat>, String> {
+        let (tx, rx) = oneshot::channel();
+        self.stills.lock().unwrap().push(tx);
+        match tokio::time::timeout(STILL_TIMEOUT, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("capture thread stopped".to_string()),
+            Err(_) => Err("still capture timed out".to_string()),
+        }
+    }
+
+    fn take_still_requests(&self) -> Vec<oneshot::Sender<Result<Arc<core::Mat>, String>>> {
+        std::mem::take(&mut *self.stills.lock().unwrap())
+    }
+
+    /// Hands pending control changes to the capture thread exactly once.
+    fn take_controls(&self) -> Option<Controls> {
+        let mut pending = self.controls.lock().unwrap();
+        std::mem::take(&mut pending.dirty).then_some(pending.current)
+    }
+
+    /// Grays and masks a captured frame as the controls say, then
+    /// publishes it.
+    fn publish_captured(&self, frame: &core::Mat, controls: &Controls) {
+        let pass = self.pipeline.pass();
+        let mut out = if controls.grayscale {
+            to_grayscale(frame).unwrap_or_else(|e| {
+                eprintln!("[ERR] Grayscale conversion failed: {}", e);
+                frame.clone()
+            })
+        } else {
+            frame.clone()
+        };
+        if let Err(e) = self.masks.apply(&mut out) {
+            eprintln!("[ERR] Privacy masks failed: {}", e);
+        }
+        self.update(out);
+        drop(pass);
     }
 
     pub fn update(&self, frame: core::Mat) {
-        if let Ok(mut locked_frame) = self.raw_frame.lock() {
-            *locked_frame = Some(frame);
+        self.capture_rate.tick();
+        let frame = Arc::new(frame);
+        self.latest.send_replace(Some(Arc::clone(&frame)));
+        self.dispatcher.publish(frame);
+    }
+
+    pub fn capture_rate(&self) -> RateStats {
+        self.capture_rate.stats()
+    }
+
+    /// Frames delivered to each consumer, after decimation.
+    pub fn consumer_rates(&self) -> Vec<(String, RateStats)> {
+        self.dispatcher.rates()
+    }
+
+    pub fn consumers(&self) -> Vec<ConsumerInfo> {
+        self.dispatcher.consumers()
+    }
+
+    /// Registers a consumer that receives frames at its declared rate.
+    pub fn subscribe(&self, name: &str, decimation: Decimation) -> FrameSubscription<core::Mat> {
+        self.dispatcher.subscribe(name, decimation)
+    }
+
+    /// The newest frame, shared with every other reader. Clone the `Mat`
+    /// only to draw on it.
+    pub fn get(&self) -> Option<Arc<core::Mat>> {
+        self.latest.borrow().clone()
+    }
+
+    /// Follows the newest frame without a declared rate: `changed()` wakes
+    /// on every capture, and a reader that falls behind only sees the
+    /// latest one.
+    pub fn watch(&self) -> watch::Receiver<Option<Arc<core::Mat>>> {
+        self.latest.subscribe()
+    }
+
+    /// Dimensions of the latest frame without copying it.
+    pub fn frame_size(&self) -> Option<(i32, i32)> {
+        let size = self.latest.borrow().as_ref()?.size().ok()?;
+        Some((size.width, size.height))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureSettings {
+    pub width: i32,
+    pub height: i32,
+    pub fps: i32,
+    /// Manual exposure, gain and white balance; automatic when unset.
+    pub controls: ImageControls,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            fps: 30,
+            controls: ImageControls::default(),
         }
     }
+}
+
+/// Checks a mode and its image controls before the camera is opened in it.
+pub fn validate_settings(settings: &CaptureSettings) -> Result<(), String> {
+    if settings.width <= 0 || settings.height <= 0 || settings.fps <= 0 {
+        return Err("sizes and fps must be positive".to_string());
+    }
+    let controls = &settings.controls;
+    if controls.exposure_us == Some(0) {
+        return Err("exposure_us must be positive".to_string());
+    }
+    if controls.gain.is_some_and(|g| !(g > 0.0 && g.is_finite())) {
+        return Err("gain must be positive".to_string());
+    }
+    if controls
+        .white_balance_k
+        .is_some_and(|k| !WHITE_BALANCE_RANGE.contains(&k))
+    {
+        return Err(format!(
+            "white_balance_k must be within {}..={}",
+            WHITE_BALANCE_RANGE.start(),
+            WHITE_BALANCE_RANGE.end()
+        ));
+    }
+    Ok(())
+}
 
-    pub fn get(&self) -> Option<core::Mat> {
-        if let Ok(locked_frame) = self.raw_frame.lock() {
-            if let Some(ref frame) = *locked_frame {
-                // Return a clone (deep copy) of the matrix
-                return Some(frame.clone());
+pub fn parse_resolution(value: &str) -> Option<(i32, i32)> {
+    let (w, h) = value.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+impl CaptureSettings {
+    /// Full-resolution still mode of the Camera Module v2 sensor.
+    pub fn still_default() -> Self {
+        Self {
+            width: 3280,
+            height: 2464,
+            fps: 10,
+            controls: ImageControls::default(),
+        }
+    }
+
+    /// `settings` with the size from `name` (e.g. `1280x720`), if it parses.
+    pub fn from_env_var(name: &str, mut settings: Self) -> Self {
+        if let Ok(value) = std::env::var(name) {
+            match parse_resolution(&value) {
+                Some((width, height)) => {
+                    settings.width = width;
+                    settings.height = height;
+                }
+                None => println!("[WARN] Ignoring invalid {} '{}'", name, value),
             }
         }
-        None
+        settings
     }
 }
 
-pub fn start_camera_thread() -> Arc<FrameManager> {
-    let frame_manager = Arc::new(FrameManager::new());
-    let fm_clone = Arc::clone(&frame_manager);
+/// `libcamerasrc` properties for manual `controls`. Setting exposure or
+/// gain turns off libcamera's AE for both; `locked` turns off AE and AWB,
+/// holding what the sensor starts with.
+pub fn libcamera_controls(controls: &ImageControls, locked: bool) -> String {
+    let mut properties = Vec::new();
+    if locked || controls.exposure_us.is_some() || controls.gain.is_some() {
+        properties.push("ae-enable=false".to_string());
+    }
+    if let Some(us) = controls.exposure_us {
+        properties.push(format!("exposure-time={}", us));
+    }
+    if let Some(gain) = controls.gain {
+        properties.push(format!("analogue-gain={}", gain));
+    }
+    if locked || controls.white_balance_k.is_some() {
+        properties.push("awb-enable=false".to_string());
+    }
+    if let Some(kelvin) = controls.white_balance_k {
+        properties.push(format!("colour-temperature={}", kelvin));
+    }
+    properties.join(" ")
+}
 
-    thread::spawn(move || {
-        println!("[INFO] Starting Rust camera capture thread...");
-
-        // Try GStreamer pipeline for CSI camera
-        let gst_pipeline = "libcamerasrc ! video/x-raw, width=640, height=480, framerate=30/1 ! videoconvert ! appsink";
-        let mut cap = match videoio::VideoCapture::from_file(gst_pipeline, videoio::CAP_GSTREAMER) {
-            Ok(c) => {
-                if opencv::videoio::VideoCapture::is_opened(&c).unwrap_or(false) {
-                    println!("[OK] Opened CSI Camera via GStreamer");
-                    c
-                } else {
-                    println!("[WARN] GStreamer failed, falling back to V4L2 /dev/video0");
-                    let mut fallback = videoio::VideoCapture::new(0, videoio::CAP_V4L2).unwrap();
-                    let _ = fallback.set(videoio::CAP_PROP_FRAME_WIDTH, 640.0);
-                    let _ = fallback.set(videoio::CAP_PROP_FRAME_HEIGHT, 480.0);
-                    fallback
-                }
-            },
-            Err(_) => {
-                println!("[WARN] GStreamer API error, falling back to index 0");
-                let mut fallback = videoio::VideoCapture::new(0, videoio::CAP_ANY).unwrap();
-                let _ = fallback.set(videoio::CAP_PROP_FRAME_WIDTH, 640.0);
-                let _ = fallback.set(videoio::CAP_PROP_FRAME_HEIGHT, 480.0);
+/// libcamera source for the CSI camera, delivering BGR frames to OpenCV.
+pub fn gstreamer_pipeline(settings: &CaptureSettings, locked: bool) -> String {
+    let source = format!(
+        "libcamerasrc {}",
+        libcamera_controls(&settings.controls, locked)
+    );
+    format!(
+        "{} ! video/x-raw, width={}, height={}, framerate={}/1 ! videoconvert ! appsink",
+        source.trim_end(),
+        settings.width,
+        settings.height,
+        settings.fps
+    )
+}
+
+/// How long a read from the GStreamer pipeline may wait for a frame in
+/// `settings` before it gives up.
+fn read_timeout(settings: &CaptureSettings) -> Duration {
+    (Duration::from_secs(READ_TIMEOUT_FRAMES as u64) / settings.fps.max(1) as u32)
+        .max(MIN_READ_TIMEOUT)
+}
+
+/// `settings` as the libcamera pipeline is built for `controls`: low light
+/// holds the shutter open for the whole frame interval at a high gain.
+fn pipeline_settings(settings: &CaptureSettings, controls: &Controls) -> CaptureSettings {
+    let mut settings = *settings;
+    if controls.low_light {
+        settings.controls.exposure_us = Some(1_000_000 / settings.fps.max(1) as u32);
+        settings.controls.gain = Some(LIBCAMERA_LOW_LIGHT_GAIN);
+    }
+    settings
+}
+
+/// Opens the CSI camera through GStreamer, falling back to V4L2, or the
+/// configured or hot-plugged camera at `device`. The flag tells whether CAP_PROP
+/// exposure/WB controls work on the opened device, which is the case for
+/// all but the GStreamer pipeline; that one is built with `controls`
+/// instead.
+fn open_capture(
+    config: &CameraConfig,
+    settings: &CaptureSettings,
+    device: Option<&str>,
+    controls: &Controls,
+) -> Option<(videoio::VideoCapture, bool)> {
+    if let Some(path) = device {
+        let mut cap = videoio::VideoCapture::from_file(path, videoio::CAP_V4L2).ok()?;
+        let _ = cap.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64);
+        let _ = cap.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64);
+        let _ = cap.set(videoio::CAP_PROP_FPS, settings.fps as f64);
+        if !cap.is_opened().unwrap_or(false) {
+            return None;
+        }
+        println!("[OK] Opened V4L2 camera {}", path);
+        return Some((cap, true));
+    }
+    // Try GStreamer pipeline for CSI camera
+    let gst_pipeline = config.pipeline(
+        &pipeline_settings(settings, controls),
+        controls.exposure_locked,
+    );
+    // libcamerasrc controls are fixed when the pipeline is built, so
+    // CAP_PROP exposure/WB changes only reach V4L2 devices
+    let mut supports_controls = true;
+    // Bounded reads, so a pipeline that stalls without ending is noticed
+    let params = core::Vector::from_slice(&[
+        videoio::CAP_PROP_READ_TIMEOUT_MSEC,
+        read_timeout(settings).as_millis() as i32,
+    ]);
+    let cap = match videoio::VideoCapture::from_file_with_params(
+        &gst_pipeline,
+        videoio::CAP_GSTREAMER,
+        &params,
+    ) {
+        Ok(c) => {
+            if opencv::videoio::VideoCapture::is_opened(&c).unwrap_or(false) {
+                println!("[OK] Opened CSI Camera via GStreamer");
+                supports_controls = false;
+                c
+            } else {
+                println!("[WARN] GStreamer failed, falling back to V4L2 /dev/video0");
+                let mut fallback = videoio::VideoCapture::new(0, videoio::CAP_V4L2).ok()?;
+                let _ = fallback.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64);
+                let _ = fallback.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64);
+                let _ = fallback.set(videoio::CAP_PROP_FPS, settings.fps as f64);
                 fallback
             }
-        };
+        }
+        Err(_) => {
+            println!("[WARN] GStreamer API error, falling back to index 0");
+            let mut fallback = videoio::VideoCapture::new(0, videoio::CAP_ANY).ok()?;
+            let _ = fallback.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64);
+            let _ = fallback.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64);
+            let _ = fallback.set(videoio::CAP_PROP_FPS, settings.fps as f64);
+            fallback
+        }
+    };
+
+    if !opencv::videoio::VideoCapture::is_opened(&cap).unwrap_or(false) {
+        return None;
+    }
+    Some((cap, supports_controls))
+}
 
-        if !opencv::videoio::VideoCapture::is_opened(&cap).unwrap_or(false) {
-            eprintln!("[ERR] Could not open any camera in Rust backend.");
+/// The hot-plugged camera standing in, else the configured V4L2 device.
+fn capture_device(frames: &FrameManager, config: &CameraConfig) -> Option<String> {
+    let hotplugged = frames.hotplugged.lock().unwrap().clone();
+    hotplugged.or_else(|| config.device.clone())
+}
+
+/// Reopens the video mode after the sensor was released.
+fn reopen_video(
+    frames: &FrameManager,
+    config: &CameraConfig,
+) -> Option<(videoio::VideoCapture, bool)> {
+    let device = capture_device(frames, config);
+    let controls = frames.controls.lock().unwrap().current;
+    let (cap, supports_controls) =
+        open_capture(config, &config.video, device.as_deref(), &controls)?;
+    frames
+        .controls_supported
+        .store(supports_controls, Ordering::Relaxed);
+    // Reopening resets exposure/gain, so reapply whatever is set
+    frames.change_controls(|_| {});
+    Some((cap, supports_controls))
+}
+
+/// Waits for a camera to be plugged in (see `devices`), then opens it.
+fn wait_for_camera(frames: &FrameManager, config: &CameraConfig) -> (videoio::VideoCapture, bool) {
+    println!("[INFO] Waiting for a camera to be plugged in");
+    frames.set_capture_state(CaptureState::Offline);
+    loop {
+        thread::sleep(SUSPEND_POLL);
+        if frames.take_reattach() {
+            if let Some(opened) = reopen_video(frames, config) {
+                return opened;
+            }
+        }
+    }
+}
+
+/// Reopens a camera that stopped delivering: the configured pipeline, then
+/// V4L2 (or the hot-plugged device), retried with backoff until one opens.
+fn reconnect(
+    frames: &FrameManager,
+    config: &CameraConfig,
+    cap: &mut videoio::VideoCapture,
+) -> (videoio::VideoCapture, bool) {
+    let _ = cap.release();
+    frames.set_capture_state(CaptureState::Reconnecting);
+    let mut delay = RECONNECT_MIN_DELAY;
+    let mut attempts = 0u32;
+    loop {
+        attempts += 1;
+        if let Some(opened) = reopen_video(frames, config) {
+            let mut health = frames.health.lock().unwrap();
+            health.reconnects += 1;
+            health.consecutive_failures = 0;
+            drop(health);
+            println!("[OK] Camera reconnected after {} attempt(s)", attempts);
+            return opened;
+        }
+        if attempts == 1 {
+            eprintln!("[ERR] Could not reopen the camera, retrying");
+        }
+        thread::sleep(delay);
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+/// Records and broadcasts why the pipeline is being rebuilt, then reopens
+/// the camera.
+fn restart(
+    frames: &FrameManager,
+    config: &CameraConfig,
+    cap: &mut videoio::VideoCapture,
+    cause: RestartCause,
+    detail: String,
+) -> (videoio::VideoCapture, bool) {
+    eprintln!(
+        "[ERR] Camera '{}' restarting ({:?}): {}",
+        config.id, cause, detail
+    );
+    let restart = CameraRestart {
+        camera: config.id.clone(),
+        cause,
+        detail,
+        unix_ms: unix_ms(),
+    };
+    frames.health.lock().unwrap().last_restart = Some(restart.clone());
+    let _ = frames.restarts.send(restart);
+    reconnect(frames, config, cap)
+}
+
+/// Frames discarded after switching modes so AE/AWB can settle.
+const STILL_WARMUP_FRAMES: usize = 8;
+const STILL_JPEG_QUALITY: i32 = 95;
+
+/// Captures one frame in the still mode, privacy masks applied. The video
+/// capture must already be released, since the sensor can only be opened once.
+fn capture_still(
+    config: &CameraConfig,
+    device: Option<&str>,
+    masks: &MaskStore,
+) -> Result<Arc<core::Mat>, String> {
+    let (mut cap, _) = open_capture(config, &config.still, device, &Controls::default())
+        .ok_or("could not open camera in still mode")?;
+    let mut frame = core::Mat::default();
+    for _ in 0..STILL_WARMUP_FRAMES {
+        let _ = cap.read(&mut frame);
+    }
+    if !cap.read(&mut frame).unwrap_or(false) || frame.empty() {
+        return Err("no frame in still mode".to_string());
+    }
+    masks.apply(&mut frame).map_err(|e| e.to_string())?;
+    println!(
+        "[OK] Full-resolution still captured ({}x{})",
+        frame.cols(),
+        frame.rows()
+    );
+    Ok(Arc::new(frame))
+}
+
+/// JPEG-encodes `frame` at `quality` (0-100).
+pub fn encode_jpeg(frame: &core::Mat, quality: i32) -> opencv::Result<Vec<u8>> {
+    let mut jpeg = core::Vector::<u8>::new();
+    let params = core::Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, quality]);
+    imgcodecs::imencode(".jpg", frame, &mut jpeg, &params)?;
+    Ok(jpeg.to_vec())
+}
+
+/// Plays a recorded `source` through the pipeline at `fps` in place of the
+/// camera. Exposure controls don't apply, and stills are the frame on show.
+fn run_playback(frames: &FrameManager, mut source: Box<dyn FrameSource>, fps: f32) {
+    println!(
+        "[INFO] Playing {} at {} fps instead of the camera",
+        source.describe(),
+        fps
+    );
+    let interval = Duration::from_secs_f32(1.0 / fps);
+    let mut controls = Controls::default();
+    let mut next = Instant::now();
+    let mut finished = false;
+    loop {
+        if faults::killed("camera") {
             return;
         }
+        if frames.suspended() {
+            frames.set_capture_state(CaptureState::Suspended);
+            thread::sleep(SUSPEND_POLL);
+            next = Instant::now();
+            continue;
+        }
+
+        let stills = frames.take_still_requests();
+        if !stills.is_empty() {
+            let still = frames.get().ok_or_else(|| "no frame yet".to_string());
+            for reply in stills {
+                let _ = reply.send(still.clone());
+            }
+        }
+        if let Some(next_controls) = frames.take_controls() {
+            controls = next_controls;
+        }
+
+        if !finished {
+            match source.next_frame() {
+                Ok(Some(_)) if faults::drop_frame() => {}
+                Ok(Some(frame)) => {
+                    frames.read_succeeded();
+                    frames.publish_captured(&frame, &controls);
+                }
+                Ok(None) => {
+                    println!("[INFO] Playback of {} finished", source.describe());
+                    frames.set_capture_state(CaptureState::Offline);
+                    finished = true;
+                }
+                Err(e) => {
+                    eprintln!("[ERR] Playback read failed: {}", e);
+                    frames.read_failed(e.to_string());
+                }
+            }
+        }
+
+        // Paced by the clock, so a slow read doesn't slow playback down
+        next += interval;
+        let now = Instant::now();
+        if next > now {
+            thread::sleep(next - now);
+        } else {
+            next = now;
+        }
+    }
+}
+
+/// Privacy `masks` are painted into every frame before anything else sees
+/// it. With `config.playback` set, recorded frames stand in for the camera,
+/// if `allow_playback` (the profile's `allow_simulation`).
+pub fn start_camera_thread(
+    mut config: CameraConfig,
+    masks: Arc<MaskStore>,
+    allow_playback: bool,
+) -> Arc<FrameManager> {
+    let frame_manager = Arc::new(FrameManager::new(masks));
+    let fm_clone = Arc::clone(&frame_manager);
+    frame_manager.video.lock().unwrap().current = config.video;
+
+    if config.playback.is_some() && !allow_playback {
+        println!(
+            "[WARN] Playback for '{}' refused, the profile allows no simulation; using the camera",
+            config.id
+        );
+    } else if let Some(playback) = &config.playback {
+        match source::open(playback) {
+            Ok(source) => {
+                frame_manager.playback.store(true, Ordering::Relaxed);
+                let fps = playback
+                    .fps
+                    .or_else(|| source.native_fps())
+                    .unwrap_or(config.video.fps as f32);
+                thread::spawn(move || {
+                    threads::label("capture");
+                    run_playback(&fm_clone, source, fps)
+                });
+                return frame_manager;
+            }
+            Err(e) => eprintln!(
+                "[ERR] Could not open playback source, using the camera: {}",
+                e
+            ),
+        }
+    }
+
+    thread::spawn(move || {
+        threads::label("capture");
+        println!(
+            "[INFO] Starting Rust camera capture thread for '{}' ({}x{} @ {} fps)...",
+            config.id, config.video.width, config.video.height, config.video.fps
+        );
+
+        let (mut cap, mut supports_controls) = match open_capture(
+            &config,
+            &config.video,
+            config.device.as_deref(),
+            &Controls::default(),
+        ) {
+            Some(opened) => opened,
+            None => {
+                eprintln!(
+                    "[ERR] Could not open camera '{}' in Rust backend.",
+                    config.id
+                );
+                wait_for_camera(&fm_clone, &config)
+            }
+        };
+        fm_clone
+            .controls_supported
+            .store(supports_controls, Ordering::Relaxed);
 
         let mut frame = core::Mat::default();
+        let mut controls = Controls::default();
+        let mut saved_gain = None;
+        let mut read_back = Instant::now();
         loop {
-            match cap.read(&mut frame) {
-                Ok(true) => {
-                    // Slight resize if not native 640x480 could be done here
-                    fm_clone.update(frame.clone());
-                    thread::sleep(Duration::from_millis(5)); // yield
+            if faults::killed("camera") {
+                return;
+            }
+            if fm_clone.suspended() {
+                let _ = cap.release();
+                println!("[INFO] Camera capture suspended");
+                fm_clone.set_capture_state(CaptureState::Suspended);
+                while fm_clone.suspended() {
+                    thread::sleep(SUSPEND_POLL);
                 }
-                _ => {
-                    thread::sleep(Duration::from_millis(50));
+                (cap, supports_controls) = match reopen_video(&fm_clone, &config) {
+                    Some(reopened) => reopened,
+                    None => reconnect(&fm_clone, &config, &mut cap),
+                };
+                saved_gain = None;
+                println!("[INFO] Camera capture resumed");
+            }
+
+            let stills = fm_clone.take_still_requests();
+            if !stills.is_empty() {
+                // Release the sensor, grab the still, then restore the video mode
+                let _ = cap.release();
+                let device = capture_device(&fm_clone, &config);
+                let still = capture_still(&config, device.as_deref(), &fm_clone.masks);
+                for reply in stills {
+                    let _ = reply.send(still.clone());
                 }
+                (cap, supports_controls) = match reopen_video(&fm_clone, &config) {
+                    Some(reopened) => reopened,
+                    None => {
+                        eprintln!("[ERR] Could not restore video mode after still capture");
+                        reconnect(&fm_clone, &config, &mut cap)
+                    }
+                };
+                saved_gain = None;
+            }
+
+            if fm_clone.take_reattach() {
+                let _ = cap.release();
+                (cap, supports_controls) = match reopen_video(&fm_clone, &config) {
+                    Some(reopened) => reopened,
+                    None => {
+                        eprintln!("[ERR] Could not switch cameras");
+                        wait_for_camera(&fm_clone, &config)
+                    }
+                };
+                saved_gain = None;
+            }
+
+            if let Some(next) = fm_clone.take_settings() {
+                // V4L2 takes new image controls on the open device; a new
+                // mode, or any change to the GStreamer pipeline, reopens it
+                let previous = config.video;
+                let same_mode = (next.width, next.height, next.fps)
+                    == (previous.width, previous.height, previous.fps);
+                config.video = next;
+                if same_mode && supports_controls {
+                    fm_clone.change_controls(|_| {});
+                } else {
+                    let _ = cap.release();
+                    (cap, supports_controls) = match reopen_video(&fm_clone, &config) {
+                        Some(reopened) => reopened,
+                        None => {
+                            eprintln!(
+                                "[ERR] Could not open the camera at {}x{} @ {} fps, going back",
+                                next.width, next.height, next.fps
+                            );
+                            config.video = previous;
+                            fm_clone.restore_settings(previous);
+                            reconnect(&fm_clone, &config, &mut cap)
+                        }
+                    };
+                    saved_gain = None;
+                }
+                println!(
+                    "[INFO] Camera settings: {}x{} @ {} fps, {:?}",
+                    config.video.width,
+                    config.video.height,
+                    config.video.fps,
+                    config.video.controls
+                );
+            }
+
+            if let Some(next) = fm_clone.take_controls() {
+                let rebuild = next.exposure_locked != controls.exposure_locked
+                    || next.low_light != controls.low_light;
+                controls = next;
+                if supports_controls {
+                    apply_controls(&mut cap, next, &config.video.controls, &mut saved_gain);
+                } else if rebuild {
+                    // libcamerasrc takes controls only when the pipeline is
+                    // built, so reopen it like for new settings
+                    let _ = cap.release();
+                    (cap, supports_controls) = match reopen_video(&fm_clone, &config) {
+                        Some(reopened) => reopened,
+                        None => {
+                            eprintln!("[ERR] Could not rebuild the camera pipeline for {:?}", next);
+                            reconnect(&fm_clone, &config, &mut cap)
+                        }
+                    };
+                    saved_gain = None;
+                }
+            }
+            if supports_controls && read_back.elapsed() >= READBACK_INTERVAL {
+                *fm_clone.device_controls.lock().unwrap() = Some(read_device_controls(&cap));
+                read_back = Instant::now();
+            }
+            let gstreamer = !supports_controls;
+            let timeout = read_timeout(&config.video);
+            let started = Instant::now();
+            // The GStreamer backend only comes back without a frame once the
+            // pipeline has ended, or when a stalled one runs out the read
+            // timeout; anything else is a read error
+            let (cause, error) = match cap.read(&mut frame) {
+                Ok(true) if faults::drop_frame() => continue,
+                Ok(true) if !frame.empty() => {
+                    fm_clone.read_succeeded();
+                    fm_clone.publish_captured(&frame, &controls);
+                    thread::sleep(Duration::from_millis(5)); // yield
+                    continue;
+                }
+                Ok(_) if gstreamer && started.elapsed() >= timeout => (
+                    RestartCause::Stalled,
+                    format!("no frame for {} ms", started.elapsed().as_millis()),
+                ),
+                Ok(_) if gstreamer => (RestartCause::EndOfStream, "end of stream".to_string()),
+                Ok(_) => (RestartCause::ReadErrors, "no frame".to_string()),
+                Err(e) => (RestartCause::ReadErrors, e.to_string()),
+            };
+            let failures = fm_clone.read_failed(error.clone());
+            let rebuild = match cause {
+                RestartCause::Stalled => true,
+                RestartCause::EndOfStream => failures >= END_OF_STREAM_READS,
+                // An unplugged camera fails every read
+                RestartCause::ReadErrors => failures >= MAX_READ_FAILURES,
+            };
+            if rebuild {
+                let detail = match cause {
+                    RestartCause::ReadErrors => {
+                        format!("{} reads failed in a row ({})", failures, error)
+                    }
+                    _ => error,
+                };
+                (cap, supports_controls) = restart(&fm_clone, &config, &mut cap, cause, detail);
+                saved_gain = None;
+            } else {
+                thread::sleep(Duration::from_millis(50));
             }
         }
     });
 
     frame_manager
 }
+
+/// A capture thread and the detections inferred from its frames.
+pub struct Camera {
+    pub id: String,
+    /// Configured V4L2 device, so a replugged camera finds its way back.
+    pub device: Option<String>,
+    pub frames: Arc<FrameManager>,
+    pub detections: Arc<DetectionHub>,
+}
+
+impl Camera {
+    /// Whether `path` is this camera's configured device, symlinks such as
+    /// `/dev/v4l/by-id/...` resolved.
+    pub fn owns_device(&self, path: &str) -> bool {
+        let Some(device) = &self.device else {
+            return false;
+        };
+        let resolve = |p: &str| std::fs::canonicalize(p).unwrap_or_else(|_| p.into());
+        resolve(device) == resolve(path)
+    }
+
+    pub fn summary(&self, primary: bool) -> CameraSummary {
+        CameraSummary {
+            id: self.id.clone(),
+            primary,
+            frame_size: self.frames.frame_size().map(|(w, h)| [w, h]),
+            capture_fps: self.frames.capture_rate().hz,
+            state: self.frames.health().state,
+        }
+    }
+}
+
+/// Every configured camera, the primary one first.
+pub struct CameraSet {
+    cameras: Vec<Arc<Camera>>,
+}
+
+impl CameraSet {
+    /// Starts a capture thread per camera. The primary paints `masks`; the
+    /// others keep masks of their own (see `MaskStore::for_camera`).
+    /// Configured playback replaces a camera only if `allow_playback`.
+    pub fn start(
+        primary: &CameraConfig,
+        others: &[CameraConfig],
+        masks: Arc<MaskStore>,
+        allow_playback: bool,
+    ) -> Self {
+        let start = |config: &CameraConfig, masks: Arc<MaskStore>| {
+            Arc::new(Camera {
+                id: config.id.clone(),
+                device: config.device.clone(),
+                frames: start_camera_thread(config.clone(), masks, allow_playback),
+                detections: Arc::new(DetectionHub::from_env()),
+            })
+        };
+        let mut cameras = vec![start(primary, masks)];
+        for config in others {
+            cameras.push(start(config, Arc::new(MaskStore::for_camera(&config.id))));
+        }
+        Self { cameras }
+    }
+
+    pub fn primary(&self) -> &Arc<Camera> {
+        &self.cameras[0]
+    }
+
+    pub fn is_primary(&self, camera: &Camera) -> bool {
+        std::ptr::eq(camera, self.primary().as_ref())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Arc<Camera>> {
+        self.cameras.iter().find(|c| c.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Camera>> {
+        self.cameras.iter()
+    }
+
+    pub fn summaries(&self) -> Vec<CameraSummary> {
+        self.cameras
+            .iter()
+            .enumerate()
+            .map(|(i, camera)| camera.summary(i == 0))
+            .collect()
+    }
+}
+
+/// Gray replicated into three channels, so consumers expecting BGR still work.
+fn to_grayscale(frame: &core::Mat) -> opencv::Result<core::Mat> {
+    let mut gray = core::Mat::default();
+    imgproc::cvt_color_def(frame, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+    let mut bgr = core::Mat::default();
+    imgproc::cvt_color_def(&gray, &mut bgr, imgproc::COLOR_GRAY2BGR)?;
+    Ok(bgr)
+}
+
+/// V4L2 semantics: `CAP_PROP_AUTO_EXPOSURE` 1 = manual, 3 = aperture priority.
+/// Switching to manual keeps the exposure the AE loop last settled on. Low
+/// light overrides the manual `image` exposure and gain while it is on.
+fn apply_controls(
+    cap: &mut videoio::VideoCapture,
+    controls: Controls,
+    image: &ImageControls,
+    saved_gain: &mut Option<f64>,
+) {
+    let manual = controls.exposure_locked || controls.low_light || image.exposure_us.is_some();
+    let mut ok = cap
+        .set(
+            videoio::CAP_PROP_AUTO_EXPOSURE,
+            if manual { 1.0 } else { 3.0 },
+        )
+        .unwrap_or(false);
+    let fixed_wb = controls.exposure_locked || image.white_balance_k.is_some();
+    ok &= cap
+        .set(videoio::CAP_PROP_AUTO_WB, if fixed_wb { 0.0 } else { 1.0 })
+        .unwrap_or(false);
+    if let Some(kelvin) = image.white_balance_k {
+        ok &= cap
+            .set(videoio::CAP_PROP_WB_TEMPERATURE, kelvin as f64)
+            .unwrap_or(false);
+    }
+
+    let exposure = if controls.low_light {
+        Some(LOW_LIGHT_EXPOSURE)
+    } else {
+        image.exposure_us.map(|us| us as f64 / 100.0)
+    };
+    if let Some(exposure) = exposure {
+        ok &= cap
+            .set(videoio::CAP_PROP_EXPOSURE, exposure)
+            .unwrap_or(false);
+    }
+    let gain = if controls.low_light {
+        Some(LOW_LIGHT_GAIN)
+    } else {
+        image.gain.map(f64::from)
+    };
+    match gain {
+        Some(gain) => {
+            if saved_gain.is_none() {
+                *saved_gain = cap.get(videoio::CAP_PROP_GAIN).ok();
+            }
+            ok &= cap.set(videoio::CAP_PROP_GAIN, gain).unwrap_or(false);
+        }
+        None => {
+            if let Some(gain) = saved_gain.take() {
+                ok &= cap.set(videoio::CAP_PROP_GAIN, gain).unwrap_or(false);
+            }
+        }
+    }
+
+    if ok {
+        println!("[OK] Camera controls applied: {:?}, {:?}", controls, image);
+    } else {
+        println!(
+            "[WARN] Camera rejected some controls: {:?}, {:?}",
+            controls, image
+        );
+    }
+}
+
+/// What a V4L2 device reports for the image controls; drivers answer 0 or
+/// less for the ones they lack.
+fn read_device_controls(cap: &videoio::VideoCapture) -> ImageControls {
+    let get = |prop| cap.get(prop).ok().filter(|v| *v > 0.0);
+    ImageControls {
+        exposure_us: get(videoio::CAP_PROP_EXPOSURE).map(|v| (v * 100.0) as u32),
+        gain: get(videoio::CAP_PROP_GAIN).map(|v| v as f32),
+        white_balance_k: get(videoio::CAP_PROP_WB_TEMPERATURE).map(|v| v as u32),
+    }
+}
+
+/// Low light switches the IR illuminator along with the camera controls.
+/// All but `/cameras` act on the primary camera.
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/cameras", get(list_cameras))
+        .route("/camera/exposure-lock", get(get_exposure).put(set_exposure))
+        .route("/camera/low-light", get(get_low_light).put(set_low_light))
+        .route("/camera/still", post(take_still))
+        .route("/camera/health", get(get_health))
+        .route("/camera/settings", get(get_settings).post(change_settings))
+        .with_state(state)
+}
+
+async fn get_settings(State(state): State<AppState>) -> Json<CameraSettingsStatus> {
+    Json(state.frames.settings())
+}
+
+async fn change_settings(
+    State(state): State<AppState>,
+    Json(req): Json<CameraSettingsRequest>,
+) -> Result<Json<CameraSettingsStatus>, (StatusCode, String)> {
+    state
+        .frames
+        .change_settings(req)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+async fn list_cameras(State(state): State<AppState>) -> Json<Vec<CameraSummary>> {
+    Json(state.cameras.summaries())
+}
+
+async fn get_health(State(state): State<AppState>) -> Json<CameraHealth> {
+    Json(state.frames.health())
+}
+
+async fn get_exposure(State(state): State<AppState>) -> Json<ExposureStatus> {
+    Json(state.frames.exposure())
+}
+
+async fn set_exposure(
+    State(state): State<AppState>,
+    Json(req): Json<ExposureLockRequest>,
+) -> Json<ExposureStatus> {
+    state.frames.set_exposure_lock(req.locked);
+    Json(state.frames.exposure())
+}
+
+fn low_light_status(state: &AppState) -> LowLightStatus {
+    let (enabled, grayscale) = state.frames.low_light();
+    LowLightStatus {
+        enabled,
+        grayscale,
+        ir_illuminator: state.illuminator.as_ref().map(|ir| ir.is_on()),
+    }
+}
+
+async fn get_low_light(State(state): State<AppState>) -> Json<LowLightStatus> {
+    Json(low_light_status(&state))
+}
+
+async fn set_low_light(
+    State(state): State<AppState>,
+    Json(req): Json<LowLightRequest>,
+) -> Json<LowLightStatus> {
+    state.frames.set_low_light(req.enabled, req.grayscale);
+    if let Some(ir) = &state.illuminator {
+        ir.set(req.enabled);
+    }
+    println!(
+        "[INFO] Low-light mode {}",
+        if req.enabled { "on" } else { "off" }
+    );
+    Json(low_light_status(&state))
+}
+
+/// The still as JPEG, faces blurred like any other published frame.
+async fn take_still(State(state): State<AppState>) -> Response {
+    let still = match state.frames.capture_still().await {
+        Ok(frame) => tokio::task::spawn_blocking(move || {
+            annotate::publish_jpeg(&state, &frame, STILL_JPEG_QUALITY, None)
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.to_string())),
+        Err(e) => Err(e),
+    };
+    match still {
+        Ok(jpeg) => {
+            let chunks: Vec<Result<Bytes, std::io::Error>> = jpeg
+                .chunks(STILL_CHUNK_SIZE)
+                .map(|c| Ok(Bytes::copy_from_slice(c)))
+                .collect();
+            (
+                [(header::CONTENT_TYPE, "image/jpeg")],
+                Body::from_stream(futures_util::stream::iter(chunks)),
+            )
+                .into_response()
+        }
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, e).into_response(),
+    }
+}
@@ -0,0 +1,203 @@
+//! Charger detection, so the robot can't drive off the bench while it is
+//! plugged in.
+//!
+//! The charger shows either on a GPIO sense line (`charging.sense_pin`) or
+//! as current flowing into the battery on an ADC channel the MCU reports
+//! (`charging.current_channel`); either one is enough. While charging, the
+//! arbiter refuses every drive command unless `charging.block_drive` is
+//! off. The state goes into telemetry as the `charging` stream, once a
+//! second and on every change.
+
+use crate::config::ChargingConfig;
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::power::{ChargeSense, ChargingStatus};
+use rppal::gpio::{Gpio, InputPin};
+use serde_json::{Map, Value};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+/// A current reading older than this no longer says anything.
+const CURRENT_MAX_AGE: Duration = Duration::from_secs(2);
+const INTERLOCK_REASON: &str = "robot is charging";
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+struct Charge {
+    detected_by: Option<ChargeSense>,
+    since_unix_ms: u64,
+}
+
+pub struct ChargeMonitor {
+    config: Mutex<ChargingConfig>,
+    sense: Mutex<Option<InputPin>>,
+    current: Mutex<Option<(f32, Instant)>>,
+    charge: Mutex<Charge>,
+}
+
+impl ChargeMonitor {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(ChargingConfig::default()),
+            sense: Mutex::new(None),
+            current: Mutex::new(None),
+            charge: Mutex::new(Charge {
+                detected_by: None,
+                since_unix_ms: now_ms(),
+            }),
+        }
+    }
+
+    /// Takes the sense line and current channel from the startup config,
+    /// opening the sense line's pin.
+    pub fn configure(&self, config: ChargingConfig) {
+        if let Some(pin) = config.sense_pin {
+            let opened = Gpio::new().and_then(|gpio| gpio.get(pin)).map(|pin| {
+                if config.active_low {
+                    pin.into_input_pullup()
+                } else {
+                    pin.into_input_pulldown()
+                }
+            });
+            match opened {
+                Ok(input) => {
+                    println!("[OK] Charger sense line on GPIO{}", pin);
+                    *self.sense.lock().unwrap() = Some(input);
+                }
+                Err(e) => println!("[WARN] Charger sense line unavailable: {}", e),
+            }
+        }
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Whether anything can tell the charger is plugged in.
+    fn can_detect(&self) -> bool {
+        self.sense.lock().unwrap().is_some()
+            || self.config.lock().unwrap().current_channel.is_some()
+    }
+
+    /// Takes a calibrated ADC reading; only the configured current channel
+    /// is kept.
+    pub fn record_channel(&self, name: &str, value: f32) {
+        if self.config.lock().unwrap().current_channel.as_deref() == Some(name) {
+            *self.current.lock().unwrap() = Some((value, Instant::now()));
+        }
+    }
+
+    fn current_a(&self) -> Option<f32> {
+        self.current
+            .lock()
+            .unwrap()
+            .filter(|(_, at)| at.elapsed() < CURRENT_MAX_AGE)
+            .map(|(amps, _)| amps)
+    }
+
+    /// What currently shows the charger, if anything does.
+    fn sense(&self) -> Option<ChargeSense> {
+        let config = self.config.lock().unwrap().clone();
+        let line = self
+            .sense
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|pin| pin.is_high() != config.active_low);
+        if line {
+            return Some(ChargeSense::SenseLine);
+        }
+        self.current_a()
+            .filter(|amps| *amps >= config.charge_current_a)
+            .map(|_| ChargeSense::Current)
+    }
+
+    pub fn status(&self) -> ChargingStatus {
+        let charge = self.charge.lock().unwrap();
+        let charging = charge.detected_by.is_some();
+        ChargingStatus {
+            charging,
+            detected_by: charge.detected_by,
+            current_a: self.current_a(),
+            drive_blocked: charging && self.config.lock().unwrap().block_drive,
+            since_unix_ms: charge.since_unix_ms,
+        }
+    }
+
+    /// Updates the state from the sensors; returns whether charging started
+    /// or stopped.
+    fn update(&self) -> bool {
+        let detected_by = self.sense();
+        let mut charge = self.charge.lock().unwrap();
+        let changed = charge.detected_by.is_some() != detected_by.is_some();
+        if changed {
+            charge.since_unix_ms = now_ms();
+        }
+        charge.detected_by = detected_by;
+        changed
+    }
+}
+
+fn record(state: &AppState, status: &ChargingStatus) {
+    let mut values = Map::new();
+    values.insert("charging".to_string(), Value::from(status.charging));
+    if let Some(amps) = status.current_a {
+        values.insert("current_a".to_string(), Value::from(amps));
+    }
+    values.insert(
+        "drive_blocked".to_string(),
+        Value::from(status.drive_blocked),
+    );
+    state.record_telemetry("charging", values);
+}
+
+/// Follows the charger, engaging the arbiter's interlock while charging if
+/// drive is to be blocked. Does nothing when nothing can sense the charger.
+pub fn start_charge_monitor(state: AppState) {
+    if !state.charging.can_detect() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut published = Instant::now();
+        loop {
+            interval.tick().await;
+            let changed = state.charging.update();
+            let status = state.charging.status();
+            if changed {
+                match status.detected_by {
+                    Some(ChargeSense::SenseLine) => {
+                        println!("[INFO] Charger plugged in (sense line)")
+                    }
+                    Some(ChargeSense::Current) => println!(
+                        "[INFO] Charger plugged in ({:.2} A into the battery)",
+                        status.current_a.unwrap_or(0.0)
+                    ),
+                    None => println!("[INFO] Charger unplugged"),
+                }
+                let interlock = status.drive_blocked.then(|| INTERLOCK_REASON.to_string());
+                if let Err(e) = state.arbiter.set_interlock("charging", interlock) {
+                    eprintln!("[ERR] Could not stop motors on the charger: {}", e);
+                }
+            }
+            if changed || published.elapsed() >= PUBLISH_INTERVAL {
+                record(&state, &status);
+                published = Instant::now();
+            }
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/power/charging", get(get_status))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<ChargingStatus> {
+    Json(state.charging.status())
+}
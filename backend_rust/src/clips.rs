@@ -0,0 +1,200 @@
+//! Short MP4 clips around detection events, so reviewing a run does not
+//! mean scrubbing an hour of video.
+//!
+//! A pre-roll ring buffer holds the last few seconds of frames. The first
+//! time a class shows up during a run, the buffer plus a few seconds of
+//! post-roll are written to `<session>/clips/<class>-<unix_ms>.mp4`. Clips
+//! are only taken while a run is active, and at most `CLIP_MAX_PER_SESSION`
+//! per run. Faces in clips are blurred when face blur is on.
+//!
+//! Tunable via `CLIP_PRE_S`, `CLIP_POST_S`, `CLIP_FPS`, `CLIP_MAX_PER_SESSION`
+//! and `CLIP_CLASSES` (comma-separated; every class when unset).
+
+use crate::annotate;
+use crate::dispatch::Decimation;
+use crate::state::AppState;
+use crate::threads;
+use opencv::{core::Mat, prelude::*, videoio};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::TryRecvError;
+
+pub const CLIPS_DIR: &str = "clips";
+
+#[derive(Debug, Clone)]
+pub struct ClipConfig {
+    pub pre_roll: Duration,
+    pub post_roll: Duration,
+    pub fps: f32,
+    pub max_per_session: usize,
+    /// Only these classes trigger clips; all when `None`.
+    pub classes: Option<Vec<String>>,
+}
+
+impl ClipConfig {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok()?.trim().parse().ok()
+        }
+        Self {
+            pre_roll: Duration::from_secs_f32(var("CLIP_PRE_S").unwrap_or(3.0)),
+            post_roll: Duration::from_secs_f32(var("CLIP_POST_S").unwrap_or(5.0)),
+            fps: var("CLIP_FPS").unwrap_or(10.0),
+            max_per_session: var("CLIP_MAX_PER_SESSION").unwrap_or(20),
+            classes: std::env::var("CLIP_CLASSES").ok().map(|v| {
+                v.split(',')
+                    .map(|c| c.trim().to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect()
+            }),
+        }
+    }
+
+    fn wants(&self, class: &str) -> bool {
+        self.classes
+            .as_ref()
+            .is_none_or(|classes| classes.iter().any(|c| c == class))
+    }
+}
+
+struct PendingClip {
+    path: PathBuf,
+    until: Instant,
+    frames: Vec<Arc<Mat>>,
+}
+
+/// Clips already taken in the current run, and the classes that triggered them.
+#[derive(Default)]
+struct RunClips {
+    session: Option<String>,
+    seen: HashSet<String>,
+    taken: usize,
+}
+
+pub fn start_clip_recorder(state: &AppState, config: ClipConfig) {
+    let subscription = state
+        .frames
+        .subscribe("clips", Decimation::MaxFps(config.fps));
+    let mut detections = state.detections.subscribe();
+    let sessions = Arc::clone(&state.sessions);
+    let state = state.clone();
+    thread::spawn(move || {
+        threads::label("clips");
+        let mut preroll: VecDeque<(Instant, Arc<Mat>)> = VecDeque::new();
+        let mut pending: Option<PendingClip> = None;
+        let mut run = RunClips::default();
+        loop {
+            let Some(frame) = subscription.recv_timeout(Duration::from_secs(1)) else {
+                continue;
+            };
+            let now = Instant::now();
+            preroll.push_back((now, Arc::clone(&frame)));
+            while preroll
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > config.pre_roll)
+            {
+                preroll.pop_front();
+            }
+
+            if let Some(clip) = pending.as_mut() {
+                clip.frames.push(frame);
+                if now >= clip.until {
+                    let clip = pending.take().unwrap();
+                    let fps = config.fps;
+                    let state = state.clone();
+                    thread::spawn(move || write_clip(&state, &clip.path, &clip.frames, fps));
+                }
+            }
+
+            let active = sessions.active_id();
+            if active != run.session {
+                run = RunClips {
+                    session: active.clone(),
+                    ..RunClips::default()
+                };
+            }
+            loop {
+                let published = match detections.try_recv() {
+                    Ok(published) => published,
+                    Err(TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                let Some(session) = active.as_deref() else {
+                    continue;
+                };
+                for object in &published.objects {
+                    // While a clip is being taken, new classes wait for the next one
+                    if pending.is_some()
+                        || run.taken >= config.max_per_session
+                        || !config.wants(&object.class)
+                        || run.seen.contains(&object.class)
+                    {
+                        continue;
+                    }
+                    let Some(dir) = sessions.session_dir(session) else {
+                        continue;
+                    };
+                    run.seen.insert(object.class.clone());
+                    run.taken += 1;
+                    pending = Some(PendingClip {
+                        path: dir.join(CLIPS_DIR).join(clip_name(&object.class)),
+                        until: now + config.post_roll,
+                        frames: preroll.iter().map(|(_, f)| Arc::clone(f)).collect(),
+                    });
+                }
+            }
+        }
+    });
+}
+
+fn clip_name(class: &str) -> String {
+    let class: String = class
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}-{}.mp4", class, unix_ms)
+}
+
+fn write_clip(state: &AppState, path: &Path, frames: &[Arc<Mat>], fps: f32) {
+    match encode_clip(state, path, frames, fps) {
+        Ok(()) => println!(
+            "[INFO] Clip saved: {} ({} frames)",
+            path.display(),
+            frames.len()
+        ),
+        Err(e) => eprintln!("[ERR] Could not write clip {}: {}", path.display(), e),
+    }
+}
+
+fn encode_clip(
+    state: &AppState,
+    path: &Path,
+    frames: &[Arc<Mat>],
+    fps: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(first) = frames.first() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut writer = videoio::VideoWriter::new(
+        &path.to_string_lossy(),
+        videoio::VideoWriter::fourcc('m', 'p', '4', 'v')?,
+        fps as f64,
+        first.size()?,
+        true,
+    )?;
+    for frame in frames {
+        writer.write(&annotate::publish(state, frame, None))?;
+    }
+    writer.release()?;
+    Ok(())
+}
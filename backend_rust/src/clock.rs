@@ -0,0 +1,53 @@
+//! Time source for components whose output depends on elapsed time.
+//!
+//! Live, they read the system clock. The `replay` subcommand hands them a
+//! [`VirtualClock`] that only moves when the recording says so, so feeding
+//! the same run twice gives the same output regardless of how fast the
+//! machine replays it. Nothing on these paths draws random numbers, so the
+//! clock is the only input that has to be pinned down.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that stands still until told to move.
+pub struct VirtualClock {
+    origin: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves to `elapsed` since the origin; never goes backwards.
+    pub fn set(&self, elapsed: Duration) {
+        let mut current = self.elapsed.lock().unwrap();
+        *current = (*current).max(elapsed);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+}
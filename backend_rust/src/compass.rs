@@ -0,0 +1,273 @@
+//! QMC5883L magnetometer with hard/soft-iron calibration and heading output.
+//!
+//! The motors distort the field badly, so raw heading is unusable until a
+//! calibration has been captured by slowly rotating the robot in place.
+//!
+//! With an IMU, its accelerometer tilt-compensates the heading (see
+//! [`CompassManager::heading_with_gravity`]); the two boards are expected to
+//! be mounted with their axes aligned.
+
+use crate::faults;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::compass::{CalibrateRequest, Calibration, CompassReading};
+use rppal::i2c::I2c;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const I2C_ADDR: u16 = 0x0D;
+const REG_DATA: u8 = 0x00;
+const REG_STATUS: u8 = 0x06;
+const REG_CONTROL: u8 = 0x09;
+const REG_SET_RESET: u8 = 0x0B;
+/// Continuous mode, 200 Hz, 8 G range, 512x oversampling.
+const CONTROL_CONTINUOUS: u8 = 0x1D;
+
+pub const CALIBRATION_PATH: &str = "data/compass_calibration.json";
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Below this spread (raw counts) an axis was not rotated through enough to calibrate.
+const MIN_AXIS_SPAN: f32 = 200.0;
+
+fn load_calibration(path: &Path) -> Option<Calibration> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn save_calibration(calibration: &Calibration, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(calibration)?)
+}
+
+/// Heading in degrees (0 = magnetic north, clockwise), tilt-compensated when
+/// an accelerometer vector is available.
+pub fn heading_deg(mag: [f32; 3], accel: Option<[f32; 3]>) -> f32 {
+    let [mx, my, mz] = mag;
+    let (xh, yh) = match accel {
+        Some([ax, ay, az]) => {
+            let roll = ay.atan2(az);
+            let pitch = (-ax).atan2((ay * ay + az * az).sqrt());
+            (
+                mx * pitch.cos() + my * roll.sin() * pitch.sin() + mz * roll.cos() * pitch.sin(),
+                my * roll.cos() - mz * roll.sin(),
+            )
+        }
+        None => (mx, my),
+    };
+    (-yh).atan2(xh).to_degrees().rem_euclid(360.0)
+}
+
+struct Capture {
+    min: [f32; 3],
+    max: [f32; 3],
+    until: Instant,
+}
+
+struct CompassState {
+    raw: [f32; 3],
+    calibration: Option<Calibration>,
+    capture: Option<Capture>,
+    /// Latest accelerometer vector from the IMU, when there is one.
+    gravity: Option<[f32; 3]>,
+}
+
+pub struct CompassManager {
+    state: Mutex<CompassState>,
+    calibration_path: PathBuf,
+}
+
+impl CompassManager {
+    pub fn reading(&self) -> CompassReading {
+        let state = self.state.lock().unwrap();
+        let mag = state
+            .calibration
+            .map(|c| c.apply(state.raw))
+            .unwrap_or(state.raw);
+        CompassReading {
+            raw: state.raw,
+            heading_deg: heading_deg(mag, state.gravity),
+            calibrated: state.calibration.is_some(),
+            calibrating: state.capture.is_some(),
+        }
+    }
+
+    /// Calibrated field vector, for consumers that have their own accelerometer.
+    pub fn field(&self) -> [f32; 3] {
+        let state = self.state.lock().unwrap();
+        state
+            .calibration
+            .map(|c| c.apply(state.raw))
+            .unwrap_or(state.raw)
+    }
+
+    /// Tilt-compensated heading with `accel` as the direction of gravity,
+    /// which later readings use too; `None` until calibrated.
+    pub fn heading_with_gravity(&self, accel: [f32; 3]) -> Option<f32> {
+        let mut state = self.state.lock().unwrap();
+        state.gravity = Some(accel);
+        let calibration = state.calibration?;
+        Some(heading_deg(calibration.apply(state.raw), Some(accel)))
+    }
+
+    pub fn calibration(&self) -> Option<Calibration> {
+        self.state.lock().unwrap().calibration
+    }
+
+    /// Starts collecting min/max extents; the robot must rotate in place
+    /// (at least one full turn) until `duration` has elapsed.
+    pub fn start_calibration(&self, duration: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.capture.is_some() {
+            return false;
+        }
+        println!(
+            "[INFO] Compass calibration started ({:.0}s), rotate the robot in place",
+            duration.as_secs_f32()
+        );
+        state.capture = Some(Capture {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+            until: Instant::now() + duration,
+        });
+        true
+    }
+
+    fn update(&self, raw: [f32; 3]) {
+        let mut state = self.state.lock().unwrap();
+        state.raw = raw;
+
+        let Some(capture) = state.capture.as_mut() else {
+            return;
+        };
+        for (axis, &value) in raw.iter().enumerate() {
+            capture.min[axis] = capture.min[axis].min(value);
+            capture.max[axis] = capture.max[axis].max(value);
+        }
+        if Instant::now() < capture.until {
+            return;
+        }
+
+        let capture = state.capture.take().unwrap();
+        let span: Vec<f32> = (0..3).map(|i| capture.max[i] - capture.min[i]).collect();
+        // Z barely changes when rotating flat on the floor, so only X/Y gate success
+        if span[0] < MIN_AXIS_SPAN || span[1] < MIN_AXIS_SPAN {
+            println!(
+                "[WARN] Compass calibration rejected: not enough rotation (span {:?})",
+                span
+            );
+            return;
+        }
+        let avg = (span[0] + span[1]) / 2.0;
+        let calibration = Calibration {
+            offset: [
+                (capture.max[0] + capture.min[0]) / 2.0,
+                (capture.max[1] + capture.min[1]) / 2.0,
+                (capture.max[2] + capture.min[2]) / 2.0,
+            ],
+            scale: [
+                avg / span[0],
+                avg / span[1],
+                if span[2] >= MIN_AXIS_SPAN {
+                    avg / span[2]
+                } else {
+                    1.0
+                },
+            ],
+        };
+        match save_calibration(&calibration, &self.calibration_path) {
+            Ok(()) => println!("[OK] Compass calibration saved: {:?}", calibration),
+            Err(e) => eprintln!("[ERR] Could not persist compass calibration: {}", e),
+        }
+        state.calibration = Some(calibration);
+    }
+}
+
+fn read_raw(i2c: &mut I2c) -> rppal::i2c::Result<Option<[f32; 3]>> {
+    let mut status = [0u8; 1];
+    i2c.block_read(REG_STATUS, &mut status)?;
+    if status[0] & 0x01 == 0 {
+        return Ok(None);
+    }
+    let mut buf = [0u8; 6];
+    i2c.block_read(REG_DATA, &mut buf)?;
+    Ok(Some([
+        i16::from_le_bytes([buf[0], buf[1]]) as f32,
+        i16::from_le_bytes([buf[2], buf[3]]) as f32,
+        i16::from_le_bytes([buf[4], buf[5]]) as f32,
+    ]))
+}
+
+pub fn start_compass_thread() -> Result<Arc<CompassManager>, Box<dyn std::error::Error>> {
+    let mut i2c = I2c::new()?;
+    i2c.set_slave_address(I2C_ADDR)?;
+    i2c.smbus_write_byte(REG_SET_RESET, 0x01)?;
+    i2c.smbus_write_byte(REG_CONTROL, CONTROL_CONTINUOUS)?;
+
+    let calibration_path = PathBuf::from(CALIBRATION_PATH);
+    let calibration = load_calibration(&calibration_path);
+    match calibration {
+        Some(_) => println!("[OK] Loaded compass calibration from {}", CALIBRATION_PATH),
+        None => println!("[WARN] Compass is uncalibrated, heading will be unreliable"),
+    }
+
+    let manager = Arc::new(CompassManager {
+        state: Mutex::new(CompassState {
+            raw: [0.0; 3],
+            calibration,
+            capture: None,
+            gravity: None,
+        }),
+        calibration_path,
+    });
+    let cm_clone = Arc::clone(&manager);
+
+    thread::spawn(move || loop {
+        if faults::killed("compass") {
+            return;
+        }
+        match read_raw(&mut i2c) {
+            Ok(Some([x, y, z])) if faults::compass_outlier() => cm_clone.update([-x, -y, z]),
+            Ok(Some(raw)) => cm_clone.update(raw),
+            Ok(None) => {}
+            Err(e) => eprintln!("[ERR] Compass read failed: {}", e),
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+
+    Ok(manager)
+}
+
+pub fn routes(compass: Arc<CompassManager>) -> Router {
+    Router::new()
+        .route("/compass", get(get_reading))
+        .route("/compass/calibration", get(get_calibration))
+        .route("/compass/calibrate", post(calibrate))
+        .with_state(compass)
+}
+
+async fn get_reading(State(compass): State<Arc<CompassManager>>) -> Json<CompassReading> {
+    Json(compass.reading())
+}
+
+async fn get_calibration(State(compass): State<Arc<CompassManager>>) -> Json<Option<Calibration>> {
+    Json(compass.calibration())
+}
+
+async fn calibrate(
+    State(compass): State<Arc<CompassManager>>,
+    Json(req): Json<CalibrateRequest>,
+) -> StatusCode {
+    let duration = Duration::from_secs_f32(req.duration_s.clamp(5.0, 120.0));
+    if compass.start_calibration(duration) {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::CONFLICT
+    }
+}
@@ -0,0 +1,907 @@
+//! Startup configuration: robot, camera, MCU, charging, gimbal, IMU, range
+//! sensors, drive, operator presence, power, mission, model, streaming,
+//! recording, privacy and server settings from a TOML file, with the
+//! environment variables the backend always read taking precedence. Those
+//! are applied here and nowhere else (see [`Config::load`]).
+//!
+//! The file is `config.toml` in the working directory, or whatever
+//! `RASPIBOT_CONFIG` points at; without one, the built-in defaults apply.
+//! Every section and key is optional (see `config.example.toml`). Tuning
+//! that changes at runtime (zones, servo gains, ...) stays in `data/`.
+
+use crate::camera::CaptureSettings;
+use crate::net;
+use crate::privacy;
+use crate::serial;
+use crate::source::PlaybackConfig;
+use crate::tls::TlsSettings;
+use crate::yolo::{self, Thresholds, TileConfig};
+use raspibot_protocol::inference::SessionOptions;
+use raspibot_protocol::mission::ApprovalAction;
+use raspibot_protocol::servo::AxisGains;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+pub const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub robot: RobotConfig,
+    /// The primary camera, which navigation, recording and everything not
+    /// tied to a camera id uses.
+    pub camera: CameraConfig,
+    /// Further cameras (`[[cameras]]`), each with its own id.
+    pub cameras: Vec<CameraConfig>,
+    pub mcu: McuConfig,
+    pub charging: ChargingConfig,
+    pub gimbal: GimbalConfig,
+    pub imu: ImuConfig,
+    pub illuminator: IlluminatorConfig,
+    pub range: RangeConfig,
+    pub velocity: VelocityConfig,
+    pub drive: DriveConfig,
+    pub presence: PresenceConfig,
+    pub power: PowerConfig,
+    pub mission: MissionConfig,
+    pub model: ModelConfig,
+    pub reid: ReidConfig,
+    pub telemetry: TelemetryConfig,
+    pub stream: StreamConfig,
+    pub recording: RecordingConfig,
+    pub privacy: PrivacyConfig,
+    pub server: ServerConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotConfig {
+    /// Shown to operators so they know which robot they reached; the
+    /// hostname when unset.
+    pub name: String,
+    pub team: Option<String>,
+    /// Print the robot's identity as a banner at startup.
+    pub splash: bool,
+}
+
+impl Default for RobotConfig {
+    fn default() -> Self {
+        let name = std::fs::read_to_string("/etc/hostname")
+            .ok()
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .unwrap_or_else(|| "raspibot".to_string());
+        Self {
+            name,
+            team: None,
+            splash: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    /// Names the camera in URLs such as `/stream/<id>`.
+    pub id: String,
+    /// V4L2 device (e.g. `/dev/video1`) to open instead of the CSI camera.
+    pub device: Option<String>,
+    pub video: CaptureSettings,
+    /// Sensor-native mode for full-resolution stills.
+    pub still: CaptureSettings,
+    /// GStreamer pipeline with `{width}`, `{height}`, `{fps}` and
+    /// `{controls}` (`libcamerasrc` properties for the manual image
+    /// controls) placeholders; the libcamera one when unset.
+    pub pipeline: Option<String>,
+    /// Recorded frames to play instead of opening the camera.
+    pub playback: Option<PlaybackConfig>,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            id: "front".to_string(),
+            device: None,
+            video: CaptureSettings::default(),
+            still: CaptureSettings::still_default(),
+            pipeline: None,
+            playback: None,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// The GStreamer pipeline for capturing in `settings`' mode, with AE and
+    /// AWB off when `locked`.
+    pub fn pipeline(&self, settings: &CaptureSettings, locked: bool) -> String {
+        match &self.pipeline {
+            Some(template) => template
+                .replace("{width}", &settings.width.to_string())
+                .replace("{height}", &settings.height.to_string())
+                .replace("{fps}", &settings.fps.to_string())
+                .replace(
+                    "{controls}",
+                    &crate::camera::libcamera_controls(&settings.controls, locked),
+                ),
+            None => crate::camera::gstreamer_pipeline(settings, locked),
+        }
+    }
+
+    fn validate(&self, section: &str) -> Result<(), String> {
+        let valid_id = !self.id.is_empty()
+            && self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(format!(
+                "{}: id must be letters, digits, '-' or '_'",
+                section
+            ));
+        }
+        for (name, settings) in [("video", &self.video), ("still", &self.still)] {
+            crate::camera::validate_settings(settings)
+                .map_err(|e| format!("{}.{}: {}", section, name, e))?;
+        }
+        if let Some(fps) = self.playback.as_ref().and_then(|p| p.fps) {
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err(format!("{}.playback: fps must be positive", section));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct McuConfig {
+    /// Serial port of the auxiliary microcontroller.
+    pub port: String,
+    pub baud: u32,
+    /// The MCU runs the drive motors: commands go over the bridge instead of
+    /// to the GPIO or CAN motors.
+    pub drive: bool,
+}
+
+impl Default for McuConfig {
+    fn default() -> Self {
+        Self {
+            port: serial::DEFAULT_PORT.to_string(),
+            baud: serial::DEFAULT_BAUD,
+            drive: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChargingConfig {
+    /// BCM pin of the charger's sense line.
+    pub sense_pin: Option<u8>,
+    /// The sense line is pulled low, rather than high, while plugged in.
+    pub active_low: bool,
+    /// ADC channel, by its name in the unit calibration, that reads battery
+    /// current in amps, positive while charging.
+    pub current_channel: Option<String>,
+    /// Current above which the battery counts as charging.
+    pub charge_current_a: f32,
+    /// Refuse drive commands while charging.
+    pub block_drive: bool,
+}
+
+impl Default for ChargingConfig {
+    fn default() -> Self {
+        Self {
+            sense_pin: None,
+            active_low: false,
+            current_channel: None,
+            charge_current_a: 0.1,
+            block_drive: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GimbalDriver {
+    /// Software PWM on two GPIO pins.
+    Gpio,
+    /// A PCA9685 PWM board on the I2C bus.
+    Pca9685,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GimbalConfig {
+    /// What drives the pan and tilt servos; no gimbal when unset.
+    pub driver: Option<GimbalDriver>,
+    /// I2C address of the PCA9685.
+    pub i2c_address: u16,
+    pub pan: ServoAxisConfig,
+    pub tilt: ServoAxisConfig,
+    /// Fastest either axis turns, in degrees per second.
+    pub slew_deg_s: f32,
+}
+
+impl Default for GimbalConfig {
+    fn default() -> Self {
+        Self {
+            driver: None,
+            i2c_address: 0x40,
+            pan: ServoAxisConfig::default(),
+            tilt: ServoAxisConfig {
+                channel: 1,
+                min_deg: -45.0,
+                max_deg: 45.0,
+                ..ServoAxisConfig::default()
+            },
+            slew_deg_s: 90.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServoAxisConfig {
+    /// BCM pin with the `gpio` driver, PCA9685 channel (0-15) with
+    /// `pca9685`.
+    pub channel: u8,
+    /// Travel allowed, in degrees from center.
+    pub min_deg: f32,
+    pub max_deg: f32,
+    /// Pulse widths that turn the servo to -90 and +90 degrees.
+    pub min_pulse_us: f32,
+    pub max_pulse_us: f32,
+    /// The servo is mounted so it turns the other way.
+    pub reversed: bool,
+}
+
+impl Default for ServoAxisConfig {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            min_deg: -90.0,
+            max_deg: 90.0,
+            min_pulse_us: 500.0,
+            max_pulse_us: 2500.0,
+            reversed: false,
+        }
+    }
+}
+
+impl ServoAxisConfig {
+    fn validate(&self, section: &str, driver: Option<GimbalDriver>) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.min_deg)
+            || !(-90.0..=90.0).contains(&self.max_deg)
+            || self.min_deg >= self.max_deg
+        {
+            return Err(format!(
+                "{}: min_deg and max_deg must be within -90..=90, min below max",
+                section
+            ));
+        }
+        if !(self.min_pulse_us > 0.0 && self.min_pulse_us < self.max_pulse_us) {
+            return Err(format!(
+                "{}: pulse widths must be positive, min below max",
+                section
+            ));
+        }
+        if driver == Some(GimbalDriver::Pca9685) && self.channel > 15 {
+            return Err(format!("{}: the PCA9685 has channels 0-15", section));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImuChip {
+    /// InvenSense MPU-6050: accelerometer and gyro, orientation fused by
+    /// the backend.
+    Mpu6050,
+    /// Bosch BNO055, which fuses orientation itself.
+    Bno055,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImuConfig {
+    /// The IMU on the I2C bus; none when unset.
+    pub chip: Option<ImuChip>,
+    /// The chip's usual address (0x68, 0x28) when unset.
+    pub i2c_address: Option<u16>,
+    /// Readings per second.
+    pub rate_hz: f32,
+}
+
+impl Default for ImuConfig {
+    fn default() -> Self {
+        Self {
+            chip: None,
+            i2c_address: None,
+            rate_hz: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IlluminatorConfig {
+    /// BCM pin wired to the IR illuminator's MOSFET gate; no illuminator
+    /// when unset.
+    pub pin: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeSensorKind {
+    /// Ultrasonic, trigger and echo on GPIO pins.
+    HcSr04,
+    /// Time-of-flight laser on the I2C bus.
+    Vl53l0x,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangeSensorConfig {
+    /// Names the sensor in readings and telemetry, e.g. `front`.
+    pub name: String,
+    pub kind: RangeSensorKind,
+    /// BCM pins of an HC-SR04.
+    #[serde(default)]
+    pub trigger_pin: Option<u8>,
+    #[serde(default)]
+    pub echo_pin: Option<u8>,
+    /// I2C address of a VL53L0X.
+    #[serde(default = "default_vl53l0x_address")]
+    pub i2c_address: u16,
+    /// Looks where the robot drives forward; only these stop it.
+    #[serde(default = "default_true")]
+    pub forward: bool,
+}
+
+fn default_vl53l0x_address() -> u16 {
+    0x29
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RangeConfig {
+    /// Distance sensors (`[[range.sensors]]`), read one after the other.
+    pub sensors: Vec<RangeSensorConfig>,
+    /// Rounds of readings per second.
+    pub rate_hz: f32,
+    /// Forward drive is blocked while a forward sensor reads closer than
+    /// this; no guard when unset.
+    pub stop_distance_m: Option<f32>,
+}
+
+impl Default for RangeConfig {
+    fn default() -> Self {
+        Self {
+            sensors: Vec::new(),
+            rate_hz: 15.0,
+            stop_distance_m: None,
+        }
+    }
+}
+
+/// Closed-loop drive (`POST /drive/velocity`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VelocityConfig {
+    /// Control steps per second.
+    pub rate_hz: f32,
+    /// Per wheel, from speed error (m/s) to duty added to the speed model's.
+    pub wheel: AxisGains,
+    /// From heading error (degrees) to turn rate (degrees per second),
+    /// holding the heading while driving straight.
+    pub heading: AxisGains,
+    /// The robot stops once no setpoint has arrived for this long.
+    pub timeout_ms: u64,
+}
+
+impl Default for VelocityConfig {
+    fn default() -> Self {
+        Self {
+            rate_hz: 50.0,
+            wheel: AxisGains {
+                kp: 0.8,
+                ki: 2.0,
+                kd: 0.0,
+                // The integral carries the steady-state correction, a
+                // deadband would keep dropping it
+                deadband: 0.0,
+                max_rate: 0.3,
+            },
+            heading: AxisGains {
+                kp: 4.0,
+                ki: 0.0,
+                kd: 0.2,
+                deadband: 0.5,
+                max_rate: 60.0,
+            },
+            timeout_ms: 500,
+        }
+    }
+}
+
+/// Direct and teleop drive.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DriveConfig {
+    /// The watchdog stops the motors once no drive command has arrived for
+    /// this long; the profile's `command_timeout_ms` when unset.
+    pub timeout_ms: Option<u64>,
+    /// Teleop stops once the driving client has been silent this long.
+    pub deadman_ms: u64,
+}
+
+impl Default for DriveConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: None,
+            deadman_ms: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresenceConfig {
+    /// Teleop idles once no dashboard has sent a heartbeat for this long.
+    pub timeout_s: f32,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self { timeout_s: 5.0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    /// Idle by itself after this long without operator activity; only when
+    /// asked to when unset.
+    pub idle_after_s: Option<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MissionConfig {
+    /// Longest autonomous run the competition allows; the robot is sent to
+    /// idle when it is up. Unlimited when unset.
+    pub max_autonomous_s: Option<f32>,
+    /// How long before the limit operators are warned.
+    pub warn_before_s: f32,
+    /// Steps of autonomous behaviors that wait for the operator's approval.
+    pub approvals: Vec<ApprovalGateConfig>,
+}
+
+impl Default for MissionConfig {
+    fn default() -> Self {
+        Self {
+            max_autonomous_s: None,
+            warn_before_s: 10.0,
+            approvals: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalGateConfig {
+    /// The step behaviors ask about, e.g. `grasp`.
+    pub step: String,
+    #[serde(default = "default_approval_timeout")]
+    pub timeout_s: f32,
+    /// What happens when nobody decides in time.
+    #[serde(default = "default_approval_action")]
+    pub on_timeout: ApprovalAction,
+}
+
+fn default_approval_timeout() -> f32 {
+    30.0
+}
+
+fn default_approval_action() -> ApprovalAction {
+    ApprovalAction::Skip
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    pub path: String,
+    /// Best class score a box needs to be decoded at all.
+    pub confidence: f32,
+    /// IoU above which same-class boxes of one pass are suppressed.
+    pub iou: f32,
+    /// Tiled inference on top of the full-frame pass; off when unset.
+    pub tiles: Option<TileConfig>,
+    /// ONNX Runtime threading and memory.
+    pub session: SessionOptions,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        let thresholds = Thresholds::default();
+        Self {
+            path: yolo::DEFAULT_MODEL_PATH.to_string(),
+            confidence: thresholds.confidence,
+            iou: thresholds.iou,
+            tiles: None,
+            session: SessionOptions::default(),
+        }
+    }
+}
+
+impl ModelConfig {
+    pub fn thresholds(&self) -> Thresholds {
+        Thresholds {
+            confidence: self.confidence,
+            iou: self.iou,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReidConfig {
+    /// How long an identity that left view is remembered.
+    pub memory_s: f32,
+}
+
+impl Default for ReidConfig {
+    fn default() -> Self {
+        Self { memory_s: 60.0 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Rate streams are sent to remote clients at, unless changed at runtime;
+    /// 0 sends every sample.
+    pub remote_hz: f32,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self { remote_hz: 10.0 }
+    }
+}
+
+/// MJPEG stream limits; the profile's `stream_max_fps` and
+/// `stream_jpeg_quality` when unset.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamConfig {
+    /// Fastest rate a client may ask for.
+    pub max_fps: Option<f32>,
+    pub jpeg_quality: Option<i32>,
+    /// Draw detections and the overlay unless a client asks otherwise.
+    pub annotate: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordingConfig {
+    /// Frame rate of match recordings that don't ask for one.
+    pub fps: f32,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self { fps: 15.0 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrivacyConfig {
+    /// Haar cascade face blur finds faces with.
+    pub face_cascade: String,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            face_cascade: privacy::DEFAULT_CASCADE.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Listen addresses, e.g. `["0.0.0.0:8080", "[::]:8080"]`.
+    pub bind: Vec<String>,
+    /// Serve HTTPS/WSS with this certificate pair (`[server.tls]`).
+    pub tls: Option<TlsSettings>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: vec![net::DEFAULT_BIND.to_string()],
+            tls: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file (if any), then applies `ROBOT_NAME`, `ROBOT_TEAM`,
+    /// `CAMERA_RESOLUTION`, `STILL_RESOLUTION`, `CAMERA_PIPELINE`,
+    /// `CAMERA_PLAYBACK`, `DRIVE_TIMEOUT_MS`, `TELEOP_DEADMAN_MS`,
+    /// `PRESENCE_TIMEOUT_S`, `POWER_IDLE_AFTER_S`, `YOLO_MODEL`,
+    /// `YOLO_CONFIDENCE`, `YOLO_IOU`, `YOLO_TILES`, `YOLO_TILE_OVERLAP`, the
+    /// `YOLO_*` session options, `REID_MEMORY_S`, `TELEMETRY_REMOTE_HZ`,
+    /// `STREAM_MAX_FPS`, `STREAM_JPEG_QUALITY`, `STREAM_ANNOTATE`,
+    /// `RECORD_FPS`, `FACE_CASCADE`, `BIND_ADDRS` and `TLS_ENABLED`,
+    /// `TLS_CERT`, `TLS_KEY` on top, and validates the result. A file that
+    /// exists but does not parse is an error rather than silently ignored,
+    /// and so is an override out of range.
+    pub fn load() -> Result<Self, String> {
+        let path = std::env::var("RASPIBOT_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_PATH));
+        let (mut config, source) = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let config: Self =
+                    toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+                println!("[INFO] Configuration loaded from {}", path.display());
+                (config, path.display().to_string())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                (Self::default(), "built-in defaults".to_string())
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        // Overrides are checked like the file, so none reaches a subsystem
+        // unvalidated
+        config.apply_env();
+        config
+            .validate()
+            .map_err(|e| format!("{} with environment overrides: {}", source, e))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.robot.name.trim().is_empty() {
+            return Err("robot: name must not be empty".to_string());
+        }
+        self.camera.validate("camera")?;
+        let mut ids = vec![self.camera.id.as_str()];
+        for camera in &self.cameras {
+            camera.validate(&format!("cameras.{}", camera.id))?;
+            if ids.contains(&camera.id.as_str()) {
+                return Err(format!("cameras: id '{}' is used twice", camera.id));
+            }
+            ids.push(&camera.id);
+        }
+        if self.mcu.port.trim().is_empty() || self.mcu.baud == 0 {
+            return Err("mcu: port and baud must be set".to_string());
+        }
+        if !(self.charging.charge_current_a > 0.0 && self.charging.charge_current_a.is_finite()) {
+            return Err("charging: charge_current_a must be positive".to_string());
+        }
+        let gimbal = &self.gimbal;
+        gimbal.pan.validate("gimbal.pan", gimbal.driver)?;
+        gimbal.tilt.validate("gimbal.tilt", gimbal.driver)?;
+        if gimbal.pan.channel == gimbal.tilt.channel {
+            return Err("gimbal: pan and tilt need their own channels".to_string());
+        }
+        if !(gimbal.slew_deg_s > 0.0 && gimbal.slew_deg_s.is_finite()) {
+            return Err("gimbal: slew_deg_s must be positive".to_string());
+        }
+        if !(self.imu.rate_hz > 0.0 && self.imu.rate_hz <= 200.0) {
+            return Err("imu: rate_hz must be positive and at most 200".to_string());
+        }
+        let mut names = Vec::new();
+        for sensor in &self.range.sensors {
+            if sensor.name.trim().is_empty() || names.contains(&sensor.name.as_str()) {
+                return Err("range: sensors need unique, non-empty names".to_string());
+            }
+            names.push(&sensor.name);
+            if sensor.kind == RangeSensorKind::HcSr04
+                && (sensor.trigger_pin.is_none() || sensor.echo_pin.is_none())
+            {
+                return Err(format!(
+                    "range.sensors.{}: an HC-SR04 needs trigger_pin and echo_pin",
+                    sensor.name
+                ));
+            }
+        }
+        if !(self.range.rate_hz > 0.0 && self.range.rate_hz <= 50.0) {
+            return Err("range: rate_hz must be positive and at most 50".to_string());
+        }
+        if let Some(distance) = self.range.stop_distance_m {
+            if !(distance > 0.0 && distance.is_finite()) {
+                return Err("range: stop_distance_m must be positive".to_string());
+            }
+        }
+        if !(self.velocity.rate_hz > 0.0 && self.velocity.rate_hz <= 200.0) {
+            return Err("velocity: rate_hz must be positive and at most 200".to_string());
+        }
+        for (name, g) in [
+            ("wheel", self.velocity.wheel),
+            ("heading", self.velocity.heading),
+        ] {
+            let values = [g.kp, g.ki, g.kd, g.deadband, g.max_rate];
+            if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+                return Err(format!(
+                    "velocity.{}: gains must be finite and non-negative",
+                    name
+                ));
+            }
+        }
+        if self.velocity.timeout_ms == 0 {
+            return Err("velocity: timeout_ms must be positive".to_string());
+        }
+        if let Some(limit) = self.mission.max_autonomous_s {
+            if !(limit > 0.0 && limit.is_finite()) {
+                return Err("mission: max_autonomous_s must be positive".to_string());
+            }
+        }
+        if !(self.mission.warn_before_s >= 0.0 && self.mission.warn_before_s.is_finite()) {
+            return Err("mission: warn_before_s must not be negative".to_string());
+        }
+        for gate in &self.mission.approvals {
+            if gate.step.trim().is_empty() {
+                return Err("mission.approvals: every gate needs a step".to_string());
+            }
+            if !(gate.timeout_s > 0.0 && gate.timeout_s.is_finite()) {
+                return Err(format!(
+                    "mission.approvals.{}: timeout_s must be positive",
+                    gate.step
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.model.confidence) || !(0.0..=1.0).contains(&self.model.iou) {
+            return Err("model: confidence and iou must be within 0..=1".to_string());
+        }
+        if self.drive.timeout_ms == Some(0) || self.drive.deadman_ms == 0 {
+            return Err("drive: timeout_ms and deadman_ms must be positive".to_string());
+        }
+        if !(self.presence.timeout_s > 0.0 && self.presence.timeout_s.is_finite()) {
+            return Err("presence: timeout_s must be positive".to_string());
+        }
+        if let Some(idle_after) = self.power.idle_after_s {
+            if !(idle_after > 0.0 && idle_after.is_finite()) {
+                return Err("power: idle_after_s must be positive".to_string());
+            }
+        }
+        if let Some(tiles) = &self.model.tiles {
+            if tiles.cols < 1 || tiles.rows < 1 || !(0.0..1.0).contains(&tiles.overlap) {
+                return Err(
+                    "model.tiles: cols and rows must be positive, overlap within 0..1".to_string(),
+                );
+            }
+        }
+        if !(self.reid.memory_s >= 0.0 && self.reid.memory_s.is_finite()) {
+            return Err("reid: memory_s must not be negative".to_string());
+        }
+        if !(self.telemetry.remote_hz >= 0.0 && self.telemetry.remote_hz.is_finite()) {
+            return Err("telemetry: remote_hz must not be negative".to_string());
+        }
+        if !(self.recording.fps > 0.0 && self.recording.fps.is_finite()) {
+            return Err("recording: fps must be positive".to_string());
+        }
+        if let Some(fps) = self.stream.max_fps {
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err("stream: max_fps must be positive".to_string());
+            }
+        }
+        if let Some(quality) = self.stream.jpeg_quality {
+            if !(1..=100).contains(&quality) {
+                return Err("stream: jpeg_quality must be within 1..=100".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_env(&mut self) {
+        if let Ok(name) = std::env::var("ROBOT_NAME") {
+            self.robot.name = name;
+        }
+        if let Ok(team) = std::env::var("ROBOT_TEAM") {
+            self.robot.team = Some(team);
+        }
+        self.camera.video = CaptureSettings::from_env_var("CAMERA_RESOLUTION", self.camera.video);
+        self.camera.still = CaptureSettings::from_env_var("STILL_RESOLUTION", self.camera.still);
+        if let Ok(pipeline) = std::env::var("CAMERA_PIPELINE") {
+            self.camera.pipeline = Some(pipeline);
+        }
+        if let Some(playback) = PlaybackConfig::from_env() {
+            self.camera.playback = Some(playback);
+        }
+        if let Ok(path) = std::env::var("YOLO_MODEL") {
+            self.model.path = path;
+        }
+        let thresholds = yolo::thresholds_with_env(self.model.thresholds());
+        self.model.confidence = thresholds.confidence;
+        self.model.iou = thresholds.iou;
+        self.model.tiles = yolo::tiling_with_env(self.model.tiles);
+        self.model.session = yolo::session_options_with_env(self.model.session.clone());
+        if let Some(ms) = env_parse::<u64>("DRIVE_TIMEOUT_MS").filter(|ms| *ms > 0) {
+            self.drive.timeout_ms = Some(ms);
+        }
+        if let Some(ms) = env_parse::<u64>("TELEOP_DEADMAN_MS").filter(|ms| *ms > 0) {
+            self.drive.deadman_ms = ms;
+        }
+        if let Some(s) = env_parse::<f32>("PRESENCE_TIMEOUT_S").filter(|s| *s > 0.0) {
+            self.presence.timeout_s = s;
+        }
+        if let Some(s) = env_parse::<f32>("POWER_IDLE_AFTER_S").filter(|s| *s > 0.0) {
+            self.power.idle_after_s = Some(s);
+        }
+        if let Some(s) = env_parse::<f32>("REID_MEMORY_S").filter(|s| *s >= 0.0) {
+            self.reid.memory_s = s;
+        }
+        if let Some(hz) = env_parse::<f32>("TELEMETRY_REMOTE_HZ").filter(|hz| *hz >= 0.0) {
+            self.telemetry.remote_hz = hz;
+        }
+        if let Some(fps) = env_parse::<f32>("STREAM_MAX_FPS").filter(|fps| *fps > 0.0) {
+            self.stream.max_fps = Some(fps);
+        }
+        if let Some(quality) = env_parse::<i32>("STREAM_JPEG_QUALITY") {
+            self.stream.jpeg_quality = Some(quality.clamp(1, 100));
+        }
+        if let Some(annotate) = env_flag("STREAM_ANNOTATE") {
+            self.stream.annotate = annotate;
+        }
+        if let Some(fps) = env_parse::<f32>("RECORD_FPS").filter(|fps| *fps > 0.0) {
+            self.recording.fps = fps;
+        }
+        if let Ok(path) = std::env::var("FACE_CASCADE") {
+            self.privacy.face_cascade = path;
+        }
+        if let Ok(value) = std::env::var("BIND_ADDRS") {
+            self.server.bind = value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        match env_flag("TLS_ENABLED") {
+            Some(true) if self.server.tls.is_none() => {
+                self.server.tls = Some(TlsSettings::default());
+            }
+            Some(false) => self.server.tls = None,
+            _ => {}
+        }
+        if let Some(tls) = self.server.tls.as_mut() {
+            if let Ok(cert) = std::env::var("TLS_CERT") {
+                tls.cert = cert.into();
+            }
+            if let Ok(key) = std::env::var("TLS_KEY") {
+                tls.key = key.into();
+            }
+        }
+    }
+}
+
+/// `name` parsed as a `T`; `None` when unset or unparseable.
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+/// `name` as a switch: `1`/`true`/`yes` or `0`/`false`/`no`.
+fn env_flag(name: &str) -> Option<bool> {
+    match std::env::var(name)
+        .ok()?
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
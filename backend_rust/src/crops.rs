@@ -0,0 +1,173 @@
+//! Image crops of tracked objects, so the operator can check the robot is
+//! about to pick the right item before approving an autonomous action.
+//!
+//! The inference workers cut every tracked object out of the frame it was
+//! detected in, with some margin for context, keeping per track the crop
+//! from the latest set and the one from its best-confidence set.
+//! `GET /detections/{track_id}/crop` serves the latest as a JPEG,
+//! `?pick=best` the best; face blur applies as it does to anything leaving
+//! the robot. Crops stay in memory and go with their track.
+
+use crate::annotate;
+use crate::detections::Published;
+use crate::state::AppState;
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use opencv::{
+    core::{Mat, Rect},
+    prelude::*,
+};
+use raspibot_protocol::inference::{CropPick, CropQuery};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Share of the box's size added on every side.
+const MARGIN: f32 = 0.15;
+const JPEG_QUALITY: i32 = 90;
+/// Tracks not seen for this long lose their crops.
+const RETENTION: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone)]
+struct Crop {
+    image: Arc<Mat>,
+    class: String,
+    confidence: f32,
+    captured_unix_ms: u64,
+}
+
+struct TrackCrops {
+    camera: String,
+    latest: Crop,
+    best: Crop,
+    last_seen: Instant,
+}
+
+pub struct CropStore {
+    tracks: Mutex<HashMap<u64, TrackCrops>>,
+}
+
+/// The box `[x, y, w, h]` grown by `MARGIN` and clipped to the frame;
+/// `None` when nothing of it is left.
+fn crop_rect(bbox: [i32; 4], cols: i32, rows: i32) -> Option<Rect> {
+    let [x, y, w, h] = bbox;
+    let dx = (w as f32 * MARGIN).round() as i32;
+    let dy = (h as f32 * MARGIN).round() as i32;
+    let x1 = (x - dx).max(0);
+    let y1 = (y - dy).max(0);
+    let x2 = (x + w + dx).min(cols);
+    let y2 = (y + h + dy).min(rows);
+    (x2 > x1 && y2 > y1).then(|| Rect::new(x1, y1, x2 - x1, y2 - y1))
+}
+
+impl CropStore {
+    pub fn new() -> Self {
+        Self {
+            tracks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keeps crops of the tracked objects of `published`, cut from `frame`,
+    /// the frame camera `camera` detected them in.
+    pub fn record(&self, camera: &str, frame: &Mat, published: &Published) {
+        let now = Instant::now();
+        let mut tracks = self.tracks.lock().unwrap();
+        tracks.retain(|_, t| now.duration_since(t.last_seen) < RETENTION);
+        for object in &published.objects {
+            let Some(track_id) = object.track_id else {
+                continue;
+            };
+            let Some(rect) = crop_rect(object.bbox, frame.cols(), frame.rows()) else {
+                continue;
+            };
+            let image = match Mat::roi(frame, rect).and_then(|roi| roi.try_clone()) {
+                Ok(image) => Arc::new(image),
+                Err(e) => {
+                    eprintln!("[ERR] Could not crop track {}: {}", track_id, e);
+                    continue;
+                }
+            };
+            let crop = Crop {
+                image,
+                class: object.class.clone(),
+                confidence: object.confidence,
+                captured_unix_ms: published.captured_unix_ms,
+            };
+            match tracks.get_mut(&track_id) {
+                Some(track) => {
+                    if crop.confidence > track.best.confidence {
+                        track.best = crop.clone();
+                    }
+                    track.camera = camera.to_string();
+                    track.latest = crop;
+                    track.last_seen = now;
+                }
+                None => {
+                    tracks.insert(
+                        track_id,
+                        TrackCrops {
+                            camera: camera.to_string(),
+                            best: crop.clone(),
+                            latest: crop,
+                            last_seen: now,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn get(&self, track_id: u64, pick: CropPick) -> Option<(String, Crop)> {
+        let tracks = self.tracks.lock().unwrap();
+        let track = tracks.get(&track_id)?;
+        let crop = match pick {
+            CropPick::Latest => &track.latest,
+            CropPick::Best => &track.best,
+        };
+        Some((track.camera.clone(), crop.clone()))
+    }
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/detections/{id}/crop", get(get_crop))
+        .with_state(state)
+}
+
+/// The JPEG, with what it shows in `x-track-*` headers.
+async fn get_crop(
+    State(state): State<AppState>,
+    UrlPath(track_id): UrlPath<u64>,
+    Query(query): Query<CropQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let (camera_id, crop) = state.crops.get(track_id, query.pick).ok_or((
+        StatusCode::NOT_FOUND,
+        format!("no crop of track {}", track_id),
+    ))?;
+    let image = crop.image.clone();
+    let jpeg = tokio::task::spawn_blocking(move || {
+        annotate::publish_jpeg(&state, &image, JPEG_QUALITY, None)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let headers = [
+        (header::CONTENT_TYPE, "image/jpeg".to_string()),
+        (HeaderName::from_static("x-track-camera"), camera_id),
+        (HeaderName::from_static("x-track-class"), crop.class),
+        (
+            HeaderName::from_static("x-track-confidence"),
+            format!("{:.3}", crop.confidence),
+        ),
+        (
+            HeaderName::from_static("x-track-captured-unix-ms"),
+            crop.captured_unix_ms.to_string(),
+        ),
+    ];
+    Ok((headers, jpeg).into_response())
+}
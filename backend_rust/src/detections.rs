@@ -0,0 +1,183 @@
+//! Latest inference results, published with their capture time so every
+//! consumer can tell how old they are.
+//!
+//! An inference hiccup (thermal throttling, a slow tile pass) leaves the last
+//! set in place; instead of acting on it as if it were current, controllers
+//! scale their output by [`StalenessLimits::gain_scale`] and stop once it
+//! reaches zero.
+
+use crate::rate::RateMeter;
+use crate::state::AppState;
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use raspibot_protocol::inference::{DetectedObject, DetectionSet, StalenessLimits};
+use raspibot_protocol::locale::LocaleQuery;
+use raspibot_protocol::rates::RateStats;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Sets a slow receiver can fall behind by before it starts missing them.
+pub const CHANNEL_CAPACITY: usize = 8;
+
+pub struct Published {
+    pub seq: u64,
+    pub captured: Instant,
+    pub captured_unix_ms: u64,
+    pub objects: Vec<DetectedObject>,
+}
+
+impl Published {
+    pub fn age(&self) -> Duration {
+        self.captured.elapsed()
+    }
+
+    /// Wire form, tagged against `limits` at the moment of the call.
+    pub fn to_set(&self, limits: &StalenessLimits) -> DetectionSet {
+        let age_ms = self.age().as_millis() as u64;
+        DetectionSet {
+            seq: self.seq,
+            captured_unix_ms: self.captured_unix_ms,
+            age_ms,
+            freshness: limits.freshness(age_ms),
+            objects: self.objects.clone(),
+        }
+    }
+}
+
+pub struct DetectionHub {
+    latest: RwLock<Option<Arc<Published>>>,
+    seq: AtomicU64,
+    /// Tagging applied to sets sent to dashboards; controllers use their own.
+    limits: StalenessLimits,
+    tx: broadcast::Sender<Arc<Published>>,
+    rate: RateMeter,
+}
+
+impl DetectionHub {
+    /// `DETECTION_FRESH_MS` / `DETECTION_STALE_MS` override the limits used
+    /// to tag published sets.
+    pub fn from_env() -> Self {
+        let mut limits = StalenessLimits::default();
+        let parse = |name: &str| std::env::var(name).ok()?.trim().parse::<u64>().ok();
+        if let Some(ms) = parse("DETECTION_FRESH_MS") {
+            limits.fresh_ms = ms;
+        }
+        if let Some(ms) = parse("DETECTION_STALE_MS") {
+            limits.stale_ms = ms;
+        }
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            latest: RwLock::new(None),
+            seq: AtomicU64::new(0),
+            limits,
+            tx,
+            rate: RateMeter::new(),
+        }
+    }
+
+    pub fn limits(&self) -> StalenessLimits {
+        self.limits
+    }
+
+    /// Publishes the detections of the frame captured at `captured`.
+    pub fn publish(&self, objects: Vec<DetectedObject>, captured: Instant) -> Arc<Published> {
+        self.rate.tick();
+        let now_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let published = Arc::new(Published {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            captured,
+            captured_unix_ms: now_unix_ms.saturating_sub(captured.elapsed().as_millis() as u64),
+            objects,
+        });
+        *self.latest.write().unwrap() = Some(published.clone());
+        let _ = self.tx.send(published.clone());
+        published
+    }
+
+    /// Inference rate, as seen by consumers.
+    pub fn rate(&self) -> RateStats {
+        self.rate.stats()
+    }
+
+    pub fn latest(&self) -> Option<Arc<Published>> {
+        self.latest.read().unwrap().clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Published>> {
+        self.tx.subscribe()
+    }
+
+    /// Receivers of published sets, and the sets the furthest behind of
+    /// them has yet to take.
+    pub fn backlog(&self) -> (usize, usize) {
+        (self.tx.receiver_count(), self.tx.len())
+    }
+}
+
+/// Records every published set as the `detections` stream, so a run's
+/// blackbox can be replayed through the tracker (see `replay`).
+pub fn start_detection_recorder(state: AppState) {
+    let mut sets = state.detections.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let published = match sets.recv().await {
+                Ok(published) => published,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let mut values = serde_json::Map::new();
+            values.insert("seq".into(), published.seq.into());
+            values.insert(
+                "objects".into(),
+                serde_json::to_value(&published.objects).unwrap_or_default(),
+            );
+            state.record_telemetry("detections", values);
+        }
+    });
+}
+
+/// `/detections/latest` is the primary camera's, `/detections/<id>/latest`
+/// any camera's; `?locale=` picks the language of the display names.
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/detections/latest", get(latest))
+        .route("/detections/{id}/latest", get(latest_of_camera))
+        .with_state(state)
+}
+
+fn latest_set(state: &AppState, hub: &DetectionHub, query: &LocaleQuery) -> Option<DetectionSet> {
+    hub.latest().map(|p| {
+        state
+            .labels
+            .localize(p.to_set(&hub.limits()), query.locale())
+    })
+}
+
+async fn latest(
+    State(state): State<AppState>,
+    Query(query): Query<LocaleQuery>,
+) -> Json<Option<DetectionSet>> {
+    Json(latest_set(
+        &state,
+        &state.cameras.primary().detections,
+        &query,
+    ))
+}
+
+async fn latest_of_camera(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    Query(query): Query<LocaleQuery>,
+) -> Result<Json<Option<DetectionSet>>, StatusCode> {
+    let camera = state.cameras.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(latest_set(&state, &camera.detections, &query)))
+}
@@ -0,0 +1,196 @@
+//! Hot-plug monitoring of USB cameras, gamepads and serial adapters.
+//!
+//! A udev monitor thread keeps the registry in step with what is plugged
+//! in, serves it at `/devices` and broadcasts every change to dashboards.
+//! A camera plugged in while the configured one delivers nothing takes its
+//! place, and unplugging it hands capture back. Gamepads and serial devices
+//! are only listed: their subsystems pick ports at startup.
+
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::devices::{Device, DeviceChange, DeviceKind};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+const SUBSYSTEMS: [&str; 3] = ["video4linux", "input", "tty"];
+/// How often the monitor socket is drained.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct DeviceRegistry {
+    devices: Mutex<BTreeMap<String, Device>>,
+    tx: broadcast::Sender<DeviceChange>,
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self {
+            devices: Mutex::new(BTreeMap::new()),
+            tx,
+        }
+    }
+
+    pub fn list(&self) -> Vec<Device> {
+        self.devices.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceChange> {
+        self.tx.subscribe()
+    }
+
+    fn connect(&self, device: Device) {
+        let previous = self
+            .devices
+            .lock()
+            .unwrap()
+            .insert(device.path.clone(), device.clone());
+        if previous.is_none() {
+            let _ = self.tx.send(DeviceChange {
+                connected: true,
+                device,
+            });
+        }
+    }
+
+    fn disconnect(&self, path: &str) -> Option<Device> {
+        let device = self.devices.lock().unwrap().remove(path)?;
+        let _ = self.tx.send(DeviceChange {
+            connected: false,
+            device: device.clone(),
+        });
+        Some(device)
+    }
+}
+
+fn property(device: &udev::Device, name: &str) -> Option<String> {
+    device
+        .property_value(name)
+        .map(|v| v.to_string_lossy().into_owned())
+}
+
+/// Devices the robot can use; codec nodes, keyboards, on-board UARTs and
+/// the like are left out.
+fn classify(device: &udev::Device) -> Option<DeviceKind> {
+    let subsystem = device.subsystem()?.to_str()?;
+    let sysname = device.sysname().to_str()?;
+    match subsystem {
+        "video4linux" => property(device, "ID_V4L_CAPABILITIES")
+            .filter(|caps| caps.contains(":capture:"))
+            .map(|_| DeviceKind::Camera),
+        "input" => (sysname.starts_with("event")
+            && property(device, "ID_INPUT_JOYSTICK").as_deref() == Some("1"))
+        .then_some(DeviceKind::Gamepad),
+        "tty" => {
+            (property(device, "ID_BUS").as_deref() == Some("usb")).then_some(DeviceKind::Serial)
+        }
+        _ => None,
+    }
+}
+
+fn describe(device: &udev::Device) -> Option<Device> {
+    let kind = classify(device)?;
+    Some(Device {
+        kind,
+        path: device.devnode()?.to_string_lossy().into_owned(),
+        name: property(device, "ID_MODEL"),
+        vendor_id: property(device, "ID_VENDOR_ID"),
+        product_id: property(device, "ID_MODEL_ID"),
+        connected_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    })
+}
+
+fn on_connected(state: &AppState, device: Device) {
+    println!("[INFO] {:?} connected at {}", device.kind, device.path);
+    // A camera configured for this device gets it back, else the primary
+    // may take it over
+    if device.kind == DeviceKind::Camera {
+        let camera = state
+            .cameras
+            .iter()
+            .find(|c| c.owns_device(&device.path))
+            .unwrap_or(state.cameras.primary());
+        if camera.frames.attach_camera(&device.path) {
+            println!(
+                "[INFO] Switching '{}' capture to {}",
+                camera.id, device.path
+            );
+        }
+    }
+    state.devices.connect(device);
+}
+
+fn on_disconnected(state: &AppState, path: &str) {
+    let Some(device) = state.devices.disconnect(path) else {
+        return;
+    };
+    println!("[INFO] {:?} disconnected from {}", device.kind, path);
+    if device.kind == DeviceKind::Camera {
+        for camera in state.cameras.iter() {
+            camera.frames.detach_camera(path);
+        }
+    }
+}
+
+fn monitor(state: &AppState) -> std::io::Result<()> {
+    // Listen first, so nothing plugged in during the scan is missed
+    let mut builder = udev::MonitorBuilder::new()?;
+    for subsystem in SUBSYSTEMS {
+        builder = builder.match_subsystem(subsystem)?;
+    }
+    let socket = builder.listen()?;
+
+    for subsystem in SUBSYSTEMS {
+        let mut enumerator = udev::Enumerator::new()?;
+        enumerator.match_subsystem(subsystem)?;
+        for device in enumerator.scan_devices()?.filter_map(|d| describe(&d)) {
+            state.devices.connect(device);
+        }
+    }
+    println!(
+        "[OK] Device monitor running ({} devices present)",
+        state.devices.list().len()
+    );
+
+    loop {
+        for event in socket.iter() {
+            match event.event_type() {
+                udev::EventType::Add => {
+                    if let Some(device) = describe(&event) {
+                        on_connected(state, device);
+                    }
+                }
+                udev::EventType::Remove => {
+                    if let Some(path) = event.devnode() {
+                        on_disconnected(state, &path.to_string_lossy());
+                    }
+                }
+                _ => {}
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+pub fn start_device_monitor(state: AppState) {
+    thread::spawn(move || {
+        if let Err(e) = monitor(&state) {
+            println!("[WARN] Device monitor unavailable: {}", e);
+        }
+    });
+}
+
+pub fn routes(registry: Arc<DeviceRegistry>) -> Router {
+    Router::new()
+        .route("/devices", get(list_devices))
+        .with_state(registry)
+}
+
+async fn list_devices(State(registry): State<Arc<DeviceRegistry>>) -> Json<Vec<Device>> {
+    Json(registry.list())
+}
@@ -0,0 +1,190 @@
+//! Per-consumer frame delivery with declared rates.
+//!
+//! Consumers (inference, recorder, streams, dataset capture) subscribe with a
+//! [`Decimation`] instead of polling `FrameManager::get` in a loop. Each
+//! subscription holds only the newest frame it is due, shared via `Arc`, so a
+//! slow consumer skips frames rather than queueing them, and the capture
+//! thread never copies pixels for anyone: a consumer that needs a private
+//! `Mat` clones the one it received, once.
+
+use crate::rate::RateMeter;
+use raspibot_protocol::rates::RateStats;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// `MaxFps` below this delivers at this rate, a frame every 100 s;
+/// anything slower would overflow the frame interval.
+const MIN_FPS: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decimation {
+    EveryFrame,
+    /// Deliver one of every N captured frames.
+    EveryNth(u32),
+    /// Deliver at most this many frames per second, and at least
+    /// `MIN_FPS`.
+    MaxFps(f32),
+}
+
+impl std::fmt::Display for Decimation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Decimation::EveryFrame => write!(f, "every frame"),
+            Decimation::EveryNth(n) => write!(f, "1 in {}", n),
+            Decimation::MaxFps(fps) => write!(f, "at most {} fps", fps),
+        }
+    }
+}
+
+/// A subscriber as it stands, for `GET /pipeline`.
+pub struct ConsumerInfo {
+    pub name: String,
+    pub decimation: Decimation,
+    pub rate: RateStats,
+    /// A frame is waiting in its slot, not taken yet.
+    pub pending: bool,
+}
+
+struct Slot<T> {
+    latest: Mutex<Option<Arc<T>>>,
+    ready: Condvar,
+    notify: Notify,
+}
+
+struct Subscriber<T> {
+    name: String,
+    decimation: Decimation,
+    seen: u64,
+    last_sent: Option<Instant>,
+    /// Frames actually delivered, after decimation.
+    rate: RateMeter,
+    slot: Weak<Slot<T>>,
+}
+
+impl<T> Subscriber<T> {
+    fn due(&mut self, now: Instant) -> bool {
+        self.seen += 1;
+        match self.decimation {
+            Decimation::EveryFrame => true,
+            Decimation::EveryNth(n) => n <= 1 || self.seen % n as u64 == 1,
+            Decimation::MaxFps(fps) => match self.last_sent {
+                Some(last) if fps > 0.0 => {
+                    now.duration_since(last) >= Duration::from_secs_f32(1.0 / fps.max(MIN_FPS))
+                }
+                _ => true,
+            },
+        }
+    }
+}
+
+pub struct FrameDispatcher<T> {
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+}
+
+impl<T> FrameDispatcher<T> {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self, name: &str, decimation: Decimation) -> FrameSubscription<T> {
+        let slot = Arc::new(Slot {
+            latest: Mutex::new(None),
+            ready: Condvar::new(),
+            notify: Notify::new(),
+        });
+        self.subscribers.lock().unwrap().push(Subscriber {
+            name: name.to_string(),
+            decimation,
+            seen: 0,
+            last_sent: None,
+            rate: RateMeter::new(),
+            slot: Arc::downgrade(&slot),
+        });
+        println!(
+            "[INFO] Frame consumer '{}' subscribed ({:?})",
+            name, decimation
+        );
+        FrameSubscription { slot }
+    }
+
+    /// Hands `frame` to every subscriber that is due for one.
+    pub fn publish(&self, frame: Arc<T>) {
+        let now = Instant::now();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain_mut(|sub| {
+            let Some(slot) = sub.slot.upgrade() else {
+                println!("[INFO] Frame consumer '{}' unsubscribed", sub.name);
+                return false;
+            };
+            if sub.due(now) {
+                sub.last_sent = Some(now);
+                sub.rate.tick();
+                *slot.latest.lock().unwrap() = Some(Arc::clone(&frame));
+                slot.ready.notify_all();
+                slot.notify.notify_one();
+            }
+            true
+        });
+    }
+
+    /// Delivery rate per subscriber, by name.
+    pub fn rates(&self) -> Vec<(String, RateStats)> {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|sub| (sub.name.clone(), sub.rate.stats()))
+            .collect()
+    }
+
+    /// Every live subscriber with its rate and whether it is behind.
+    pub fn consumers(&self) -> Vec<ConsumerInfo> {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|sub| {
+                let slot = sub.slot.upgrade()?;
+                let pending = slot.latest.lock().unwrap().is_some();
+                Some(ConsumerInfo {
+                    name: sub.name.clone(),
+                    decimation: sub.decimation,
+                    rate: sub.rate.stats(),
+                    pending,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Receiving end of a subscription; dropping it unsubscribes.
+pub struct FrameSubscription<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> FrameSubscription<T> {
+    /// Blocks (for thread-based consumers) until a frame is due or `timeout` passes.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Arc<T>> {
+        let latest = self.slot.latest.lock().unwrap();
+        let (mut latest, _) = self
+            .slot
+            .ready
+            .wait_timeout_while(latest, timeout, |f| f.is_none())
+            .unwrap();
+        latest.take()
+    }
+
+    /// Waits asynchronously for the next due frame.
+    pub async fn recv(&self) -> Arc<T> {
+        loop {
+            let notified = self.slot.notify.notified();
+            if let Some(frame) = self.slot.latest.lock().unwrap().take() {
+                return frame;
+            }
+            notified.await;
+        }
+    }
+}
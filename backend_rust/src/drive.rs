@@ -0,0 +1,60 @@
+//! Direct drive control for the operator: `POST /drive {left, right}` or the
+//! `drive` Socket.IO event, both submitted to the arbiter as teleop.
+//!
+//! The watchdog stops the motors once no command has arrived for
+//! `drive.timeout_ms`, or the profile's `command_timeout_ms` without it,
+//! so a dropped connection or a crashed controller never leaves the robot
+//! driving, and engages the emergency stop, which an operator has to
+//! clear. Drivers are expected to send commands continuously, not just on
+//! change.
+
+use crate::arbiter::CommandSource;
+use crate::estop;
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use raspibot_protocol::drive::DriveCommand;
+use std::time::Duration;
+
+/// `timeout_ms` overrides the profile's `command_timeout_ms`.
+pub fn start_drive_watchdog(state: AppState, timeout_ms: Option<u64>) {
+    let ms = timeout_ms.unwrap_or(state.settings.command_timeout_ms);
+    let timeout = Duration::from_millis(ms.max(1));
+    println!(
+        "[INFO] Drive watchdog: stop after {:?} without commands",
+        timeout
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(timeout / 4);
+        loop {
+            interval.tick().await;
+            match state.arbiter.stop_if_stale(timeout) {
+                Ok(true) => {
+                    println!("[WARN] No drive command for {:?}, motors stopped", timeout);
+                    estop::engage(
+                        &state,
+                        "watchdog",
+                        Some(format!("no drive command for {:?}", timeout)),
+                    );
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("[ERR] Drive watchdog could not stop motors: {}", e),
+            }
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new().route("/drive", post(drive)).with_state(state)
+}
+
+/// Returns what reached the motors after the safety constraints.
+async fn drive(
+    State(state): State<AppState>,
+    Json(command): Json<DriveCommand>,
+) -> Result<Json<DriveCommand>, (StatusCode, String)> {
+    state
+        .arbiter
+        .submit(CommandSource::Teleop, command)
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e.to_string()))
+}
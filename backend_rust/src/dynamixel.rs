@@ -0,0 +1,223 @@
+//! Dynamixel protocol 2.0 over a half-duplex UART (X-series control table).
+
+use anyhow::{bail, Result};
+use serialport::SerialPort;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+const HEADER: [u8; 4] = [0xFF, 0xFF, 0xFD, 0x00];
+
+const INST_PING: u8 = 0x01;
+const INST_READ: u8 = 0x02;
+const INST_WRITE: u8 = 0x03;
+const INST_STATUS: u8 = 0x55;
+
+/// X-series control table addresses we use.
+pub mod addr {
+    pub const OPERATING_MODE: u16 = 11;
+    pub const TORQUE_ENABLE: u16 = 64;
+    pub const HARDWARE_ERROR: u16 = 70;
+    pub const GOAL_VELOCITY: u16 = 104;
+    pub const GOAL_POSITION: u16 = 116;
+    pub const PRESENT_CURRENT: u16 = 126;
+    pub const PRESENT_TEMPERATURE: u16 = 146;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatingMode {
+    Velocity = 1,
+    Position = 3,
+}
+
+pub const TICKS_PER_REV: f32 = 4096.0;
+/// Goal/present velocity unit, in rpm.
+pub const VELOCITY_UNIT_RPM: f32 = 0.229;
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct ServoStatus {
+    pub id: u8,
+    pub position: i32,
+    pub velocity: i32,
+    pub current: i16,
+    pub temperature: u8,
+    pub hardware_error: u8,
+}
+
+/// CRC-16 (poly 0x8005, init 0) as specified by ROBOTIS.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Inserts 0xFD after every FF FF FD sequence in the parameter area.
+fn stuff(params: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(params.len() + 2);
+    for &byte in params {
+        out.push(byte);
+        let n = out.len();
+        if n >= 3 && out[n - 3..] == [0xFF, 0xFF, 0xFD] {
+            out.push(0xFD);
+        }
+    }
+    out
+}
+
+fn unstuff(params: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(params.len());
+    let mut i = 0;
+    while i < params.len() {
+        out.push(params[i]);
+        let n = out.len();
+        if n >= 3 && out[n - 3..] == [0xFF, 0xFF, 0xFD] && params.get(i + 1) == Some(&0xFD) {
+            i += 1;
+        }
+        i += 1;
+    }
+    out
+}
+
+pub struct DynamixelBus {
+    port: Box<dyn SerialPort>,
+}
+
+impl DynamixelBus {
+    pub fn open(path: &str, baud: u32) -> Result<Self> {
+        let port = serialport::new(path, baud)
+            .timeout(Duration::from_millis(20))
+            .open()?;
+        println!("[OK] Opened Dynamixel bus on {} @ {} baud", path, baud);
+        Ok(Self { port })
+    }
+
+    fn transact(&mut self, id: u8, instruction: u8, params: &[u8]) -> Result<Vec<u8>> {
+        let body = stuff(params);
+        let len = (body.len() + 3) as u16;
+
+        let mut packet = HEADER.to_vec();
+        packet.push(id);
+        packet.extend_from_slice(&len.to_le_bytes());
+        packet.push(instruction);
+        packet.extend_from_slice(&body);
+        let crc = crc16(&packet);
+        packet.extend_from_slice(&crc.to_le_bytes());
+
+        // Half-duplex: anything still in the buffer is stale echo or noise
+        self.port.clear(serialport::ClearBuffer::Input)?;
+        self.port.write_all(&packet)?;
+        self.read_status(id)
+    }
+
+    fn read_status(&mut self, id: u8) -> Result<Vec<u8>> {
+        loop {
+            let body = self.read_packet(id)?;
+            // USB half-duplex adapters may echo our own instruction back; skip it
+            if body[0] != INST_STATUS {
+                continue;
+            }
+            let error = body[1];
+            if error & 0x7F != 0 {
+                bail!("Dynamixel {}: status error 0x{:02X}", id, error);
+            }
+            return Ok(unstuff(&body[2..]));
+        }
+    }
+
+    /// Reads one packet addressed from `id`, returning everything between the
+    /// length field and the CRC.
+    fn read_packet(&mut self, id: u8) -> Result<Vec<u8>> {
+        let mut head = [0u8; 7];
+        self.port.read_exact(&mut head)?;
+        while !(head[..4] == HEADER && head[4] == id) {
+            let mut byte = [0u8; 1];
+            self.port.read_exact(&mut byte)?;
+            head.copy_within(1.., 0);
+            head[6] = byte[0];
+        }
+
+        let len = u16::from_le_bytes([head[5], head[6]]) as usize;
+        if len < 3 {
+            bail!("Dynamixel {}: packet length {} too short", id, len);
+        }
+        let mut rest = vec![0u8; len];
+        self.port.read_exact(&mut rest)?;
+
+        let (body, crc_bytes) = rest.split_at(len - 2);
+        let mut check = head.to_vec();
+        check.extend_from_slice(body);
+        if crc16(&check) != u16::from_le_bytes([crc_bytes[0], crc_bytes[1]]) {
+            bail!("Dynamixel {}: packet CRC mismatch", id);
+        }
+        if body[0] == INST_STATUS && body.len() < 2 {
+            bail!("Dynamixel {}: status packet without error byte", id);
+        }
+        Ok(body.to_vec())
+    }
+
+    pub fn ping(&mut self, id: u8) -> Result<()> {
+        self.transact(id, INST_PING, &[]).map(|_| ())
+    }
+
+    pub fn read(&mut self, id: u8, address: u16, len: u16) -> Result<Vec<u8>> {
+        let mut params = address.to_le_bytes().to_vec();
+        params.extend_from_slice(&len.to_le_bytes());
+        let data = self.transact(id, INST_READ, &params)?;
+        if data.len() != len as usize {
+            bail!(
+                "Dynamixel {}: read {} bytes, expected {}",
+                id,
+                data.len(),
+                len
+            );
+        }
+        Ok(data)
+    }
+
+    pub fn write(&mut self, id: u8, address: u16, data: &[u8]) -> Result<()> {
+        let mut params = address.to_le_bytes().to_vec();
+        params.extend_from_slice(data);
+        self.transact(id, INST_WRITE, &params).map(|_| ())
+    }
+
+    pub fn set_torque(&mut self, id: u8, enabled: bool) -> Result<()> {
+        self.write(id, addr::TORQUE_ENABLE, &[enabled as u8])
+    }
+
+    /// Operating mode can only be changed with torque disabled.
+    pub fn set_operating_mode(&mut self, id: u8, mode: OperatingMode) -> Result<()> {
+        self.set_torque(id, false)?;
+        self.write(id, addr::OPERATING_MODE, &[mode as u8])
+    }
+
+    pub fn set_goal_position(&mut self, id: u8, ticks: i32) -> Result<()> {
+        self.write(id, addr::GOAL_POSITION, &ticks.to_le_bytes())
+    }
+
+    pub fn set_goal_velocity(&mut self, id: u8, units: i32) -> Result<()> {
+        self.write(id, addr::GOAL_VELOCITY, &units.to_le_bytes())
+    }
+
+    pub fn status(&mut self, id: u8) -> Result<ServoStatus> {
+        // Present current..present position is one contiguous block (126..=135)
+        let block = self.read(id, addr::PRESENT_CURRENT, 10)?;
+        let temperature = self.read(id, addr::PRESENT_TEMPERATURE, 1)?[0];
+        let hardware_error = self.read(id, addr::HARDWARE_ERROR, 1)?[0];
+        Ok(ServoStatus {
+            id,
+            current: i16::from_le_bytes([block[0], block[1]]),
+            velocity: i32::from_le_bytes([block[2], block[3], block[4], block[5]]),
+            position: i32::from_le_bytes([block[6], block[7], block[8], block[9]]),
+            temperature,
+            hardware_error,
+        })
+    }
+}
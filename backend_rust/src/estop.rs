@@ -0,0 +1,141 @@
+//! Emergency stop, a competition safety requirement.
+//!
+//! Engaged with `POST /estop`, the `estop` Socket.IO event, or by the drive
+//! watchdog when whoever was driving goes quiet. It stops the motors at
+//! once, sends the robot to idle, ends background missions and runs every
+//! registered halt (arm torque off, ...), then latches: the arbiter refuses
+//! every drive command until an operator clears it with `DELETE /estop` or
+//! `estop_clear`. Clearing only lifts the latch; nothing starts moving again
+//! by itself.
+
+use crate::state::AppState;
+use axum::{body::Bytes, extract::State, routing::get, Json, Router};
+use raspibot_protocol::drive::{EStopRequest, EStopStatus};
+use raspibot_protocol::mission::MissionMode;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Arbiter interlock held while latched.
+const INTERLOCK: &str = "estop";
+
+type Halt = Box<dyn Fn() -> anyhow::Result<()> + Send + Sync>;
+
+pub struct EStop {
+    status: Mutex<EStopStatus>,
+    /// Outputs outside the drive arbiter to cut, by name.
+    halts: Mutex<Vec<(&'static str, Halt)>>,
+    changes: broadcast::Sender<EStopStatus>,
+}
+
+impl EStop {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(8);
+        Self {
+            status: Mutex::new(EStopStatus::default()),
+            halts: Mutex::new(Vec::new()),
+            changes,
+        }
+    }
+
+    /// Registers an output the stop must cut besides the drive motors.
+    pub fn add_halt(&self, name: &'static str, halt: Halt) {
+        println!("[INFO] Emergency stop halts '{}'", name);
+        self.halts.lock().unwrap().push((name, halt));
+    }
+
+    pub fn status(&self) -> EStopStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<EStopStatus> {
+        self.changes.subscribe()
+    }
+}
+
+/// Stops everything and latches. Engaging again while latched stops
+/// everything again but keeps the first source and reason.
+pub fn engage(state: &AppState, source: &str, reason: Option<String>) -> EStopStatus {
+    let reason = reason.unwrap_or_else(|| "emergency stop".to_string());
+    if let Err(e) = state
+        .arbiter
+        .set_interlock(INTERLOCK, Some(format!("emergency stop ({})", reason)))
+    {
+        eprintln!("[ERR] Emergency stop could not stop the motors: {}", e);
+    }
+    for (name, halt) in state.estop.halts.lock().unwrap().iter() {
+        if let Err(e) = halt() {
+            eprintln!("[ERR] Emergency stop could not halt '{}': {}", name, e);
+        }
+    }
+    state.mission.set_mode(MissionMode::Idle, "emergency stop");
+    let background: Vec<String> = state
+        .mission
+        .state()
+        .background
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+    for name in background {
+        state.mission.stop_background(&name, "emergency stop");
+    }
+
+    let mut status = state.estop.status.lock().unwrap();
+    if !status.engaged {
+        eprintln!("[ERR] EMERGENCY STOP by {}: {}", source, reason);
+        state
+            .sessions
+            .record_fault(format!("emergency stop by {}: {}", source, reason));
+        *status = EStopStatus {
+            engaged: true,
+            source: Some(source.to_string()),
+            reason: Some(reason),
+            since_unix_ms: Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0),
+            ),
+        };
+        let _ = state.estop.changes.send(status.clone());
+    }
+    status.clone()
+}
+
+/// Lifts the latch so drive commands are accepted again.
+pub fn clear(state: &AppState) -> EStopStatus {
+    let mut status = state.estop.status.lock().unwrap();
+    if status.engaged {
+        println!("[OK] Emergency stop cleared");
+        if let Err(e) = state.arbiter.set_interlock(INTERLOCK, None) {
+            eprintln!("[ERR] Could not lift the emergency stop: {}", e);
+        }
+        *status = EStopStatus::default();
+        let _ = state.estop.changes.send(status.clone());
+    }
+    status.clone()
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/estop",
+            get(get_status).post(post_estop).delete(delete_estop),
+        )
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<EStopStatus> {
+    Json(state.estop.status())
+}
+
+/// Takes any body, or none: a stop must never be turned away for a
+/// malformed request.
+async fn post_estop(State(state): State<AppState>, body: Bytes) -> Json<EStopStatus> {
+    let request: EStopRequest = serde_json::from_slice(&body).unwrap_or_default();
+    Json(engage(&state, "api", request.reason))
+}
+
+async fn delete_estop(State(state): State<AppState>) -> Json<EStopStatus> {
+    Json(clear(&state))
+}
@@ -0,0 +1,141 @@
+//! Scoring evidence: unique tracked objects per class, with the best
+//! confidence seen and a crop from that moment saved under `data/evidence/`.
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use raspibot_protocol::evidence::{ClassEvidence, EvidenceQuery, TrackedObject};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const EVIDENCE_DIR: &str = "data/evidence";
+/// Tracks not seen for this long are forgotten (their crops stay on disk).
+const RETENTION_MS: u64 = 60 * 60 * 1000;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct EvidenceLog {
+    root: PathBuf,
+    objects: Mutex<HashMap<u64, TrackedObject>>,
+}
+
+impl EvidenceLog {
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from(EVIDENCE_DIR),
+            objects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records a sighting of track `track_id`. `crop_jpeg` is only called
+    /// when this sighting beats the track's best confidence, so callers can
+    /// defer cropping/encoding until it is actually needed.
+    pub fn record(
+        &self,
+        track_id: u64,
+        class: &str,
+        confidence: f32,
+        crop_jpeg: impl FnOnce() -> Option<Vec<u8>>,
+    ) {
+        let now = now_ms();
+        let mut objects = self.objects.lock().unwrap();
+        objects.retain(|_, o| now.saturating_sub(o.last_seen_unix_ms) < RETENTION_MS);
+
+        let object = objects.entry(track_id).or_insert_with(|| TrackedObject {
+            track_id,
+            class: class.to_string(),
+            best_confidence: 0.0,
+            first_seen_unix_ms: now,
+            last_seen_unix_ms: now,
+            crop_url: None,
+        });
+        object.last_seen_unix_ms = now;
+        if confidence <= object.best_confidence {
+            return;
+        }
+        object.best_confidence = confidence;
+        object.class = class.to_string();
+
+        let Some(jpeg) = crop_jpeg() else {
+            return;
+        };
+        // Track ids restart with the backend, so the first sighting keeps names unique
+        let file = format!("track-{}-{}.jpg", track_id, object.first_seen_unix_ms);
+        let written = std::fs::create_dir_all(&self.root)
+            .and_then(|_| std::fs::write(self.root.join(&file), jpeg));
+        match written {
+            Ok(()) => object.crop_url = Some(format!("/api/v1/evidence/crops/{}", file)),
+            Err(e) => eprintln!("[ERR] Could not save evidence crop {}: {}", file, e),
+        }
+    }
+
+    /// Objects grouped per class, optionally only those seen in the last `window_s`.
+    pub fn summary(&self, window_s: Option<f64>) -> Vec<ClassEvidence> {
+        let since = window_s.map(|w| now_ms().saturating_sub((w.max(0.0) * 1000.0) as u64));
+        let mut classes: BTreeMap<String, Vec<TrackedObject>> = BTreeMap::new();
+        for object in self.objects.lock().unwrap().values() {
+            if since.is_some_and(|since| object.last_seen_unix_ms < since) {
+                continue;
+            }
+            classes
+                .entry(object.class.clone())
+                .or_default()
+                .push(object.clone());
+        }
+        classes
+            .into_iter()
+            .map(|(class, mut objects)| {
+                objects.sort_by(|a, b| b.best_confidence.total_cmp(&a.best_confidence));
+                ClassEvidence {
+                    best_confidence: objects.first().map(|o| o.best_confidence).unwrap_or(0.0),
+                    unique_objects: objects.len(),
+                    class,
+                    objects,
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn routes(evidence: Arc<EvidenceLog>) -> Router {
+    Router::new()
+        .route("/evidence", get(get_summary))
+        .route("/evidence/crops/{file}", get(get_crop))
+        .with_state(evidence)
+}
+
+async fn get_summary(
+    State(evidence): State<Arc<EvidenceLog>>,
+    Query(query): Query<EvidenceQuery>,
+) -> Json<Vec<ClassEvidence>> {
+    Json(evidence.summary(query.window_s))
+}
+
+async fn get_crop(
+    State(evidence): State<Arc<EvidenceLog>>,
+    UrlPath(file): UrlPath<String>,
+) -> Response {
+    let valid = file.starts_with("track-")
+        && file.ends_with(".jpg")
+        && file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    match std::fs::read(evidence.root.join(&file)) {
+        Ok(jpeg) => ([(header::CONTENT_TYPE, "image/jpeg")], jpeg).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
@@ -0,0 +1,308 @@
+//! CSV/Parquet export of a run's blackbox, one table per telemetry stream
+//! (`imu.csv`, `drive.csv`, `detections.parquet`, ...), for pandas/Excel, and
+//! a zip bundle of the whole run for sharing with teammates.
+
+use crate::blackbox::{self, Sample};
+use crate::session::{SessionManager, SESSION_META_FILE};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use parquet::arrow::ArrowWriter;
+use raspibot_protocol::session::{BundleFile, BundleManifest};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+
+/// Video written by the recorder; `recording.json` next to it carries the
+/// `started_unix_ms` of its first frame for alignment.
+const RECORDING_EXTENSIONS: [&str; 3] = ["mp4", "mkv", "avi"];
+const RECORDING_META_FILE: &str = "recording.json";
+
+/// One stream's samples as columns: `t_s` first, then every key seen.
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Option<Value>>>,
+}
+
+impl Table {
+    fn numeric(&self, column: usize) -> bool {
+        self.rows
+            .iter()
+            .filter_map(|row| row[column].as_ref())
+            .all(|v| v.is_number() || v.is_boolean())
+    }
+}
+
+fn tables(samples: &[Sample]) -> BTreeMap<String, Table> {
+    let mut keys: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for sample in samples {
+        keys.entry(&sample.stream)
+            .or_default()
+            .extend(sample.values.keys().map(String::as_str));
+    }
+
+    let mut tables: BTreeMap<String, Table> = keys
+        .into_iter()
+        .map(|(stream, keys)| {
+            let columns = std::iter::once("t_s".to_string())
+                .chain(keys.into_iter().map(String::from))
+                .collect();
+            (
+                stream.to_string(),
+                Table {
+                    columns,
+                    rows: Vec::new(),
+                },
+            )
+        })
+        .collect();
+    for sample in samples {
+        let table = tables.get_mut(&sample.stream).unwrap();
+        let row = table
+            .columns
+            .iter()
+            .map(|c| match c.as_str() {
+                "t_s" => Some(sample.t_s.into()),
+                key => sample.values.get(key).cloned(),
+            })
+            .collect();
+        table.rows.push(row);
+    }
+    tables
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Bool(b) => Some(*b as u8 as f64),
+        v => v.as_f64(),
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn to_csv(table: &Table) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&table.columns)?;
+    for row in &table.rows {
+        writer.write_record(
+            row.iter()
+                .map(|v| v.as_ref().map(as_text).unwrap_or_default()),
+        )?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Numeric/bool columns become nullable Float64, everything else Utf8.
+fn to_parquet(table: &Table) -> anyhow::Result<Vec<u8>> {
+    let mut fields = Vec::with_capacity(table.columns.len());
+    let mut arrays: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+    for (i, name) in table.columns.iter().enumerate() {
+        let cells = table.rows.iter().map(|row| row[i].as_ref());
+        if table.numeric(i) {
+            fields.push(Field::new(name, DataType::Float64, true));
+            arrays.push(Arc::new(
+                cells.map(|v| v.and_then(as_f64)).collect::<Float64Array>(),
+            ));
+        } else {
+            fields.push(Field::new(name, DataType::Utf8, true));
+            arrays.push(Arc::new(
+                cells.map(|v| v.map(as_text)).collect::<StringArray>(),
+            ));
+        }
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+    let mut out = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut out, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(out)
+}
+
+fn read_started_ms(path: &Path) -> Option<u64> {
+    let meta: Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    meta.get("started_unix_ms")?.as_u64()
+}
+
+/// Zips the run folder: the recording, telemetry (raw plus one CSV per
+/// stream), config snapshot, report, and a manifest with each file's
+/// alignment offset against the run start.
+fn build_bundle(id: &str, dir: &Path) -> anyhow::Result<Vec<u8>> {
+    let run_started_ms = read_started_ms(&dir.join(SESSION_META_FILE));
+    let recording_offset_s = read_started_ms(&dir.join(RECORDING_META_FILE))
+        .zip(run_started_ms)
+        .map(|(rec, run)| (rec as f64 - run as f64) / 1000.0);
+
+    let mut entries: Vec<(String, Vec<u8>, Option<f64>)> = Vec::new();
+    for entry in std::fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()).map(String::from) else {
+            continue;
+        };
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let offset_s = if name == blackbox::FILE_NAME || name == blackbox::LEGACY_FILE_NAME {
+            Some(0.0)
+        } else if RECORDING_EXTENSIONS.contains(&ext) {
+            recording_offset_s
+        } else if ext == "json" || ext == "html" {
+            None
+        } else {
+            continue;
+        };
+        entries.push((name, std::fs::read(&path)?, offset_s));
+    }
+    if let Ok(samples) = blackbox::read(dir) {
+        for (stream, table) in tables(&samples) {
+            entries.push((
+                format!("telemetry/{}.csv", stream),
+                to_csv(&table)?,
+                Some(0.0),
+            ));
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let manifest = BundleManifest {
+        session_id: id.to_string(),
+        run_started_unix_ms: run_started_ms,
+        created_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        files: entries
+            .iter()
+            .map(|(name, data, offset_s)| BundleFile {
+                name: name.clone(),
+                bytes: data.len() as u64,
+                offset_s: *offset_s,
+            })
+            .collect(),
+    };
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    for (name, data, _) in &entries {
+        // Video is already compressed; deflating it again only costs CPU
+        let options = if name.ends_with(".mp4") || name.ends_with(".mkv") {
+            options.compression_method(zip::CompressionMethod::Stored)
+        } else {
+            options
+        };
+        zip.start_file(format!("{}/{}", id, name), options)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn routes(sessions: Arc<SessionManager>) -> Router {
+    Router::new()
+        .route("/sessions/{id}/export-bundle", post(export_bundle))
+        .route("/sessions/{id}/export", get(list_exports))
+        .route("/sessions/{id}/export/{file}", get(get_export))
+        .with_state(sessions)
+}
+
+fn load(sessions: &SessionManager, id: &str) -> Result<Vec<Sample>, StatusCode> {
+    let dir = sessions.session_dir(id).ok_or(StatusCode::BAD_REQUEST)?;
+    blackbox::read(&dir).map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// File names available for download, two per stream.
+async fn list_exports(
+    State(sessions): State<Arc<SessionManager>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    let samples = load(&sessions, &id)?;
+    Ok(Json(
+        tables(&samples)
+            .keys()
+            .flat_map(|s| [format!("{}.csv", s), format!("{}.parquet", s)])
+            .collect(),
+    ))
+}
+
+async fn get_export(
+    State(sessions): State<Arc<SessionManager>>,
+    UrlPath((id, file)): UrlPath<(String, String)>,
+) -> Response {
+    let samples = match load(&sessions, &id) {
+        Ok(samples) => samples,
+        Err(status) => return status.into_response(),
+    };
+    let Some((stream, format)) = file.rsplit_once('.') else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let tables = tables(&samples);
+    let Some(table) = tables.get(stream) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let (content_type, body) = match format {
+        "csv" => ("text/csv", to_csv(table)),
+        "parquet" => ("application/vnd.apache.parquet", to_parquet(table)),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    match body {
+        Ok(body) => (
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}-{}\"", id, file),
+                ),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn export_bundle(
+    State(sessions): State<Arc<SessionManager>>,
+    UrlPath(id): UrlPath<String>,
+) -> Response {
+    let Some(dir) = sessions.session_dir(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !dir.is_dir() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if sessions.active_id().as_deref() == Some(id.as_str()) {
+        return (StatusCode::CONFLICT, "run still in progress").into_response();
+    }
+
+    let bundle_id = id.clone();
+    match tokio::task::spawn_blocking(move || build_bundle(&bundle_id, &dir)).await {
+        Ok(Ok(zip)) => (
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.zip\"", id),
+                ),
+            ],
+            zip,
+        )
+            .into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,93 @@
+//! Fault injection hooks, so the supervisor, watchdogs and safety layers can
+//! be tested systematically rather than by unplugging cables.
+//!
+//! Only builds with the `faults` feature act on them and serve
+//! `/api/v1/faults`; in every other build each hook is a no-op.
+
+use raspibot_protocol::faults::FaultConfig;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// The hooks sit in threads started long before `AppState` exists, so the
+/// injector is process-wide.
+static INJECTOR: LazyLock<FaultInjector> = LazyLock::new(FaultInjector::new);
+
+struct FaultInjector {
+    config: Mutex<FaultConfig>,
+    frames: AtomicU64,
+}
+
+impl FaultInjector {
+    fn new() -> Self {
+        Self {
+            config: Mutex::new(FaultConfig::default()),
+            frames: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Whether the camera should discard the frame it just read.
+pub fn drop_frame() -> bool {
+    if !cfg!(feature = "faults") {
+        return false;
+    }
+    let every = INJECTOR.config.lock().unwrap().drop_frame_every as u64;
+    every > 0 && INJECTOR.frames.fetch_add(1, Ordering::Relaxed) % every == 0
+}
+
+/// How long the inference worker stalls after each pass.
+pub fn inference_delay() -> Duration {
+    if !cfg!(feature = "faults") {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(INJECTOR.config.lock().unwrap().inference_delay_ms)
+}
+
+/// Whether the next compass reading should be replaced by an outlier.
+pub fn compass_outlier() -> bool {
+    if !cfg!(feature = "faults") {
+        return false;
+    }
+    let mut config = INJECTOR.config.lock().unwrap();
+    if config.compass_outliers == 0 {
+        return false;
+    }
+    config.compass_outliers -= 1;
+    true
+}
+
+/// Checked by long-running tasks once per iteration; `true` means exit.
+pub fn killed(task: &str) -> bool {
+    if !cfg!(feature = "faults") {
+        return false;
+    }
+    let killed = INJECTOR
+        .config
+        .lock()
+        .unwrap()
+        .kill
+        .iter()
+        .any(|k| k == task);
+    if killed {
+        println!("[WARN] Fault injection: killing task '{}'", task);
+    }
+    killed
+}
+
+#[cfg(feature = "faults")]
+pub fn routes() -> axum::Router {
+    axum::Router::new().route("/faults", axum::routing::get(get_faults).put(set_faults))
+}
+
+#[cfg(feature = "faults")]
+async fn get_faults() -> axum::Json<FaultConfig> {
+    axum::Json(INJECTOR.config.lock().unwrap().clone())
+}
+
+#[cfg(feature = "faults")]
+async fn set_faults(axum::Json(config): axum::Json<FaultConfig>) -> axum::Json<FaultConfig> {
+    println!("[WARN] Fault injection set: {:?}", config);
+    *INJECTOR.config.lock().unwrap() = config.clone();
+    axum::Json(config)
+}
@@ -0,0 +1,246 @@
+//! NMEA GPS receiver over UART for the outdoor navigation segment.
+//!
+//! Parses GGA (position/quality) and RMC (speed/course) sentences from any
+//! talker (GP, GN, GL, ...) and raises geofence enter/exit events. Every
+//! new position is also broadcast, which `pose` blends into the odometry.
+
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::gps::{Geofence, GeofenceEvent, GpsFix};
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+pub const DEFAULT_PORT: &str = "/dev/ttyACM0";
+pub const DEFAULT_BAUD: u32 = 9600;
+
+const KNOTS_TO_MPS: f64 = 0.514_444;
+
+/// Validates the `*HH` checksum and returns the comma-separated fields.
+fn split_sentence(line: &str) -> Option<Vec<&str>> {
+    let line = line.trim().strip_prefix('$')?;
+    let (body, checksum) = line.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    let actual = body.bytes().fold(0u8, |acc, b| acc ^ b);
+    if actual != expected {
+        return None;
+    }
+    Some(body.split(',').collect())
+}
+
+/// Converts NMEA `ddmm.mmmm` / `dddmm.mmmm` plus hemisphere to signed degrees.
+fn parse_coord(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc();
+    let decimal = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Applies one NMEA sentence to `fix`; returns true if the position changed.
+pub fn apply_sentence(fix: &mut GpsFix, line: &str) -> bool {
+    let Some(fields) = split_sentence(line) else {
+        return false;
+    };
+    let kind = fields[0].get(2..).unwrap_or("");
+
+    match kind {
+        "GGA" if fields.len() >= 10 => {
+            let quality: u32 = fields[6].parse().unwrap_or(0);
+            fix.satellites = fields[7].parse().unwrap_or(0);
+            fix.hdop = fields[8].parse().unwrap_or(99.9);
+            fix.altitude_m = fields[9].parse().unwrap_or(fix.altitude_m);
+            fix.valid = quality > 0;
+            if let (Some(lat), Some(lon)) = (
+                parse_coord(fields[2], fields[3]),
+                parse_coord(fields[4], fields[5]),
+            ) {
+                fix.lat = lat;
+                fix.lon = lon;
+                return fix.valid;
+            }
+        }
+        "RMC" if fields.len() >= 9 => {
+            if fields[2] != "A" {
+                fix.valid = false;
+                return false;
+            }
+            fix.speed_mps = fields[7].parse::<f64>().unwrap_or(0.0) * KNOTS_TO_MPS;
+            // Course is noise when standing still
+            fix.heading_deg = if fix.speed_mps > 0.5 {
+                fields[8].parse().ok()
+            } else {
+                None
+            };
+            if let (Some(lat), Some(lon)) = (
+                parse_coord(fields[3], fields[4]),
+                parse_coord(fields[5], fields[6]),
+            ) {
+                fix.lat = lat;
+                fix.lon = lon;
+                fix.valid = true;
+                return true;
+            }
+        }
+        _ => {}
+    }
+    false
+}
+
+struct FenceState {
+    fence: Geofence,
+    inside: bool,
+}
+
+pub struct GpsManager {
+    fix: Mutex<GpsFix>,
+    fences: Mutex<Vec<FenceState>>,
+    events_tx: broadcast::Sender<GeofenceEvent>,
+    fixes_tx: broadcast::Sender<GpsFix>,
+}
+
+impl GpsManager {
+    fn new() -> Self {
+        let (events_tx, _) = broadcast::channel(32);
+        let (fixes_tx, _) = broadcast::channel(8);
+        Self {
+            fix: Mutex::new(GpsFix::default()),
+            fences: Mutex::new(Vec::new()),
+            events_tx,
+            fixes_tx,
+        }
+    }
+
+    pub fn fix(&self) -> GpsFix {
+        self.fix.lock().unwrap().clone()
+    }
+
+    pub fn events(&self) -> broadcast::Receiver<GeofenceEvent> {
+        self.events_tx.subscribe()
+    }
+
+    /// The fix, each time the position changes.
+    pub fn fixes(&self) -> broadcast::Receiver<GpsFix> {
+        self.fixes_tx.subscribe()
+    }
+
+    pub fn geofences(&self) -> Vec<Geofence> {
+        self.fences
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|f| f.fence.clone())
+            .collect()
+    }
+
+    pub fn set_geofences(&self, fences: Vec<Geofence>) {
+        let fix = self.fix();
+        let states = fences
+            .into_iter()
+            .map(|fence| FenceState {
+                inside: fix.valid && fence.contains(fix.lat, fix.lon),
+                fence,
+            })
+            .collect();
+        *self.fences.lock().unwrap() = states;
+    }
+
+    fn check_fences(&self, lat: f64, lon: f64) {
+        for state in self.fences.lock().unwrap().iter_mut() {
+            let inside = state.fence.contains(lat, lon);
+            if inside == state.inside {
+                continue;
+            }
+            state.inside = inside;
+            let fence = state.fence.name().to_string();
+            println!(
+                "[INFO] Geofence '{}': {}",
+                fence,
+                if inside { "entered" } else { "exited" }
+            );
+            let event = if inside {
+                GeofenceEvent::Enter { fence, lat, lon }
+            } else {
+                GeofenceEvent::Exit { fence, lat, lon }
+            };
+            let _ = self.events_tx.send(event);
+        }
+    }
+}
+
+pub fn start_gps_thread(path: &str, baud: u32) -> Arc<GpsManager> {
+    let manager = Arc::new(GpsManager::new());
+    let gm_clone = Arc::clone(&manager);
+    let path = path.to_string();
+
+    thread::spawn(move || {
+        println!("[INFO] Starting GPS reader on {}...", path);
+        loop {
+            let port = match serialport::new(&path, baud)
+                .timeout(Duration::from_secs(2))
+                .open()
+            {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("[ERR] Could not open GPS port {}: {}", path, e);
+                    thread::sleep(Duration::from_secs(5));
+                    continue;
+                }
+            };
+
+            let mut reader = BufReader::new(port);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        let mut fix = gm_clone.fix.lock().unwrap();
+                        if apply_sentence(&mut fix, &line) {
+                            let current = fix.clone();
+                            drop(fix);
+                            gm_clone.check_fences(current.lat, current.lon);
+                            let _ = gm_clone.fixes_tx.send(current);
+                        }
+                    }
+                    // Timeouts just mean the receiver is quiet (no fix yet)
+                    Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+                    Err(e) => {
+                        eprintln!("[ERR] GPS read failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+
+    manager
+}
+
+pub fn routes(gps: Arc<GpsManager>) -> Router {
+    Router::new()
+        .route("/gps", get(get_fix))
+        .route("/gps/geofences", get(get_geofences).put(put_geofences))
+        .with_state(gps)
+}
+
+async fn get_fix(State(gps): State<Arc<GpsManager>>) -> Json<GpsFix> {
+    Json(gps.fix())
+}
+
+async fn get_geofences(State(gps): State<Arc<GpsManager>>) -> Json<Vec<Geofence>> {
+    Json(gps.geofences())
+}
+
+async fn put_geofences(
+    State(gps): State<Arc<GpsManager>>,
+    Json(fences): Json<Vec<Geofence>>,
+) -> Json<Vec<Geofence>> {
+    gps.set_geofences(fences);
+    Json(gps.geofences())
+}
@@ -0,0 +1,23 @@
+use crate::robot;
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::health::HealthResponse;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .with_state(state)
+}
+
+async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_s: state.started.elapsed().as_secs_f64(),
+        profile: state.profile.to_string(),
+        settings: state.settings.clone(),
+        camera: state.frames.health().state,
+        robot: robot::info(&state),
+        cpu: state.cpu.report(),
+    })
+}
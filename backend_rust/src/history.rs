@@ -0,0 +1,239 @@
+//! Detection history in SQLite (`data/detections.sqlite`), in builds with
+//! the `sqlite` feature.
+//!
+//! Every published object is stored with its run, track id and zone, so
+//! post-run analysis and scoring query `/detections/history` instead of
+//! parsing blackbox logs. Each set is written in one transaction, on a
+//! thread of its own so a slow SD card never holds up inference.
+
+use crate::detections::Published;
+use crate::state::AppState;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use raspibot_protocol::inference::{ClassHistory, DetectionQuery, DetectionRecord};
+use rusqlite::{params, params_from_iter, types::Value, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::broadcast;
+
+pub const DB_PATH: &str = "data/detections.sqlite";
+const DEFAULT_LIMIT: u32 = 1000;
+const MAX_LIMIT: u32 = 100_000;
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+PRAGMA synchronous = NORMAL;
+CREATE TABLE IF NOT EXISTS detections (
+    id INTEGER PRIMARY KEY,
+    session TEXT,
+    seq INTEGER NOT NULL,
+    captured_unix_ms INTEGER NOT NULL,
+    class TEXT NOT NULL,
+    confidence REAL NOT NULL,
+    track_id INTEGER,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    w INTEGER NOT NULL,
+    h INTEGER NOT NULL,
+    zone TEXT
+);
+CREATE INDEX IF NOT EXISTS detections_time ON detections (captured_unix_ms);
+CREATE INDEX IF NOT EXISTS detections_session ON detections (session, captured_unix_ms);
+CREATE INDEX IF NOT EXISTS detections_class ON detections (class, captured_unix_ms);
+";
+
+pub struct DetectionHistory {
+    conn: Mutex<Connection>,
+}
+
+impl DetectionHistory {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        if let Some(dir) = Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Stores one set; `zones` holds the zone of each object, in order.
+    fn insert(
+        &self,
+        session: Option<&str>,
+        published: &Published,
+        zones: &[Option<String>],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO detections
+                     (session, seq, captured_unix_ms, class, confidence, track_id, x, y, w, h, zone)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+            for (object, zone) in published.objects.iter().zip(zones) {
+                let [x, y, w, h] = object.bbox;
+                insert.execute(params![
+                    session,
+                    published.seq as i64,
+                    published.captured_unix_ms as i64,
+                    object.class,
+                    object.confidence as f64,
+                    object.track_id.map(|id| id as i64),
+                    x,
+                    y,
+                    w,
+                    h,
+                    zone,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Matching detections, newest first.
+    pub fn query(&self, query: &DetectionQuery) -> rusqlite::Result<Vec<DetectionRecord>> {
+        let (filter, values) = filter(query);
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let sql = format!(
+            "SELECT session, seq, captured_unix_ms, class, confidence, track_id, x, y, w, h, zone
+             FROM detections{} ORDER BY captured_unix_ms DESC, id DESC LIMIT {}",
+            filter, limit
+        );
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok(DetectionRecord {
+                session: row.get(0)?,
+                seq: row.get::<_, i64>(1)? as u64,
+                captured_unix_ms: row.get::<_, i64>(2)? as u64,
+                class: row.get(3)?,
+                confidence: row.get::<_, f64>(4)? as f32,
+                track_id: row.get::<_, Option<i64>>(5)?.map(|id| id as u64),
+                bbox: [row.get(6)?, row.get(7)?, row.get(8)?, row.get(9)?],
+                zone: row.get(10)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Per-class totals of the matching detections; `limit` does not apply.
+    pub fn summary(&self, query: &DetectionQuery) -> rusqlite::Result<Vec<ClassHistory>> {
+        let (filter, values) = filter(query);
+        let sql = format!(
+            "SELECT class, COUNT(*), COUNT(DISTINCT track_id), MAX(confidence),
+                    MIN(captured_unix_ms), MAX(captured_unix_ms)
+             FROM detections{} GROUP BY class ORDER BY class",
+            filter
+        );
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok(ClassHistory {
+                class: row.get(0)?,
+                detections: row.get::<_, i64>(1)? as u64,
+                tracks: row.get::<_, i64>(2)? as u64,
+                max_confidence: row.get::<_, f64>(3)? as f32,
+                first_unix_ms: row.get::<_, i64>(4)? as u64,
+                last_unix_ms: row.get::<_, i64>(5)? as u64,
+            })
+        })?;
+        rows.collect()
+    }
+}
+
+/// `WHERE` clause for `query`, with its parameters in order.
+fn filter(query: &DetectionQuery) -> (String, Vec<Value>) {
+    let mut clauses = Vec::new();
+    let mut values = Vec::new();
+    let mut add = |condition: &str, value: Value| {
+        values.push(value);
+        clauses.push(format!("{} ?{}", condition, values.len()));
+    };
+    if let Some(session) = &query.session {
+        add("session =", Value::Text(session.clone()));
+    }
+    if let Some(class) = &query.class {
+        add("class =", Value::Text(class.clone()));
+    }
+    if let Some(zone) = &query.zone {
+        add("zone =", Value::Text(zone.clone()));
+    }
+    if let Some(id) = query.track_id {
+        add("track_id =", Value::Integer(id as i64));
+    }
+    if let Some(ms) = query.from_ms {
+        add("captured_unix_ms >=", Value::Integer(ms as i64));
+    }
+    if let Some(ms) = query.to_ms {
+        add("captured_unix_ms <=", Value::Integer(ms as i64));
+    }
+    if clauses.is_empty() {
+        (String::new(), values)
+    } else {
+        (format!(" WHERE {}", clauses.join(" AND ")), values)
+    }
+}
+
+/// Stores every published set, tagged with the run in progress.
+pub fn start_history_recorder(state: AppState, history: Arc<DetectionHistory>) {
+    let mut sets = state.detections.subscribe();
+    thread::spawn(move || loop {
+        let published = match sets.blocking_recv() {
+            Ok(published) => published,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if published.objects.is_empty() {
+            continue;
+        }
+        let zones: Vec<Option<String>> = match state.frames.frame_size() {
+            Some((width, height)) => published
+                .objects
+                .iter()
+                .map(|o| state.zones.zone_of(o.bbox, width, height))
+                .collect(),
+            None => vec![None; published.objects.len()],
+        };
+        let session = state.sessions.active_id();
+        if let Err(e) = history.insert(session.as_deref(), &published, &zones) {
+            eprintln!("[ERR] Detection history write failed: {}", e);
+        }
+    });
+}
+
+pub fn routes(history: Arc<DetectionHistory>) -> Router {
+    Router::new()
+        .route("/detections/history", get(get_history))
+        .route("/detections/history/summary", get(get_summary))
+        .with_state(history)
+}
+
+async fn get_history(
+    State(history): State<Arc<DetectionHistory>>,
+    Query(query): Query<DetectionQuery>,
+) -> Result<Json<Vec<DetectionRecord>>, (StatusCode, String)> {
+    let records = tokio::task::spawn_blocking(move || history.query(&query))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(records))
+}
+
+async fn get_summary(
+    State(history): State<Arc<DetectionHistory>>,
+    Query(query): Query<DetectionQuery>,
+) -> Result<Json<Vec<ClassHistory>>, (StatusCode, String)> {
+    let classes = tokio::task::spawn_blocking(move || history.summary(&query))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(classes))
+}
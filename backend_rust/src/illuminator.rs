@@ -0,0 +1,31 @@
+//! IR LED illuminator switched through a GPIO pin (driven via a MOSFET).
+
+use rppal::gpio::{Gpio, OutputPin};
+use std::sync::Mutex;
+
+pub struct IrIlluminator {
+    pin: Mutex<OutputPin>,
+}
+
+impl IrIlluminator {
+    pub fn open(bcm_pin: u8) -> Result<Self, rppal::gpio::Error> {
+        let pin = Gpio::new()?.get(bcm_pin)?.into_output_low();
+        println!("[OK] IR illuminator on GPIO{}", bcm_pin);
+        Ok(Self {
+            pin: Mutex::new(pin),
+        })
+    }
+
+    pub fn set(&self, on: bool) {
+        let mut pin = self.pin.lock().unwrap();
+        if on {
+            pin.set_high();
+        } else {
+            pin.set_low();
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.pin.lock().unwrap().is_set_high()
+    }
+}
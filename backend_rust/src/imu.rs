@@ -0,0 +1,372 @@
+//! Inertial measurement unit on the I2C bus (`[imu]` in the config): an
+//! MPU-6050, accelerometer and gyro only, or a BNO055, which fuses
+//! orientation itself. Read at `rate_hz` and recorded as the `imu`
+//! telemetry stream, for heading hold and turns by an angle.
+//!
+//! The MPU-6050's gyro bias is measured when it opens, so the robot must
+//! stand still for that second; roll and pitch then come from a
+//! complementary filter and heading from the integrated yaw rate, which
+//! drifts a few degrees a minute. The BNO055 runs in its IMU mode, which
+//! leaves the magnetometer out since the motors distort the field. Either
+//! way heading counts from where it was zeroed (at startup, or
+//! `POST /imu/zero`), not from north.
+//!
+//! North comes from the compass, when there is one: each reading carries
+//! its heading as `magnetic_heading_deg`, tilt-compensated with this
+//! reading's accelerometer vector.
+
+use crate::compass::CompassManager;
+use crate::config::{ImuChip, ImuConfig};
+use crate::faults;
+use crate::threads;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::imu::ImuReading;
+use rppal::i2c::I2c;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+const STANDARD_GRAVITY: f32 = 9.806_65;
+
+const MPU6050_ADDR: u16 = 0x68;
+const MPU6050_SMPLRT_DIV: u8 = 0x19;
+const MPU6050_CONFIG: u8 = 0x1A;
+const MPU6050_GYRO_CONFIG: u8 = 0x1B;
+const MPU6050_ACCEL_CONFIG: u8 = 0x1C;
+const MPU6050_ACCEL_XOUT_H: u8 = 0x3B;
+const MPU6050_PWR_MGMT_1: u8 = 0x6B;
+/// Out of sleep, clocked from the X gyro's PLL.
+const PWR_CLOCK_PLL_X: u8 = 0x01;
+/// 44 Hz digital low-pass filter, against motor vibration.
+const CONFIG_DLPF_44HZ: u8 = 0x03;
+/// ±500 °/s, 65.5 counts per °/s.
+const GYRO_500DPS: u8 = 0x08;
+const GYRO_COUNTS_PER_DPS: f32 = 65.5;
+/// ±4 g, 8192 counts per g.
+const ACCEL_4G: u8 = 0x08;
+const ACCEL_COUNTS_PER_G: f32 = 8192.0;
+/// Samples averaged for the gyro bias, 10 ms apart.
+const BIAS_SAMPLES: usize = 100;
+/// Share of roll and pitch taken from the integrated gyro rather than the
+/// accelerometer on each sample.
+const COMPLEMENTARY_GYRO_WEIGHT: f32 = 0.98;
+
+const BNO055_ADDR: u16 = 0x28;
+const BNO055_CHIP_ID: u8 = 0x00;
+const BNO055_ACC_DATA: u8 = 0x08;
+const BNO055_GYR_DATA: u8 = 0x14;
+const BNO055_EUL_DATA: u8 = 0x1A;
+const BNO055_OPR_MODE: u8 = 0x3D;
+const BNO055_ID: u8 = 0xA0;
+const MODE_CONFIG: u8 = 0x00;
+/// Accelerometer and gyro fusion, relative heading, magnetometer off.
+const MODE_IMU: u8 = 0x08;
+/// Default units: m/s² at 100 counts, °/s and degrees at 16 counts.
+const BNO055_ACCEL_COUNTS: f32 = 100.0;
+const BNO055_ANGLE_COUNTS: f32 = 16.0;
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Roll and pitch in degrees from the direction of gravity.
+fn tilt_deg([ax, ay, az]: [f32; 3]) -> (f32, f32) {
+    let roll = ay.atan2(az).to_degrees();
+    let pitch = (-ax).atan2((ay * ay + az * az).sqrt()).to_degrees();
+    (roll, pitch)
+}
+
+/// What one read of the chip gives.
+struct Sample {
+    accel_mps2: [f32; 3],
+    gyro_dps: [f32; 3],
+    /// Roll, pitch and clockwise heading, from chips that fuse them.
+    orientation: Option<[f32; 3]>,
+}
+
+trait ImuDevice: Send {
+    fn read(&mut self) -> anyhow::Result<Sample>;
+}
+
+struct Mpu6050 {
+    i2c: I2c,
+    gyro_bias: [f32; 3],
+}
+
+impl Mpu6050 {
+    fn open(address: u16, rate_hz: f32) -> anyhow::Result<Self> {
+        let mut i2c = I2c::new()?;
+        i2c.set_slave_address(address)?;
+        i2c.smbus_write_byte(MPU6050_PWR_MGMT_1, PWR_CLOCK_PLL_X)?;
+        thread::sleep(Duration::from_millis(100));
+        i2c.smbus_write_byte(MPU6050_CONFIG, CONFIG_DLPF_44HZ)?;
+        // With the filter on, the gyro runs at 1 kHz before the divider
+        let divider = ((1000.0 / rate_hz).round().clamp(1.0, 256.0) as u16 - 1) as u8;
+        i2c.smbus_write_byte(MPU6050_SMPLRT_DIV, divider)?;
+        i2c.smbus_write_byte(MPU6050_GYRO_CONFIG, GYRO_500DPS)?;
+        i2c.smbus_write_byte(MPU6050_ACCEL_CONFIG, ACCEL_4G)?;
+
+        let mut imu = Self {
+            i2c,
+            gyro_bias: [0.0; 3],
+        };
+        let mut sum = [0.0f32; 3];
+        for _ in 0..BIAS_SAMPLES {
+            let (_, gyro) = imu.read_raw()?;
+            for (total, rate) in sum.iter_mut().zip(gyro) {
+                *total += rate;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        imu.gyro_bias = sum.map(|total| total / BIAS_SAMPLES as f32);
+        println!("[OK] MPU-6050 gyro bias {:?} °/s", imu.gyro_bias);
+        Ok(imu)
+    }
+
+    /// Acceleration in m/s² and uncorrected rates in °/s.
+    fn read_raw(&mut self) -> rppal::i2c::Result<([f32; 3], [f32; 3])> {
+        // Accel X/Y/Z, temperature, gyro X/Y/Z, big-endian
+        let mut buf = [0u8; 14];
+        self.i2c.block_read(MPU6050_ACCEL_XOUT_H, &mut buf)?;
+        let word = |i: usize| i16::from_be_bytes([buf[2 * i], buf[2 * i + 1]]) as f32;
+        let accel = [0, 1, 2].map(|i| word(i) / ACCEL_COUNTS_PER_G * STANDARD_GRAVITY);
+        let gyro = [4, 5, 6].map(|i| word(i) / GYRO_COUNTS_PER_DPS);
+        Ok((accel, gyro))
+    }
+}
+
+impl ImuDevice for Mpu6050 {
+    fn read(&mut self) -> anyhow::Result<Sample> {
+        let (accel_mps2, gyro) = self.read_raw()?;
+        let mut gyro_dps = gyro;
+        for (rate, bias) in gyro_dps.iter_mut().zip(self.gyro_bias) {
+            *rate -= bias;
+        }
+        Ok(Sample {
+            accel_mps2,
+            gyro_dps,
+            orientation: None,
+        })
+    }
+}
+
+struct Bno055 {
+    i2c: I2c,
+}
+
+impl Bno055 {
+    fn open(address: u16) -> anyhow::Result<Self> {
+        let mut i2c = I2c::new()?;
+        i2c.set_slave_address(address)?;
+        let id = i2c.smbus_read_byte(BNO055_CHIP_ID)?;
+        if id != BNO055_ID {
+            anyhow::bail!("no BNO055 at {:#04x} (chip id {:#04x})", address, id);
+        }
+        i2c.smbus_write_byte(BNO055_OPR_MODE, MODE_CONFIG)?;
+        thread::sleep(Duration::from_millis(25));
+        i2c.smbus_write_byte(BNO055_OPR_MODE, MODE_IMU)?;
+        thread::sleep(Duration::from_millis(20));
+        Ok(Self { i2c })
+    }
+
+    fn read_vector(&mut self, register: u8, counts: f32) -> rppal::i2c::Result<[f32; 3]> {
+        let mut buf = [0u8; 6];
+        self.i2c.block_read(register, &mut buf)?;
+        Ok([0, 1, 2].map(|i| i16::from_le_bytes([buf[2 * i], buf[2 * i + 1]]) as f32 / counts))
+    }
+}
+
+impl ImuDevice for Bno055 {
+    fn read(&mut self) -> anyhow::Result<Sample> {
+        let accel_mps2 = self.read_vector(BNO055_ACC_DATA, BNO055_ACCEL_COUNTS)?;
+        let gyro_dps = self.read_vector(BNO055_GYR_DATA, BNO055_ANGLE_COUNTS)?;
+        // Heading, roll, pitch
+        let [heading, roll, pitch] = self.read_vector(BNO055_EUL_DATA, BNO055_ANGLE_COUNTS)?;
+        Ok(Sample {
+            accel_mps2,
+            gyro_dps,
+            orientation: Some([roll, pitch, heading]),
+        })
+    }
+}
+
+/// Roll, pitch and heading fused from the raw rates, for chips that don't.
+struct Fusion {
+    roll: f32,
+    pitch: f32,
+    heading: f32,
+}
+
+impl Fusion {
+    fn start(accel: [f32; 3]) -> Self {
+        let (roll, pitch) = tilt_deg(accel);
+        Self {
+            roll,
+            pitch,
+            heading: 0.0,
+        }
+    }
+
+    fn update(&mut self, sample: &Sample, dt: f32) -> [f32; 3] {
+        let (accel_roll, accel_pitch) = tilt_deg(sample.accel_mps2);
+        let [gx, gy, gz] = sample.gyro_dps;
+        let k = COMPLEMENTARY_GYRO_WEIGHT;
+        self.roll = k * (self.roll + gx * dt) + (1.0 - k) * accel_roll;
+        self.pitch = k * (self.pitch + gy * dt) + (1.0 - k) * accel_pitch;
+        // Z points up, so a positive yaw rate turns counter-clockwise
+        self.heading = (self.heading - gz * dt).rem_euclid(360.0);
+        [self.roll, self.pitch, self.heading]
+    }
+}
+
+struct Latest {
+    reading: Option<ImuReading>,
+    /// Heading as the chip or the fusion has it, reported as 0.
+    zero: f32,
+    /// Heading of the latest sample before zeroing.
+    unzeroed: f32,
+}
+
+pub struct Imu {
+    chip: Mutex<Box<dyn ImuDevice>>,
+    interval: Duration,
+    latest: Mutex<Latest>,
+    samples: broadcast::Sender<ImuReading>,
+    compass: Mutex<Option<Arc<CompassManager>>>,
+}
+
+impl Imu {
+    /// Opens the configured chip; `None` when no IMU is configured.
+    pub fn open(config: &ImuConfig) -> anyhow::Result<Option<Self>> {
+        let Some(chip) = config.chip else {
+            return Ok(None);
+        };
+        let driver: Box<dyn ImuDevice> = match chip {
+            ImuChip::Mpu6050 => Box::new(Mpu6050::open(
+                config.i2c_address.unwrap_or(MPU6050_ADDR),
+                config.rate_hz,
+            )?),
+            ImuChip::Bno055 => Box::new(Bno055::open(config.i2c_address.unwrap_or(BNO055_ADDR))?),
+        };
+        println!("[OK] IMU {:?} at {} Hz", chip, config.rate_hz);
+        Ok(Some(Self {
+            chip: Mutex::new(driver),
+            interval: Duration::from_secs_f32(1.0 / config.rate_hz),
+            latest: Mutex::new(Latest {
+                reading: None,
+                zero: 0.0,
+                unzeroed: 0.0,
+            }),
+            samples: broadcast::channel(64).0,
+            compass: Mutex::new(None),
+        }))
+    }
+
+    /// The latest reading; `None` before the first.
+    pub fn reading(&self) -> Option<ImuReading> {
+        self.latest.lock().unwrap().reading
+    }
+
+    /// Every reading, at the configured rate.
+    pub fn subscribe(&self) -> broadcast::Receiver<ImuReading> {
+        self.samples.subscribe()
+    }
+
+    /// Tilt-compensates `compass` and adds its heading to every reading.
+    pub fn attach_compass(&self, compass: Arc<CompassManager>) {
+        *self.compass.lock().unwrap() = Some(compass);
+    }
+
+    /// Counts heading from the robot's current direction.
+    pub fn zero_heading(&self) {
+        let mut latest = self.latest.lock().unwrap();
+        latest.zero = latest.unzeroed;
+        if let Some(reading) = &mut latest.reading {
+            reading.heading_deg = 0.0;
+        }
+    }
+
+    fn update(&self, sample: &Sample, [roll, pitch, heading]: [f32; 3]) {
+        let magnetic_heading_deg = self
+            .compass
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|compass| compass.heading_with_gravity(sample.accel_mps2));
+        let mut latest = self.latest.lock().unwrap();
+        latest.unzeroed = heading;
+        let reading = ImuReading {
+            accel_mps2: sample.accel_mps2,
+            gyro_dps: sample.gyro_dps,
+            roll_deg: roll,
+            pitch_deg: pitch,
+            heading_deg: (heading - latest.zero).rem_euclid(360.0),
+            magnetic_heading_deg,
+            fused: sample.orientation.is_some(),
+            taken_unix_ms: unix_ms(),
+        };
+        latest.reading = Some(reading);
+        let _ = self.samples.send(reading);
+    }
+}
+
+/// Reads the IMU at its rate until the `imu` task is killed.
+pub fn start_imu_thread(imu: Arc<Imu>) {
+    thread::spawn(move || {
+        threads::label("imu");
+        let mut fusion: Option<Fusion> = None;
+        let mut last = Instant::now();
+        loop {
+            if faults::killed("imu") {
+                return;
+            }
+            let sample = imu.chip.lock().unwrap().read();
+            let dt = last.elapsed().as_secs_f32();
+            last = Instant::now();
+            match sample {
+                Ok(sample) => {
+                    let orientation = match sample.orientation {
+                        Some(orientation) => orientation,
+                        None => fusion
+                            .get_or_insert_with(|| Fusion::start(sample.accel_mps2))
+                            .update(&sample, dt),
+                    };
+                    imu.update(&sample, orientation);
+                }
+                Err(e) => eprintln!("[ERR] IMU read failed: {}", e),
+            }
+            thread::sleep(imu.interval.saturating_sub(last.elapsed()));
+        }
+    });
+}
+
+pub fn routes(imu: Arc<Imu>) -> Router {
+    Router::new()
+        .route("/imu", get(get_reading))
+        .route("/imu/zero", post(zero))
+        .with_state(imu)
+}
+
+async fn get_reading(
+    State(imu): State<Arc<Imu>>,
+) -> Result<Json<ImuReading>, (StatusCode, String)> {
+    imu.reading().map(Json).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "no IMU reading yet".to_string(),
+    ))
+}
+
+async fn zero(State(imu): State<Arc<Imu>>) -> StatusCode {
+    imu.zero_heading();
+    println!("[INFO] IMU heading zeroed");
+    StatusCode::NO_CONTENT
+}
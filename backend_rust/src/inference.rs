@@ -0,0 +1,152 @@
+//! The inference workers: camera frames in, published detection sets out,
+//! one worker per camera sharing the detector.
+//!
+//! Each runs on its own thread, since a pass blocks for hundreds of
+//! milliseconds on the Pi. It takes the newest frame it is due from the
+//! dispatcher, so a slow pass skips frames instead of falling behind, and
+//! publishes through the camera's
+//! [`DetectionHub`](crate::detections::DetectionHub) that the HTTP and
+//! Socket.IO layers already read from. Every set goes through the camera's
+//! tracker first, so published objects carry stable track ids, and their
+//! crops are kept for the operator to check (see [`crate::crops`]).
+
+use crate::camera::Camera;
+use crate::dispatch::Decimation;
+use crate::faults;
+use crate::reid::ReidModel;
+use crate::state::AppState;
+use crate::thermal;
+use crate::threads;
+use crate::tracker::{color_histogram, Appearance, Observation, Tracker, TrackerConfig};
+use crate::transform::BoxF;
+use crate::yolo::ModelSlot;
+use opencv::{
+    core::{Mat, Rect},
+    prelude::*,
+};
+use raspibot_protocol::inference::DetectedObject;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// `INFERENCE_MAX_FPS` caps the passes per second; unset, the model runs
+/// as fast as it can.
+fn decimation_from_env() -> Decimation {
+    let Ok(value) = std::env::var("INFERENCE_MAX_FPS") else {
+        return Decimation::EveryFrame;
+    };
+    match value.trim().parse::<f32>() {
+        Ok(fps) if fps > 0.0 => Decimation::MaxFps(fps),
+        _ => {
+            println!("[WARN] Ignoring invalid INFERENCE_MAX_FPS='{}'", value);
+            Decimation::EveryFrame
+        }
+    }
+}
+
+/// Sets the track id of every object a track was matched to; `boxes` are
+/// the model's boxes behind `objects`, in the same order.
+fn assign_tracks(
+    tracker: &mut Tracker,
+    reid: Option<&ReidModel>,
+    frame: &Mat,
+    boxes: &[(Rect, f32, i64)],
+    objects: &mut [DetectedObject],
+    captured: Instant,
+) {
+    let observations: Vec<Observation> = boxes
+        .iter()
+        .map(|&(rect, confidence, class)| Observation {
+            detection: (
+                BoxF {
+                    x: rect.x as f32,
+                    y: rect.y as f32,
+                    w: rect.width as f32,
+                    h: rect.height as f32,
+                },
+                confidence,
+                class,
+            ),
+            appearance: Appearance {
+                histogram: color_histogram(frame, rect).ok().filter(|h| !h.is_empty()),
+                embedding: reid.and_then(|model| model.embed(frame, rect).ok()),
+            },
+        })
+        .collect();
+    for track in tracker.update(&observations, captured) {
+        if let Some(object) = track.observation.and_then(|i| objects.get_mut(i)) {
+            object.track_id = Some(track.id);
+        }
+    }
+}
+
+pub fn start_inference_worker(state: AppState) {
+    let Some(model) = state.model.clone() else {
+        println!("[WARN] No detector, inference worker not started");
+        return;
+    };
+    for camera in state.cameras.iter() {
+        start_camera_worker(state.clone(), camera.clone(), model.clone());
+    }
+}
+
+/// Hot spots of the thermal camera are added to the primary camera's
+/// detections only, the one it is registered to.
+fn start_camera_worker(state: AppState, camera: Arc<Camera>, model: Arc<ModelSlot>) {
+    let subscription = camera.frames.subscribe("inference", decimation_from_env());
+    let thermal = state
+        .thermal
+        .clone()
+        .filter(|_| state.cameras.is_primary(&camera));
+    thread::spawn(move || {
+        threads::label("inference");
+        let mut boxes = Vec::new();
+        let mut tracker =
+            Tracker::with_gallery(TrackerConfig::from_env(), state.reid_gallery.clone());
+        loop {
+            if faults::killed("inference") {
+                return;
+            }
+            let Some(frame) = subscription.recv_timeout(Duration::from_secs(1)) else {
+                continue;
+            };
+            let captured = Instant::now();
+            let _pass = camera.frames.pipeline().pass();
+            let detected = model.detect(&frame, &mut boxes);
+            // An injected stall holds up this camera's pipeline like a slow
+            // model would
+            thread::sleep(faults::inference_delay());
+            match detected {
+                Ok(Some(mut objects)) => {
+                    // Hot spots are tracked and published like the model's
+                    if let Some(thermal) = &thermal {
+                        let optics = state.units.camera();
+                        for object in thermal.hot_objects(&optics, frame.cols(), frame.rows()) {
+                            let [x, y, w, h] = object.bbox;
+                            boxes.push((
+                                Rect::new(x, y, w, h),
+                                object.confidence,
+                                thermal::HOT_CLASS_ID,
+                            ));
+                            objects.push(object);
+                        }
+                    }
+                    assign_tracks(
+                        &mut tracker,
+                        state.reid.as_deref(),
+                        &frame,
+                        &boxes,
+                        &mut objects,
+                        captured,
+                    );
+                    let published = camera.detections.publish(objects, captured);
+                    state.crops.record(&camera.id, &frame, &published);
+                    state.sessions.record_detections(&published.objects);
+                }
+                // Unloaded while idling; frames stop soon after anyway
+                Ok(None) => {}
+                Err(e) => eprintln!("[ERR] Inference on '{}' failed: {}", camera.id, e),
+            }
+        }
+    });
+}
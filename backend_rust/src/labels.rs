@@ -0,0 +1,123 @@
+//! Localized class display names, so judges and team each read detections
+//! in their own language (English, Indonesian, Japanese).
+//!
+//! Names are set per model class in `data/class_labels.json`
+//! (`PUT /detections/labels`, or the settings import); a class without
+//! names shows as the model names it. Clients pick a language with
+//! `?locale=` on the Socket.IO handshake (`/` and `/detections`), on
+//! `/detections/latest` and on the annotated stream and snapshot. Each
+//! Socket.IO client joins its language's room and gets every detection set
+//! with `display_name` filled in for it.
+
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::inference::DetectionSet;
+use raspibot_protocol::locale::{ClassLabels, Locale};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const CONFIG_PATH: &str = "data/class_labels.json";
+
+pub struct ClassLabelStore {
+    path: PathBuf,
+    labels: Mutex<ClassLabels>,
+}
+
+impl ClassLabelStore {
+    pub fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let labels = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            labels: Mutex::new(labels),
+        }
+    }
+
+    pub fn config(&self) -> ClassLabels {
+        self.labels.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, labels: ClassLabels) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&labels)?)?;
+        *self.labels.lock().unwrap() = labels;
+        Ok(())
+    }
+
+    /// `set` with every object's display name in `locale`.
+    pub fn localize(&self, mut set: DetectionSet, locale: Locale) -> DetectionSet {
+        let labels = self.labels.lock().unwrap();
+        for object in &mut set.objects {
+            object.display_name = labels.name(&object.class, locale).map(str::to_string);
+        }
+        set
+    }
+
+    /// What to show for `class` in `locale`.
+    pub fn display(&self, class: &str, locale: Locale) -> String {
+        self.labels
+            .lock()
+            .unwrap()
+            .name(class, locale)
+            .unwrap_or(class)
+            .to_string()
+    }
+}
+
+/// Socket.IO room holding the clients that read `locale`.
+pub fn room(locale: Locale) -> String {
+    format!("locale-{}", locale.code())
+}
+
+/// The `locale` of a Socket.IO handshake query; English when unset or
+/// unknown.
+pub fn from_handshake(query: Option<&str>) -> Locale {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "locale")
+        .and_then(|(_, value)| Locale::from_tag(value))
+        .unwrap_or_default()
+}
+
+pub fn validate(labels: &ClassLabels) -> Result<(), String> {
+    let blank = |name: &Option<String>| name.as_ref().is_some_and(|n| n.trim().is_empty());
+    for (class, names) in &labels.classes {
+        if [&names.en, &names.id, &names.ja].into_iter().any(blank) {
+            return Err(format!("class '{}' has a blank name", class));
+        }
+    }
+    Ok(())
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/detections/labels", get(get_labels).put(set_labels))
+        .with_state(state)
+}
+
+async fn get_labels(State(state): State<AppState>) -> Json<ClassLabels> {
+    Json(state.labels.config())
+}
+
+async fn set_labels(
+    State(state): State<AppState>,
+    Json(labels): Json<ClassLabels>,
+) -> Result<Json<ClassLabels>, (StatusCode, String)> {
+    validate(&labels).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state
+        .labels
+        .set_config(labels.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Class display names set for {} classes",
+        labels.classes.len()
+    );
+    Ok(Json(labels))
+}
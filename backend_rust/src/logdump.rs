@@ -0,0 +1,76 @@
+//! `logdump`: converts a run's blackbox back to JSONL, one sample per
+//! line, for jq, pandas and the like.
+//!
+//! `logdump <session dir> [--from S] [--to S] [--stream NAME]... [--out
+//! FILE]`, times in seconds since the run started; `--from` seeks through
+//! the blackbox index instead of decompressing the whole run. Writes
+//! `telemetry.dump.jsonl` in the session directory unless `--out` says
+//! otherwise.
+
+use crate::blackbox;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+const DEFAULT_OUT: &str = "telemetry.dump.jsonl";
+
+pub struct LogdumpArgs {
+    pub session: PathBuf,
+    pub from_s: f64,
+    pub to_s: f64,
+    /// Streams to keep; every stream when empty.
+    pub streams: Vec<String>,
+    pub out: PathBuf,
+}
+
+fn seconds(flag: &str, value: Option<String>) -> Result<f64, String> {
+    value
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|s| s.is_finite())
+        .ok_or(format!("{} needs a time in seconds", flag))
+}
+
+impl LogdumpArgs {
+    /// Parses the arguments after `logdump`; the session directory comes
+    /// first, `--profile` is taken out beforehand.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let session: PathBuf = match args.peek() {
+            Some(arg) if !arg.starts_with("--") => args.next().unwrap_or_default().into(),
+            _ => return Err("logdump needs a session directory".to_string()),
+        };
+        let mut parsed = Self {
+            out: session.join(DEFAULT_OUT),
+            session,
+            from_s: f64::NEG_INFINITY,
+            to_s: f64::INFINITY,
+            streams: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--from" => parsed.from_s = seconds("--from", args.next())?,
+                "--to" => parsed.to_s = seconds("--to", args.next())?,
+                "--stream" => parsed
+                    .streams
+                    .push(args.next().ok_or("--stream needs a name")?),
+                "--out" => parsed.out = args.next().ok_or("--out needs a path")?.into(),
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Writes the selected samples in time order; returns how many.
+pub fn run(args: &LogdumpArgs) -> anyhow::Result<usize> {
+    let mut samples = blackbox::read_range(&args.session, args.from_s, args.to_s)?;
+    samples.retain(|s| args.streams.is_empty() || args.streams.contains(&s.stream));
+    samples.sort_by(|a, b| a.t_s.total_cmp(&b.t_s));
+    let mut out = BufWriter::new(File::create(&args.out)?);
+    for sample in &samples {
+        serde_json::to_writer(&mut out, sample)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(samples.len())
+}
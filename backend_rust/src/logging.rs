@@ -0,0 +1,466 @@
+//! Tracing setup: stdout plus sinks that can be switched while running —
+//! daily rolling files, journald, and a UDP/TCP stream to the ground station.
+//!
+//! Events are formatted on the calling thread and handed to a writer thread
+//! through a bounded queue, so a slow disk or dead network link never stalls
+//! the caller; when the queue is full, lines are dropped.
+//!
+//! Most of the backend still logs with `println!`/`eprintln!` and a `[INFO]`,
+//! `[OK]`, `[WARN]` or `[ERR]` tag, so stdout and stderr are swapped for pipes
+//! whose lines are copied to the terminal and queued for the sinks as well,
+//! at the level their tag gives.
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::logging::{LogSinkConfig, NetworkSink, Transport};
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::fd::{FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub const SINKS_PATH: &str = "data/log_sinks.json";
+const QUEUE_LEN: usize = 4096;
+const FILE_PREFIX: &str = "backend-";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const TCP_RETRY: Duration = Duration::from_secs(5);
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum Message {
+    Line { level: Level, text: String },
+    Reconfigure(LogSinkConfig),
+}
+
+pub struct LogSinks {
+    path: PathBuf,
+    config: Mutex<LogSinkConfig>,
+    tx: SyncSender<Message>,
+}
+
+impl LogSinks {
+    pub fn config(&self) -> LogSinkConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: LogSinkConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config.clone();
+        // Reconfiguring must not be lost to a full queue
+        let _ = self.tx.send(Message::Reconfigure(config));
+        Ok(())
+    }
+}
+
+/// Installs the global subscriber at `level` and starts the sink writer.
+pub fn init(level: Level) -> Arc<LogSinks> {
+    let path = PathBuf::from(SINKS_PATH);
+    let config: LogSinkConfig = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+
+    let (tx, rx) = mpsc::sync_channel(QUEUE_LEN);
+    let initial = config.clone();
+    thread::spawn(move || run_writer(rx, initial));
+
+    // Tracing events reach the sinks through `SinkLayer`, so they're
+    // printed to the terminal itself rather than into the pipe
+    let terminal = match capture(libc::STDOUT_FILENO, "stdout", level, tx.clone()) {
+        Ok(terminal) => BoxMakeWriter::new(Mutex::new(terminal)),
+        Err(e) => {
+            eprintln!("[ERR] Printed lines won't reach the log sinks: {}", e);
+            BoxMakeWriter::new(std::io::stdout)
+        }
+    };
+    if let Err(e) = capture(libc::STDERR_FILENO, "stderr", level, tx.clone()) {
+        eprintln!("[ERR] stderr won't reach the log sinks: {}", e);
+    }
+
+    let filter = LevelFilter::from_level(level);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(terminal)
+                .with_filter(filter),
+        )
+        .with(SinkLayer { tx: tx.clone() }.with_filter(filter))
+        .init();
+
+    Arc::new(LogSinks {
+        path,
+        config: Mutex::new(config),
+        tx,
+    })
+}
+
+fn os_result(ret: libc::c_int) -> std::io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Points `fd` at a pipe whose lines go on to the terminal `fd` was and to
+/// the sinks; returns that terminal.
+fn capture(
+    fd: RawFd,
+    target: &'static str,
+    max_level: Level,
+    tx: SyncSender<Message>,
+) -> std::io::Result<File> {
+    let mut pipe = [0; 2];
+    // SAFETY: plain descriptor calls; every descriptor made here is owned
+    // by exactly one `File` or closed right away
+    let (reader, terminal) = unsafe {
+        os_result(libc::pipe(pipe.as_mut_ptr()))?;
+        let reader = File::from_raw_fd(pipe[0]);
+        let writer = File::from_raw_fd(pipe[1]);
+        let terminal = File::from_raw_fd(os_result(libc::dup(fd))?);
+        os_result(libc::dup2(pipe[1], fd))?;
+        drop(writer);
+        (reader, terminal)
+    };
+    let mut echo = terminal.try_clone()?;
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            let _ = writeln!(echo, "{}", line);
+            let level = tagged_level(&line, target);
+            if level <= max_level {
+                let _ = tx.try_send(Message::Line {
+                    text: format_line(level, target, &line),
+                    level,
+                });
+            }
+        }
+    });
+    Ok(terminal)
+}
+
+/// The level of a printed line from its tag; untagged lines on stderr
+/// (OpenCV, GStreamer) count as warnings.
+fn tagged_level(line: &str, target: &str) -> Level {
+    if line.starts_with("[ERR]") {
+        Level::ERROR
+    } else if line.starts_with("[WARN]") || (target == "stderr" && !line.starts_with('[')) {
+        Level::WARN
+    } else {
+        Level::INFO
+    }
+}
+
+/// `<unix time> <level> <target>: <message>`, one line per event.
+fn format_line(level: Level, target: &str, message: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "{}.{:03} {} {}: {}",
+        now.as_secs(),
+        now.subsec_millis(),
+        level,
+        target,
+        message
+    )
+}
+
+struct SinkLayer {
+    tx: SyncSender<Message>,
+}
+
+impl<S: Subscriber> Layer<S> for SinkLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let _ = self.tx.try_send(Message::Line {
+            level: *metadata.level(),
+            text: format_line(
+                *metadata.level(),
+                metadata.target(),
+                &(visitor.message + &visitor.fields),
+            ),
+        });
+    }
+}
+
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+fn run_writer(rx: Receiver<Message>, config: LogSinkConfig) {
+    let mut outputs = Outputs::open(&config);
+    for message in rx {
+        match message {
+            Message::Line { level, text } => outputs.write(level, &text),
+            Message::Reconfigure(config) => outputs = Outputs::open(&config),
+        }
+    }
+}
+
+struct Outputs {
+    file: Option<RollingFile>,
+    journald: Option<UnixDatagram>,
+    network: Option<Network>,
+}
+
+impl Outputs {
+    fn open(config: &LogSinkConfig) -> Self {
+        let journald = if config.journald {
+            match UnixDatagram::unbound() {
+                Ok(socket) => Some(socket),
+                Err(e) => {
+                    eprintln!("[ERR] journald sink unavailable: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Self {
+            file: config
+                .file
+                .then(|| RollingFile::new(&config.file_dir, config.file_keep)),
+            journald,
+            network: config.network.as_ref().and_then(Network::open),
+        }
+    }
+
+    fn write(&mut self, level: Level, text: &str) {
+        if let Some(file) = self.file.as_mut() {
+            file.write_line(text);
+        }
+        if let Some(socket) = &self.journald {
+            // Native protocol; newlines would need the binary field encoding
+            let payload = format!(
+                "PRIORITY={}\nSYSLOG_IDENTIFIER=raspibot\nMESSAGE={}\n",
+                journald_priority(level),
+                text.replace('\n', " ")
+            );
+            let _ = socket.send_to(payload.as_bytes(), JOURNALD_SOCKET);
+        }
+        if let Some(network) = self.network.as_mut() {
+            network.send(text);
+        }
+    }
+}
+
+fn journald_priority(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+/// `backend-YYYY-MM-DD.log`, switching files at UTC midnight.
+struct RollingFile {
+    dir: PathBuf,
+    keep: usize,
+    day: i64,
+    file: Option<File>,
+}
+
+impl RollingFile {
+    fn new(dir: &str, keep: usize) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            keep: keep.max(1),
+            day: -1,
+            file: None,
+        }
+    }
+
+    fn write_line(&mut self, text: &str) {
+        let day = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| (d.as_secs() / 86_400) as i64)
+            .unwrap_or(0);
+        if day != self.day {
+            self.day = day;
+            self.file = self.open(day);
+        }
+        if let Some(file) = self.file.as_mut() {
+            let _ = writeln!(file, "{}", text);
+        }
+    }
+
+    fn open(&self, day: i64) -> Option<File> {
+        let (y, m, d) = civil_from_days(day);
+        let path = self
+            .dir
+            .join(format!("{}{:04}-{:02}-{:02}.log", FILE_PREFIX, y, m, d));
+        let file = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| OpenOptions::new().create(true).append(true).open(&path));
+        match file {
+            Ok(file) => {
+                prune(&self.dir, self.keep);
+                Some(file)
+            }
+            Err(e) => {
+                eprintln!("[ERR] Could not open log file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Deletes all but the newest `keep` log files; the date in the name sorts.
+fn prune(dir: &Path, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(FILE_PREFIX) && n.ends_with(".log"))
+        })
+        .collect();
+    files.sort();
+    let excess = files.len().saturating_sub(keep);
+    for path in &files[..excess] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Days since 1970-01-01 to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+enum Network {
+    Udp(UdpSocket),
+    Tcp {
+        addr: String,
+        stream: Option<TcpStream>,
+        retry_at: Instant,
+    },
+}
+
+impl Network {
+    fn open(sink: &NetworkSink) -> Option<Self> {
+        match sink.transport {
+            Transport::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").and_then(|s| {
+                    s.connect(&sink.addr)?;
+                    Ok(s)
+                });
+                match socket {
+                    Ok(socket) => Some(Network::Udp(socket)),
+                    Err(e) => {
+                        eprintln!("[ERR] UDP log sink {} unavailable: {}", sink.addr, e);
+                        None
+                    }
+                }
+            }
+            Transport::Tcp => Some(Network::Tcp {
+                addr: sink.addr.clone(),
+                stream: None,
+                retry_at: Instant::now(),
+            }),
+        }
+    }
+
+    fn send(&mut self, text: &str) {
+        match self {
+            Network::Udp(socket) => {
+                let _ = socket.send(text.as_bytes());
+            }
+            Network::Tcp {
+                addr,
+                stream,
+                retry_at,
+            } => {
+                if stream.is_none() && Instant::now() >= *retry_at {
+                    *stream = connect_tcp(addr);
+                    *retry_at = Instant::now() + TCP_RETRY;
+                }
+                if let Some(s) = stream.as_mut() {
+                    if writeln!(s, "{}", text).is_err() {
+                        *stream = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn connect_tcp(addr: &str) -> Option<TcpStream> {
+    let target = addr.to_socket_addrs().ok()?.next()?;
+    let stream = TcpStream::connect_timeout(&target, TCP_CONNECT_TIMEOUT).ok()?;
+    let _ = stream.set_write_timeout(Some(TCP_CONNECT_TIMEOUT));
+    Some(stream)
+}
+
+pub fn routes(sinks: Arc<LogSinks>) -> Router {
+    Router::new()
+        .route("/logging/sinks", get(get_sinks).put(set_sinks))
+        .with_state(sinks)
+}
+
+async fn get_sinks(State(sinks): State<Arc<LogSinks>>) -> Json<LogSinkConfig> {
+    Json(sinks.config())
+}
+
+async fn set_sinks(
+    State(sinks): State<Arc<LogSinks>>,
+    Json(config): Json<LogSinkConfig>,
+) -> Result<Json<LogSinkConfig>, (StatusCode, String)> {
+    // Resolving the address and queueing the change both block
+    let applied = config.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Some(network) = &applied.network {
+            if network.addr.to_socket_addrs().is_err() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("cannot resolve network sink '{}'", network.addr),
+                ));
+            }
+        }
+        sinks
+            .set_config(applied)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    tracing::info!(?config, "log sinks reconfigured");
+    Ok(Json(config))
+}
@@ -1,7 +1,96 @@
+mod adaptive;
+mod annotate;
+mod arbiter;
+#[cfg(feature = "arm")]
+mod arm;
+mod autostart;
+mod bench;
+mod bench_postprocess;
+mod blackbox;
+mod boundary;
+mod bumper;
 mod camera;
+mod charging;
+mod clips;
+mod clock;
+mod compass;
+mod config;
+mod crops;
+mod detections;
+mod devices;
+mod dispatch;
+mod drive;
+#[cfg(feature = "arm")]
+mod dynamixel;
+mod estop;
+mod evidence;
+mod export;
+mod faults;
+mod gps;
+mod health;
+#[cfg(feature = "sqlite")]
+mod history;
+mod illuminator;
+mod imu;
+mod inference;
+mod labels;
+mod logdump;
+mod logging;
+mod mission;
+mod motors;
+mod net;
+mod nms;
+mod nudge;
+mod overlay;
+mod persist;
+mod pid;
+mod pipeline;
+mod pose;
+mod power;
+mod presence;
+mod privacy;
+mod profile;
+mod quality;
+mod range;
+mod rate;
+mod recorder;
+mod reid;
+mod replay;
+mod robot;
+mod run_timer;
+mod schemas;
+mod serial;
+mod servo;
+mod session;
+mod settings;
+mod socket;
+mod source;
+mod speed_calibration;
+mod state;
+mod steering;
+mod stereo;
+mod stream;
+mod target;
+mod telemetry;
+mod teleop;
+mod thermal;
+mod threads;
+mod tls;
+mod topology;
+mod tracker;
+mod transform;
+mod units;
+mod validate;
+mod velocity;
+mod version;
+mod viewers;
+mod visual_servo;
 mod yolo;
+mod zones;
 
-use axum::{routing::get, Router};
+use axum::{middleware, routing::get, Router};
+use futures_util::FutureExt;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 
@@ -9,22 +98,520 @@ use tower_http::cors::CorsLayer;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("[INFO] Starting PENS-KAIT 2026 Rust Backend...");
 
-    // 1. Initialize YOLO
-    // let yolo = yolo::YoloModel::new("../backend/models/yolov8s-worldv2.onnx")?;
+    // 0. Select dev/competition profile (--profile overrides the one the
+    // previous run used, which overrides the build default); what's left is
+    // the subcommand and its arguments
+    let saved = persist::RuntimeState::load();
+    let (profile_arg, args) = profile::Profile::from_args(std::env::args().skip(1))?;
+    let profile = profile_arg
+        .or_else(|| saved.as_ref().and_then(|s| s.profile()))
+        .unwrap_or_else(profile::Profile::build_default);
+    let settings = profile.settings();
+    // Log sinks (files/journald/network) are switchable at runtime
+    let log_sinks = logging::init(
+        settings
+            .log_level
+            .parse::<tracing::Level>()
+            .unwrap_or(tracing::Level::INFO),
+    );
+    println!("[INFO] Active profile: {}", profile);
 
-    // 2. Start Camera
-    let _frame_manager = camera::start_camera_thread();
+    // Camera, model and server settings from config.toml, env vars on top
+    let config = config::Config::load()?;
 
-    // 3. Setup router (to be integrated with socketioxide)
+    // 1. Initialize YOLO (session threading/memory from [model.session])
+    let session_options = config.model.session.clone();
+
+    // `validate` runs a pre-match dry run of the detection pipeline,
+    // `bench-capture` compares capture backends, `bench-postprocess` times
+    // sequential against parallel decoding, `replay` checks a recorded run
+    // against expected outputs and `logdump` converts a run's blackbox to
+    // JSONL; all exit when done
+    let mut args = args.into_iter().peekable();
+    match args.peek().map(String::as_str) {
+        Some("validate") => {
+            let validate_args = validate::ValidateArgs::parse(args.skip(1))?;
+            let passed = validate::run(&validate_args, &config.model, &session_options)?;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        Some("bench-capture") => {
+            let bench_args = bench::BenchArgs::parse(args.skip(1), config.camera.clone())?;
+            bench::run(&bench_args)?;
+            std::process::exit(0);
+        }
+        Some("bench-postprocess") => {
+            let bench_args = bench_postprocess::PostprocessBenchArgs::parse(args.skip(1))?;
+            bench_postprocess::run(&bench_args)?;
+            std::process::exit(0);
+        }
+        Some("replay") => {
+            let replay_args = replay::ReplayArgs::parse(args.skip(1))?;
+            let passed = replay::run(&replay_args)?;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        Some("logdump") => {
+            let logdump_args = logdump::LogdumpArgs::parse(args.skip(1))?;
+            let written = logdump::run(&logdump_args)?;
+            println!(
+                "[OK] Wrote {} samples to {}",
+                written,
+                logdump_args.out.display()
+            );
+            std::process::exit(0);
+        }
+        _ => {}
+    }
+
+    // Per-zone thresholds are applied inside `predict`, before anything fires;
+    // the zone test undistorts boxes with the unit calibration's lens model
+    let units = std::sync::Arc::new(units::UnitStore::load());
+    let zones = std::sync::Arc::new(zones::ZoneStore::load(units));
+    // Unloaded while the robot idles (see `power`)
+    let model = std::sync::Arc::new(yolo::ModelSlot::load(
+        config.model.clone(),
+        session_options.clone(),
+        zones.clone(),
+    ));
+    // Optional: appearance embeddings for re-identifying tracks
+    let reid = reid::ReidModel::from_env().map(std::sync::Arc::new);
+    let inference_info = raspibot_protocol::inference::InferenceSessionInfo {
+        model_path: config.model.path.clone(),
+        loaded: model.loaded(),
+        options: session_options,
+    };
+
+    // 2. Start the cameras (privacy masks are blacked out before any consumer)
+    let masks = std::sync::Arc::new(privacy::MaskStore::load());
+    let cameras = std::sync::Arc::new(camera::CameraSet::start(
+        &config.camera,
+        &config.cameras,
+        masks,
+        settings.allow_simulation,
+    ));
+    let frame_manager = cameras.primary().frames.clone();
+    // Boundary tape detection on the camera feed (idle until enabled)
+    let boundary = boundary::start_boundary_thread(&frame_manager);
+    // Synchronized pairs from the dual-camera rig, when STEREO_CAMERAS is set
+    let stereo =
+        stereo::StereoConfig::from_env(config.camera.video).and_then(stereo::start_stereo_rig);
+
+    // 3. Connect to the auxiliary MCU (optional, not every chassis has one)
+    let mcu = match serial::McuBridge::open(&config.mcu.port, config.mcu.baud) {
+        Ok(bridge) => Some(bridge),
+        Err(e) => {
+            println!(
+                "[WARN] MCU bridge unavailable ({}), continuing without it",
+                e
+            );
+            None
+        }
+    };
+
+    // 4. A chassis whose MCU runs the motors drives through the bridge
+    let mcu_drive: Option<Box<dyn motors::MotorDriver>> = match &mcu {
+        Some(mcu) if config.mcu.drive => {
+            println!("[OK] Drive motors run by the MCU");
+            Some(Box::new(motors::mcu::McuMotorDriver::new(mcu.clone())))
+        }
+        Some(_) => None,
+        None => {
+            if config.mcu.drive {
+                println!("[WARN] mcu.drive is set but the MCU is unavailable");
+            }
+            None
+        }
+    };
+    // Brushless chassis drives through CAN motor controllers
+    #[cfg(feature = "can")]
+    let drive: Option<Box<dyn motors::MotorDriver>> = if mcu_drive.is_some() {
+        mcu_drive
+    } else {
+        use motors::can::{CanMotorConfig, CanMotorDriver, VescMode, VescProtocol};
+        let protocol = std::sync::Arc::new(VescProtocol {
+            mode: VescMode::Duty,
+        });
+        match CanMotorDriver::open(CanMotorConfig::default(), protocol) {
+            Ok(driver) => Some(Box::new(driver)),
+            Err(e) => {
+                println!("[WARN] CAN motor controllers unavailable: {}", e);
+                None
+            }
+        }
+    };
+    // Otherwise brushed motors on an H-bridge, straight off the GPIO header
+    #[cfg(not(feature = "can"))]
+    let drive: Option<Box<dyn motors::MotorDriver>> = if mcu_drive.is_some() {
+        mcu_drive
+    } else {
+        use motors::gpio::{GpioMotorConfig, GpioMotorDriver};
+        match GpioMotorDriver::open(GpioMotorConfig::default()) {
+            Ok(driver) => Some(Box::new(driver)),
+            Err(e) => {
+                println!("[WARN] GPIO motors unavailable: {}", e);
+                None
+            }
+        }
+    };
+
+    // Every drive command goes through the arbiter and its safety constraints
+    let arbiter = std::sync::Arc::new(arbiter::CommandArbiter::new(
+        drive,
+        settings.max_drive_speed,
+    ));
+    arbiter.add_constraint(boundary.clone());
+
+    // 5. Dynamixel arm on the precision manipulator build
+    #[cfg(feature = "arm")]
+    let arm = match arm::Arm::open(arm::DEFAULT_PORT, arm::DEFAULT_BAUD) {
+        Ok(arm) => Some(std::sync::Arc::new(arm)),
+        Err(e) => {
+            println!("[WARN] Arm unavailable: {}", e);
+            None
+        }
+    };
+
+    // 6. GPS receiver for outdoor mode, only when one is plugged in
+    let gps = std::path::Path::new(gps::DEFAULT_PORT)
+        .exists()
+        .then(|| gps::start_gps_thread(gps::DEFAULT_PORT, gps::DEFAULT_BAUD));
+
+    // 7. Magnetometer heading (calibration persisted under data/)
+    let compass = match compass::start_compass_thread() {
+        Ok(c) => Some(c),
+        Err(e) => {
+            println!("[WARN] Compass unavailable: {}", e);
+            None
+        }
+    };
+
+    // 8. IMU for heading hold and turns by an angle, when one is configured
+    let imu = match imu::Imu::open(&config.imu) {
+        Ok(i) => i.map(std::sync::Arc::new),
+        Err(e) => {
+            println!("[WARN] IMU unavailable: {}", e);
+            None
+        }
+    };
+
+    // 9. Distance sensors and the obstacle stop, when any are configured
+    let range = range::RangeSensors::open(&config.range).map(std::sync::Arc::new);
+
+    // 10. IR illuminator for the low-light camera mode
+    let illuminator = match config.illuminator.pin.map(illuminator::IrIlluminator::open) {
+        Some(Ok(ir)) => Some(std::sync::Arc::new(ir)),
+        Some(Err(e)) => {
+            println!("[WARN] IR illuminator unavailable: {}", e);
+            None
+        }
+        None => None,
+    };
+
+    // 11. MLX90640 thermal camera for hot objects and the heat overlay
+    let thermal = match thermal::start_thermal_thread() {
+        Ok(t) => Some(t),
+        Err(e) => {
+            println!("[WARN] Thermal camera unavailable: {}", e);
+            None
+        }
+    };
+
+    // 12. Pan/tilt camera gimbal, when one is configured
+    let gimbal = match servo::Gimbal::open(&config.gimbal) {
+        Ok(g) => g.map(std::sync::Arc::new),
+        Err(e) => {
+            println!("[WARN] Gimbal unavailable: {}", e);
+            None
+        }
+    };
+
+    // 13. Run sessions and post-run reports
+    let sessions = std::sync::Arc::new(session::SessionManager::new());
+
+    // 14. Face blur for published streams/recordings (off until enabled)
+    let face_blur = std::sync::Arc::new(privacy::FaceBlur::load(&config.privacy.face_cascade));
+
+    let mut state = state::AppState::new(
+        profile, &config, cameras, sessions, face_blur, zones, arbiter, boundary,
+    );
+    // Close detections slow forward motion, like the boundary tape does
+    state.arbiter.add_constraint(state.bumper.clone());
+    // Turning is capped at speed so speed runs don't spin out
+    state.arbiter.add_constraint(state.steering.clone());
+    // Motors stop and the emergency stop latches when commands stop arriving
+    drive::start_drive_watchdog(state.clone(), config.drive.timeout_ms);
+    // The emergency stop also cuts the arm's torque
+    #[cfg(feature = "arm")]
+    if let Some(arm) = arm {
+        state
+            .estop
+            .add_halt("arm", Box::new(move || arm.set_torque(false)));
+    }
+    state.gps = gps;
+    state.compass = compass;
+    if let Some(gimbal) = gimbal {
+        servo::start_gimbal(state.clone(), gimbal.clone());
+        // The emergency stop holds the gimbal where it is
+        let holding = gimbal.clone();
+        state.estop.add_halt(
+            "gimbal",
+            Box::new(move || {
+                holding.hold();
+                Ok(())
+            }),
+        );
+        state.gimbal = Some(gimbal);
+    }
+    if let Some(imu) = imu {
+        // The IMU's accelerometer tilt-compensates the compass heading
+        if let Some(compass) = &state.compass {
+            imu.attach_compass(compass.clone());
+        }
+        imu::start_imu_thread(imu.clone());
+        telemetry::forward_imu(state.clone(), &imu);
+        state.imu = Some(imu);
+    }
+    if let Some(range) = range {
+        // Range sensors block forward drive near obstacles
+        state.arbiter.add_constraint(range.clone());
+        range::start_range_thread(range.clone());
+        telemetry::forward_range(state.clone(), &range);
+        state.range = Some(range);
+    }
+    // Pose from the MCU's encoder counts, steered by the IMU when there is one
+    if let Some(mcu) = &mcu {
+        let tracker = std::sync::Arc::new(pose::PoseTracker::new());
+        pose::start_pose_tracking(state.clone(), tracker.clone(), mcu);
+        // Good GPS fixes hold the odometry's drift in check outdoors
+        if let Some(gps) = &state.gps {
+            pose::start_gps_fusion(state.clone(), tracker.clone(), gps);
+        }
+        state.pose = Some(tracker);
+    }
+    state.illuminator = illuminator;
+    state.reid = reid;
+    state.model = Some(model);
+    state.stereo = stereo;
+    state.thermal = thermal;
+    state.robot.configure(config.robot.clone());
+    state.run_timer.configure(config.mission.clone());
+    state.velocity.configure(config.velocity.clone());
+    state
+        .mission
+        .configure_approvals(config.mission.approvals.clone());
+    state.charging.configure(config.charging.clone());
+
+    // Pick up where a crashed run left off, with any mission paused
+    if let Some(saved) = &saved {
+        saved.restore(&state);
+    }
+    persist::start_persistence(state.clone());
+    // MCU sensor and encoder pushes: full rate to the blackbox, downsampled
+    // to dashboards
+    if let Some(mcu) = &mcu {
+        telemetry::forward_mcu(state.clone(), mcu);
+        telemetry::forward_odometry(state.clone(), mcu);
+        velocity::track_encoders(state.clone(), mcu);
+    }
+    // Closed-loop drive, idle until a velocity setpoint arrives
+    velocity::start_velocity_control(state.clone());
+    // Camera frames through the detector, published to every consumer
+    inference::start_inference_worker(state.clone());
+    // Detection sets into the blackbox, for `replay`
+    detections::start_detection_recorder(state.clone());
+    // Camera restarts into the run report's faults
+    session::start_fault_recorder(state.clone());
+    // ...and into SQLite, queryable by run, class, zone and time
+    #[cfg(feature = "sqlite")]
+    let detection_history = match history::DetectionHistory::open(history::DB_PATH) {
+        Ok(h) => {
+            let h = std::sync::Arc::new(h);
+            history::start_history_recorder(state.clone(), h.clone());
+            Some(h)
+        }
+        Err(e) => {
+            println!("[WARN] Detection history unavailable: {}", e);
+            None
+        }
+    };
+    // Confidence threshold follows how many detections fail to persist
+    adaptive::start_adaptive_threshold(state.clone());
+    // Clips around the first sighting of each class during a run
+    clips::start_clip_recorder(&state, clips::ClipConfig::from_env());
+    // Blur, fog and exposure problems raised before they pass for bad detections
+    quality::start_quality_monitor(state.clone());
+
+    // USB cameras, gamepads and serial adapters coming and going
+    devices::start_device_monitor(state.clone());
+    // Configured match-ready actions, once the self-test passes
+    autostart::start_autostart(state.clone());
+
+    if config.robot.splash {
+        robot::print_splash(&robot::info(&state));
+    }
+
+    // 15. Socket.IO for the dashboard
+    let (socket_layer, io) = socket::build_layer(state.clone());
+    socket::spawn_broadcasts(&state, io.clone());
+    // Who this robot is, to every client now and whenever one asks
+    robot::start_announcer(state.clone(), io.clone());
+    // Camera frames for clients on the `/frames` namespace
+    socket::spawn_frame_stream(&state, io.clone());
+    // Teleop drops to idle when no dashboard heartbeat arrives
+    presence::start_presence_monitor(state.clone(), io.clone());
+    // Teleop over /control stops when the driving client goes quiet
+    teleop::start_deadman(state.clone(), io.clone());
+    // Per-thread CPU use for /api/health
+    threads::start_cpu_monitor(state.clone());
+    // Autonomous runs end at the competition's time limit
+    run_timer::start_run_timer(state.clone(), io.clone());
+    // Camera off, model unloaded and CPU throttled between matches
+    power::start_power_monitor(state.clone());
+    // No driving off the bench while plugged in
+    charging::start_charge_monitor(state.clone());
+    // Capture/inference/control rates, published once a second
+    rate::start_rate_monitor(state.clone(), io);
+    // Stereo pairing skew, into telemetry
+    if let Some(rig) = state.stereo.clone() {
+        stereo::start_skew_telemetry(state.clone(), rig);
+    }
+
+    // 16. Setup router (server.bind picks the listeners, IPv4 and/or IPv6;
+    // server.tls serves them over HTTPS)
+    let tls_settings = config.server.tls.clone();
+    let listeners = net::Listeners {
+        addrs: net::parse_bind_addrs(&config.server.bind),
+        secure: tls_settings.is_some(),
+    };
+    // Every route is served under /api/v1, and under /api through the
+    // version 0 shim for dashboards that predate versioning
+    let mut api = Router::new()
+        .merge(health::routes(state.clone()))
+        .merge(camera::routes(state.clone()))
+        .merge(stream::routes(state.clone()))
+        .merge(recorder::routes(state.clone()))
+        .merge(privacy::routes(state.clone()))
+        .merge(quality::routes(state.quality.clone()))
+        .merge(yolo::routes(inference_info))
+        .merge(adaptive::routes(state.clone()))
+        .merge(evidence::routes(state.evidence.clone()))
+        .merge(detections::routes(state.clone()))
+        .merge(crops::routes(state.clone()))
+        .merge(labels::routes(state.clone()))
+        .merge(devices::routes(state.devices.clone()))
+        .merge(visual_servo::routes(state.servo_gains.clone()))
+        .merge(overlay::routes(state.overlay.clone()))
+        .merge(zones::routes(state.zones.clone()))
+        .merge(target::routes(state.clone()))
+        .merge(boundary::routes(state.boundary.clone()))
+        .merge(bumper::routes(state.bumper.clone()))
+        .merge(steering::routes(state.steering.clone()))
+        .merge(units::routes(state.units.clone()))
+        .merge(speed_calibration::routes(state.clone()))
+        .merge(mission::routes(state.mission.clone()))
+        .merge(mission::executor::routes(state.clone()))
+        .merge(run_timer::routes(state.clone()))
+        .merge(drive::routes(state.clone()))
+        .merge(estop::routes(state.clone()))
+        .merge(nudge::routes(state.clone()))
+        .merge(velocity::routes(state.clone()))
+        .merge(logging::routes(log_sinks))
+        .merge(net::routes(listeners.clone()))
+        .merge(viewers::routes(state.viewers.clone()))
+        .merge(session::routes(state.clone()))
+        .merge(settings::routes(state.clone()))
+        .merge(rate::routes(state.clone()))
+        .merge(topology::routes(state.clone()))
+        .merge(power::routes(state.clone()))
+        .merge(charging::routes(state.clone()))
+        .merge(telemetry::routes(state.telemetry.clone()))
+        .merge(schemas::routes())
+        .merge(export::routes(state.sessions.clone()));
+    if let Some(model) = state.model.clone() {
+        api = api.merge(yolo::class_routes(model));
+    }
+    if let Some(gps) = state.gps.clone() {
+        api = api.merge(gps::routes(gps));
+    }
+    if let Some(rig) = state.stereo.clone() {
+        api = api.merge(stereo::routes(rig));
+    }
+    if let Some(compass) = state.compass.clone() {
+        api = api.merge(compass::routes(compass));
+    }
+    if let Some(gimbal) = state.gimbal.clone() {
+        api = api.merge(servo::routes(gimbal));
+    }
+    if let Some(imu) = state.imu.clone() {
+        api = api.merge(imu::routes(imu));
+    }
+    if let Some(range) = state.range.clone() {
+        api = api.merge(range::routes(range));
+    }
+    if let Some(tracker) = state.pose.clone() {
+        api = api.merge(pose::routes(tracker));
+    }
+    if let Some(thermal) = state.thermal.clone() {
+        api = api.merge(thermal::routes(thermal));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(history) = detection_history {
+        api = api.merge(history::routes(history));
+    }
+    #[cfg(feature = "faults")]
+    {
+        println!("[WARN] Fault injection API enabled");
+        api = api.merge(faults::routes());
+    }
+    // Operator requests wake the robot from idle power mode
+    let api = api.layer(middleware::from_fn_with_state(
+        state.clone(),
+        power::wake_on_request,
+    ));
     let app = Router::new()
         .route("/", get(|| async { "Rust Backend Running" }))
+        .nest("/api/v1", api.clone())
+        .nest("/api", api.layer(middleware::from_fn(version::legacy_shim)))
+        .layer(socket_layer)
         .layer(CorsLayer::permissive());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    println!("[INFO] Listening on http://{}", addr);
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    let tls = match &tls_settings {
+        Some(settings) => Some(settings.load().await?),
+        None => None,
+    };
+    let mut servers = Vec::new();
+    for addr in &listeners.addrs {
+        let listener = match net::bind(*addr) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("[ERR] Could not listen on {}: {}", addr, e);
+                continue;
+            }
+        };
+        println!("[INFO] Listening on {}", addr);
+        // Peer addresses feed the admin client list
+        let service = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let server = match &tls {
+            Some(config) => axum_server::from_tcp_rustls(listener, config.clone())
+                .serve(service)
+                .boxed(),
+            None => axum::serve(tokio::net::TcpListener::from_std(listener)?, service)
+                .into_future()
+                .boxed(),
+        };
+        servers.push(server);
+    }
+    if servers.is_empty() {
+        return Err("no listener could be bound".into());
+    }
+    for url in net::reachable_urls(&listeners) {
+        println!(
+            "[INFO] Dashboard reachable at {} ({})",
+            url.url, url.interface
+        );
+    }
+    futures_util::future::try_join_all(servers).await?;
 
     Ok(())
 }
@@ -0,0 +1,608 @@
+//! Mission plans (`POST /mission/plan`): a list of steps (drive a distance,
+//! turn to a heading, wait for a detection, point the gimbal, wait) run in
+//! order as an autonomous mission, one plan at a time. Progress goes out as
+//! `mission_progress`.
+//!
+//! `POST /mission/plan/pause` holds the robot still mid-step until
+//! `.../resume`; `.../abort`, or switching the mission mode away, ends the
+//! plan. Distances come from the pose where the encoders give one and from
+//! the speed model otherwise; turns need the IMU, and the compass for
+//! magnetic headings.
+
+use crate::arbiter::CommandSource;
+use crate::nudge::angle_between;
+use crate::pid::Pid;
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::drive::DriveCommand;
+use raspibot_protocol::mission::{MissionMode, MissionPlan, MissionStep, PlanPhase, PlanProgress};
+use raspibot_protocol::servo::AxisGains;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, watch};
+
+const TICK: Duration = Duration::from_millis(20);
+/// Progress within a step goes out at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// How often a paused plan checks whether its mode is still on.
+const PAUSE_POLL: Duration = Duration::from_millis(250);
+const MAX_DISTANCE_M: f32 = 20.0;
+const MAX_SPEED_MPS: f32 = 1.0;
+const MAX_WAIT_S: f32 = 600.0;
+/// Wheel speed near the end of a move, still clear of the deadband.
+const MIN_SPEED_MPS: f32 = 0.03;
+/// Wheel speed per metre left, for the ramp-down.
+const SLOWDOWN_PER_S: f32 = 2.0;
+const DISTANCE_TOLERANCE_M: f32 = 0.01;
+const HEADING_TOLERANCE_DEG: f32 = 2.0;
+/// A drive may take this many times as long as at its speed throughout,
+/// plus `STEP_GRACE`, before it counts as blocked.
+const DRIVE_TIME_FACTOR: f32 = 3.0;
+const STEP_GRACE: Duration = Duration::from_secs(5);
+const TURN_TIMEOUT: Duration = Duration::from_secs(15);
+/// Heading error (degrees) to wheel speed (m/s) for turns.
+const TURN_GAINS: AxisGains = AxisGains {
+    kp: 0.004,
+    ki: 0.0,
+    kd: 0.0005,
+    deadband: 0.0,
+    max_rate: 0.15,
+};
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    Run,
+    Pause,
+    Abort,
+}
+
+/// Why a plan ended early.
+enum Stop {
+    Aborted(String),
+    Failed(String),
+}
+
+pub struct MissionExecutor {
+    progress: Mutex<Option<PlanProgress>>,
+    /// Counts plans started; each run knows its own, so it only ever ends
+    /// itself.
+    runs: AtomicU64,
+    directive: watch::Sender<Directive>,
+    updates: broadcast::Sender<PlanProgress>,
+}
+
+fn validate(plan: &MissionPlan) -> Result<(), String> {
+    if plan.name.trim().is_empty() {
+        return Err("a mission plan needs a name".to_string());
+    }
+    if plan.steps.is_empty() {
+        return Err("a mission plan needs steps".to_string());
+    }
+    for (i, step) in plan.steps.iter().enumerate() {
+        let valid = match step {
+            MissionStep::Drive {
+                distance_m,
+                speed_mps,
+            } => {
+                distance_m.abs() <= MAX_DISTANCE_M
+                    && *speed_mps > 0.0
+                    && *speed_mps <= MAX_SPEED_MPS
+            }
+            MissionStep::TurnTo { heading_deg, .. } => heading_deg.is_finite(),
+            MissionStep::WaitFor {
+                class,
+                min_confidence,
+                timeout_s,
+            } => {
+                !class.trim().is_empty()
+                    && (0.0..=1.0).contains(min_confidence)
+                    && *timeout_s > 0.0
+                    && *timeout_s <= MAX_WAIT_S
+            }
+            MissionStep::Servo { pan_deg, tilt_deg } => pan_deg.is_finite() && tilt_deg.is_finite(),
+            MissionStep::Wait { seconds } => *seconds > 0.0 && *seconds <= MAX_WAIT_S,
+        };
+        if !valid {
+            return Err(format!("step {} is out of range: {:?}", i, step));
+        }
+    }
+    Ok(())
+}
+
+impl MissionExecutor {
+    pub fn new() -> Self {
+        Self {
+            progress: Mutex::new(None),
+            runs: AtomicU64::new(0),
+            directive: watch::channel(Directive::Run).0,
+            updates: broadcast::channel(32).0,
+        }
+    }
+
+    /// The running plan's progress, or the last one's.
+    pub fn progress(&self) -> Option<PlanProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PlanProgress> {
+        self.updates.subscribe()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut PlanProgress)) {
+        let mut progress = self.progress.lock().unwrap();
+        if let Some(progress) = progress.as_mut() {
+            change(progress);
+            progress.updated_unix_ms = now_ms();
+            let _ = self.updates.send(progress.clone());
+        }
+    }
+
+    /// Publishes how run `run` ended and leaves its mode, unless the
+    /// operator has moved on. Both happen under the progress lock, which
+    /// `start` takes too, so a plan started meanwhile (even one of the same
+    /// name) keeps its mode.
+    fn finish(
+        &self,
+        state: &AppState,
+        run: u64,
+        mode: &MissionMode,
+        reason: &str,
+        change: impl FnOnce(&mut PlanProgress),
+    ) {
+        let mut progress = self.progress.lock().unwrap();
+        if self.runs.load(Ordering::SeqCst) != run {
+            return;
+        }
+        if let Some(progress) = progress.as_mut() {
+            change(progress);
+            progress.updated_unix_ms = now_ms();
+            let _ = self.updates.send(progress.clone());
+        }
+        if state.mission.mode() == *mode {
+            state.mission.set_mode(MissionMode::Idle, reason);
+        }
+    }
+
+    /// Starts `plan` in autonomous mode; fails while another plan runs.
+    pub fn start(&self, state: &AppState, plan: MissionPlan) -> Result<PlanProgress, String> {
+        validate(&plan)?;
+        let mode = MissionMode::Autonomous {
+            mission: plan.name.clone(),
+        };
+        let (progress, run) = {
+            let mut progress = self.progress.lock().unwrap();
+            if progress
+                .as_ref()
+                .is_some_and(|p| matches!(p.phase, PlanPhase::Running | PlanPhase::Paused))
+            {
+                return Err("a mission plan is already running".to_string());
+            }
+            let now = now_ms();
+            let started = PlanProgress {
+                mission: plan.name.clone(),
+                phase: PlanPhase::Running,
+                step: 0,
+                steps: plan.steps.len(),
+                current: None,
+                fraction: None,
+                detail: None,
+                started_unix_ms: now,
+                updated_unix_ms: now,
+            };
+            *progress = Some(started.clone());
+            let run = self.runs.fetch_add(1, Ordering::SeqCst) + 1;
+            self.directive.send_replace(Directive::Run);
+            state.mission.set_mode(mode.clone(), "mission plan");
+            (started, run)
+        };
+        println!(
+            "[INFO] Mission plan '{}' started ({} steps)",
+            plan.name,
+            plan.steps.len()
+        );
+        let _ = self.updates.send(progress.clone());
+        tokio::spawn(execute(
+            state.clone(),
+            plan,
+            run,
+            mode,
+            self.directive.subscribe(),
+        ));
+        Ok(progress)
+    }
+
+    fn direct(&self, directive: Directive) -> Result<PlanProgress, String> {
+        let progress = self
+            .progress()
+            .filter(|p| matches!(p.phase, PlanPhase::Running | PlanPhase::Paused))
+            .ok_or("no mission plan is running".to_string())?;
+        self.directive.send_replace(directive);
+        Ok(progress)
+    }
+
+    pub fn pause(&self) -> Result<PlanProgress, String> {
+        self.direct(Directive::Pause)
+    }
+
+    pub fn resume(&self) -> Result<PlanProgress, String> {
+        self.direct(Directive::Run)
+    }
+
+    pub fn abort(&self) -> Result<PlanProgress, String> {
+        self.direct(Directive::Abort)
+    }
+}
+
+struct Runner {
+    state: AppState,
+    source: CommandSource,
+    mode: MissionMode,
+    directive: watch::Receiver<Directive>,
+    interval: tokio::time::Interval,
+    last_report: Instant,
+}
+
+impl Runner {
+    /// Fails once the plan is aborted or its mode left.
+    fn check(&self) -> Result<(), Stop> {
+        if self.state.mission.mode() != self.mode {
+            return Err(Stop::Aborted("mission mode changed".to_string()));
+        }
+        if *self.directive.borrow() == Directive::Abort {
+            return Err(Stop::Aborted("aborted by the operator".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Waits for the next control step. While paused the robot holds still
+    /// until resumed; returns how long that took, to extend step deadlines.
+    async fn tick(&mut self) -> Result<Duration, Stop> {
+        self.interval.tick().await;
+        self.check()?;
+        if *self.directive.borrow() != Directive::Pause {
+            return Ok(Duration::ZERO);
+        }
+        let since = Instant::now();
+        self.halt();
+        println!("[INFO] Mission plan paused");
+        self.state.executor.update(|p| p.phase = PlanPhase::Paused);
+        while *self.directive.borrow() == Directive::Pause {
+            // Wakes on resume or abort, and now and then to notice a mode
+            // change
+            let _ = tokio::time::timeout(PAUSE_POLL, self.directive.changed()).await;
+            self.check()?;
+        }
+        println!("[INFO] Mission plan resumed");
+        self.state.executor.update(|p| p.phase = PlanPhase::Running);
+        Ok(since.elapsed())
+    }
+
+    fn submit(&self, command: DriveCommand) -> Result<DriveCommand, Stop> {
+        self.state
+            .arbiter
+            .submit(self.source.clone(), command)
+            .map_err(|e| Stop::Failed(e.to_string()))
+    }
+
+    fn halt(&self) {
+        // Refused once the mode changed, when the motors were stopped anyway
+        let _ = self
+            .state
+            .arbiter
+            .submit(self.source.clone(), DriveCommand::default());
+    }
+
+    fn report(&mut self, fraction: f32) {
+        if self.last_report.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_report = Instant::now();
+        let fraction = fraction.clamp(0.0, 1.0);
+        self.state.executor.update(|p| p.fraction = Some(fraction));
+    }
+
+    async fn run(&mut self, steps: &[MissionStep]) -> Result<(), Stop> {
+        for (index, step) in steps.iter().enumerate() {
+            println!(
+                "[INFO] Mission plan step {}/{}: {:?}",
+                index + 1,
+                steps.len(),
+                step
+            );
+            self.state.executor.update(|p| {
+                p.step = index;
+                p.current = Some(step.clone());
+                p.fraction = None;
+                p.detail = None;
+            });
+            match step {
+                MissionStep::Drive {
+                    distance_m,
+                    speed_mps,
+                } => self.drive(*distance_m, *speed_mps).await?,
+                MissionStep::TurnTo {
+                    heading_deg,
+                    magnetic,
+                } => self.turn_to(*heading_deg, *magnetic).await?,
+                MissionStep::WaitFor {
+                    class,
+                    min_confidence,
+                    timeout_s,
+                } => self.wait_for(class, *min_confidence, *timeout_s).await?,
+                MissionStep::Servo { pan_deg, tilt_deg } => {
+                    let gimbal = self
+                        .state
+                        .gimbal
+                        .as_ref()
+                        .ok_or(Stop::Failed("no gimbal".to_string()))?;
+                    gimbal.point(*pan_deg, *tilt_deg);
+                }
+                MissionStep::Wait { seconds } => self.wait(*seconds).await?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn drive(&mut self, distance_m: f32, speed_mps: f32) -> Result<(), Stop> {
+        let pose = self.state.pose.clone();
+        let start = pose.as_ref().map(|p| p.pose().travelled_m);
+        let target = distance_m.abs();
+        let mut deadline = Instant::now()
+            + Duration::from_secs_f32(target / speed_mps * DRIVE_TIME_FACTOR)
+            + STEP_GRACE;
+        let mut sent = DriveCommand::default();
+        let mut done = 0.0f32;
+        let mut last = Instant::now();
+        loop {
+            let paused = self.tick().await?;
+            if !paused.is_zero() {
+                deadline += paused;
+                sent = DriveCommand::default();
+            }
+            let dt = last.elapsed().as_secs_f32();
+            last = Instant::now();
+            let wheels = self
+                .state
+                .units
+                .get()
+                .wheels_at(self.state.power.battery_v());
+            done = match (&pose, start) {
+                (Some(pose), Some(start)) => (pose.pose().travelled_m - start).max(0.0),
+                _ => {
+                    done + ((wheels.speed_for(sent.left) + wheels.speed_for(sent.right)) / 2.0 * dt)
+                        .abs()
+                }
+            };
+            let remaining = target - done;
+            if remaining <= DISTANCE_TOLERANCE_M {
+                self.halt();
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                self.halt();
+                return Err(Stop::Failed(format!(
+                    "drive stopped {:.2} m short, blocked?",
+                    remaining
+                )));
+            }
+            self.report(done / target);
+            let speed =
+                (remaining * SLOWDOWN_PER_S).clamp(MIN_SPEED_MPS, speed_mps.max(MIN_SPEED_MPS));
+            let duty = wheels.duty_for(speed).copysign(distance_m);
+            sent = self.submit(DriveCommand::new(duty, duty))?;
+        }
+    }
+
+    /// Turns to `heading_deg` on the IMU's relative heading, or on its
+    /// tilt-compensated compass heading when `magnetic`.
+    async fn turn_to(&mut self, heading_deg: f32, magnetic: bool) -> Result<(), Stop> {
+        let imu = self.state.imu.clone().ok_or(Stop::Failed(
+            "turning to a heading needs the IMU".to_string(),
+        ))?;
+        let mut pid = Pid::new(TURN_GAINS);
+        let mut deadline = Instant::now() + TURN_TIMEOUT;
+        let mut initial = None;
+        let mut last = Instant::now();
+        loop {
+            deadline += self.tick().await?;
+            let dt = last.elapsed().as_secs_f32();
+            last = Instant::now();
+            let reading = imu
+                .reading()
+                .ok_or(Stop::Failed("no IMU reading".to_string()))?;
+            let now = if magnetic {
+                reading.magnetic_heading_deg.ok_or(Stop::Failed(
+                    "no compass heading, is the compass calibrated?".to_string(),
+                ))?
+            } else {
+                reading.heading_deg
+            };
+            // Positive: the target is clockwise of the robot
+            let error = angle_between(heading_deg, now);
+            if error.abs() <= HEADING_TOLERANCE_DEG {
+                self.halt();
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                self.halt();
+                return Err(Stop::Failed(format!(
+                    "turn stopped {:.1}° short, blocked?",
+                    error
+                )));
+            }
+            let initial = *initial.get_or_insert(error);
+            self.report(1.0 - error.abs() / f32::abs(initial));
+            let wheels = self
+                .state
+                .units
+                .get()
+                .wheels_at(self.state.power.battery_v());
+            let speed = pid.update(error, dt);
+            let duty = wheels
+                .duty_for(speed.abs().max(MIN_SPEED_MPS))
+                .copysign(error);
+            self.submit(DriveCommand::new(duty, -duty))?;
+        }
+    }
+
+    async fn wait_for(
+        &mut self,
+        class: &str,
+        min_confidence: f32,
+        timeout_s: f32,
+    ) -> Result<(), Stop> {
+        let mut detections = self.state.detections.subscribe();
+        let mut deadline = Instant::now() + Duration::from_secs_f32(timeout_s);
+        self.state
+            .executor
+            .update(|p| p.detail = Some(format!("waiting for {}", class)));
+        loop {
+            deadline += self.tick().await?;
+            loop {
+                match detections.try_recv() {
+                    Ok(set) => {
+                        if set
+                            .objects
+                            .iter()
+                            .any(|o| o.class == class && o.confidence >= min_confidence)
+                        {
+                            return Ok(());
+                        }
+                    }
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(Stop::Failed(format!(
+                    "no {} seen in {:.0} s",
+                    class, timeout_s
+                )));
+            }
+        }
+    }
+
+    async fn wait(&mut self, seconds: f32) -> Result<(), Stop> {
+        let total = Duration::from_secs_f32(seconds);
+        let mut deadline = Instant::now() + total;
+        loop {
+            deadline += self.tick().await?;
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(());
+            }
+            self.report(1.0 - left.as_secs_f32() / seconds);
+        }
+    }
+}
+
+async fn execute(
+    state: AppState,
+    plan: MissionPlan,
+    run: u64,
+    mode: MissionMode,
+    directive: watch::Receiver<Directive>,
+) {
+    let mut runner = Runner {
+        state: state.clone(),
+        source: CommandSource::Autonomous(plan.name.clone()),
+        mode: mode.clone(),
+        directive,
+        interval: tokio::time::interval(TICK),
+        last_report: Instant::now(),
+    };
+    let result = runner.run(&plan.steps).await;
+    runner.halt();
+    let (phase, detail) = match result {
+        Ok(()) => (PlanPhase::Completed, None),
+        Err(Stop::Aborted(reason)) => (PlanPhase::Aborted, Some(reason)),
+        Err(Stop::Failed(e)) => (PlanPhase::Failed, Some(e)),
+    };
+    match &detail {
+        Some(detail) => println!(
+            "[WARN] Mission plan '{}' {:?}: {}",
+            plan.name, phase, detail
+        ),
+        None => println!("[OK] Mission plan '{}' completed", plan.name),
+    }
+    let reason = detail
+        .clone()
+        .unwrap_or_else(|| "mission plan completed".to_string());
+    state.executor.finish(&state, run, &mode, &reason, |p| {
+        p.phase = phase;
+        p.detail = detail;
+        p.fraction = None;
+        if phase == PlanPhase::Completed {
+            p.step = p.steps;
+            p.current = None;
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/mission/plan", get(get_progress).post(start_plan))
+        .route("/mission/plan/pause", post(pause))
+        .route("/mission/plan/resume", post(resume))
+        .route("/mission/plan/abort", post(abort))
+        .with_state(state)
+}
+
+async fn get_progress(
+    State(state): State<AppState>,
+) -> Result<Json<PlanProgress>, (StatusCode, String)> {
+    state
+        .executor
+        .progress()
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "no mission plan has run".to_string()))
+}
+
+async fn start_plan(
+    State(state): State<AppState>,
+    Json(plan): Json<MissionPlan>,
+) -> Result<Json<PlanProgress>, (StatusCode, String)> {
+    validate(&plan).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    state
+        .executor
+        .start(&state, plan)
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn pause(State(state): State<AppState>) -> Result<Json<PlanProgress>, (StatusCode, String)> {
+    state
+        .executor
+        .pause()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn resume(State(state): State<AppState>) -> Result<Json<PlanProgress>, (StatusCode, String)> {
+    state
+        .executor
+        .resume()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn abort(State(state): State<AppState>) -> Result<Json<PlanProgress>, (StatusCode, String)> {
+    state
+        .executor
+        .abort()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
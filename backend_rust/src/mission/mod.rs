@@ -0,0 +1,385 @@
+//! Mission state machine: which mode (idle, teleop, an autonomous mission)
+//! runs in the foreground, plus any background missions next to it.
+//!
+//! Only one of them drives at a time: the arbiter is told who owns the drive
+//! (the foreground, unless a background mission outranks it) and rejects
+//! everyone else. Every transition of mode or owner stops the motors, so a
+//! new owner always starts from standstill.
+//!
+//! Behaviors pause at critical steps (`mission.approvals` in the config,
+//! e.g. before grasping) through [`MissionController::approval`]: the motors
+//! stop, the step shows in the mission state, and the behavior waits for
+//! the operator's decision (`POST /mission/approve` or `mission_approve`),
+//! the gate's timeout, or the end of its mission, which aborts it. Behaviors
+//! outside the backend ask through `POST /mission/approval`, which answers
+//! once the step is decided.
+//!
+//! Missions given as a list of steps run in [`executor`].
+
+pub mod executor;
+
+use crate::arbiter::{CommandArbiter, CommandSource};
+use crate::config::ApprovalGateConfig;
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use raspibot_protocol::mission::{
+    ApprovalAction, ApprovalDecision, ApprovalRequest, ApprovalResolution, BackgroundMission,
+    BackgroundRequest, MissionMode, MissionState, ModeRequest, PendingApproval,
+    FOREGROUND_PRIORITY,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, oneshot};
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct MissionController {
+    state: Mutex<MissionState>,
+    arbiter: Arc<CommandArbiter>,
+    changes: broadcast::Sender<MissionState>,
+    gates: Mutex<Vec<ApprovalGateConfig>>,
+    /// Behaviors waiting at a step, by approval id.
+    waiting: Mutex<HashMap<u64, oneshot::Sender<ApprovalAction>>>,
+    next_approval: AtomicU64,
+}
+
+/// Which source the arbiter should let drive: the highest-priority claim,
+/// with the foreground winning ties.
+fn drive_owner(state: &MissionState) -> Option<CommandSource> {
+    let foreground = match &state.mode {
+        MissionMode::Teleop => Some(CommandSource::Teleop),
+        MissionMode::Autonomous { mission } => Some(CommandSource::Autonomous(mission.clone())),
+        MissionMode::Idle | MissionMode::Paused { .. } => None,
+    };
+    // Kept sorted by priority, so the first driving one ranks highest
+    let background = state.background.iter().find(|m| m.drive);
+    match (foreground, background) {
+        (Some(_), Some(bg)) if bg.priority > FOREGROUND_PRIORITY => {
+            Some(CommandSource::Background(bg.name.clone()))
+        }
+        (Some(fg), _) => Some(fg),
+        (None, bg) => bg.map(|m| CommandSource::Background(m.name.clone())),
+    }
+}
+
+impl MissionController {
+    pub fn new(arbiter: Arc<CommandArbiter>) -> Self {
+        let (changes, _) = broadcast::channel(16);
+        Self {
+            state: Mutex::new(MissionState {
+                mode: MissionMode::Idle,
+                since_unix_ms: now_ms(),
+                reason: "startup".to_string(),
+                background: Vec::new(),
+                drive_owner: None,
+                approvals: Vec::new(),
+            }),
+            arbiter,
+            changes,
+            gates: Mutex::new(Vec::new()),
+            waiting: Mutex::new(HashMap::new()),
+            next_approval: AtomicU64::new(1),
+        }
+    }
+
+    /// Takes the approval gates from the startup config.
+    pub fn configure_approvals(&self, gates: Vec<ApprovalGateConfig>) {
+        *self.gates.lock().unwrap() = gates;
+    }
+
+    pub fn state(&self) -> MissionState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn mode(&self) -> MissionMode {
+        self.state.lock().unwrap().mode.clone()
+    }
+
+    /// Nothing running at all: idle foreground and no background missions.
+    pub fn is_idle(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.mode == MissionMode::Idle && state.background.is_empty()
+    }
+
+    /// Switches to `mode`, returning the new state; a no-op if already there.
+    pub fn set_mode(&self, mode: MissionMode, reason: &str) -> MissionState {
+        let mut state = self.state.lock().unwrap();
+        if state.mode == mode {
+            return state.clone();
+        }
+        if let Err(e) = self.arbiter.stop() {
+            eprintln!("[ERR] Could not stop motors on mode change: {}", e);
+        }
+        println!(
+            "[INFO] Mission mode {:?} -> {:?} ({})",
+            state.mode, mode, reason
+        );
+        if let MissionMode::Autonomous { mission } = &state.mode {
+            let mission = mission.clone();
+            self.abort_approvals(&mut state, &mission, reason);
+        }
+        state.mode = mode;
+        state.since_unix_ms = now_ms();
+        state.reason = reason.to_string();
+        self.publish(&mut state);
+        state.clone()
+    }
+
+    /// Starts (or re-prioritizes) a background mission.
+    pub fn start_background(&self, request: BackgroundRequest) -> MissionState {
+        let reason = request.reason.as_deref().unwrap_or("operator request");
+        let mut state = self.state.lock().unwrap();
+        let since_unix_ms = state
+            .background
+            .iter()
+            .find(|m| m.name == request.name)
+            .map_or_else(now_ms, |m| m.since_unix_ms);
+        state.background.retain(|m| m.name != request.name);
+        println!(
+            "[INFO] Background mission '{}' running at priority {} ({})",
+            request.name, request.priority, reason
+        );
+        state.background.push(BackgroundMission {
+            name: request.name,
+            priority: request.priority,
+            drive: request.drive,
+            since_unix_ms,
+        });
+        state
+            .background
+            .sort_by(|a, b| b.priority.cmp(&a.priority).then(a.name.cmp(&b.name)));
+        state.reason = reason.to_string();
+        self.publish(&mut state);
+        state.clone()
+    }
+
+    /// Stops a background mission; `None` if it was not running.
+    pub fn stop_background(&self, name: &str, reason: &str) -> Option<MissionState> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.background.len();
+        state.background.retain(|m| m.name != name);
+        if state.background.len() == before {
+            return None;
+        }
+        println!("[INFO] Background mission '{}' stopped ({})", name, reason);
+        self.abort_approvals(&mut state, name, reason);
+        state.reason = reason.to_string();
+        self.publish(&mut state);
+        Some(state.clone())
+    }
+
+    /// Pauses `mission` at `step` until it is decided, when the step has an
+    /// approval gate; `detail` tells the operator what is about to happen.
+    /// Steps without a gate proceed straight away.
+    pub async fn approval(&self, mission: &str, step: &str, detail: &str) -> ApprovalAction {
+        let Some(gate) = self
+            .gates
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|g| g.step == step)
+            .cloned()
+        else {
+            return ApprovalAction::Proceed;
+        };
+        let id = self.next_approval.fetch_add(1, Ordering::Relaxed);
+        let (decided, mut decision) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            // The robot holds still while the operator looks
+            if let Err(e) = self.arbiter.stop() {
+                eprintln!("[ERR] Could not stop motors for approval: {}", e);
+            }
+            let now = now_ms();
+            state.approvals.push(PendingApproval {
+                id,
+                mission: mission.to_string(),
+                step: step.to_string(),
+                detail: detail.to_string(),
+                requested_unix_ms: now,
+                deadline_unix_ms: now + (gate.timeout_s * 1000.0) as u64,
+                on_timeout: gate.on_timeout,
+            });
+            self.waiting.lock().unwrap().insert(id, decided);
+            println!(
+                "[INFO] Mission '{}' waiting for approval of {} #{}: {}",
+                mission, step, id, detail
+            );
+            self.publish(&mut state);
+        }
+        let timeout = Duration::from_secs_f32(gate.timeout_s);
+        match tokio::time::timeout(timeout, &mut decision).await {
+            Ok(action) => action.unwrap_or(ApprovalAction::Abort),
+            Err(_) => match self.resolve(id, gate.on_timeout, "timeout") {
+                Some(resolution) => resolution.action,
+                // Decided just as the timeout hit
+                None => decision.await.unwrap_or(ApprovalAction::Abort),
+            },
+        }
+    }
+
+    /// Decides pending approval `id`; `None` if nothing waits under it.
+    pub fn resolve(
+        &self,
+        id: u64,
+        action: ApprovalAction,
+        reason: &str,
+    ) -> Option<ApprovalResolution> {
+        let mut state = self.state.lock().unwrap();
+        let index = state.approvals.iter().position(|a| a.id == id)?;
+        let pending = state.approvals.remove(index);
+        let resolution = self.decide(pending, action, reason);
+        self.publish(&mut state);
+        Some(resolution)
+    }
+
+    /// Aborts every step `mission` waits at, as it has ended.
+    fn abort_approvals(&self, state: &mut MissionState, mission: &str, reason: &str) {
+        let (ended, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut state.approvals)
+            .into_iter()
+            .partition(|a| a.mission == mission);
+        state.approvals = pending;
+        for approval in ended {
+            self.decide(approval, ApprovalAction::Abort, reason);
+        }
+    }
+
+    fn decide(
+        &self,
+        pending: PendingApproval,
+        action: ApprovalAction,
+        reason: &str,
+    ) -> ApprovalResolution {
+        println!(
+            "[INFO] {} #{} of mission '{}': {:?} ({})",
+            pending.step, pending.id, pending.mission, action, reason
+        );
+        if let Some(decided) = self.waiting.lock().unwrap().remove(&pending.id) {
+            let _ = decided.send(action);
+        }
+        ApprovalResolution {
+            id: pending.id,
+            mission: pending.mission,
+            step: pending.step,
+            action,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Hands the drive to whoever should own it now and announces the new
+    /// state. A new owner starts from standstill, like a new mode does.
+    fn publish(&self, state: &mut MissionState) {
+        let owner = drive_owner(state);
+        let owner_name = owner.as_ref().map(ToString::to_string);
+        if owner_name != state.drive_owner {
+            if let Err(e) = self.arbiter.stop() {
+                eprintln!("[ERR] Could not stop motors on drive handover: {}", e);
+            }
+            if let Some(name) = &owner_name {
+                println!("[INFO] Drive owned by {}", name);
+            }
+            state.drive_owner = owner_name;
+        }
+        self.arbiter.set_owner(owner);
+        let _ = self.changes.send(state.clone());
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MissionState> {
+        self.changes.subscribe()
+    }
+}
+
+pub fn routes(mission: Arc<MissionController>) -> Router {
+    Router::new()
+        .route("/mission", get(get_mission))
+        .route("/mission/mode", put(set_mode))
+        .route("/mission/background", put(start_background))
+        .route("/mission/background/{name}", delete(stop_background))
+        .route("/mission/approval", post(request_approval))
+        .route("/mission/approve", post(approve))
+        .with_state(mission)
+}
+
+async fn get_mission(State(mission): State<Arc<MissionController>>) -> Json<MissionState> {
+    Json(mission.state())
+}
+
+async fn set_mode(
+    State(mission): State<Arc<MissionController>>,
+    Json(request): Json<ModeRequest>,
+) -> Result<Json<MissionState>, (StatusCode, String)> {
+    if let MissionMode::Autonomous { mission: name } = &request.mode {
+        if name.trim().is_empty() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "autonomous mode needs a mission name".to_string(),
+            ));
+        }
+    }
+    let reason = request.reason.as_deref().unwrap_or("operator request");
+    Ok(Json(mission.set_mode(request.mode, reason)))
+}
+
+async fn start_background(
+    State(mission): State<Arc<MissionController>>,
+    Json(request): Json<BackgroundRequest>,
+) -> Result<Json<MissionState>, (StatusCode, String)> {
+    if request.name.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "background mission needs a name".to_string(),
+        ));
+    }
+    Ok(Json(mission.start_background(request)))
+}
+
+async fn stop_background(
+    State(mission): State<Arc<MissionController>>,
+    UrlPath(name): UrlPath<String>,
+) -> Result<Json<MissionState>, StatusCode> {
+    mission
+        .stop_background(&name, "operator request")
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn request_approval(
+    State(mission): State<Arc<MissionController>>,
+    Json(request): Json<ApprovalRequest>,
+) -> Result<Json<ApprovalAction>, (StatusCode, String)> {
+    if request.mission.trim().is_empty() || request.step.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "approval needs a mission and a step".to_string(),
+        ));
+    }
+    Ok(Json(
+        mission
+            .approval(&request.mission, &request.step, &request.detail)
+            .await,
+    ))
+}
+
+async fn approve(
+    State(mission): State<Arc<MissionController>>,
+    Json(request): Json<ApprovalDecision>,
+) -> Result<Json<ApprovalResolution>, (StatusCode, String)> {
+    mission
+        .resolve(request.id, request.action, "operator")
+        .map(Json)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            format!("no step waits for approval #{}", request.id),
+        ))
+}
@@ -0,0 +1,183 @@
+//! SocketCAN backend for smart motor controllers (brushless-drive chassis).
+//!
+//! The wire format of each controller family lives behind [`CanProtocol`], so
+//! adding another family only means another impl; VESC is the one we run today.
+
+use super::MotorDriver;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use socketcan::{CanFrame, CanSocket, EmbeddedFrame, ExtendedId, Id, Socket};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Status decoded from a controller's periodic broadcast.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ControllerStatus {
+    pub rpm: f32,
+    pub current: f32,
+    pub duty: f32,
+    #[serde(skip)]
+    pub received_at: Instant,
+}
+
+pub trait CanProtocol: Send + Sync {
+    /// Builds the command frame driving `controller_id` at normalized `speed`.
+    fn command(&self, controller_id: u8, speed: f32) -> Option<CanFrame>;
+
+    /// Decodes a status frame, returning the controller it belongs to.
+    fn parse_status(&self, frame: &CanFrame) -> Option<(u8, ControllerStatus)>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum VescMode {
+    /// Normalized speed maps to duty cycle.
+    Duty,
+    /// Normalized speed maps to electrical RPM, scaled by `max_erpm`.
+    Rpm { max_erpm: f32 },
+}
+
+pub struct VescProtocol {
+    pub mode: VescMode,
+}
+
+impl VescProtocol {
+    const SET_DUTY: u32 = 0;
+    const SET_RPM: u32 = 3;
+    const STATUS: u32 = 9;
+
+    fn frame(packet: u32, controller_id: u8, value: i32) -> Option<CanFrame> {
+        let id = ExtendedId::new((packet << 8) | controller_id as u32)?;
+        CanFrame::new(id, &value.to_be_bytes())
+    }
+}
+
+impl CanProtocol for VescProtocol {
+    fn command(&self, controller_id: u8, speed: f32) -> Option<CanFrame> {
+        let speed = speed.clamp(-1.0, 1.0);
+        match self.mode {
+            VescMode::Duty => {
+                Self::frame(Self::SET_DUTY, controller_id, (speed * 100_000.0) as i32)
+            }
+            VescMode::Rpm { max_erpm } => {
+                Self::frame(Self::SET_RPM, controller_id, (speed * max_erpm) as i32)
+            }
+        }
+    }
+
+    fn parse_status(&self, frame: &CanFrame) -> Option<(u8, ControllerStatus)> {
+        let Id::Extended(id) = frame.id() else {
+            return None;
+        };
+        let raw = id.as_raw();
+        if raw >> 8 != Self::STATUS {
+            return None;
+        }
+        let d = frame.data();
+        if d.len() < 8 {
+            return None;
+        }
+        let status = ControllerStatus {
+            rpm: i32::from_be_bytes([d[0], d[1], d[2], d[3]]) as f32,
+            current: i16::from_be_bytes([d[4], d[5]]) as f32 / 10.0,
+            duty: i16::from_be_bytes([d[6], d[7]]) as f32 / 1000.0,
+            received_at: Instant::now(),
+        };
+        Some(((raw & 0xFF) as u8, status))
+    }
+}
+
+pub struct CanMotorConfig {
+    pub interface: String,
+    pub left_id: u8,
+    pub right_id: u8,
+    /// Flip a side when its motor is mounted mirrored.
+    pub invert_left: bool,
+    pub invert_right: bool,
+}
+
+impl Default for CanMotorConfig {
+    fn default() -> Self {
+        Self {
+            interface: "can0".to_string(),
+            left_id: 1,
+            right_id: 2,
+            invert_left: false,
+            invert_right: true,
+        }
+    }
+}
+
+pub struct CanMotorDriver {
+    socket: CanSocket,
+    protocol: Arc<dyn CanProtocol>,
+    config: CanMotorConfig,
+    status: Arc<Mutex<HashMap<u8, ControllerStatus>>>,
+}
+
+impl CanMotorDriver {
+    pub fn open(config: CanMotorConfig, protocol: Arc<dyn CanProtocol>) -> Result<Self> {
+        let socket = CanSocket::open(&config.interface)?;
+        let status = Arc::new(Mutex::new(HashMap::new()));
+
+        // Status frames are read on a second socket so commands never block on reads
+        let reader = CanSocket::open(&config.interface)?;
+        let status_clone = Arc::clone(&status);
+        let reader_protocol = Arc::clone(&protocol);
+        thread::spawn(move || loop {
+            match reader.read_frame() {
+                Ok(frame) => {
+                    if let Some((id, s)) = reader_protocol.parse_status(&frame) {
+                        if let Ok(mut map) = status_clone.lock() {
+                            map.insert(id, s);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("[ERR] CAN read failed: {}", e);
+                    thread::sleep(std::time::Duration::from_millis(100));
+                }
+            }
+        });
+
+        println!("[OK] Opened CAN motor controllers on {}", config.interface);
+        Ok(Self {
+            socket,
+            protocol,
+            config,
+            status,
+        })
+    }
+
+    /// Latest status per controller id, for telemetry.
+    pub fn status(&self) -> HashMap<u8, ControllerStatus> {
+        self.status.lock().map(|m| m.clone()).unwrap_or_default()
+    }
+
+    fn send(&self, controller_id: u8, speed: f32) -> Result<()> {
+        let frame = self
+            .protocol
+            .command(controller_id, speed)
+            .ok_or_else(|| anyhow!("could not build CAN frame for controller {}", controller_id))?;
+        self.socket.write_frame(&frame)?;
+        Ok(())
+    }
+}
+
+impl MotorDriver for CanMotorDriver {
+    fn name(&self) -> &'static str {
+        "can"
+    }
+
+    fn set_speeds(&mut self, left: f32, right: f32) -> Result<()> {
+        let left = if self.config.invert_left { -left } else { left };
+        let right = if self.config.invert_right {
+            -right
+        } else {
+            right
+        };
+        self.send(self.config.left_id, left)?;
+        self.send(self.config.right_id, right)
+    }
+}
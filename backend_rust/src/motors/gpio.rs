@@ -0,0 +1,103 @@
+//! Brushed motors on an H-bridge driver (e.g. DRV8833/L298N in PWM + DIR
+//! mode), driven straight from the Pi's GPIO header: one PWM pin sets each
+//! side's speed, one direction pin its sense.
+
+use super::MotorDriver;
+use anyhow::Result;
+use rppal::gpio::{Gpio, OutputPin};
+
+/// BCM pins of one side.
+#[derive(Debug, Clone, Copy)]
+pub struct SidePins {
+    pub pwm: u8,
+    pub dir: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpioMotorConfig {
+    pub left: SidePins,
+    pub right: SidePins,
+    pub pwm_hz: f64,
+    /// Flip a side whose motor is mounted mirrored.
+    pub invert_left: bool,
+    pub invert_right: bool,
+}
+
+impl Default for GpioMotorConfig {
+    fn default() -> Self {
+        Self {
+            left: SidePins { pwm: 12, dir: 5 },
+            right: SidePins { pwm: 13, dir: 6 },
+            pwm_hz: 1000.0,
+            invert_left: false,
+            invert_right: true,
+        }
+    }
+}
+
+struct Side {
+    pwm: OutputPin,
+    dir: OutputPin,
+    invert: bool,
+}
+
+impl Side {
+    fn open(gpio: &Gpio, pins: SidePins, invert: bool) -> Result<Self> {
+        Ok(Self {
+            pwm: gpio.get(pins.pwm)?.into_output_low(),
+            dir: gpio.get(pins.dir)?.into_output_low(),
+            invert,
+        })
+    }
+
+    fn set(&mut self, speed: f32, pwm_hz: f64) -> Result<()> {
+        let speed = if self.invert { -speed } else { speed };
+        if speed < 0.0 {
+            self.dir.set_high();
+        } else {
+            self.dir.set_low();
+        }
+        let duty = speed.abs().min(1.0) as f64;
+        if duty == 0.0 {
+            self.pwm.clear_pwm()?;
+            self.pwm.set_low();
+        } else {
+            self.pwm.set_pwm_frequency(pwm_hz, duty)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct GpioMotorDriver {
+    left: Side,
+    right: Side,
+    pwm_hz: f64,
+}
+
+impl GpioMotorDriver {
+    pub fn open(config: GpioMotorConfig) -> Result<Self> {
+        let gpio = Gpio::new()?;
+        let left = Side::open(&gpio, config.left, config.invert_left)?;
+        let right = Side::open(&gpio, config.right, config.invert_right)?;
+        println!(
+            "[OK] GPIO motors: left PWM GPIO{} / DIR GPIO{}, right PWM GPIO{} / DIR GPIO{}",
+            config.left.pwm, config.left.dir, config.right.pwm, config.right.dir
+        );
+        Ok(Self {
+            left,
+            right,
+            pwm_hz: config.pwm_hz,
+        })
+    }
+}
+
+impl MotorDriver for GpioMotorDriver {
+    fn name(&self) -> &'static str {
+        "gpio"
+    }
+
+    fn set_speeds(&mut self, left: f32, right: f32) -> Result<()> {
+        self.left.set(left, self.pwm_hz)?;
+        self.right.set(right, self.pwm_hz)
+    }
+}
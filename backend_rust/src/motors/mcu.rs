@@ -0,0 +1,57 @@
+//! Drive motors run by the auxiliary MCU, for chassis whose Arduino drives
+//! the motors and reads the encoders itself. Commands go over the serial
+//! bridge as `DRIVE` packets (see [`crate::serial`]).
+//!
+//! The arbiter sets speeds from its own thread and can't wait on the
+//! bridge, so the newest command is handed to a task that sends it;
+//! commands the bridge had no time for are replaced rather than queued.
+
+use super::MotorDriver;
+use crate::serial::McuBridge;
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The MCU's full-scale speed.
+const FULL_SCALE: f32 = 1000.0;
+
+pub struct McuMotorDriver {
+    commands: watch::Sender<(i16, i16)>,
+}
+
+impl McuMotorDriver {
+    pub fn new(bridge: Arc<McuBridge>) -> Self {
+        let (commands, mut pending) = watch::channel((0, 0));
+        tokio::spawn(async move {
+            let mut failing = false;
+            while pending.changed().await.is_ok() {
+                let (left, right) = *pending.borrow_and_update();
+                match bridge.drive(left, right).await {
+                    Ok(()) if failing => {
+                        println!("[OK] MCU accepting drive commands again");
+                        failing = false;
+                    }
+                    Ok(()) => {}
+                    Err(e) if !failing => {
+                        eprintln!("[ERR] MCU drive command failed: {}", e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+        Self { commands }
+    }
+}
+
+impl MotorDriver for McuMotorDriver {
+    fn name(&self) -> &'static str {
+        "mcu"
+    }
+
+    fn set_speeds(&mut self, left: f32, right: f32) -> Result<()> {
+        let scale = |speed: f32| (speed.clamp(-1.0, 1.0) * FULL_SCALE).round() as i16;
+        self.commands.send_replace((scale(left), scale(right)));
+        Ok(())
+    }
+}
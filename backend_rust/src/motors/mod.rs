@@ -0,0 +1,22 @@
+//! Drive motor abstraction shared by the different chassis variants.
+
+#[cfg(feature = "can")]
+pub mod can;
+pub mod gpio;
+pub mod mcu;
+
+use anyhow::Result;
+
+/// A differential-drive motor backend.
+///
+/// Speeds are normalized to `-1.0..=1.0` per side; each backend maps that
+/// onto its own units (PWM duty, RPM, current, ...).
+pub trait MotorDriver: Send {
+    fn name(&self) -> &'static str;
+
+    fn set_speeds(&mut self, left: f32, right: f32) -> Result<()>;
+
+    fn stop(&mut self) -> Result<()> {
+        self.set_speeds(0.0, 0.0)
+    }
+}
@@ -0,0 +1,114 @@
+//! Listener setup for one or more bind addresses (IPv4 and/or IPv6), and the
+//! list of URLs the dashboard can actually be reached at.
+//!
+//! `server.bind` in the configuration (or `BIND_ADDRS`, comma-separated)
+//! takes a list such as `0.0.0.0:8080,[::]:8080`.
+//! IPv6 listeners are made v6-only so both families can share a port.
+
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::network::{NetworkInfo, ReachableUrl};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, SocketAddr};
+
+pub const DEFAULT_BIND: &str = "0.0.0.0:8080";
+const BACKLOG: i32 = 1024;
+
+/// Parses the configured bind addresses; invalid entries are skipped with a
+/// warning.
+pub fn parse_bind_addrs(values: &[String]) -> Vec<SocketAddr> {
+    let addrs: Vec<SocketAddr> = values
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(addr) => Some(addr),
+            Err(_) => {
+                println!("[WARN] Ignoring invalid bind address '{}'", s);
+                None
+            }
+        })
+        .collect();
+    if addrs.is_empty() {
+        println!("[WARN] No valid bind address, using {}", DEFAULT_BIND);
+        return vec![DEFAULT_BIND.parse().unwrap()];
+    }
+    addrs
+}
+
+/// Configured listeners, and whether they serve HTTPS.
+#[derive(Debug, Clone)]
+pub struct Listeners {
+    pub addrs: Vec<SocketAddr>,
+    pub secure: bool,
+}
+
+/// A non-blocking listening socket, ready for tokio or the TLS acceptor.
+pub fn bind(addr: SocketAddr) -> std::io::Result<std::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    Ok(socket.into())
+}
+
+/// Expands wildcard listeners into one URL per interface address of the
+/// same family. Loopback and IPv6 link-local addresses are left out, since
+/// nobody else can reach them.
+pub fn reachable_urls(listeners: &Listeners) -> Vec<ReachableUrl> {
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    let mut urls = Vec::new();
+    for listener in &listeners.addrs {
+        if !listener.ip().is_unspecified() {
+            urls.push(ReachableUrl {
+                interface: interfaces
+                    .iter()
+                    .find(|i| i.ip() == listener.ip())
+                    .map(|i| i.name.clone())
+                    .unwrap_or_default(),
+                url: url(listener.ip(), listener.port(), listeners.secure),
+            });
+            continue;
+        }
+        for interface in &interfaces {
+            let ip = interface.ip();
+            if interface.is_loopback() || ip.is_ipv6() != listener.is_ipv6() {
+                continue;
+            }
+            if let IpAddr::V6(v6) = ip {
+                if v6.is_unicast_link_local() {
+                    continue;
+                }
+            }
+            urls.push(ReachableUrl {
+                interface: interface.name.clone(),
+                url: url(ip, listener.port(), listeners.secure),
+            });
+        }
+    }
+    urls
+}
+
+fn url(ip: IpAddr, port: u16, secure: bool) -> String {
+    let scheme = if secure { "https" } else { "http" };
+    match ip {
+        IpAddr::V4(v4) => format!("{}://{}:{}", scheme, v4, port),
+        IpAddr::V6(v6) => format!("{}://[{}]:{}", scheme, v6, port),
+    }
+}
+
+pub fn routes(listeners: Listeners) -> Router {
+    Router::new()
+        .route("/network", get(get_network))
+        .with_state(listeners)
+}
+
+async fn get_network(State(listeners): State<Listeners>) -> Json<NetworkInfo> {
+    Json(NetworkInfo {
+        listeners: listeners.addrs.iter().map(|a| a.to_string()).collect(),
+        urls: reachable_urls(&listeners),
+    })
+}
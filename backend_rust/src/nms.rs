@@ -0,0 +1,76 @@
+//! Non-maximum suppression over decoded detections.
+
+use crate::transform::BoxF;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// A decoded detection: box, confidence, class id.
+pub type Detection = (BoxF, f32, i64);
+
+pub fn iou(a: &BoxF, b: &BoxF) -> f32 {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.w).min(b.x + b.w);
+    let y2 = (a.y + a.h).min(b.y + b.h);
+    let inter = (x2 - x1).max(0.0) * (y2 - y1).max(0.0);
+    let union = a.w * a.h + b.w * b.h - inter;
+    if union > 0.0 {
+        inter / union
+    } else {
+        0.0
+    }
+}
+
+/// Class-aware NMS: keeps the highest-scoring box of every same-class group
+/// overlapping by more than `iou_threshold`. Works in place, so the caller's
+/// buffer is reused from frame to frame.
+///
+/// With `parallel`, classes are suppressed independently on the rayon pool;
+/// the result is the same, in the same order, so callers pick it purely on
+/// cost (see `bench-postprocess`).
+pub fn nms(detections: &mut Vec<Detection>, iou_threshold: f32, parallel: bool) {
+    detections.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if parallel {
+        nms_per_class(detections, iou_threshold);
+        return;
+    }
+    let mut kept = 0;
+    for i in 0..detections.len() {
+        let det = detections[i];
+        let suppressed = detections[..kept]
+            .iter()
+            .any(|k| k.2 == det.2 && iou(&k.0, &det.0) > iou_threshold);
+        if !suppressed {
+            detections[kept] = det;
+            kept += 1;
+        }
+    }
+    detections.truncate(kept);
+}
+
+/// Partitions score-sorted `detections` by class, suppresses each class on
+/// its own and merges the survivors back in their original order.
+fn nms_per_class(detections: &mut Vec<Detection>, iou_threshold: f32) {
+    let mut classes: HashMap<i64, Vec<(usize, Detection)>> = HashMap::new();
+    for (i, det) in detections.iter().enumerate() {
+        classes.entry(det.2).or_default().push((i, *det));
+    }
+    let mut kept: Vec<(usize, Detection)> = classes
+        .into_par_iter()
+        .flat_map_iter(|(_, group)| {
+            let mut survivors: Vec<(usize, Detection)> = Vec::with_capacity(group.len());
+            for (i, det) in group {
+                if !survivors
+                    .iter()
+                    .any(|(_, k)| iou(&k.0, &det.0) > iou_threshold)
+                {
+                    survivors.push((i, det));
+                }
+            }
+            survivors
+        })
+        .collect();
+    kept.sort_unstable_by_key(|(i, _)| *i);
+    detections.clear();
+    detections.extend(kept.into_iter().map(|(_, det)| det));
+}
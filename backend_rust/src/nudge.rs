@@ -0,0 +1,257 @@
+//! Fine positioning: `POST /drive/nudge` moves a few centimetres or degrees
+//! and stops, for the operator lining up on a scoring zone and for
+//! alignment routines.
+//!
+//! There are no wheel encoders, so distance is dead-reckoned from what the
+//! arbiter actually sent (after the safety constraints) through the wheel
+//! calibration, at the current battery voltage where the speed curve has
+//! it (see `speed_calibration`). Rotations follow the IMU's heading instead
+//! where there is one, else the compass when it is calibrated, which also
+//! catches wheel slip. Moves are bounded, slow down towards the end, and
+//! give up when a part takes too long, e.g. against an obstacle.
+
+use crate::arbiter::CommandSource;
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use raspibot_protocol::drive::{DriveCommand, NudgeRequest, NudgeResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+pub const MAX_DISTANCE_M: f32 = 0.10;
+pub const MAX_ROTATION_DEG: f32 = 30.0;
+/// Wheel speed for most of a move.
+const SPEED_MPS: f32 = 0.08;
+/// Wheel speed near the end, still clear of the deadband.
+const MIN_SPEED_MPS: f32 = 0.02;
+/// Wheel speed per metre of wheel travel left, for the ramp-down.
+const SLOWDOWN_PER_S: f32 = 2.0;
+const DISTANCE_TOLERANCE_M: f32 = 0.003;
+const ROTATION_TOLERANCE_DEG: f32 = 1.0;
+const TICK: Duration = Duration::from_millis(20);
+const PART_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Held while a nudge runs; nudges don't queue.
+pub struct NudgeLock {
+    active: AtomicBool,
+}
+
+/// Releases the `NudgeLock` however the nudge ends.
+struct ActiveGuard<'a>(&'a AtomicBool);
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl NudgeLock {
+    pub fn new() -> Self {
+        Self {
+            active: AtomicBool::new(false),
+        }
+    }
+
+    /// `None` while another nudge runs.
+    fn try_lock(&self) -> Option<ActiveGuard<'_>> {
+        (!self.active.swap(true, Ordering::AcqRel)).then_some(ActiveGuard(&self.active))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Forward(f32),
+    /// Clockwise degrees.
+    Rotate(f32),
+}
+
+/// `a - b` in degrees, wrapped to -180..180.
+pub fn angle_between(a: f32, b: f32) -> f32 {
+    (a - b + 180.0).rem_euclid(360.0) - 180.0
+}
+
+pub fn validate(request: &NudgeRequest) -> Result<(), String> {
+    let NudgeRequest {
+        forward_m,
+        right_m,
+        rotate_deg,
+    } = *request;
+    if ![forward_m, right_m, rotate_deg]
+        .iter()
+        .all(|v| v.is_finite())
+    {
+        return Err("nudge values must be finite".to_string());
+    }
+    if forward_m.abs() > MAX_DISTANCE_M || right_m.abs() > MAX_DISTANCE_M {
+        return Err(format!(
+            "forward_m and right_m are limited to {} m",
+            MAX_DISTANCE_M
+        ));
+    }
+    if rotate_deg.abs() > MAX_ROTATION_DEG {
+        return Err(format!(
+            "rotate_deg is limited to {} degrees",
+            MAX_ROTATION_DEG
+        ));
+    }
+    Ok(())
+}
+
+/// Drives one part to its target, returning how far it got by the
+/// estimate that stopped it.
+async fn run_part(
+    state: &AppState,
+    source: &CommandSource,
+    part: Part,
+    measured: bool,
+) -> Result<f32, String> {
+    let wheels = state.units.get().wheels_at(state.power.battery_v());
+    let heading = || match &state.imu {
+        Some(imu) => imu.reading().map(|r| r.heading_deg),
+        None => state.compass.as_ref().map(|c| c.reading().heading_deg),
+    };
+    let start_heading = if measured { heading() } else { None };
+    let (target, tolerance, travel_per_unit) = match part {
+        Part::Forward(m) => (m, DISTANCE_TOLERANCE_M, 1.0),
+        Part::Rotate(deg) => (
+            deg,
+            ROTATION_TOLERANCE_DEG,
+            wheels.track_width_m / 2.0 * std::f32::consts::PI / 180.0,
+        ),
+    };
+
+    let started = Instant::now();
+    let mut last = started;
+    let mut sent = DriveCommand::default();
+    let mut done = 0.0f32;
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        let dt = last.elapsed().as_secs_f32();
+        last = Instant::now();
+        match (part, start_heading) {
+            (Part::Forward(_), _) => {
+                done += (wheels.speed_for(sent.left) + wheels.speed_for(sent.right)) / 2.0 * dt;
+            }
+            (Part::Rotate(_), Some(start)) => {
+                if let Some(now) = heading() {
+                    done = angle_between(now, start);
+                }
+            }
+            (Part::Rotate(_), None) => done += wheels.turn_rate_deg(sent.left, sent.right) * dt,
+        }
+
+        let remaining = target - done;
+        if remaining * target.signum() <= tolerance {
+            return Ok(done);
+        }
+        if started.elapsed() >= PART_TIMEOUT {
+            return Err(format!(
+                "{:?} stopped at {:.3} after {:?}, blocked?",
+                part, done, PART_TIMEOUT
+            ));
+        }
+
+        let speed =
+            (remaining.abs() * travel_per_unit * SLOWDOWN_PER_S).clamp(MIN_SPEED_MPS, SPEED_MPS);
+        let duty = wheels.duty_for(speed).copysign(remaining);
+        let command = match part {
+            Part::Forward(_) => DriveCommand::new(duty, duty),
+            Part::Rotate(_) => DriveCommand::new(duty, -duty),
+        };
+        sent = state
+            .arbiter
+            .submit(source.clone(), command)
+            .map_err(|e| e.to_string())?;
+    }
+}
+
+async fn run(
+    state: &AppState,
+    source: &CommandSource,
+    request: NudgeRequest,
+    measured: bool,
+) -> Result<NudgeRequest, String> {
+    let mut moved = NudgeRequest::default();
+    if request.rotate_deg != 0.0 {
+        moved.rotate_deg =
+            run_part(state, source, Part::Rotate(request.rotate_deg), measured).await?;
+    }
+    if request.right_m != 0.0 {
+        let quarter = 90f32.copysign(request.right_m);
+        run_part(state, source, Part::Rotate(quarter), measured).await?;
+        let sideways = run_part(
+            state,
+            source,
+            Part::Forward(request.right_m.abs()),
+            measured,
+        )
+        .await?;
+        moved.right_m = sideways.copysign(request.right_m);
+        run_part(state, source, Part::Rotate(-quarter), measured).await?;
+    }
+    if request.forward_m != 0.0 {
+        moved.forward_m =
+            run_part(state, source, Part::Forward(request.forward_m), measured).await?;
+    }
+    Ok(moved)
+}
+
+/// Runs `request` on behalf of `source` and stops. Fails if another nudge
+/// is running, another source owns the drive, or a part doesn't finish.
+pub async fn nudge(
+    state: &AppState,
+    source: CommandSource,
+    request: NudgeRequest,
+) -> Result<NudgeResult, String> {
+    validate(&request)?;
+    let _active = state
+        .nudge
+        .try_lock()
+        .ok_or("a nudge is already running".to_string())?;
+    let imu = state.imu.as_ref().is_some_and(|i| i.reading().is_some());
+    let compass = !imu
+        && state.compass.as_ref().is_some_and(|c| {
+            let reading = c.reading();
+            reading.calibrated && !reading.calibrating
+        });
+    let started = Instant::now();
+    let result = run(state, &source, request, imu || compass).await;
+    if let Err(e) = state
+        .arbiter
+        .submit(source.clone(), DriveCommand::default())
+    {
+        eprintln!("[ERR] Could not stop after nudge: {}", e);
+    }
+    let moved = result.inspect_err(|e| println!("[WARN] Nudge by {} aborted: {}", source, e))?;
+    println!(
+        "[INFO] Nudge by {}: forward {:.3} m, right {:.3} m, rotate {:.1}°",
+        source, moved.forward_m, moved.right_m, moved.rotate_deg
+    );
+    Ok(NudgeResult {
+        requested: request,
+        moved,
+        compass,
+        imu,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/drive/nudge", post(post_nudge))
+        .with_state(state)
+}
+
+/// Runs detached from the request, so a client hanging up mid-move still
+/// gets the robot stopped where the nudge ends.
+async fn post_nudge(
+    State(state): State<AppState>,
+    Json(request): Json<NudgeRequest>,
+) -> Result<Json<NudgeResult>, (StatusCode, String)> {
+    validate(&request).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    tokio::spawn(async move { nudge(&state, CommandSource::Teleop, request).await })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
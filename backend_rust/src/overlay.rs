@@ -0,0 +1,142 @@
+//! Operator-drawn overlay (planned paths, reticles, notes) pushed from the
+//! dashboard and burned into the annotated stream, so it shows up in
+//! recordings too.
+
+use axum::{extract::State, routing::get, Json, Router};
+use opencv::{
+    core::{Mat, Point, Scalar},
+    imgproc,
+    prelude::*,
+};
+use raspibot_protocol::overlay::{OverlayPrimitive, OverlayShape};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+struct Entry {
+    primitive: OverlayPrimitive,
+    expires: Option<Instant>,
+}
+
+impl Entry {
+    fn new(primitive: OverlayPrimitive) -> Self {
+        let expires = primitive
+            .ttl_s
+            .map(|ttl| Instant::now() + Duration::from_secs_f32(ttl.max(0.0)));
+        Self { primitive, expires }
+    }
+}
+
+pub struct OverlayStore {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl OverlayStore {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn set(&self, primitives: Vec<OverlayPrimitive>) {
+        *self.entries.lock().unwrap() = primitives.into_iter().map(Entry::new).collect();
+    }
+
+    pub fn add(&self, primitive: OverlayPrimitive) {
+        let mut entries = self.entries.lock().unwrap();
+        if primitive.id.is_some() {
+            entries.retain(|e| e.primitive.id != primitive.id);
+        }
+        entries.push(Entry::new(primitive));
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|e| e.primitive.id.as_deref() != Some(id));
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn list(&self) -> Vec<OverlayPrimitive> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.primitive.clone())
+            .collect()
+    }
+
+    /// Draws every live primitive onto `frame`, dropping expired ones.
+    pub fn render(&self, frame: &mut Mat) -> opencv::Result<()> {
+        let (w, h) = (frame.cols() as f32, frame.rows() as f32);
+        let pt = |p: [f32; 2]| Point::new(p[0] as i32, p[1] as i32);
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.expires.is_none_or(|t| t > now));
+        for entry in entries.iter() {
+            let p = &entry.primitive;
+            // OpenCV frames are BGR
+            let color = Scalar::new(p.color[2] as f64, p.color[1] as f64, p.color[0] as f64, 0.0);
+            match p.shape.in_pixels(w, h) {
+                OverlayShape::Line { points, thickness } => {
+                    for pair in points.windows(2) {
+                        imgproc::line(
+                            frame,
+                            pt(pair[0]),
+                            pt(pair[1]),
+                            color,
+                            thickness,
+                            imgproc::LINE_AA,
+                            0,
+                        )?;
+                    }
+                }
+                OverlayShape::Reticle { center, radius } => {
+                    let c = pt(center);
+                    let r = radius as i32;
+                    imgproc::circle(frame, c, r, color, 2, imgproc::LINE_AA, 0)?;
+                    for (dx, dy) in [(1, 0), (0, 1)] {
+                        imgproc::line(
+                            frame,
+                            Point::new(c.x - dx * r * 3 / 2, c.y - dy * r * 3 / 2),
+                            Point::new(c.x + dx * r * 3 / 2, c.y + dy * r * 3 / 2),
+                            color,
+                            1,
+                            imgproc::LINE_AA,
+                            0,
+                        )?;
+                    }
+                }
+                OverlayShape::Text { pos, text, scale } => {
+                    imgproc::put_text(
+                        frame,
+                        &text,
+                        pt(pos),
+                        imgproc::FONT_HERSHEY_SIMPLEX,
+                        scale as f64,
+                        color,
+                        2,
+                        imgproc::LINE_AA,
+                        false,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read-only view for dashboards that join late; edits go over Socket.IO.
+pub fn routes(store: Arc<OverlayStore>) -> Router {
+    Router::new()
+        .route("/overlay", get(list_overlay))
+        .with_state(store)
+}
+
+async fn list_overlay(State(store): State<Arc<OverlayStore>>) -> Json<Vec<OverlayPrimitive>> {
+    Json(store.list())
+}
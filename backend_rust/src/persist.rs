@@ -0,0 +1,109 @@
+//! Runtime state that survives a crash-restart, so systemd bringing the
+//! backend back mid-match does not reset everything to defaults.
+//!
+//! Camera controls, the IR illuminator and the profile are re-applied as they
+//! were. The mission mode is not: a robot that was driving comes back in a
+//! paused version of that mode and waits for the operator to resume it.
+
+use crate::profile::Profile;
+use crate::state::AppState;
+use raspibot_protocol::mission::MissionMode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+pub const STATE_PATH: &str = "data/runtime_state.json";
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RuntimeState {
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub exposure_locked: bool,
+    #[serde(default)]
+    pub low_light: bool,
+    #[serde(default)]
+    pub grayscale: bool,
+    #[serde(default)]
+    pub mission: Option<MissionMode>,
+}
+
+impl RuntimeState {
+    pub fn load() -> Option<Self> {
+        let text = std::fs::read_to_string(STATE_PATH).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(saved) => Some(saved),
+            Err(e) => {
+                println!("[WARN] Ignoring unreadable {}: {}", STATE_PATH, e);
+                None
+            }
+        }
+    }
+
+    pub fn profile(&self) -> Option<Profile> {
+        self.profile.as_deref()?.parse().ok()
+    }
+
+    pub fn capture(state: &AppState) -> Self {
+        let (low_light, grayscale) = state.frames.low_light();
+        Self {
+            profile: Some(state.profile.to_string()),
+            exposure_locked: state.frames.exposure().locked,
+            low_light,
+            grayscale,
+            mission: Some(state.mission.mode()),
+        }
+    }
+
+    /// Written next to the target and renamed over it, so a crash mid-write
+    /// leaves the previous state intact.
+    fn save(&self) -> std::io::Result<()> {
+        let path = Path::new(STATE_PATH);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn restore(&self, state: &AppState) {
+        state.frames.set_exposure_lock(self.exposure_locked);
+        state.frames.set_low_light(self.low_light, self.grayscale);
+        if let Some(ir) = &state.illuminator {
+            ir.set(self.low_light);
+        }
+        let paused = match &self.mission {
+            Some(MissionMode::Teleop) | Some(MissionMode::Autonomous { .. }) => {
+                self.mission.clone().map(|mode| MissionMode::Paused {
+                    resume: Box::new(mode),
+                })
+            }
+            Some(paused @ MissionMode::Paused { .. }) => Some(paused.clone()),
+            Some(MissionMode::Idle) | None => None,
+        };
+        if let Some(mode) = paused {
+            state.mission.set_mode(mode, "restored after restart");
+        }
+        println!("[INFO] Restored runtime state from {}", STATE_PATH);
+    }
+}
+
+/// Saves the runtime state whenever it changes (checked once a second).
+pub fn start_persistence(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        let mut last: Option<RuntimeState> = None;
+        loop {
+            interval.tick().await;
+            let current = RuntimeState::capture(&state);
+            if last.as_ref() == Some(&current) {
+                continue;
+            }
+            match current.save() {
+                Ok(()) => last = Some(current),
+                Err(e) => eprintln!("[ERR] Could not save runtime state: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,52 @@
+//! PID controller with a deadband, an output clamp and integral
+//! anti-windup, the building block of visual servoing and closed-loop
+//! drive.
+
+use raspibot_protocol::servo::AxisGains;
+
+pub struct Pid {
+    gains: AxisGains,
+    integral: f32,
+    last_error: Option<f32>,
+}
+
+impl Pid {
+    pub fn new(gains: AxisGains) -> Self {
+        Self {
+            gains,
+            integral: 0.0,
+            last_error: None,
+        }
+    }
+
+    /// Output for `error`, `dt` seconds after the last update, within
+    /// `±max_rate`.
+    pub fn update(&mut self, error: f32, dt: f32) -> f32 {
+        let g = self.gains;
+        if error.abs() <= g.deadband {
+            // On target: hold still and don't let the integral creep
+            self.integral = 0.0;
+            self.last_error = Some(error);
+            return 0.0;
+        }
+        let derivative = match self.last_error {
+            Some(last) if dt > 0.0 => (error - last) / dt,
+            _ => 0.0,
+        };
+        self.last_error = Some(error);
+
+        if g.ki > 0.0 {
+            // Anti-windup: the integral alone may never exceed the rate limit
+            let limit = g.max_rate / g.ki;
+            self.integral = (self.integral + error * dt).clamp(-limit, limit);
+        }
+        (g.kp * error + g.ki * self.integral + g.kd * derivative).clamp(-g.max_rate, g.max_rate)
+    }
+
+    /// Forgets the integral and the last error, so neither carries over
+    /// after a pause or a change of target.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_error = None;
+    }
+}
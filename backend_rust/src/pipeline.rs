@@ -0,0 +1,62 @@
+//! Pause barrier for the capture/inference pipeline.
+//!
+//! Several settings feed the same frame: privacy masks are painted by the
+//! capture thread, zones and the unit calibration's lens model filter the
+//! detector's output, and the confidence threshold decodes it. Changed one
+//! at a time, a frame can be processed with half of a new profile and half
+//! of the old one. Each stage holds a [`PipelinePass`] while it works on a
+//! frame; [`PipelineBarrier::pause`] waits for the passes in flight, keeps
+//! new ones from starting, and lets the pipeline go once the returned guard
+//! is dropped, so a batch of changes lands between two frames.
+
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
+
+pub struct PipelineBarrier {
+    gate: RwLock<()>,
+}
+
+/// Held by a stage for the duration of one frame.
+pub struct PipelinePass<'a> {
+    _guard: RwLockReadGuard<'a, ()>,
+}
+
+/// Held while settings change; the pipeline resumes when it is dropped.
+pub struct PipelinePause<'a> {
+    _guard: RwLockWriteGuard<'a, ()>,
+    since: Instant,
+}
+
+impl PipelineBarrier {
+    pub fn new() -> Self {
+        Self {
+            gate: RwLock::new(()),
+        }
+    }
+
+    /// Blocks while the pipeline is paused. Stages can hold passes at the
+    /// same time; only a pause excludes them.
+    pub fn pass(&self) -> PipelinePass<'_> {
+        PipelinePass {
+            _guard: self.gate.read().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    /// Blocks until every pass in flight is done, then holds the pipeline
+    /// until the guard is dropped. Don't call it from a stage holding a pass.
+    pub fn pause(&self) -> PipelinePause<'_> {
+        PipelinePause {
+            _guard: self.gate.write().unwrap_or_else(|e| e.into_inner()),
+            since: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PipelinePause<'_> {
+    fn drop(&mut self) {
+        println!(
+            "[INFO] Pipeline resumed after {} ms",
+            self.since.elapsed().as_millis()
+        );
+    }
+}
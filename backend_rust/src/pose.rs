@@ -0,0 +1,314 @@
+//! Dead-reckoned 2D pose of the differential-drive base: wheel travel from
+//! the MCU's encoder counts, turning from the IMU's heading where there is
+//! a current one (wheels slip in turns, the gyro doesn't) and from the
+//! difference between the wheels otherwise. Integrated on every odometry
+//! push, recorded as the `pose` telemetry stream and served at `GET /pose`.
+//!
+//! The pose counts from where it was last reset (`POST /pose/reset`, or
+//! startup) and drifts with distance, so anything that needs it for long
+//! should reset it against a known spot.
+//!
+//! Outdoors, GPS holds the drift in check: the first good fix after a reset
+//! anchors the pose frame to the map, turned by the magnetic heading (or the
+//! course over ground while moving), and every good fix after that pulls x/y
+//! a fraction of the way towards where it puts the robot. Fixes with few
+//! satellites or a poor HDOP are left out, and so is heading.
+
+use crate::gps::GpsManager;
+use crate::serial::{McuBridge, Odometry};
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::gps::GpsFix;
+use raspibot_protocol::pose::{HeadingSource, Pose, PoseResetRequest};
+use raspibot_protocol::units::WheelCalibration;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// IMU readings older than this don't steer the pose.
+const IMU_STALE_MS: u64 = 200;
+/// A fix needs at least this many satellites and at most this HDOP to
+/// correct the pose.
+const GPS_MIN_SATELLITES: u32 = 6;
+const GPS_MAX_HDOP: f64 = 2.0;
+/// Share of the gap to the GPS position closed by each good fix.
+const GPS_WEIGHT: f32 = 0.1;
+/// Course over ground is only trusted for anchoring above this speed.
+const GPS_MIN_COURSE_SPEED_MPS: f64 = 0.5;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// `degrees` in -180..180.
+fn wrap_deg(degrees: f32) -> f32 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Where the pose frame sits on the map.
+#[derive(Debug, Clone, Copy)]
+struct GpsAnchor {
+    lat: f64,
+    lon: f64,
+    x_m: f32,
+    y_m: f32,
+    /// Direction of north in the pose frame, counter-clockwise from `x`.
+    north_deg: f32,
+}
+
+impl GpsAnchor {
+    /// `fix` in the pose frame; flat earth, fine over a competition field.
+    fn to_pose_frame(&self, fix: &GpsFix) -> (f32, f32) {
+        let north = (fix.lat - self.lat).to_radians() * EARTH_RADIUS_M;
+        let east = (fix.lon - self.lon).to_radians() * EARTH_RADIUS_M * self.lat.to_radians().cos();
+        let (sin, cos) = self.north_deg.to_radians().sin_cos();
+        let (north, east) = (north as f32, east as f32);
+        // East is a quarter turn clockwise from north
+        (
+            self.x_m + north * cos + east * sin,
+            self.y_m + north * sin - east * cos,
+        )
+    }
+}
+
+struct Tracked {
+    pose: Pose,
+    /// The counts and clockwise IMU heading integrated last.
+    last: Option<(Odometry, Option<f32>)>,
+    /// Set by the first good fix after a reset.
+    anchor: Option<GpsAnchor>,
+}
+
+pub struct PoseTracker {
+    tracked: Mutex<Tracked>,
+}
+
+impl PoseTracker {
+    pub fn new() -> Self {
+        Self {
+            tracked: Mutex::new(Tracked {
+                pose: Pose {
+                    x_m: 0.0,
+                    y_m: 0.0,
+                    theta_deg: 0.0,
+                    travelled_m: 0.0,
+                    heading_source: HeadingSource::Encoders,
+                    updated_unix_ms: unix_ms(),
+                },
+                last: None,
+                anchor: None,
+            }),
+        }
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.tracked.lock().unwrap().pose
+    }
+
+    /// Puts the robot at `request`, keeping the counts integrated so far as
+    /// the baseline.
+    pub fn reset(&self, request: PoseResetRequest) -> Pose {
+        let mut tracked = self.tracked.lock().unwrap();
+        tracked.pose = Pose {
+            x_m: request.x_m,
+            y_m: request.y_m,
+            theta_deg: wrap_deg(request.theta_deg),
+            travelled_m: 0.0,
+            heading_source: tracked.pose.heading_source,
+            updated_unix_ms: unix_ms(),
+        };
+        tracked.anchor = None;
+        tracked.pose
+    }
+
+    /// Pulls x/y towards a good `fix`; the first one after a reset anchors
+    /// the frame instead, which needs `heading_deg` (clockwise from north)
+    /// to know how the frame is turned.
+    fn correct(&self, fix: &GpsFix, heading_deg: Option<f32>) -> Option<Pose> {
+        if !fix.valid || fix.satellites < GPS_MIN_SATELLITES || fix.hdop > GPS_MAX_HDOP {
+            return None;
+        }
+        let mut tracked = self.tracked.lock().unwrap();
+        let Some(anchor) = tracked.anchor else {
+            // The robot faces `heading_deg` clockwise of north, so north is
+            // that far counter-clockwise of theta
+            let anchor = GpsAnchor {
+                lat: fix.lat,
+                lon: fix.lon,
+                x_m: tracked.pose.x_m,
+                y_m: tracked.pose.y_m,
+                north_deg: tracked.pose.theta_deg + heading_deg?,
+            };
+            println!(
+                "[INFO] Pose anchored to GPS at {:.6}, {:.6}",
+                fix.lat, fix.lon
+            );
+            tracked.anchor = Some(anchor);
+            return None;
+        };
+        let (x, y) = anchor.to_pose_frame(fix);
+        let pose = &mut tracked.pose;
+        pose.x_m += GPS_WEIGHT * (x - pose.x_m);
+        pose.y_m += GPS_WEIGHT * (y - pose.y_m);
+        pose.updated_unix_ms = unix_ms();
+        Some(*pose)
+    }
+
+    /// Moves the pose by the wheel travel since the last push. The first
+    /// push, and one after the MCU restarted, only sets the baseline.
+    fn update(
+        &self,
+        counts: Odometry,
+        imu_heading: Option<f32>,
+        wheels: &WheelCalibration,
+    ) -> Pose {
+        let mut tracked = self.tracked.lock().unwrap();
+        let Some((previous, previous_heading)) = tracked.last.replace((counts, imu_heading)) else {
+            return tracked.pose;
+        };
+        if counts.mcu_ms < previous.mcu_ms {
+            println!("[WARN] MCU restarted, pose continues from its new counts");
+            return tracked.pose;
+        }
+        // The counters wrap, so a push after a wrap is still a small step
+        let left = counts.left_ticks.wrapping_sub(previous.left_ticks) as f32 / wheels.ticks_per_m;
+        let right =
+            counts.right_ticks.wrapping_sub(previous.right_ticks) as f32 / wheels.ticks_per_m;
+        let distance = (left + right) / 2.0;
+        let (turn_deg, source) = match (previous_heading, imu_heading) {
+            // The IMU's heading counts clockwise, theta counter-clockwise
+            (Some(from), Some(to)) => (-wrap_deg(to - from), HeadingSource::Imu),
+            _ => (
+                ((right - left) / wheels.track_width_m).to_degrees(),
+                HeadingSource::Encoders,
+            ),
+        };
+        let pose = &mut tracked.pose;
+        // Along the heading halfway through the step, which is exact for arcs
+        let heading = (pose.theta_deg + turn_deg / 2.0).to_radians();
+        pose.x_m += distance * heading.cos();
+        pose.y_m += distance * heading.sin();
+        pose.theta_deg = wrap_deg(pose.theta_deg + turn_deg);
+        pose.travelled_m += distance.abs();
+        pose.heading_source = source;
+        pose.updated_unix_ms = unix_ms();
+        *pose
+    }
+}
+
+/// Integrates every odometry push from the MCU and records the result as
+/// the `pose` stream.
+pub fn start_pose_tracking(state: AppState, tracker: Arc<PoseTracker>, mcu: &McuBridge) {
+    let mut odometry = mcu.odometry();
+    tokio::spawn(async move {
+        let mut travelled_m = tracker.pose().travelled_m;
+        loop {
+            let counts = match odometry.recv().await {
+                Ok(counts) => counts,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let imu_heading = state
+                .imu
+                .as_ref()
+                .and_then(|imu| imu.reading())
+                .filter(|r| unix_ms().saturating_sub(r.taken_unix_ms) < IMU_STALE_MS)
+                .map(|r| r.heading_deg);
+            let pose = tracker.update(counts, imu_heading, &state.units.get().wheels);
+            // A reset starts the count over from zero
+            let delta_m = if pose.travelled_m >= travelled_m {
+                pose.travelled_m - travelled_m
+            } else {
+                pose.travelled_m
+            };
+            travelled_m = pose.travelled_m;
+            state.sessions.record_distance(delta_m);
+            let mut values = Map::new();
+            values.insert("x_m".to_string(), Value::from(pose.x_m));
+            values.insert("y_m".to_string(), Value::from(pose.y_m));
+            values.insert("theta_deg".to_string(), Value::from(pose.theta_deg));
+            values.insert("travelled_m".to_string(), Value::from(pose.travelled_m));
+            state.record_telemetry("pose", values);
+        }
+    });
+}
+
+/// Blends every new GPS fix into the pose.
+pub fn start_gps_fusion(state: AppState, tracker: Arc<PoseTracker>, gps: &GpsManager) {
+    let mut fixes = gps.fixes();
+    tokio::spawn(async move {
+        loop {
+            let fix = match fixes.recv().await {
+                Ok(fix) => fix,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let heading = magnetic_heading(&state).or_else(|| {
+                fix.heading_deg
+                    .filter(|_| fix.speed_mps > GPS_MIN_COURSE_SPEED_MPS)
+                    .map(|course| course as f32)
+            });
+            if let Some(pose) = tracker.correct(&fix, heading) {
+                let mut values = Map::new();
+                values.insert("x_m".to_string(), Value::from(pose.x_m));
+                values.insert("y_m".to_string(), Value::from(pose.y_m));
+                values.insert("gps_hdop".to_string(), Value::from(fix.hdop));
+                state.record_telemetry("pose", values);
+            }
+        }
+    });
+}
+
+/// Clockwise from magnetic north: the IMU's tilt-compensated one, else a
+/// calibrated compass's.
+fn magnetic_heading(state: &AppState) -> Option<f32> {
+    let from_imu = state
+        .imu
+        .as_ref()
+        .and_then(|imu| imu.reading())
+        .filter(|r| unix_ms().saturating_sub(r.taken_unix_ms) < IMU_STALE_MS)
+        .and_then(|r| r.magnetic_heading_deg);
+    from_imu.or_else(|| {
+        let reading = state.compass.as_ref()?.reading();
+        reading.calibrated.then_some(reading.heading_deg)
+    })
+}
+
+pub fn routes(tracker: Arc<PoseTracker>) -> Router {
+    Router::new()
+        .route("/pose", get(get_pose))
+        .route("/pose/reset", post(reset_pose))
+        .with_state(tracker)
+}
+
+async fn get_pose(State(tracker): State<Arc<PoseTracker>>) -> Json<Pose> {
+    Json(tracker.pose())
+}
+
+async fn reset_pose(
+    State(tracker): State<Arc<PoseTracker>>,
+    Json(request): Json<PoseResetRequest>,
+) -> Result<Json<Pose>, (StatusCode, String)> {
+    if ![request.x_m, request.y_m, request.theta_deg]
+        .iter()
+        .all(|v| v.is_finite())
+    {
+        return Err((StatusCode::BAD_REQUEST, "pose must be finite".to_string()));
+    }
+    let pose = tracker.reset(request);
+    println!(
+        "[INFO] Pose reset to ({:.2}, {:.2}) m at {:.1}°",
+        pose.x_m, pose.y_m, pose.theta_deg
+    );
+    Ok(Json(pose))
+}
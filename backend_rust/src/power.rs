@@ -0,0 +1,251 @@
+//! Idle power management for the wait between matches: the camera is
+//! released, the detector unloaded and the CPU put on the `powersave`
+//! governor. Any operator activity (a mutating API request, gamepad input
+//! over Socket.IO) wakes everything back up.
+//!
+//! With `power.idle_after_s` set, the robot also idles by itself after that
+//! long without activity, as long as no mission (foreground or background)
+//! is running and no run is being recorded.
+
+use crate::state::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use raspibot_protocol::power::{PowerRequest, PowerStatus};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+const CPUFREQ_DIR: &str = "/sys/devices/system/cpu/cpufreq";
+const IDLE_GOVERNOR: &str = "powersave";
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+struct Idle {
+    since_unix_ms: u64,
+    /// Governor each cpufreq policy had before, restored on wake.
+    governors: Vec<(PathBuf, String)>,
+}
+
+pub struct PowerManager {
+    idle_after: Option<Duration>,
+    last_activity: Mutex<Instant>,
+    idle: Mutex<Option<Idle>>,
+    reason: Mutex<String>,
+    changes: broadcast::Sender<PowerStatus>,
+    battery_v: Mutex<Option<f32>>,
+}
+
+impl PowerManager {
+    /// Idles by itself after `idle_after` without activity, when set.
+    pub fn new(idle_after: Option<Duration>) -> Self {
+        let (changes, _) = broadcast::channel(8);
+        Self {
+            idle_after,
+            last_activity: Mutex::new(Instant::now()),
+            idle: Mutex::new(None),
+            reason: Mutex::new("startup".to_string()),
+            changes,
+            battery_v: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> PowerStatus {
+        let idle = self.idle.lock().unwrap();
+        PowerStatus {
+            idle: idle.is_some(),
+            idle_since_unix_ms: idle.as_ref().map(|i| i.since_unix_ms),
+            reason: self.reason.lock().unwrap().clone(),
+            idle_after_s: self.idle_after.map(|d| d.as_secs_f32()),
+            last_activity_age_ms: self.last_activity.lock().unwrap().elapsed().as_millis() as u64,
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle.lock().unwrap().is_some()
+    }
+
+    /// Latest battery voltage reported by the MCU.
+    pub fn battery_v(&self) -> Option<f32> {
+        *self.battery_v.lock().unwrap()
+    }
+
+    pub fn record_battery(&self, volts: f32) {
+        *self.battery_v.lock().unwrap() = Some(volts);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PowerStatus> {
+        self.changes.subscribe()
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn inactive_for(&self, limit: Duration) -> bool {
+        self.last_activity.lock().unwrap().elapsed() >= limit
+    }
+
+    fn announce(&self, reason: &str) {
+        *self.reason.lock().unwrap() = reason.to_string();
+        let _ = self.changes.send(self.status());
+    }
+}
+
+/// Every cpufreq policy's governor file and its current value.
+fn governors() -> Vec<(PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(CPUFREQ_DIR) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("policy"))
+        .filter_map(|e| {
+            let path = e.path().join("scaling_governor");
+            let current = std::fs::read_to_string(&path).ok()?;
+            Some((path, current.trim().to_string()))
+        })
+        .collect()
+}
+
+fn write_governor(path: &Path, governor: &str) {
+    if let Err(e) = std::fs::write(path, governor) {
+        println!(
+            "[WARN] Could not set CPU governor in {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Puts the robot to sleep; a no-op if it already is. Only allowed while no
+/// mission owns the robot.
+pub fn sleep(state: &AppState, reason: &str) -> Result<(), String> {
+    if !state.mission.is_idle() {
+        return Err("a mission is active".to_string());
+    }
+    let mut idle = state.power.idle.lock().unwrap();
+    if idle.is_some() {
+        return Ok(());
+    }
+    for camera in state.cameras.iter() {
+        camera.frames.set_suspended(true);
+    }
+    if let Some(model) = &state.model {
+        model.unload();
+    }
+    let saved = governors();
+    for (path, _) in &saved {
+        write_governor(path, IDLE_GOVERNOR);
+    }
+    *idle = Some(Idle {
+        since_unix_ms: now_ms(),
+        governors: saved,
+    });
+    drop(idle);
+    println!("[INFO] Entering idle power mode ({})", reason);
+    state.power.announce(reason);
+    Ok(())
+}
+
+/// Restores everything [`sleep`] turned off; a no-op when awake.
+pub fn wake(state: &AppState, reason: &str) {
+    state.power.touch();
+    let Some(idle) = state.power.idle.lock().unwrap().take() else {
+        return;
+    };
+    for (path, governor) in &idle.governors {
+        write_governor(path, governor);
+    }
+    for camera in state.cameras.iter() {
+        camera.frames.set_suspended(false);
+    }
+    if let Some(model) = state.model.clone() {
+        // Rebuilding the session takes a while; don't hold up the request
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = model.reload() {
+                eprintln!("[ERR] Could not reload YOLO model on wake: {}", e);
+            }
+        });
+    }
+    println!("[INFO] Leaving idle power mode ({})", reason);
+    state.power.announce(reason);
+}
+
+/// Operator activity: resets the inactivity timer and wakes the robot.
+pub fn activity(state: &AppState, reason: &str) {
+    if state.power.is_idle() {
+        wake(state, reason);
+    } else {
+        state.power.touch();
+    }
+}
+
+/// Counts mutating API requests as activity. Reads are not: a dashboard
+/// left open polls, and that alone shouldn't keep the robot awake.
+pub async fn wake_on_request(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        activity(&state, "api request");
+    }
+    next.run(request).await
+}
+
+/// Idles the robot after `power.idle_after_s` without activity.
+pub fn start_power_monitor(state: AppState) {
+    let Some(idle_after) = state.power.idle_after else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.power.is_idle()
+                || !state.power.inactive_for(idle_after)
+                || !state.mission.is_idle()
+                || state.sessions.active_id().is_some()
+            {
+                continue;
+            }
+            let _ = sleep(&state, "inactivity");
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/power", get(get_status).put(set_power))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<PowerStatus> {
+    Json(state.power.status())
+}
+
+async fn set_power(
+    State(state): State<AppState>,
+    Json(request): Json<PowerRequest>,
+) -> Result<Json<PowerStatus>, (StatusCode, String)> {
+    // The request itself already woke the robot (see `wake_on_request`)
+    if request.idle {
+        sleep(&state, "operator request").map_err(|e| (StatusCode::CONFLICT, e))?;
+    } else {
+        wake(&state, "operator request");
+    }
+    Ok(Json(state.power.status()))
+}
@@ -0,0 +1,95 @@
+//! Operator presence from dashboard heartbeats. If teleop is active and no
+//! dashboard has sent a heartbeat for `presence.timeout_s` (default 5 s),
+//! e.g. because the laptop's WiFi dropped, the robot goes idle and stops.
+
+use crate::faults;
+use crate::state::AppState;
+use raspibot_protocol::events;
+use raspibot_protocol::mission::MissionMode;
+use raspibot_protocol::presence::PresenceStatus;
+use socketioxide::SocketIo;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Presence {
+    timeout: Duration,
+    /// Last heartbeat per Socket.IO client id.
+    beats: Mutex<HashMap<String, Instant>>,
+    /// Survives disconnects, so "none for N seconds" counts from the last beat.
+    last_beat: Mutex<Option<Instant>>,
+}
+
+impl Presence {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            beats: Mutex::new(HashMap::new()),
+            last_beat: Mutex::new(None),
+        }
+    }
+
+    pub fn beat(&self, client: &str) {
+        let now = Instant::now();
+        self.beats.lock().unwrap().insert(client.to_string(), now);
+        *self.last_beat.lock().unwrap() = Some(now);
+    }
+
+    pub fn leave(&self, client: &str) {
+        self.beats.lock().unwrap().remove(client);
+    }
+
+    pub fn status(&self) -> PresenceStatus {
+        let mut beats = self.beats.lock().unwrap();
+        beats.retain(|_, at| at.elapsed() < self.timeout);
+        PresenceStatus {
+            operators: beats.len(),
+            last_heartbeat_age_ms: self
+                .last_beat
+                .lock()
+                .unwrap()
+                .map(|at| at.elapsed().as_millis() as u64),
+            timeout_ms: self.timeout.as_millis() as u64,
+        }
+    }
+
+    fn expired(&self) -> bool {
+        self.last_beat
+            .lock()
+            .unwrap()
+            .is_none_or(|at| at.elapsed() >= self.timeout)
+    }
+}
+
+/// Publishes presence once a second (Socket.IO and the run's blackbox) and
+/// auto-idles teleop when no operator is left.
+pub fn start_presence_monitor(state: AppState, io: SocketIo) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if faults::killed("presence") {
+                return;
+            }
+            let status = state.presence.status();
+
+            if state.mission.mode() == MissionMode::Teleop && state.presence.expired() {
+                println!("[WARN] No operator heartbeat, leaving teleop");
+                state
+                    .mission
+                    .set_mode(MissionMode::Idle, "no operator present");
+            }
+
+            let mut values = serde_json::Map::new();
+            values.insert("operators".into(), status.operators.into());
+            values.insert(
+                "last_heartbeat_age_ms".into(),
+                status.last_heartbeat_age_ms.into(),
+            );
+            state.record_telemetry("presence", values);
+            let _ = io.emit(events::PRESENCE, &status).await;
+        }
+    });
+}
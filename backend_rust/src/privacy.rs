@@ -0,0 +1,261 @@
+//! Face blurring for published output (streams, recordings), and static
+//! privacy masks.
+//!
+//! Only frames leaving the robot go through face blur; detection keeps
+//! consuming the raw frames, so blurring never costs recall. Masks are the
+//! opposite: they are painted black in the capture thread, so masked areas
+//! produce no detections and never reach a stream or recording. They are
+//! persisted in `data/privacy_masks.json`.
+
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use opencv::{
+    core::{Mat, Point, Rect, Scalar, Size, Vector},
+    imgproc, objdetect,
+    prelude::*,
+};
+use raspibot_protocol::privacy::{FaceBlurRequest, FaceBlurStatus, PrivacyMask};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+pub const DEFAULT_CASCADE: &str =
+    "/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml";
+/// Faces are searched on a downscaled gray copy to keep the stage cheap.
+const DETECT_SCALE: f64 = 0.5;
+pub const MASKS_PATH: &str = "data/privacy_masks.json";
+
+pub struct FaceBlur {
+    detector: Option<Mutex<objdetect::CascadeClassifier>>,
+    enabled: AtomicBool,
+}
+
+impl FaceBlur {
+    /// Loads the Haar cascade at `path`. Without it the filter stays
+    /// unavailable rather than failing startup.
+    pub fn load(path: &str) -> Self {
+        let detector = match objdetect::CascadeClassifier::new(path) {
+            Ok(c) if !c.empty().unwrap_or(true) => {
+                println!("[OK] Loaded face detector from {}", path);
+                Some(Mutex::new(c))
+            }
+            _ => {
+                println!(
+                    "[WARN] Face detector not found at {}, face blur unavailable",
+                    path
+                );
+                None
+            }
+        };
+        Self {
+            detector,
+            enabled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn status(&self) -> FaceBlurStatus {
+        FaceBlurStatus {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            available: self.detector.is_some(),
+        }
+    }
+
+    /// Returns false if enabling was requested but no detector is loaded.
+    pub fn set_enabled(&self, enabled: bool) -> bool {
+        if enabled && self.detector.is_none() {
+            return false;
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+        true
+    }
+
+    /// Copy of `frame` with faces blurred, or a plain copy when disabled.
+    pub fn process(&self, frame: &Mat) -> Mat {
+        let mut out = frame.clone();
+        if !self.enabled.load(Ordering::Relaxed) {
+            return out;
+        }
+        if let Err(e) = self.blur_faces(&mut out) {
+            eprintln!("[ERR] Face blur failed: {}", e);
+        }
+        out
+    }
+
+    fn blur_faces(&self, frame: &mut Mat) -> opencv::Result<()> {
+        let Some(detector) = &self.detector else {
+            return Ok(());
+        };
+        let mut gray = Mat::default();
+        imgproc::cvt_color_def(&*frame, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+        let mut small = Mat::default();
+        imgproc::resize(
+            &gray,
+            &mut small,
+            Size::new(0, 0),
+            DETECT_SCALE,
+            DETECT_SCALE,
+            imgproc::INTER_AREA,
+        )?;
+
+        let mut faces = Vector::<Rect>::new();
+        detector.lock().unwrap().detect_multi_scale(
+            &small,
+            &mut faces,
+            1.1,
+            4,
+            0,
+            Size::new(24, 24),
+            Size::new(0, 0),
+        )?;
+
+        let bounds = Rect::new(0, 0, frame.cols(), frame.rows());
+        for face in faces {
+            // Back to full resolution, padded a little so hairlines are covered too
+            let pad = (face.width as f64 * 0.15) as i32;
+            let rect = Rect::new(
+                ((face.x - pad) as f64 / DETECT_SCALE) as i32,
+                ((face.y - pad) as f64 / DETECT_SCALE) as i32,
+                ((face.width + 2 * pad) as f64 / DETECT_SCALE) as i32,
+                ((face.height + 2 * pad) as f64 / DETECT_SCALE) as i32,
+            ) & bounds;
+            if rect.width <= 0 || rect.height <= 0 {
+                continue;
+            }
+            let region = Mat::roi(frame, rect)?.try_clone()?;
+            let mut blurred = Mat::default();
+            let k = (rect.width / 3) | 1;
+            imgproc::gaussian_blur_def(&region, &mut blurred, Size::new(k, k), 0.0)?;
+            let mut target = Mat::roi_mut(frame, rect)?;
+            blurred.copy_to(&mut *target)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct MaskStore {
+    path: PathBuf,
+    masks: Mutex<Vec<PrivacyMask>>,
+}
+
+impl MaskStore {
+    pub fn load() -> Self {
+        Self::load_from(PathBuf::from(MASKS_PATH))
+    }
+
+    /// Masks of a camera other than the primary one, in
+    /// `data/privacy_masks.<id>.json`.
+    pub fn for_camera(id: &str) -> Self {
+        Self::load_from(Path::new(MASKS_PATH).with_extension(format!("{}.json", id)))
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let masks = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            masks: Mutex::new(masks),
+        }
+    }
+
+    pub fn get(&self) -> Vec<PrivacyMask> {
+        self.masks.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, masks: Vec<PrivacyMask>) -> std::io::Result<()> {
+        let mut current = self.masks.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&masks)?)?;
+        *current = masks;
+        Ok(())
+    }
+
+    /// Paints every mask black, in place.
+    pub fn apply(&self, frame: &mut Mat) -> opencv::Result<()> {
+        let masks = self.masks.lock().unwrap();
+        if masks.is_empty() {
+            return Ok(());
+        }
+        let (w, h) = (frame.cols() as f32, frame.rows() as f32);
+        let polygons: Vector<Vector<Point>> = masks
+            .iter()
+            .map(|mask| {
+                mask.polygon
+                    .iter()
+                    .map(|[x, y]| Point::new((x * w).round() as i32, (y * h).round() as i32))
+                    .collect()
+            })
+            .collect();
+        imgproc::fill_poly(
+            frame,
+            &polygons,
+            Scalar::all(0.0),
+            imgproc::LINE_8,
+            0,
+            Point::new(0, 0),
+        )
+    }
+}
+
+pub fn validate_mask(mask: &PrivacyMask) -> Result<(), String> {
+    if mask.polygon.len() < 3 {
+        return Err(format!("mask '{}' needs at least 3 points", mask.name));
+    }
+    if mask
+        .polygon
+        .iter()
+        .flatten()
+        .any(|v| !(0.0..=1.0).contains(v))
+    {
+        return Err(format!("mask '{}' points must be within 0..=1", mask.name));
+    }
+    Ok(())
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/privacy/face-blur", get(get_face_blur).put(set_face_blur))
+        .route("/privacy/masks", get(get_masks).put(set_masks))
+        .with_state(state)
+}
+
+async fn get_face_blur(State(state): State<AppState>) -> Json<FaceBlurStatus> {
+    Json(state.face_blur.status())
+}
+
+async fn set_face_blur(
+    State(state): State<AppState>,
+    Json(req): Json<FaceBlurRequest>,
+) -> Result<Json<FaceBlurStatus>, StatusCode> {
+    if !state.face_blur.set_enabled(req.enabled) {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    println!(
+        "[INFO] Face blur {}",
+        if req.enabled { "enabled" } else { "disabled" }
+    );
+    Ok(Json(state.face_blur.status()))
+}
+
+async fn get_masks(State(state): State<AppState>) -> Json<Vec<PrivacyMask>> {
+    Json(state.frames.masks().get())
+}
+
+async fn set_masks(
+    State(state): State<AppState>,
+    Json(masks): Json<Vec<PrivacyMask>>,
+) -> Result<Json<Vec<PrivacyMask>>, (StatusCode, String)> {
+    for mask in &masks {
+        validate_mask(mask).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    state
+        .frames
+        .masks()
+        .set(masks.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("[INFO] Privacy masks updated ({} masks)", masks.len());
+    Ok(Json(masks))
+}
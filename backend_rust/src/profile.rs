@@ -0,0 +1,95 @@
+//! Build/runtime profiles: "dev" on the bench, "competition" at the venue.
+//!
+//! Debug builds default to `dev` and release builds to `competition`; either
+//! can be overridden with `--profile <name>`, anywhere on the command line,
+//! subcommands included. Without the flag, a restart keeps the profile the
+//! previous run used (see `persist`).
+
+use raspibot_protocol::health::ProfileSettings;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Dev,
+    Competition,
+}
+
+impl Profile {
+    pub fn build_default() -> Self {
+        if cfg!(debug_assertions) {
+            Profile::Dev
+        } else {
+            Profile::Competition
+        }
+    }
+
+    /// Takes `--profile <name>` / `--profile=<name>` out of `args`,
+    /// returning the profile (`None` when none was given) and the other
+    /// arguments in order, for the subcommand.
+    pub fn from_args(
+        args: impl IntoIterator<Item = String>,
+    ) -> Result<(Option<Self>, Vec<String>), String> {
+        let mut profile = None;
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let value = if let Some(v) = arg.strip_prefix("--profile=") {
+                v.to_string()
+            } else if arg == "--profile" {
+                args.next()
+                    .ok_or_else(|| "--profile needs a value".to_string())?
+            } else {
+                rest.push(arg);
+                continue;
+            };
+            profile = Some(value.parse()?);
+        }
+        Ok((profile, rest))
+    }
+
+    pub fn settings(self) -> ProfileSettings {
+        match self {
+            Profile::Dev => ProfileSettings {
+                log_level: "debug".to_string(),
+                max_drive_speed: 0.5,
+                command_timeout_ms: 1000,
+                stream_jpeg_quality: 60,
+                stream_max_fps: 15,
+                allow_simulation: true,
+            },
+            Profile::Competition => ProfileSettings {
+                log_level: "info".to_string(),
+                max_drive_speed: 1.0,
+                command_timeout_ms: 300,
+                stream_jpeg_quality: 80,
+                stream_max_fps: 30,
+                allow_simulation: false,
+            },
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dev" => Ok(Profile::Dev),
+            "competition" | "comp" => Ok(Profile::Competition),
+            other => Err(format!(
+                "unknown profile '{}' (expected dev or competition)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::Dev => "dev",
+            Profile::Competition => "competition",
+        })
+    }
+}
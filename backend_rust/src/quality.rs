@@ -0,0 +1,317 @@
+//! Image quality monitoring.
+//!
+//! A degrading feed (smudged lens, fog, a sensor left in the dark) otherwise
+//! just looks like the model getting worse. A couple of frames a second are
+//! measured: sharpness as the variance of the Laplacian, and the mean,
+//! spread and histogram of the gray levels. A condition that holds for
+//! `hold_samples` samples in a row is raised to dashboards, and cleared the
+//! same way once it is gone, so a single dark or shaky frame raises nothing.
+
+use crate::dispatch::Decimation;
+use crate::faults;
+use crate::state::AppState;
+use crate::threads;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use opencv::{
+    core::{self, Mat, Size, Vector},
+    imgproc,
+    prelude::*,
+};
+use raspibot_protocol::camera::{
+    ImageQuality, ImageQualityConfig, ImageQualityStatus, QualityChange, QualityIssue,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+pub const CONFIG_PATH: &str = "data/image_quality.json";
+const SAMPLE_FPS: f32 = 2.0;
+/// Width of the grayscale copy everything is measured on.
+const ANALYSIS_WIDTH: i32 = 320;
+const HISTOGRAM_BINS: usize = 16;
+/// Gray level from which a pixel counts as blown out.
+const CLIP_LEVEL: u8 = 250;
+const ISSUES: [QualityIssue; 4] = [
+    QualityIssue::Blur,
+    QualityIssue::Fog,
+    QualityIssue::UnderExposed,
+    QualityIssue::OverExposed,
+];
+
+#[derive(Default)]
+struct Tracker {
+    latest: Option<ImageQuality>,
+    /// Samples in a row that disagreed with an issue's current state.
+    streaks: BTreeMap<QualityIssue, u32>,
+    active: BTreeSet<QualityIssue>,
+}
+
+pub struct QualityMonitor {
+    path: PathBuf,
+    config: Mutex<ImageQualityConfig>,
+    tracker: Mutex<Tracker>,
+    tx: broadcast::Sender<QualityChange>,
+}
+
+impl QualityMonitor {
+    pub fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let (tx, _) = broadcast::channel(16);
+        Self {
+            path,
+            config: Mutex::new(config),
+            tracker: Mutex::new(Tracker::default()),
+            tx,
+        }
+    }
+
+    pub fn config(&self) -> ImageQualityConfig {
+        *self.config.lock().unwrap()
+    }
+
+    /// Saves `config`; disabling the monitor clears whatever it had raised.
+    pub fn set_config(&self, config: ImageQualityConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config;
+        if !config.enabled {
+            let tracker = std::mem::take(&mut *self.tracker.lock().unwrap());
+            if let Some(quality) = tracker.latest {
+                for issue in tracker.active {
+                    let _ = self.tx.send(QualityChange {
+                        issue,
+                        active: false,
+                        quality: quality.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> ImageQualityStatus {
+        let tracker = self.tracker.lock().unwrap();
+        ImageQualityStatus {
+            enabled: self.config().enabled,
+            latest: tracker.latest.clone(),
+            issues: tracker.active.iter().copied().collect(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<QualityChange> {
+        self.tx.subscribe()
+    }
+
+    fn observe(&self, quality: ImageQuality) {
+        let config = self.config();
+        let present = issues(&quality, &config);
+        let mut changes = Vec::new();
+        {
+            let mut tracker = self.tracker.lock().unwrap();
+            for issue in ISSUES {
+                let active = tracker.active.contains(&issue);
+                let streak = tracker.streaks.entry(issue).or_default();
+                if present.contains(&issue) == active {
+                    *streak = 0;
+                    continue;
+                }
+                *streak += 1;
+                if *streak < config.hold_samples.max(1) {
+                    continue;
+                }
+                *streak = 0;
+                if active {
+                    tracker.active.remove(&issue);
+                } else {
+                    tracker.active.insert(issue);
+                }
+                changes.push(QualityChange {
+                    issue,
+                    active: !active,
+                    quality: quality.clone(),
+                });
+            }
+            tracker.latest = Some(quality);
+        }
+        for change in changes {
+            let q = &change.quality;
+            if change.active {
+                println!(
+                    "[WARN] Image quality: {:?} (sharpness {:.0}, brightness {:.0}, contrast {:.0})",
+                    change.issue, q.sharpness, q.brightness, q.contrast
+                );
+            } else {
+                println!("[INFO] Image quality: {:?} cleared", change.issue);
+            }
+            let _ = self.tx.send(change);
+        }
+    }
+}
+
+/// The conditions one sample shows. Fog and blur both soften the image;
+/// fog is told apart by the missing contrast, and neither is judged in the
+/// dark, where there is no detail to measure either by.
+fn issues(quality: &ImageQuality, config: &ImageQualityConfig) -> BTreeSet<QualityIssue> {
+    let mut issues = BTreeSet::new();
+    if quality.brightness < config.min_brightness {
+        issues.insert(QualityIssue::UnderExposed);
+        return issues;
+    }
+    if quality.clipped > config.max_clipped {
+        issues.insert(QualityIssue::OverExposed);
+    }
+    if quality.contrast < config.min_contrast {
+        issues.insert(QualityIssue::Fog);
+    } else if quality.sharpness < config.min_sharpness {
+        issues.insert(QualityIssue::Blur);
+    }
+    issues
+}
+
+fn measure(frame: &Mat) -> opencv::Result<ImageQuality> {
+    let mut gray = Mat::default();
+    if frame.channels() == 1 {
+        frame.copy_to(&mut gray)?;
+    } else {
+        imgproc::cvt_color_def(frame, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+    }
+    let height = (gray.rows() as i64 * ANALYSIS_WIDTH as i64 / gray.cols().max(1) as i64).max(1);
+    let mut small = Mat::default();
+    imgproc::resize(
+        &gray,
+        &mut small,
+        Size::new(ANALYSIS_WIDTH, height as i32),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+
+    let mut laplacian = Mat::default();
+    imgproc::laplacian_def(&small, &mut laplacian, core::CV_64F)?;
+    let mut mean = Vector::<f64>::new();
+    let mut stddev = Vector::<f64>::new();
+    core::mean_std_dev_def(&laplacian, &mut mean, &mut stddev)?;
+    let sharpness = stddev.get(0)?.powi(2) as f32;
+
+    let pixels = small.data_bytes()?;
+    let total = pixels.len().max(1) as f64;
+    let mut counts = [0u64; HISTOGRAM_BINS];
+    let (mut sum, mut sum_sq, mut clipped) = (0u64, 0u64, 0u64);
+    for &p in pixels {
+        counts[p as usize * HISTOGRAM_BINS / 256] += 1;
+        sum += p as u64;
+        sum_sq += (p as u64).pow(2);
+        if p >= CLIP_LEVEL {
+            clipped += 1;
+        }
+    }
+    let brightness = sum as f64 / total;
+    let contrast = (sum_sq as f64 / total - brightness.powi(2)).max(0.0).sqrt();
+
+    Ok(ImageQuality {
+        sharpness,
+        brightness: brightness as f32,
+        contrast: contrast as f32,
+        clipped: (clipped as f64 / total) as f32,
+        histogram: counts.iter().map(|&c| (c as f64 / total) as f32).collect(),
+        taken_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    })
+}
+
+/// Samples the camera on its own thread; metrics also go into the
+/// `image_quality` telemetry stream.
+pub fn start_quality_monitor(state: AppState) {
+    let subscription = state
+        .frames
+        .subscribe("quality", Decimation::MaxFps(SAMPLE_FPS));
+    thread::spawn(move || {
+        threads::label("quality");
+        loop {
+            if faults::killed("quality") {
+                return;
+            }
+            let Some(frame) = subscription.recv_timeout(Duration::from_secs(1)) else {
+                continue;
+            };
+            if !state.quality.config().enabled {
+                continue;
+            }
+            match measure(&frame) {
+                Ok(quality) => {
+                    let mut values = serde_json::Map::new();
+                    values.insert("sharpness".into(), quality.sharpness.into());
+                    values.insert("brightness".into(), quality.brightness.into());
+                    values.insert("contrast".into(), quality.contrast.into());
+                    values.insert("clipped".into(), quality.clipped.into());
+                    state.record_telemetry("image_quality", values);
+                    state.quality.observe(quality);
+                }
+                Err(e) => eprintln!("[ERR] Image quality measurement failed: {}", e),
+            }
+        }
+    });
+}
+
+pub fn validate(config: &ImageQualityConfig) -> Result<(), String> {
+    if config.min_sharpness < 0.0 {
+        return Err("min_sharpness must not be negative".to_string());
+    }
+    if !(0.0..=255.0).contains(&config.min_contrast)
+        || !(0.0..=255.0).contains(&config.min_brightness)
+    {
+        return Err("min_contrast and min_brightness must be within 0..=255".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.max_clipped) {
+        return Err("max_clipped must be within 0..=1".to_string());
+    }
+    if config.hold_samples == 0 {
+        return Err("hold_samples must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+pub fn routes(monitor: Arc<QualityMonitor>) -> Router {
+    Router::new()
+        .route("/camera/quality", get(get_status))
+        .route("/camera/quality/config", get(get_config).put(set_config))
+        .with_state(monitor)
+}
+
+async fn get_status(State(monitor): State<Arc<QualityMonitor>>) -> Json<ImageQualityStatus> {
+    Json(monitor.status())
+}
+
+async fn get_config(State(monitor): State<Arc<QualityMonitor>>) -> Json<ImageQualityConfig> {
+    Json(monitor.config())
+}
+
+async fn set_config(
+    State(monitor): State<Arc<QualityMonitor>>,
+    Json(config): Json<ImageQualityConfig>,
+) -> Result<Json<ImageQualityConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    monitor
+        .set_config(config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Image quality monitor {}",
+        if config.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    Ok(Json(config))
+}
@@ -0,0 +1,301 @@
+//! Distance sensors (`[range]` in the config): HC-SR04 ultrasonic modules
+//! timed on GPIO, VL53L0X time-of-flight sensors on I2C. Read in turn at
+//! `rate_hz`, so ultrasonic sensors don't hear each other's pings, and
+//! recorded as the `range` telemetry stream.
+//!
+//! With `stop_distance_m` set, the obstacle-stop guard blocks forward drive
+//! while a forward-facing sensor reads closer than that; turning and
+//! reversing stay allowed so the robot can get away. Without a current
+//! reading from every forward sensor, autonomous modes don't drive forward,
+//! as with the virtual bumper.
+//!
+//! The HC-SR04 echo is a 5 V signal and needs a divider before the Pi's
+//! pin. The VL53L0X runs single-shot with its power-on settings, which skip
+//! ST's tuning but are good to a couple of centimetres at the short
+//! distances the guard cares about.
+
+use crate::arbiter::{CommandSource, Constraint};
+use crate::config::{RangeConfig, RangeSensorConfig, RangeSensorKind};
+use crate::faults;
+use crate::threads;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::drive::DriveCommand;
+use raspibot_protocol::range::{RangeReading, RangeStatus};
+use rppal::gpio::{Gpio, InputPin, OutputPin};
+use rppal::i2c::I2c;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Readings older than this don't count for the guard.
+const STALE_AFTER: Duration = Duration::from_millis(500);
+
+const SPEED_OF_SOUND_MPS: f32 = 343.0;
+const TRIGGER_PULSE: Duration = Duration::from_micros(10);
+/// The module starts its echo pulse well within this after the trigger.
+const ECHO_START_TIMEOUT: Duration = Duration::from_millis(5);
+/// An echo longer than this (about 4 m) is nothing in range; the module
+/// holds it for 38 ms then.
+const MAX_ECHO: Duration = Duration::from_millis(25);
+
+const VL53L0X_SYSRANGE_START: u8 = 0x00;
+const VL53L0X_INTERRUPT_CLEAR: u8 = 0x0B;
+const VL53L0X_INTERRUPT_STATUS: u8 = 0x13;
+/// Range in millimetres, big-endian, inside the result block at 0x14.
+const VL53L0X_RESULT_RANGE_MM: u8 = 0x1E;
+const VL53L0X_MODEL_ID: u8 = 0xC0;
+const VL53L0X_ID: u8 = 0xEE;
+const VL53L0X_TIMEOUT: Duration = Duration::from_millis(50);
+/// What the sensor reports when nothing is in range.
+const VL53L0X_OUT_OF_RANGE_MM: u16 = 8190;
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+trait RangeSensor: Send {
+    /// Distance in metres; `None` when nothing is in range.
+    fn measure(&mut self) -> anyhow::Result<Option<f32>>;
+}
+
+struct HcSr04 {
+    trigger: OutputPin,
+    echo: InputPin,
+}
+
+impl HcSr04 {
+    fn open(trigger: u8, echo: u8) -> rppal::gpio::Result<Self> {
+        let gpio = Gpio::new()?;
+        Ok(Self {
+            trigger: gpio.get(trigger)?.into_output_low(),
+            echo: gpio.get(echo)?.into_input(),
+        })
+    }
+}
+
+impl RangeSensor for HcSr04 {
+    fn measure(&mut self) -> anyhow::Result<Option<f32>> {
+        self.trigger.set_high();
+        thread::sleep(TRIGGER_PULSE);
+        self.trigger.set_low();
+        // Microseconds matter, so the echo is timed by polling rather than
+        // by interrupts
+        let triggered = Instant::now();
+        while self.echo.is_low() {
+            if triggered.elapsed() > ECHO_START_TIMEOUT {
+                anyhow::bail!("no echo pulse");
+            }
+        }
+        let rise = Instant::now();
+        while self.echo.is_high() {
+            if rise.elapsed() > MAX_ECHO {
+                return Ok(None);
+            }
+        }
+        Ok(Some(
+            rise.elapsed().as_secs_f32() * SPEED_OF_SOUND_MPS / 2.0,
+        ))
+    }
+}
+
+struct Vl53l0x {
+    i2c: I2c,
+}
+
+impl Vl53l0x {
+    fn open(address: u16) -> anyhow::Result<Self> {
+        let mut i2c = I2c::new()?;
+        i2c.set_slave_address(address)?;
+        let id = i2c.smbus_read_byte(VL53L0X_MODEL_ID)?;
+        if id != VL53L0X_ID {
+            anyhow::bail!("no VL53L0X at {:#04x} (model id {:#04x})", address, id);
+        }
+        Ok(Self { i2c })
+    }
+}
+
+impl RangeSensor for Vl53l0x {
+    fn measure(&mut self) -> anyhow::Result<Option<f32>> {
+        self.i2c.smbus_write_byte(VL53L0X_SYSRANGE_START, 0x01)?;
+        let started = Instant::now();
+        while self.i2c.smbus_read_byte(VL53L0X_INTERRUPT_STATUS)? & 0x07 == 0 {
+            if started.elapsed() > VL53L0X_TIMEOUT {
+                anyhow::bail!("ranging timed out");
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        let mut buf = [0u8; 2];
+        self.i2c.block_read(VL53L0X_RESULT_RANGE_MM, &mut buf)?;
+        self.i2c.smbus_write_byte(VL53L0X_INTERRUPT_CLEAR, 0x01)?;
+        let mm = u16::from_be_bytes(buf);
+        Ok((mm < VL53L0X_OUT_OF_RANGE_MM).then(|| mm as f32 / 1000.0))
+    }
+}
+
+fn open_sensor(config: &RangeSensorConfig) -> anyhow::Result<Box<dyn RangeSensor>> {
+    Ok(match config.kind {
+        RangeSensorKind::HcSr04 => {
+            let (Some(trigger), Some(echo)) = (config.trigger_pin, config.echo_pin) else {
+                anyhow::bail!("an HC-SR04 needs trigger_pin and echo_pin");
+            };
+            Box::new(HcSr04::open(trigger, echo)?)
+        }
+        RangeSensorKind::Vl53l0x => Box::new(Vl53l0x::open(config.i2c_address)?),
+    })
+}
+
+struct Measurement {
+    reading: RangeReading,
+    at: Instant,
+    /// The read worked; a failed one leaves `distance_m` unset too.
+    ok: bool,
+}
+
+pub struct RangeSensors {
+    sensors: Mutex<Vec<(RangeSensorConfig, Box<dyn RangeSensor>)>>,
+    latest: Mutex<Vec<Measurement>>,
+    interval: Duration,
+    stop_distance_m: Option<f32>,
+    rounds: broadcast::Sender<Vec<RangeReading>>,
+}
+
+impl RangeSensors {
+    /// Opens every configured sensor, leaving out those that fail; `None`
+    /// when none are configured or none opened.
+    pub fn open(config: &RangeConfig) -> Option<Self> {
+        let mut sensors = Vec::new();
+        for sensor in &config.sensors {
+            match open_sensor(sensor) {
+                Ok(opened) => {
+                    println!(
+                        "[OK] Range sensor '{}' ({:?}) ready",
+                        sensor.name, sensor.kind
+                    );
+                    sensors.push((sensor.clone(), opened));
+                }
+                Err(e) => println!("[WARN] Range sensor '{}' unavailable: {}", sensor.name, e),
+            }
+        }
+        if sensors.is_empty() {
+            return None;
+        }
+        if let Some(distance) = config.stop_distance_m {
+            println!("[INFO] Obstacle stop at {:.2} m", distance);
+        }
+        Some(Self {
+            sensors: Mutex::new(sensors),
+            latest: Mutex::new(Vec::new()),
+            interval: Duration::from_secs_f32(1.0 / config.rate_hz),
+            stop_distance_m: config.stop_distance_m,
+            rounds: broadcast::channel(16).0,
+        })
+    }
+
+    /// Each round of readings, every sensor's in it.
+    pub fn subscribe(&self) -> broadcast::Receiver<Vec<RangeReading>> {
+        self.rounds.subscribe()
+    }
+
+    pub fn status(&self) -> RangeStatus {
+        RangeStatus {
+            readings: self
+                .latest
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|m| m.reading.clone())
+                .collect(),
+            stop_distance_m: self.stop_distance_m,
+            blocked: self.blocked(false),
+        }
+    }
+
+    /// Whether forward drive is blocked; `autonomous` also blocks it when
+    /// a forward sensor has no current reading.
+    fn blocked(&self, autonomous: bool) -> bool {
+        let Some(stop) = self.stop_distance_m else {
+            return false;
+        };
+        let latest = self.latest.lock().unwrap();
+        let forward = latest.iter().filter(|m| m.reading.forward);
+        let mut blind = latest.is_empty();
+        for measurement in forward {
+            if !measurement.ok || measurement.at.elapsed() > STALE_AFTER {
+                blind = true;
+            } else if measurement.reading.distance_m.is_some_and(|d| d < stop) {
+                return true;
+            }
+        }
+        blind && autonomous
+    }
+
+    fn read_round(&self) {
+        let mut round = Vec::new();
+        for (config, sensor) in self.sensors.lock().unwrap().iter_mut() {
+            let (distance_m, ok) = match sensor.measure() {
+                Ok(distance) => (distance, true),
+                Err(e) => {
+                    eprintln!("[ERR] Range sensor '{}' read failed: {}", config.name, e);
+                    (None, false)
+                }
+            };
+            round.push(Measurement {
+                reading: RangeReading {
+                    sensor: config.name.clone(),
+                    distance_m,
+                    forward: config.forward,
+                    taken_unix_ms: unix_ms(),
+                },
+                at: Instant::now(),
+                ok,
+            });
+        }
+        let readings = round.iter().map(|m| m.reading.clone()).collect();
+        *self.latest.lock().unwrap() = round;
+        let _ = self.rounds.send(readings);
+    }
+}
+
+impl Constraint for RangeSensors {
+    fn name(&self) -> &'static str {
+        "range"
+    }
+
+    fn apply(&self, source: &CommandSource, command: DriveCommand) -> DriveCommand {
+        let (forward, turn) = command.forward_turn();
+        if forward <= 0.0 || !self.blocked(source.is_autonomous()) {
+            return command;
+        }
+        DriveCommand::from_forward_turn(0.0, turn)
+    }
+}
+
+/// Reads every sensor in turn at the configured rate until the `range`
+/// task is killed.
+pub fn start_range_thread(sensors: Arc<RangeSensors>) {
+    thread::spawn(move || {
+        threads::label("range");
+        loop {
+            if faults::killed("range") {
+                return;
+            }
+            let started = Instant::now();
+            sensors.read_round();
+            thread::sleep(sensors.interval.saturating_sub(started.elapsed()));
+        }
+    });
+}
+
+pub fn routes(sensors: Arc<RangeSensors>) -> Router {
+    Router::new()
+        .route("/range", get(get_status))
+        .with_state(sensors)
+}
+
+async fn get_status(State(sensors): State<Arc<RangeSensors>>) -> Json<RangeStatus> {
+    Json(sensors.status())
+}
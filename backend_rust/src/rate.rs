@@ -0,0 +1,105 @@
+//! Sliding-window rate and jitter meters, shared by every loop worth
+//! watching (capture, each frame consumer, inference, drive commands), so
+//! they are all measured the same way.
+
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::events;
+use raspibot_protocol::rates::RateStats;
+use socketioxide::SocketIo;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct RateMeter {
+    window: Duration,
+    ticks: Mutex<VecDeque<Instant>>,
+}
+
+impl RateMeter {
+    pub fn new() -> Self {
+        Self::with_window(DEFAULT_WINDOW)
+    }
+
+    pub fn with_window(window: Duration) -> Self {
+        Self {
+            window,
+            ticks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Call once per iteration of the loop being measured.
+    pub fn tick(&self) {
+        let now = Instant::now();
+        let mut ticks = self.ticks.lock().unwrap();
+        ticks.push_back(now);
+        Self::prune(&mut ticks, now, self.window);
+    }
+
+    fn prune(ticks: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+        while ticks
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > window)
+        {
+            ticks.pop_front();
+        }
+    }
+
+    /// All zero once the loop has stalled for a whole window.
+    pub fn stats(&self) -> RateStats {
+        let mut ticks = self.ticks.lock().unwrap();
+        Self::prune(&mut ticks, Instant::now(), self.window);
+        let intervals: Vec<f64> = ticks
+            .iter()
+            .zip(ticks.iter().skip(1))
+            .map(|(a, b)| b.duration_since(*a).as_secs_f64() * 1000.0)
+            .collect();
+        if intervals.is_empty() {
+            return RateStats {
+                samples: ticks.len(),
+                ..RateStats::default()
+            };
+        }
+        let n = intervals.len() as f64;
+        let mean = intervals.iter().sum::<f64>() / n;
+        let variance = intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+        RateStats {
+            hz: if mean > 0.0 { 1000.0 / mean } else { 0.0 },
+            mean_interval_ms: mean,
+            jitter_ms: variance.sqrt(),
+            max_interval_ms: intervals.iter().copied().fold(0.0, f64::max),
+            samples: ticks.len(),
+        }
+    }
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/rates", get(get_rates))
+        .with_state(state)
+}
+
+async fn get_rates(State(state): State<AppState>) -> Json<BTreeMap<String, RateStats>> {
+    Json(state.rates())
+}
+
+/// Publishes every meter once a second, to Socket.IO and the run's blackbox.
+pub fn start_rate_monitor(state: AppState, io: SocketIo) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let rates = state.rates();
+            let mut values = serde_json::Map::new();
+            for (name, stats) in &rates {
+                values.insert(format!("{}_hz", name), stats.hz.into());
+                values.insert(format!("{}_jitter_ms", name), stats.jitter_ms.into());
+            }
+            state.record_telemetry("rates", values);
+            let _ = io.emit(events::RATES, &rates).await;
+        }
+    });
+}
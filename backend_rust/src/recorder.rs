@@ -0,0 +1,335 @@
+//! Match recordings: the camera feed as MP4 with a JSONL sidecar of the
+//! detections published meanwhile, started and stopped by the operator
+//! (`POST /record/start`, `POST /record/stop`) for post-match replays.
+//!
+//! A recording made during a run goes under the run's directory
+//! (`<session>/recordings/`), so it is exported with it; otherwise under
+//! `data/recordings/`. Each sidecar line names the video frame that was
+//! showing when the set was published. `recording.fps` in the config sets
+//! the frame rate when a request doesn't.
+//!
+//! Frames are recorded as they are published (see `annotate`): faces
+//! blurred when face blur is on, with detections and the operator overlay
+//! drawn on unless the request turns annotation off.
+
+use crate::annotate;
+use crate::camera::Camera;
+use crate::detections::Published;
+use crate::dispatch::{Decimation, FrameSubscription};
+use crate::faults;
+use crate::state::AppState;
+use crate::threads;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use opencv::{
+    core::{Mat, Size},
+    imgproc,
+    prelude::*,
+    videoio,
+};
+use raspibot_protocol::locale::Locale;
+use raspibot_protocol::recording::{RecordedDetections, RecordingRequest, RecordingStatus};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+pub const RECORDINGS_DIR: &str = "recordings";
+pub const VIDEO_FILE: &str = "video.mp4";
+pub const DETECTIONS_FILE: &str = "detections.jsonl";
+const MAX_FPS: f32 = 30.0;
+
+#[derive(Default)]
+struct Progress {
+    frames: AtomicU64,
+    detection_sets: AtomicU64,
+}
+
+struct ActiveRecording {
+    dir: PathBuf,
+    session: Option<String>,
+    started_unix_ms: u64,
+    fps: f32,
+    progress: Arc<Progress>,
+    stop: Arc<AtomicBool>,
+    /// Taken once the recording is stopped.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ActiveRecording {
+    fn status(&self) -> RecordingStatus {
+        RecordingStatus {
+            recording: self.thread.as_ref().is_some_and(|t| !t.is_finished()),
+            dir: Some(self.dir.display().to_string()),
+            session: self.session.clone(),
+            started_unix_ms: Some(self.started_unix_ms),
+            fps: self.fps,
+            frames: self.progress.frames.load(Ordering::Relaxed),
+            detection_sets: self.progress.detection_sets.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// At most one recording at a time.
+pub struct Recorder {
+    /// Frame rate of requests that don't ask for one.
+    default_fps: f32,
+    active: Mutex<Option<ActiveRecording>>,
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Recorder {
+    pub fn new(default_fps: f32) -> Self {
+        Self {
+            default_fps,
+            active: Mutex::new(None),
+        }
+    }
+
+    /// The recording in progress, or the last one until the next starts.
+    pub fn status(&self) -> RecordingStatus {
+        self.active
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(ActiveRecording::status)
+            .unwrap_or_else(|| RecordingStatus {
+                fps: self.default_fps,
+                ..RecordingStatus::default()
+            })
+    }
+
+    pub fn start(
+        &self,
+        state: &AppState,
+        request: RecordingRequest,
+    ) -> Result<RecordingStatus, String> {
+        let mut active = self.active.lock().unwrap();
+        if active.as_ref().is_some_and(|a| a.status().recording) {
+            return Err("a recording is already in progress".to_string());
+        }
+        let fps = request.fps.unwrap_or(self.default_fps);
+        if !(fps > 0.0 && fps <= MAX_FPS) {
+            return Err(format!("fps must be within 0..={}", MAX_FPS));
+        }
+
+        let session = state.sessions.active_id();
+        let parent = match session
+            .as_deref()
+            .and_then(|id| state.sessions.session_dir(id))
+        {
+            Some(dir) => dir.join(RECORDINGS_DIR),
+            None => Path::new("data").join(RECORDINGS_DIR),
+        };
+        let started_unix_ms = unix_ms();
+        let name: String = request
+            .name
+            .as_deref()
+            .unwrap_or("match")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let dir = parent.join(format!("{}-{}", name, started_unix_ms));
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let sidecar = File::create(dir.join(DETECTIONS_FILE)).map_err(|e| e.to_string())?;
+
+        let progress = Arc::new(Progress::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let frames = state.frames.subscribe("recorder", Decimation::MaxFps(fps));
+        let detections = state.detections.subscribe();
+        let annotate = request
+            .annotate
+            .unwrap_or(true)
+            .then(|| state.cameras.primary().clone());
+        let thread = {
+            let state = state.clone();
+            let dir = dir.clone();
+            let progress = progress.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                threads::label("recorder");
+                let recording = Recording {
+                    state: &state,
+                    annotate: annotate.as_deref(),
+                    dir: &dir,
+                    fps,
+                    progress: &progress,
+                    stop: &stop,
+                };
+                match recording.run(frames, detections, BufWriter::new(sidecar)) {
+                    Ok(()) => println!("[INFO] Recording saved: {}", dir.display()),
+                    Err(e) => eprintln!("[ERR] Recording {} failed: {}", dir.display(), e),
+                }
+            })
+        };
+        println!("[INFO] Recording to {} at {} fps", dir.display(), fps);
+        let recording = active.insert(ActiveRecording {
+            dir,
+            session,
+            started_unix_ms,
+            fps,
+            progress,
+            stop,
+            thread: Some(thread),
+        });
+        Ok(recording.status())
+    }
+
+    /// Stops the recording and waits for the video to be finalized, which
+    /// blocks for up to a second; `None` if nothing was recording.
+    pub fn stop(&self) -> Option<RecordingStatus> {
+        // Joined outside the lock, so status requests don't wait on it
+        let thread = {
+            let mut active = self.active.lock().unwrap();
+            let recording = active.as_mut()?;
+            let thread = recording.thread.take().filter(|t| !t.is_finished())?;
+            recording.stop.store(true, Ordering::Relaxed);
+            thread
+        };
+        let _ = thread.join();
+        self.active
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(ActiveRecording::status)
+    }
+}
+
+struct Recording<'a> {
+    state: &'a AppState,
+    /// The camera whose detections are drawn on, when annotating.
+    annotate: Option<&'a Camera>,
+    dir: &'a Path,
+    fps: f32,
+    progress: &'a Progress,
+    stop: &'a AtomicBool,
+}
+
+impl Recording<'_> {
+    fn run(
+        &self,
+        frames: FrameSubscription<Mat>,
+        mut detections: broadcast::Receiver<Arc<Published>>,
+        mut sidecar: BufWriter<File>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let mut writer: Option<(videoio::VideoWriter, Size)> = None;
+        let mut written = 0u64;
+        while !self.stop.load(Ordering::Relaxed) && !faults::killed("recorder") {
+            let Some(frame) = frames.recv_timeout(Duration::from_secs(1)) else {
+                continue;
+            };
+            if writer.is_none() {
+                let size = frame.size()?;
+                let video = videoio::VideoWriter::new(
+                    &self.dir.join(VIDEO_FILE).to_string_lossy(),
+                    videoio::VideoWriter::fourcc('m', 'p', '4', 'v')?,
+                    self.fps as f64,
+                    size,
+                    true,
+                )?;
+                writer = Some((video, size));
+            }
+            let (video, size) = writer.as_mut().unwrap();
+            let published =
+                annotate::publish(self.state, &frame, self.annotate.map(|c| (c, Locale::En)));
+            video.write(&fit(&published, *size)?)?;
+            written += 1;
+            self.progress.frames.store(written, Ordering::Relaxed);
+
+            loop {
+                let published = match detections.try_recv() {
+                    Ok(published) => published,
+                    Err(TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                let line = RecordedDetections {
+                    frame: written - 1,
+                    offset_ms: started.elapsed().as_millis() as u64,
+                    seq: published.seq,
+                    captured_unix_ms: published.captured_unix_ms,
+                    objects: published.objects.clone(),
+                };
+                serde_json::to_writer(&mut sidecar, &line)?;
+                sidecar.write_all(b"\n")?;
+                self.progress.detection_sets.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        sidecar.flush()?;
+        if let Some((mut video, _)) = writer {
+            video.release()?;
+        }
+        Ok(())
+    }
+}
+
+/// `frame` as a BGR image of the video's size; the camera can change
+/// resolution or drop to grayscale mid-recording.
+fn fit(frame: &Mat, size: Size) -> opencv::Result<Mat> {
+    let mut color = Mat::default();
+    if frame.channels() == 1 {
+        imgproc::cvt_color_def(frame, &mut color, imgproc::COLOR_GRAY2BGR)?;
+    } else {
+        frame.copy_to(&mut color)?;
+    }
+    if color.size()? == size {
+        return Ok(color);
+    }
+    let mut resized = Mat::default();
+    imgproc::resize(&color, &mut resized, size, 0.0, 0.0, imgproc::INTER_AREA)?;
+    Ok(resized)
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/record", get(get_status))
+        .route("/record/start", post(start))
+        .route("/record/stop", post(stop))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<RecordingStatus> {
+    Json(state.recorder.status())
+}
+
+async fn start(
+    State(state): State<AppState>,
+    request: Option<Json<RecordingRequest>>,
+) -> Result<Json<RecordingStatus>, (StatusCode, String)> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    state
+        .recorder
+        .start(&state, request)
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn stop(
+    State(state): State<AppState>,
+) -> Result<Json<RecordingStatus>, (StatusCode, String)> {
+    tokio::task::spawn_blocking(move || state.recorder.stop())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .ok_or((StatusCode::CONFLICT, "not recording".to_string()))
+}
@@ -0,0 +1,207 @@
+//! Appearance embeddings for re-identification.
+//!
+//! An optional small ONNX model (OSNet-style, `REID_MODEL`) turns detection
+//! crops into L2-normalized feature vectors. Tracks keep a running embedding,
+//! and a [`ReidGallery`] shared by every camera's tracker remembers recently
+//! seen identities, so an object that comes back after a long gap, or shows
+//! up on another camera, gets its old id back.
+
+use opencv::{
+    core::{self, Mat, Size},
+    imgproc,
+    prelude::*,
+};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Tensor;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Person re-id models are usually trained on 128x256 (w x h) crops.
+const DEFAULT_INPUT: (i32, i32) = (128, 256);
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+pub struct ReidModel {
+    session: Mutex<Session>,
+    input_w: i32,
+    input_h: i32,
+}
+
+impl ReidModel {
+    /// Loads `REID_MODEL` if set; re-id stays off otherwise.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("REID_MODEL").ok()?;
+        match Self::new(&path) {
+            Ok(model) => Some(model),
+            Err(e) => {
+                println!("[WARN] Re-id model unavailable: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn new(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(1)?
+            .commit_from_file(path)?;
+        // NCHW; dynamic dimensions come back as -1
+        let (input_w, input_h) = session
+            .inputs()
+            .first()
+            .and_then(|input| input.dtype().tensor_shape())
+            .filter(|shape| shape.len() == 4 && shape[2] > 0 && shape[3] > 0)
+            .map(|shape| (shape[3] as i32, shape[2] as i32))
+            .unwrap_or(DEFAULT_INPUT);
+        println!(
+            "[OK] Loaded re-id model from {} ({}x{} input)",
+            path, input_w, input_h
+        );
+        Ok(Self {
+            session: Mutex::new(session),
+            input_w,
+            input_h,
+        })
+    }
+
+    /// Embedding of the `rect` crop of a BGR frame, L2-normalized.
+    pub fn embed(
+        &self,
+        frame: &Mat,
+        rect: core::Rect,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let x1 = rect.x.max(0);
+        let y1 = rect.y.max(0);
+        let x2 = (rect.x + rect.width).min(frame.cols());
+        let y2 = (rect.y + rect.height).min(frame.rows());
+        if x2 <= x1 || y2 <= y1 {
+            return Err("crop outside the frame".into());
+        }
+        let crop = Mat::roi(frame, core::Rect::new(x1, y1, x2 - x1, y2 - y1))?;
+        let mut resized = Mat::default();
+        imgproc::resize(
+            &*crop,
+            &mut resized,
+            Size::new(self.input_w, self.input_h),
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+
+        // BGR HWC u8 -> RGB CHW f32, ImageNet-normalized
+        let (w, h) = (self.input_w as usize, self.input_h as usize);
+        let bytes = resized.data_bytes()?;
+        let mut input = vec![0f32; 3 * w * h];
+        for (i, px) in bytes.chunks_exact(3).enumerate() {
+            for c in 0..3 {
+                let value = px[2 - c] as f32 / 255.0;
+                input[c * w * h + i] = (value - MEAN[c]) / STD[c];
+            }
+        }
+        let tensor = Tensor::from_array((vec![1i64, 3, h as i64, w as i64], input))?;
+
+        let mut session = self.session.lock().unwrap();
+        let outputs = session.run(ort::inputs![tensor])?;
+        let (_, features) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok(normalize(features.to_vec()))
+    }
+}
+
+fn normalize(mut v: Vec<f32>) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+    v
+}
+
+/// Cosine similarity of two L2-normalized embeddings.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Blends a new embedding into a running one, keeping it normalized.
+pub fn blend(old: &[f32], new: &[f32], weight: f32) -> Vec<f32> {
+    if old.len() != new.len() {
+        return new.to_vec();
+    }
+    normalize(
+        old.iter()
+            .zip(new)
+            .map(|(o, n)| o + weight * (n - o))
+            .collect(),
+    )
+}
+
+struct Identity {
+    id: u64,
+    class: i64,
+    embedding: Vec<f32>,
+    last_seen: Instant,
+}
+
+/// Recently seen identities, shared across trackers (and so cameras). It
+/// also hands out track ids, so they are unique across all of them.
+pub struct ReidGallery {
+    memory: Duration,
+    min_similarity: f32,
+    identities: Mutex<Vec<Identity>>,
+    next_id: AtomicU64,
+}
+
+impl ReidGallery {
+    /// Identities are remembered for `memory` after they were last seen.
+    pub fn new(memory: Duration) -> Self {
+        Self {
+            memory,
+            min_similarity: 0.7,
+            identities: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Best-matching remembered identity of `class`, skipping ids in `exclude`
+    /// (tracks that are already live).
+    pub fn identify(
+        &self,
+        class: i64,
+        embedding: &[f32],
+        exclude: &[u64],
+        now: Instant,
+    ) -> Option<u64> {
+        let mut identities = self.identities.lock().unwrap();
+        identities.retain(|i| now.saturating_duration_since(i.last_seen) <= self.memory);
+        identities
+            .iter()
+            .filter(|i| i.class == class && !exclude.contains(&i.id))
+            .map(|i| (i.id, cosine(&i.embedding, embedding)))
+            .filter(|(_, score)| *score >= self.min_similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    pub fn update(&self, id: u64, class: i64, embedding: &[f32], now: Instant) {
+        let mut identities = self.identities.lock().unwrap();
+        match identities.iter_mut().find(|i| i.id == id) {
+            Some(identity) => {
+                identity.class = class;
+                identity.embedding = embedding.to_vec();
+                identity.last_seen = now;
+            }
+            None => identities.push(Identity {
+                id,
+                class,
+                embedding: embedding.to_vec(),
+                last_seen: now,
+            }),
+        }
+    }
+}
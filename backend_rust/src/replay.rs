@@ -0,0 +1,233 @@
+//! `replay` subcommand: feeds a recorded run back through the time-dependent
+//! parts of the pipeline and checks their output against an expected file.
+//!
+//! Blackbox samples are played in order on a [`VirtualClock`] set to each
+//! sample's time, so the telemetry downsampler windows and the tracker's
+//! coasting see the same timing as during the run, however fast the replay
+//! goes. `detections` samples go through the tracker; every stream goes
+//! through the downsampler. Both use their built-in defaults rather than the
+//! environment, so the result only depends on the recording. Frames aren't
+//! recorded, so tracks are associated without appearance cues.
+//!
+//! ```text
+//! backend_rust replay <session dir> [--expect expected.json] [--write-expect]
+//! ```
+
+use crate::blackbox;
+use crate::clock::{Clock, VirtualClock};
+use crate::telemetry::TelemetryDownsampler;
+use crate::tracker::{Appearance, Observation, Tracker, TrackerConfig};
+use crate::transform::BoxF;
+use raspibot_protocol::inference::DetectedObject;
+use raspibot_protocol::telemetry::{DownsampleConfig, TelemetryAggregate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default expected-output file, inside the session directory.
+pub const DEFAULT_EXPECT: &str = "replay_expected.json";
+/// Mismatches listed before the rest are only counted.
+const MAX_REPORTED: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrackOut {
+    id: u64,
+    class: String,
+    /// `[x, y, w, h]` in frame pixels.
+    bbox: [f32; 4],
+    occluded: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrackFrame {
+    t_s: f64,
+    seq: u64,
+    tracks: Vec<TrackOut>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReplayOutput {
+    samples: usize,
+    aggregates: Vec<TelemetryAggregate>,
+    tracks: Vec<TrackFrame>,
+}
+
+pub struct ReplayArgs {
+    pub session: PathBuf,
+    pub expect: PathBuf,
+    /// Record the current output as the expected file instead of checking it.
+    pub write_expect: bool,
+}
+
+impl ReplayArgs {
+    /// Parses the arguments after `replay`; the session directory comes
+    /// first, `--profile` is taken out beforehand.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let session: PathBuf = match args.peek() {
+            Some(arg) if !arg.starts_with("--") => args.next().unwrap_or_default().into(),
+            _ => return Err("replay needs a session directory".to_string()),
+        };
+        let mut parsed = Self {
+            expect: session.join(DEFAULT_EXPECT),
+            session,
+            write_expect: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--expect" => {
+                    parsed.expect = args.next().ok_or("--expect needs a path")?.into();
+                }
+                "--write-expect" => parsed.write_expect = true,
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn replay(samples: &mut [blackbox::Sample]) -> ReplayOutput {
+    samples.sort_by(|a, b| a.t_s.total_cmp(&b.t_s));
+    let clock = Arc::new(VirtualClock::new());
+    let downsampler = TelemetryDownsampler::new(DownsampleConfig::default(), clock.clone());
+    let mut aggregates_rx = downsampler.subscribe();
+    let mut tracker = Tracker::new(TrackerConfig::default());
+    // Class ids in order of first appearance, the tracker only compares them
+    let mut classes: Vec<String> = Vec::new();
+
+    let mut output = ReplayOutput {
+        samples: samples.len(),
+        aggregates: Vec::new(),
+        tracks: Vec::new(),
+    };
+    for sample in samples.iter() {
+        clock.set(Duration::from_secs_f64(sample.t_s.max(0.0)));
+        downsampler.record(&sample.stream, &sample.values);
+        while let Ok(aggregate) = aggregates_rx.try_recv() {
+            output.aggregates.push(aggregate);
+        }
+
+        if sample.stream != "detections" {
+            continue;
+        }
+        let objects: Vec<DetectedObject> = sample
+            .values
+            .get("objects")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+        let observations: Vec<Observation> = objects
+            .iter()
+            .map(|o| {
+                let class = match classes.iter().position(|c| *c == o.class) {
+                    Some(i) => i,
+                    None => {
+                        classes.push(o.class.clone());
+                        classes.len() - 1
+                    }
+                };
+                let [x, y, w, h] = o.bbox;
+                Observation {
+                    detection: (
+                        BoxF {
+                            x: x as f32,
+                            y: y as f32,
+                            w: w as f32,
+                            h: h as f32,
+                        },
+                        o.confidence,
+                        class as i64,
+                    ),
+                    appearance: Appearance::default(),
+                }
+            })
+            .collect();
+        let tracks = tracker
+            .update(&observations, clock.now())
+            .iter()
+            .map(|t| TrackOut {
+                id: t.id,
+                class: classes[t.class as usize].clone(),
+                bbox: [t.bbox.x, t.bbox.y, t.bbox.w, t.bbox.h],
+                occluded: t.occluded(),
+            })
+            .collect();
+        output.tracks.push(TrackFrame {
+            t_s: sample.t_s,
+            seq: sample
+                .values
+                .get("seq")
+                .and_then(Value::as_u64)
+                .unwrap_or(0),
+            tracks,
+        });
+    }
+    output
+}
+
+/// One message per differing entry of `field`, compared in order.
+fn compare_list(field: &str, actual: &Value, expected: &Value, errors: &mut Vec<String>) {
+    let empty = Vec::new();
+    let actual = actual[field].as_array().unwrap_or(&empty);
+    let expected = expected[field].as_array().unwrap_or(&empty);
+    if actual.len() != expected.len() {
+        errors.push(format!(
+            "{}: {} entries, expected {}",
+            field,
+            actual.len(),
+            expected.len()
+        ));
+    }
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        if a != e {
+            errors.push(format!("{}[{}]: got {}, expected {}", field, i, a, e));
+        }
+    }
+}
+
+/// Returns `Ok(true)` when the replay output matches the expected file
+/// exactly.
+pub fn run(args: &ReplayArgs) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut samples = blackbox::read(&args.session)?;
+    let output = replay(&mut samples);
+    println!(
+        "[INFO] Replayed {} samples from {}: {} aggregates, {} tracked frames",
+        output.samples,
+        args.session.display(),
+        output.aggregates.len(),
+        output.tracks.len()
+    );
+
+    let text = serde_json::to_string_pretty(&output)?;
+    if args.write_expect {
+        if let Some(dir) = args.expect.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&args.expect, &text)?;
+        println!("[OK] Expected output written to {}", args.expect.display());
+        return Ok(true);
+    }
+
+    // Both sides go through the same text form, so floats compare exactly
+    let actual: Value = serde_json::from_str(&text)?;
+    let expected: Value = serde_json::from_str(&std::fs::read_to_string(&args.expect)?)?;
+    let mut errors = Vec::new();
+    if actual["samples"] != expected["samples"] {
+        errors.push(format!(
+            "samples: got {}, expected {}",
+            actual["samples"], expected["samples"]
+        ));
+    }
+    compare_list("aggregates", &actual, &expected, &mut errors);
+    compare_list("tracks", &actual, &expected, &mut errors);
+    if errors.is_empty() {
+        println!("[OK] Replay matches {}", args.expect.display());
+        return Ok(true);
+    }
+    for error in errors.iter().take(MAX_REPORTED) {
+        eprintln!("[ERR] {}", error);
+    }
+    eprintln!("[ERR] Replay differs in {} places", errors.len());
+    Ok(false)
+}
@@ -0,0 +1,129 @@
+//! The robot's identity: name, team, build and what it can do, announced as
+//! `robot_info` so an operator sees at once that they reached the right
+//! robot running the right build.
+//!
+//! It goes to every namespace at startup and whenever a client sends
+//! `announce`, to each client as it connects, and out with `/health`. With
+//! `robot.splash` set it is also printed as a banner at startup.
+
+use crate::config::RobotConfig;
+use crate::state::AppState;
+use raspibot_protocol::events;
+use raspibot_protocol::health::RobotInfo;
+use socketioxide::SocketIo;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Commit the binary was built from, stamped by `build.rs`.
+pub const GIT_HASH: &str = env!("RASPIBOT_GIT_HASH");
+
+pub struct RobotIdentity {
+    config: Mutex<RobotConfig>,
+    announcements: broadcast::Sender<()>,
+}
+
+impl RobotIdentity {
+    pub fn new() -> Self {
+        let (announcements, _) = broadcast::channel(4);
+        Self {
+            config: Mutex::new(RobotConfig::default()),
+            announcements,
+        }
+    }
+
+    /// Takes the name and team from the startup config.
+    pub fn configure(&self, config: RobotConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    pub fn config(&self) -> RobotConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Has `robot_info` sent to every client.
+    pub fn announce(&self) {
+        let _ = self.announcements.send(());
+    }
+}
+
+/// Hardware that was found, then optional features built in.
+fn capabilities(state: &AppState) -> Vec<String> {
+    [
+        ("detector", state.model.is_some()),
+        ("gps", state.gps.is_some()),
+        ("compass", state.compass.is_some()),
+        ("gimbal", state.gimbal.is_some()),
+        ("imu", state.imu.is_some()),
+        ("range", state.range.is_some()),
+        ("pose", state.pose.is_some()),
+        ("ir_illuminator", state.illuminator.is_some()),
+        ("reid", state.reid.is_some()),
+        ("stereo", state.stereo.is_some()),
+        ("thermal", state.thermal.is_some()),
+        ("can", cfg!(feature = "can")),
+        ("arm", cfg!(feature = "arm")),
+        ("sqlite", cfg!(feature = "sqlite")),
+        ("faults", cfg!(feature = "faults")),
+    ]
+    .into_iter()
+    .filter(|(_, present)| *present)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+pub fn info(state: &AppState) -> RobotInfo {
+    let config = state.robot.config();
+    RobotInfo {
+        name: config.name,
+        team: config.team,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: GIT_HASH.to_string(),
+        profile: state.profile.to_string(),
+        capabilities: capabilities(state),
+        battery_v: state.power.battery_v(),
+        uptime_s: state.started.elapsed().as_secs_f64(),
+    }
+}
+
+pub fn print_splash(info: &RobotInfo) {
+    let rule = "=".repeat(48);
+    println!("{}", rule);
+    match &info.team {
+        Some(team) => println!("  {} ({})", info.name, team),
+        None => println!("  {}", info.name),
+    }
+    println!(
+        "  backend {} ({}), {} profile",
+        info.version, info.git_hash, info.profile
+    );
+    println!("  capabilities: {}", info.capabilities.join(", "));
+    if let Some(volts) = info.battery_v {
+        println!("  battery: {:.2} V", volts);
+    }
+    println!("{}", rule);
+}
+
+/// Sends `robot_info` to every namespace now, then again on each
+/// announcement.
+pub fn start_announcer(state: AppState, io: SocketIo) {
+    let mut requests = state.robot.announcements.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let info = info(&state);
+            for namespace in [
+                "/",
+                events::FRAMES_NAMESPACE,
+                events::DETECTIONS_NAMESPACE,
+                events::CONTROL_NAMESPACE,
+            ] {
+                if let Some(ns) = io.of(namespace) {
+                    let _ = ns.emit(events::ROBOT_INFO, &info).await;
+                }
+            }
+            match requests.recv().await {
+                Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,239 @@
+//! Enforces the competition's limit on an autonomous run
+//! (`mission.max_autonomous_s`). Operators are warned `warn_before_s`
+//! ahead; when time is up the robot goes idle and background missions that
+//! drive are stopped too, so nothing takes the drive over.
+//!
+//! The clock starts on entering autonomous mode, keeps running across a
+//! switch between missions and stops on any other mode. Each autonomous
+//! stretch is recorded as a session, opened as the run starts unless one
+//! is recording already; its report, noting whether the limit ended the
+//! run, is written as the run ends.
+
+use crate::config::MissionConfig;
+use crate::faults;
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::events;
+use raspibot_protocol::mission::{MissionMode, RunPhase, RunTimerStatus};
+use socketioxide::SocketIo;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+const LIMIT_REASON: &str = "autonomous time limit";
+
+struct ActiveRun {
+    mission: String,
+    started: Instant,
+}
+
+/// A change `RunTimer::follow` saw in the mission mode.
+enum RunEvent {
+    Started(String),
+    /// Mission and length of the run.
+    Ended(String, f32),
+}
+
+#[derive(Default)]
+struct Clock {
+    run: Option<ActiveRun>,
+    /// Mission and length of the run the limit ended, until the next starts.
+    expired: Option<(String, f32)>,
+}
+
+pub struct RunTimer {
+    config: Mutex<MissionConfig>,
+    clock: Mutex<Clock>,
+}
+
+impl RunTimer {
+    pub fn new() -> Self {
+        Self {
+            config: Mutex::new(MissionConfig::default()),
+            clock: Mutex::new(Clock::default()),
+        }
+    }
+
+    /// Takes the limit from the startup config.
+    pub fn configure(&self, config: MissionConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    pub fn status(&self) -> RunTimerStatus {
+        let config = self.config.lock().unwrap().clone();
+        let clock = self.clock.lock().unwrap();
+        let (phase, mission, elapsed_s) = match (&clock.run, &clock.expired) {
+            (Some(run), _) => {
+                let elapsed = run.started.elapsed().as_secs_f32();
+                let warning = config
+                    .max_autonomous_s
+                    .is_some_and(|limit| elapsed >= limit - config.warn_before_s);
+                let phase = if warning {
+                    RunPhase::Warning
+                } else {
+                    RunPhase::Running
+                };
+                (phase, Some(run.mission.clone()), Some(elapsed))
+            }
+            (None, Some((mission, elapsed))) => {
+                (RunPhase::Expired, Some(mission.clone()), Some(*elapsed))
+            }
+            (None, None) => (RunPhase::Stopped, None, None),
+        };
+        let remaining_s = match (phase, elapsed_s) {
+            (RunPhase::Running | RunPhase::Warning, Some(elapsed)) => config
+                .max_autonomous_s
+                .map(|limit| (limit - elapsed).max(0.0)),
+            (RunPhase::Expired, _) => Some(0.0),
+            _ => None,
+        };
+        RunTimerStatus {
+            phase,
+            mission,
+            elapsed_s,
+            remaining_s,
+            limit_s: config.max_autonomous_s,
+            warn_before_s: config.warn_before_s,
+        }
+    }
+
+    /// Starts the clock on entering autonomous mode and stops it on leaving,
+    /// saying when a run started or ended.
+    fn follow(&self, mode: &MissionMode) -> Option<RunEvent> {
+        let mut clock = self.clock.lock().unwrap();
+        match mode {
+            MissionMode::Autonomous { mission } => match clock.run.as_mut() {
+                Some(run) => {
+                    run.mission = mission.clone();
+                    None
+                }
+                None => {
+                    clock.run = Some(ActiveRun {
+                        mission: mission.clone(),
+                        started: Instant::now(),
+                    });
+                    clock.expired = None;
+                    Some(RunEvent::Started(mission.clone()))
+                }
+            },
+            _ => clock
+                .run
+                .take()
+                .map(|run| RunEvent::Ended(run.mission, run.started.elapsed().as_secs_f32())),
+        }
+    }
+
+    /// Ends the run at the limit; `None` if none is running.
+    fn expire(&self) -> Option<(String, f32)> {
+        let mut clock = self.clock.lock().unwrap();
+        let run = clock.run.take()?;
+        let ended = (run.mission, run.started.elapsed().as_secs_f32());
+        clock.expired = Some(ended.clone());
+        Some(ended)
+    }
+}
+
+/// Opens a session for the run, unless one is recording already and takes
+/// the run in.
+fn start_session(state: &AppState, mission: &str) {
+    if state.sessions.active_id().is_some() {
+        return;
+    }
+    match state
+        .sessions
+        .start_for_run(Some(mission.to_string()), state.config_snapshot())
+    {
+        Ok(_) => state.frames.set_exposure_lock(true),
+        Err(e) => println!(
+            "[WARN] Could not open a session for autonomous run '{}': {}",
+            mission, e
+        ),
+    }
+}
+
+/// Records the run in its session and writes the report.
+fn end_session(state: &AppState, mission: &str, duration_s: f32, timed_out: bool) {
+    if state
+        .sessions
+        .end_autonomous(mission, duration_s, timed_out)
+        .is_some()
+    {
+        state.frames.set_exposure_lock(false);
+    }
+}
+
+/// Watches the mission mode, sends the robot to idle at the limit and
+/// publishes the clock once a second during a run and on every phase
+/// change.
+pub fn start_run_timer(state: AppState, io: SocketIo) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut last_phase = RunPhase::Stopped;
+        let mut published = Instant::now();
+        loop {
+            interval.tick().await;
+            if faults::killed("run_timer") {
+                return;
+            }
+            match state.run_timer.follow(&state.mission.mode()) {
+                Some(RunEvent::Started(mission)) => start_session(&state, &mission),
+                Some(RunEvent::Ended(mission, duration_s)) => {
+                    println!(
+                        "[INFO] Autonomous run '{}' ended after {:.1}s",
+                        mission, duration_s
+                    );
+                    end_session(&state, &mission, duration_s, false);
+                }
+                None => {}
+            }
+
+            let mut status = state.run_timer.status();
+            if status.remaining_s == Some(0.0) && status.phase != RunPhase::Expired {
+                if let Some((mission, duration_s)) = state.run_timer.expire() {
+                    println!(
+                        "[WARN] Autonomous time limit reached after {:.1}s, going idle",
+                        duration_s
+                    );
+                    state.mission.set_mode(MissionMode::Idle, LIMIT_REASON);
+                    let driving: Vec<String> = state
+                        .mission
+                        .state()
+                        .background
+                        .into_iter()
+                        .filter(|m| m.drive)
+                        .map(|m| m.name)
+                        .collect();
+                    for name in driving {
+                        state.mission.stop_background(&name, LIMIT_REASON);
+                    }
+                    end_session(&state, &mission, duration_s, true);
+                }
+                status = state.run_timer.status();
+            }
+
+            if status.phase == RunPhase::Warning && last_phase != RunPhase::Warning {
+                println!(
+                    "[WARN] Autonomous run ends in {:.0}s",
+                    status.remaining_s.unwrap_or(0.0)
+                );
+            }
+            let running = matches!(status.phase, RunPhase::Running | RunPhase::Warning);
+            if status.phase != last_phase || (running && published.elapsed() >= PUBLISH_INTERVAL) {
+                let _ = io.emit(events::RUN_TIMER, &status).await;
+                published = Instant::now();
+                last_phase = status.phase;
+            }
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/mission/timer", get(get_status))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<RunTimerStatus> {
+    Json(state.run_timer.status())
+}
@@ -0,0 +1,24 @@
+//! JSON Schemas of the Socket.IO payloads, generated from the protocol
+//! types, so the dashboard and judging integrations can validate what they
+//! receive.
+
+use axum::{extract::Path as UrlPath, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::schema::{event_schemas, EventSchema};
+use std::collections::BTreeMap;
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/schemas", get(list_schemas))
+        .route("/schemas/{event}", get(get_schema))
+}
+
+async fn list_schemas() -> Json<BTreeMap<&'static str, EventSchema>> {
+    Json(event_schemas())
+}
+
+async fn get_schema(UrlPath(event): UrlPath<String>) -> Result<Json<EventSchema>, StatusCode> {
+    event_schemas()
+        .remove(event.as_str())
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
@@ -0,0 +1,472 @@
+//! Serial bridge to the auxiliary microcontroller (Arduino/ESP32) on /dev/ttyAMA0.
+//!
+//! Wire format: every packet is `[kind][seq][payload..][crc16 lo][crc16 hi]`,
+//! COBS-encoded and terminated by a 0x00 byte. Responses reuse the request's
+//! `seq` with the high bit of `kind` set; `seq == 0` is reserved for
+//! unsolicited telemetry pushed by the MCU.
+//!
+//! On chassis where the MCU runs the drive motors and reads the wheel
+//! encoders, `DRIVE` sets both sides at once and the MCU pushes `ODOMETRY`
+//! packets with its encoder counts. Sensor readings and odometry reach the
+//! rest of the backend as broadcast channels; drive commands go through
+//! [`McuMotorDriver`](crate::motors::mcu::McuMotorDriver).
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{broadcast, oneshot, Mutex as AsyncMutex};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+pub const DEFAULT_PORT: &str = "/dev/ttyAMA0";
+pub const DEFAULT_BAUD: u32 = 115_200;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(200);
+const RESPONSE_BIT: u8 = 0x80;
+/// Far beyond any packet the MCU sends; bytes past this without a delimiter
+/// are line noise and dropped up to the next one.
+const MAX_FRAME_BYTES: usize = 1024;
+/// Wait after a failed read, doubling while reads keep failing.
+const READ_RETRY_MIN: Duration = Duration::from_millis(50);
+const READ_RETRY_MAX: Duration = Duration::from_secs(2);
+
+pub mod kind {
+    pub const PING: u8 = 0x01;
+    pub const SET_MOTOR: u8 = 0x10;
+    pub const READ_SENSOR: u8 = 0x11;
+    pub const DRIVE: u8 = 0x12;
+    pub const TELEMETRY: u8 = 0x40;
+    pub const ODOMETRY: u8 = 0x41;
+    pub const NACK: u8 = 0x7F;
+}
+
+#[derive(Debug, Clone)]
+pub struct Packet {
+    pub kind: u8,
+    pub seq: u8,
+    pub payload: Vec<u8>,
+}
+
+/// One sensor reading from an unsolicited telemetry packet.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct SensorValue {
+    pub id: u8,
+    pub value: i32,
+}
+
+/// Wheel encoder counts from an unsolicited odometry packet.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct Odometry {
+    /// Counts since the MCU booted, positive driving forward.
+    pub left_ticks: i32,
+    pub right_ticks: i32,
+    /// The MCU's clock when the counts were read, in milliseconds.
+    pub mcu_ms: u32,
+}
+
+impl Odometry {
+    /// Payload: left and right counts, then the MCU's clock, each a
+    /// little-endian 32-bit integer.
+    fn decode(payload: &[u8]) -> Option<Self> {
+        if payload.len() != 12 {
+            return None;
+        }
+        let word = |i: usize| [payload[i], payload[i + 1], payload[i + 2], payload[i + 3]];
+        Some(Self {
+            left_ticks: i32::from_le_bytes(word(0)),
+            right_ticks: i32::from_le_bytes(word(4)),
+            mcu_ms: u32::from_le_bytes(word(8)),
+        })
+    }
+}
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF), same as the MCU firmware.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 254 + 2);
+    let mut code_idx = 0;
+    out.push(0);
+    let mut code: u8 = 1;
+    for &byte in data {
+        if byte == 0 {
+            out[code_idx] = code;
+            code_idx = out.len();
+            out.push(0);
+            code = 1;
+        } else {
+            out.push(byte);
+            code += 1;
+            if code == 0xFF {
+                out[code_idx] = code;
+                code_idx = out.len();
+                out.push(0);
+                code = 1;
+            }
+        }
+    }
+    out[code_idx] = code;
+    out
+}
+
+pub fn cobs_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let code = data[i] as usize;
+        if code == 0 || i + code > data.len() {
+            return None;
+        }
+        out.extend_from_slice(&data[i + 1..i + code]);
+        i += code;
+        if code < 0xFF && i < data.len() {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.payload.len() + 4);
+        raw.push(self.kind);
+        raw.push(self.seq);
+        raw.extend_from_slice(&self.payload);
+        let crc = crc16(&raw);
+        raw.extend_from_slice(&crc.to_le_bytes());
+
+        let mut framed = cobs_encode(&raw);
+        framed.push(0);
+        framed
+    }
+
+    /// Decodes one frame (without the trailing delimiter), verifying the CRC.
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        let raw = cobs_decode(frame).ok_or_else(|| anyhow!("invalid COBS frame"))?;
+        if raw.len() < 4 {
+            bail!("frame too short ({} bytes)", raw.len());
+        }
+        let (body, crc_bytes) = raw.split_at(raw.len() - 2);
+        let expected = u16::from_le_bytes([crc_bytes[0], crc_bytes[1]]);
+        if crc16(body) != expected {
+            bail!("CRC mismatch");
+        }
+        Ok(Self {
+            kind: body[0],
+            seq: body[1],
+            payload: body[2..].to_vec(),
+        })
+    }
+}
+
+/// Splits the byte stream into frames at the delimiters, dropping one that
+/// grows past `MAX_FRAME_BYTES` up to the next delimiter.
+#[derive(Default)]
+struct Deframer {
+    frame: Vec<u8>,
+    /// Set while skipping an oversized frame.
+    overflowed: bool,
+}
+
+impl Deframer {
+    /// Takes the next byte; returns the frame it completes, if any.
+    fn push(&mut self, byte: u8) -> Option<Vec<u8>> {
+        if byte == 0 {
+            if std::mem::take(&mut self.overflowed) || self.frame.is_empty() {
+                return None;
+            }
+            return Some(std::mem::take(&mut self.frame));
+        }
+        if self.overflowed {
+            return None;
+        }
+        if self.frame.len() >= MAX_FRAME_BYTES {
+            println!(
+                "[WARN] Dropping MCU frame over {} bytes without a delimiter",
+                MAX_FRAME_BYTES
+            );
+            self.frame.clear();
+            self.overflowed = true;
+            return None;
+        }
+        self.frame.push(byte);
+        None
+    }
+}
+
+pub struct McuBridge {
+    writer: AsyncMutex<WriteHalf<SerialStream>>,
+    pending: Mutex<HashMap<u8, oneshot::Sender<Packet>>>,
+    next_seq: AtomicU8,
+    telemetry_tx: broadcast::Sender<Vec<SensorValue>>,
+    odometry_tx: broadcast::Sender<Odometry>,
+}
+
+impl McuBridge {
+    pub fn open(path: &str, baud: u32) -> Result<Arc<Self>> {
+        let port = tokio_serial::new(path, baud).open_native_async()?;
+        let (reader, writer) = tokio::io::split(port);
+        let (telemetry_tx, _) = broadcast::channel(32);
+        let (odometry_tx, _) = broadcast::channel(32);
+
+        let bridge = Arc::new(Self {
+            writer: AsyncMutex::new(writer),
+            pending: Mutex::new(HashMap::new()),
+            next_seq: AtomicU8::new(1),
+            telemetry_tx,
+            odometry_tx,
+        });
+
+        tokio::spawn(Arc::clone(&bridge).read_loop(reader));
+        println!("[OK] Opened MCU serial bridge on {} @ {} baud", path, baud);
+        Ok(bridge)
+    }
+
+    /// Subscribe to sensor telemetry pushed asynchronously by the MCU.
+    pub fn telemetry(&self) -> broadcast::Receiver<Vec<SensorValue>> {
+        self.telemetry_tx.subscribe()
+    }
+
+    /// Subscribe to wheel encoder counts pushed by the MCU.
+    pub fn odometry(&self) -> broadcast::Receiver<Odometry> {
+        self.odometry_tx.subscribe()
+    }
+
+    /// Sends a request and waits for the matching response.
+    pub async fn request(&self, kind: u8, payload: Vec<u8>) -> Result<Packet> {
+        let seq = self.alloc_seq();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(seq, tx);
+
+        let frame = Packet { kind, seq, payload }.encode();
+        if let Err(e) = self.writer.lock().await.write_all(&frame).await {
+            self.pending.lock().unwrap().remove(&seq);
+            return Err(e.into());
+        }
+
+        let response = match tokio::time::timeout(RESPONSE_TIMEOUT, rx).await {
+            Ok(Ok(packet)) => packet,
+            Ok(Err(_)) => bail!("MCU bridge closed"),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&seq);
+                bail!("MCU did not answer request 0x{:02X} (seq {})", kind, seq);
+            }
+        };
+
+        if response.kind == kind::NACK | RESPONSE_BIT {
+            let code = response.payload.first().copied().unwrap_or(0);
+            bail!("MCU rejected request 0x{:02X} with code {}", kind, code);
+        }
+        // The seq wraps, so a late answer to an earlier request can carry
+        // this one's
+        if response.kind != kind | RESPONSE_BIT {
+            bail!(
+                "MCU answered request 0x{:02X} (seq {}) with 0x{:02X}",
+                kind,
+                seq,
+                response.kind
+            );
+        }
+        Ok(response)
+    }
+
+    pub async fn ping(&self) -> Result<()> {
+        self.request(kind::PING, vec![]).await.map(|_| ())
+    }
+
+    /// Sets an MCU-driven motor channel; `speed` is in the range -1000..=1000.
+    pub async fn set_motor(&self, channel: u8, speed: i16) -> Result<()> {
+        let speed = speed.clamp(-1000, 1000);
+        let mut payload = vec![channel];
+        payload.extend_from_slice(&speed.to_le_bytes());
+        self.request(kind::SET_MOTOR, payload).await.map(|_| ())
+    }
+
+    /// Sets both drive sides at once; speeds are in the range -1000..=1000.
+    pub async fn drive(&self, left: i16, right: i16) -> Result<()> {
+        let mut payload = Vec::with_capacity(4);
+        payload.extend_from_slice(&left.clamp(-1000, 1000).to_le_bytes());
+        payload.extend_from_slice(&right.clamp(-1000, 1000).to_le_bytes());
+        self.request(kind::DRIVE, payload).await.map(|_| ())
+    }
+
+    pub async fn read_sensor(&self, id: u8) -> Result<i32> {
+        let response = self.request(kind::READ_SENSOR, vec![id]).await?;
+        match response.payload.as_slice() {
+            [resp_id, a, b, c, d] if *resp_id == id => Ok(i32::from_le_bytes([*a, *b, *c, *d])),
+            _ => bail!("malformed sensor response for id {}", id),
+        }
+    }
+
+    fn alloc_seq(&self) -> u8 {
+        // seq 0 is reserved for telemetry, so skip it on wrap-around
+        loop {
+            let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            if seq != 0 {
+                return seq;
+            }
+        }
+    }
+
+    async fn read_loop(self: Arc<Self>, mut reader: ReadHalf<SerialStream>) {
+        let mut buf = [0u8; 256];
+        let mut deframer = Deframer::default();
+        let mut retry = READ_RETRY_MIN;
+        loop {
+            let n = match reader.read(&mut buf).await {
+                Ok(0) => {
+                    eprintln!("[ERR] MCU serial port closed");
+                    break;
+                }
+                Ok(n) => n,
+                Err(e) => {
+                    eprintln!(
+                        "[ERR] MCU serial read failed, retrying in {} ms: {}",
+                        retry.as_millis(),
+                        e
+                    );
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(READ_RETRY_MAX);
+                    continue;
+                }
+            };
+            retry = READ_RETRY_MIN;
+
+            for frame in buf[..n].iter().filter_map(|&byte| deframer.push(byte)) {
+                match Packet::decode(&frame) {
+                    Ok(packet) => self.dispatch(packet),
+                    Err(e) => println!("[WARN] Dropping MCU frame: {}", e),
+                }
+            }
+        }
+
+        // Fail any in-flight requests instead of letting them time out
+        self.pending.lock().unwrap().clear();
+    }
+
+    fn dispatch(&self, packet: Packet) {
+        if packet.seq == 0 && packet.kind == kind::TELEMETRY {
+            let values = packet
+                .payload
+                .chunks_exact(5)
+                .map(|c| SensorValue {
+                    id: c[0],
+                    value: i32::from_le_bytes([c[1], c[2], c[3], c[4]]),
+                })
+                .collect();
+            let _ = self.telemetry_tx.send(values);
+            return;
+        }
+        if packet.seq == 0 && packet.kind == kind::ODOMETRY {
+            match Odometry::decode(&packet.payload) {
+                Some(odometry) => {
+                    let _ = self.odometry_tx.send(odometry);
+                }
+                None => println!(
+                    "[WARN] Dropping MCU odometry packet of {} bytes",
+                    packet.payload.len()
+                ),
+            }
+            return;
+        }
+
+        if packet.kind & RESPONSE_BIT != 0 {
+            if let Some(tx) = self.pending.lock().unwrap().remove(&packet.seq) {
+                let _ = tx.send(packet);
+                return;
+            }
+        }
+        println!(
+            "[WARN] Unexpected MCU packet kind=0x{:02X} seq={}",
+            packet.kind, packet.seq
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `bytes` through a deframer, collecting the frames.
+    fn deframe(deframer: &mut Deframer, bytes: &[u8]) -> Vec<Vec<u8>> {
+        bytes.iter().filter_map(|&b| deframer.push(b)).collect()
+    }
+
+    #[test]
+    fn crc16_matches_ccitt_false() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+    }
+
+    #[test]
+    fn cobs_round_trips() {
+        let long: Vec<u8> = (0..600).map(|i| (i % 255 + 1) as u8).collect();
+        let cases: [&[u8]; 6] = [&[], &[0], &[0, 0], &[1, 0, 2], &[0x11, 0x22, 0x00], &long];
+        for data in cases {
+            let encoded = cobs_encode(data);
+            assert!(!encoded.contains(&0), "{:?} encodes with a zero", data);
+            assert_eq!(cobs_decode(&encoded).as_deref(), Some(data));
+        }
+    }
+
+    #[test]
+    fn cobs_rejects_a_bad_code() {
+        assert_eq!(cobs_decode(&[0x05, 0x01]), None);
+        assert_eq!(cobs_decode(&[0x00]), None);
+    }
+
+    #[test]
+    fn packet_round_trips() {
+        let packet = Packet {
+            kind: kind::DRIVE,
+            seq: 7,
+            payload: vec![0x00, 0x10, 0xFF, 0x00],
+        };
+        let encoded = packet.encode();
+        assert_eq!(encoded.last(), Some(&0));
+        let decoded = Packet::decode(&encoded[..encoded.len() - 1]).unwrap();
+        assert_eq!(decoded.kind, packet.kind);
+        assert_eq!(decoded.seq, packet.seq);
+        assert_eq!(decoded.payload, packet.payload);
+    }
+
+    #[test]
+    fn packet_rejects_a_bad_crc() {
+        let mut raw = vec![kind::PING | RESPONSE_BIT, 3];
+        let crc = crc16(&raw) ^ 0x0001;
+        raw.extend_from_slice(&crc.to_le_bytes());
+        assert!(Packet::decode(&cobs_encode(&raw)).is_err());
+    }
+
+    #[test]
+    fn deframer_splits_at_delimiters() {
+        let mut deframer = Deframer::default();
+        let frames = deframe(&mut deframer, &[0, 1, 2, 0, 0, 3, 0, 4]);
+        assert_eq!(frames, [vec![1, 2], vec![3]]);
+        assert_eq!(deframe(&mut deframer, &[5, 0]), [vec![4, 5]]);
+    }
+
+    #[test]
+    fn deframer_drops_an_oversized_frame() {
+        let mut deframer = Deframer::default();
+        let noise = vec![0xAA; MAX_FRAME_BYTES + 10];
+        assert!(deframe(&mut deframer, &noise).is_empty());
+        // The rest of the oversized frame goes with it, the next one is whole
+        assert_eq!(deframe(&mut deframer, &[0xAA, 0, 1, 2, 0]), [vec![1, 2]]);
+    }
+}
@@ -0,0 +1,387 @@
+//! Pan/tilt camera gimbal on two hobby servos, driven by software PWM on
+//! GPIO pins or by a PCA9685 PWM board over I2C (`[gimbal]` in the config).
+//!
+//! `POST /gimbal` points it, in degrees from center, and puts it in manual
+//! mode; `POST /gimbal/mode` switches to auto-track, which keeps the
+//! highest-confidence detection of the primary camera centered (the
+//! gimbal's designated target instead, when the operator picked one) with
+//! the `gimbal` visual-servo gains, whose output is a share of the slew
+//! rate. Either way the servos never turn faster than `slew_deg_s` or past
+//! their limits. The emergency stop holds the gimbal where it is.
+
+use crate::config::{GimbalConfig, GimbalDriver, ServoAxisConfig};
+use crate::detections::Published;
+use crate::state::AppState;
+use crate::threads;
+use crate::visual_servo::VisualServo;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::inference::DetectedObject;
+use raspibot_protocol::servo::{GimbalCommand, GimbalMode, GimbalModeRequest, GimbalStatus};
+use raspibot_protocol::target::TargetKind;
+use rppal::gpio::{Gpio, OutputPin};
+use rppal::i2c::I2c;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Visual-servo gains and designated target auto-track uses.
+const CONTROLLER: &str = "gimbal";
+const CONTROL_INTERVAL: Duration = Duration::from_millis(20);
+/// Hobby servos expect a pulse every 20 ms.
+const PWM_HZ: f32 = 50.0;
+
+const PCA9685_MODE1: u8 = 0x00;
+const PCA9685_PRESCALE: u8 = 0xFE;
+const PCA9685_LED0_ON_L: u8 = 0x06;
+const MODE1_RESTART: u8 = 0x80;
+const MODE1_AUTO_INCREMENT: u8 = 0x20;
+const MODE1_SLEEP: u8 = 0x10;
+const PCA9685_OSC_HZ: f32 = 25_000_000.0;
+
+trait PwmOutput: Send {
+    fn set_pulse(&mut self, channel: u8, pulse_us: f32) -> anyhow::Result<()>;
+}
+
+struct GpioPwm {
+    pins: BTreeMap<u8, OutputPin>,
+}
+
+impl GpioPwm {
+    fn open(channels: [u8; 2]) -> rppal::gpio::Result<Self> {
+        let gpio = Gpio::new()?;
+        let mut pins = BTreeMap::new();
+        for channel in channels {
+            pins.insert(channel, gpio.get(channel)?.into_output_low());
+        }
+        Ok(Self { pins })
+    }
+}
+
+impl PwmOutput for GpioPwm {
+    fn set_pulse(&mut self, channel: u8, pulse_us: f32) -> anyhow::Result<()> {
+        let pin = self
+            .pins
+            .get_mut(&channel)
+            .ok_or_else(|| anyhow::anyhow!("GPIO{} is not a gimbal pin", channel))?;
+        pin.set_pwm(
+            Duration::from_secs_f32(1.0 / PWM_HZ),
+            Duration::from_secs_f32(pulse_us / 1e6),
+        )?;
+        Ok(())
+    }
+}
+
+struct Pca9685 {
+    i2c: I2c,
+}
+
+impl Pca9685 {
+    fn open(address: u16) -> rppal::i2c::Result<Self> {
+        let mut i2c = I2c::new()?;
+        i2c.set_slave_address(address)?;
+        // The prescaler can only be set while the oscillator sleeps
+        let prescale = (PCA9685_OSC_HZ / (4096.0 * PWM_HZ)).round() as u8 - 1;
+        i2c.smbus_write_byte(PCA9685_MODE1, MODE1_SLEEP)?;
+        i2c.smbus_write_byte(PCA9685_PRESCALE, prescale)?;
+        i2c.smbus_write_byte(PCA9685_MODE1, MODE1_AUTO_INCREMENT)?;
+        thread::sleep(Duration::from_millis(1));
+        i2c.smbus_write_byte(PCA9685_MODE1, MODE1_AUTO_INCREMENT | MODE1_RESTART)?;
+        Ok(Self { i2c })
+    }
+}
+
+impl PwmOutput for Pca9685 {
+    fn set_pulse(&mut self, channel: u8, pulse_us: f32) -> anyhow::Result<()> {
+        // On at the start of the period, off after the pulse, in 1/4096ths
+        let off = (pulse_us * PWM_HZ / 1e6 * 4096.0)
+            .round()
+            .clamp(0.0, 4095.0) as u16;
+        let [off_low, off_high] = off.to_le_bytes();
+        self.i2c
+            .block_write(PCA9685_LED0_ON_L + 4 * channel, &[0, 0, off_low, off_high])?;
+        Ok(())
+    }
+}
+
+/// Pulse width that turns `axis` to `angle` degrees from center.
+fn pulse_us(axis: &ServoAxisConfig, angle: f32) -> f32 {
+    let angle = if axis.reversed { -angle } else { angle };
+    let span = axis.max_pulse_us - axis.min_pulse_us;
+    axis.min_pulse_us + span * (angle + 90.0) / 180.0
+}
+
+struct Position {
+    mode: GimbalMode,
+    pan: f32,
+    tilt: f32,
+    target_pan: f32,
+    target_tilt: f32,
+    tracking: Option<DetectedObject>,
+}
+
+pub struct Gimbal {
+    output: Mutex<Box<dyn PwmOutput>>,
+    pan: ServoAxisConfig,
+    tilt: ServoAxisConfig,
+    slew_deg_s: f32,
+    position: Mutex<Position>,
+}
+
+impl Gimbal {
+    /// Opens the configured driver and centers the servos, as far as the
+    /// limits allow; `None` when no gimbal is configured.
+    pub fn open(config: &GimbalConfig) -> anyhow::Result<Option<Self>> {
+        let Some(driver) = config.driver else {
+            return Ok(None);
+        };
+        let output: Box<dyn PwmOutput> = match driver {
+            GimbalDriver::Gpio => {
+                Box::new(GpioPwm::open([config.pan.channel, config.tilt.channel])?)
+            }
+            GimbalDriver::Pca9685 => Box::new(Pca9685::open(config.i2c_address)?),
+        };
+        let pan = 0.0f32.clamp(config.pan.min_deg, config.pan.max_deg);
+        let tilt = 0.0f32.clamp(config.tilt.min_deg, config.tilt.max_deg);
+        let gimbal = Self {
+            output: Mutex::new(output),
+            pan: config.pan,
+            tilt: config.tilt,
+            slew_deg_s: config.slew_deg_s,
+            position: Mutex::new(Position {
+                mode: GimbalMode::Manual,
+                pan,
+                tilt,
+                target_pan: pan,
+                target_tilt: tilt,
+                tracking: None,
+            }),
+        };
+        gimbal.write(pan, tilt)?;
+        println!(
+            "[OK] Gimbal on {:?} (pan channel {}, tilt channel {})",
+            driver, config.pan.channel, config.tilt.channel
+        );
+        Ok(Some(gimbal))
+    }
+
+    fn write(&self, pan: f32, tilt: f32) -> anyhow::Result<()> {
+        let mut output = self.output.lock().unwrap();
+        output.set_pulse(self.pan.channel, pulse_us(&self.pan, pan))?;
+        output.set_pulse(self.tilt.channel, pulse_us(&self.tilt, tilt))
+    }
+
+    pub fn status(&self) -> GimbalStatus {
+        let position = self.position.lock().unwrap();
+        GimbalStatus {
+            mode: position.mode,
+            pan: position.pan,
+            tilt: position.tilt,
+            target_pan: position.target_pan,
+            target_tilt: position.target_tilt,
+            tracking: position.tracking.clone(),
+        }
+    }
+
+    fn mode(&self) -> GimbalMode {
+        self.position.lock().unwrap().mode
+    }
+
+    /// Slews to `pan`/`tilt`, held within the limits, in manual mode.
+    pub fn point(&self, pan: f32, tilt: f32) -> GimbalStatus {
+        {
+            let mut position = self.position.lock().unwrap();
+            position.mode = GimbalMode::Manual;
+            position.tracking = None;
+            position.target_pan = pan.clamp(self.pan.min_deg, self.pan.max_deg);
+            position.target_tilt = tilt.clamp(self.tilt.min_deg, self.tilt.max_deg);
+        }
+        self.status()
+    }
+
+    pub fn set_mode(&self, mode: GimbalMode) -> GimbalStatus {
+        {
+            let mut position = self.position.lock().unwrap();
+            position.mode = mode;
+            position.tracking = None;
+        }
+        self.status()
+    }
+
+    /// Stops where it is, in manual mode.
+    pub fn hold(&self) {
+        let mut position = self.position.lock().unwrap();
+        position.mode = GimbalMode::Manual;
+        position.tracking = None;
+        position.target_pan = position.pan;
+        position.target_tilt = position.tilt;
+    }
+
+    /// Moves the auto-track target by `rates` (shares of the slew rate,
+    /// pan right and tilt up positive) for `dt`.
+    fn track(&self, rates: (f32, f32), tracking: Option<DetectedObject>, dt: f32) {
+        let mut position = self.position.lock().unwrap();
+        if position.mode != GimbalMode::AutoTrack {
+            return;
+        }
+        let step = self.slew_deg_s * dt;
+        position.target_pan =
+            (position.target_pan + rates.0 * step).clamp(self.pan.min_deg, self.pan.max_deg);
+        position.target_tilt =
+            (position.target_tilt + rates.1 * step).clamp(self.tilt.min_deg, self.tilt.max_deg);
+        position.tracking = tracking;
+    }
+
+    /// Turns toward the target by at most the slew rate over `dt`.
+    fn step(&self, dt: f32) -> anyhow::Result<()> {
+        let (pan, tilt) = {
+            let mut position = self.position.lock().unwrap();
+            let max = self.slew_deg_s * dt;
+            let pan = position.pan + (position.target_pan - position.pan).clamp(-max, max);
+            let tilt = position.tilt + (position.target_tilt - position.tilt).clamp(-max, max);
+            if pan == position.pan && tilt == position.tilt {
+                return Ok(());
+            }
+            position.pan = pan;
+            position.tilt = tilt;
+            (pan, tilt)
+        };
+        self.write(pan, tilt)
+    }
+}
+
+/// The detection auto-track should center: the gimbal's designated track
+/// while it is in view, the most confident detection otherwise.
+fn pick<'a>(state: &AppState, objects: &'a [DetectedObject]) -> Option<&'a DetectedObject> {
+    let designated = state
+        .targets
+        .all()
+        .remove(CONTROLLER)
+        .and_then(|target| match target.kind {
+            TargetKind::Object { track_id, .. } => track_id,
+            TargetKind::Point => None,
+        });
+    designated
+        .and_then(|id| objects.iter().find(|o| o.track_id == Some(id)))
+        .or_else(|| {
+            objects
+                .iter()
+                .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+        })
+}
+
+/// Rates that center the chosen detection of `published`, and the
+/// detection; no motion when there is none or the set is too old.
+fn follow(
+    state: &AppState,
+    servo: &mut VisualServo,
+    published: &Published,
+    dt: f32,
+) -> ((f32, f32), Option<DetectedObject>) {
+    let Some((width, height)) = state.frames.frame_size() else {
+        return ((0.0, 0.0), None);
+    };
+    let Some(object) = pick(state, &published.objects) else {
+        servo.reset();
+        return ((0.0, 0.0), None);
+    };
+    let [x, y, w, h] = object.bbox;
+    let center = (x as f32 + w as f32 / 2.0, y as f32 + h as f32 / 2.0);
+    match servo.update_aged(center, width, height, dt, published.age()) {
+        // Below center means tilting down
+        Some((pan, tilt)) => ((pan, -tilt), Some(object.clone())),
+        None => ((0.0, 0.0), None),
+    }
+}
+
+/// Runs the slew limiting and auto-track on its own thread.
+pub fn start_gimbal(state: AppState, gimbal: Arc<Gimbal>) {
+    thread::spawn(move || {
+        threads::label("gimbal");
+        let mut servo = state.servo_gains.controller(CONTROLLER);
+        let mut last_seq = None;
+        let mut last_set = Instant::now();
+        let mut rates = (0.0, 0.0);
+        let mut tracking = None;
+        let mut failing = false;
+        let mut last = Instant::now();
+        loop {
+            thread::sleep(CONTROL_INTERVAL);
+            let dt = last.elapsed().as_secs_f32();
+            last = Instant::now();
+
+            if gimbal.mode() == GimbalMode::AutoTrack {
+                if let Some(published) = state.detections.latest() {
+                    if last_seq != Some(published.seq) {
+                        // The first set has nothing to take a rate against
+                        let set_dt = if last_seq.is_some() {
+                            last_set.elapsed().as_secs_f32()
+                        } else {
+                            0.0
+                        };
+                        last_seq = Some(published.seq);
+                        last_set = Instant::now();
+                        (rates, tracking) = follow(&state, &mut servo, &published, set_dt);
+                    }
+                }
+                gimbal.track(rates, tracking.clone(), dt);
+            } else if last_seq.is_some() {
+                // Picks up gains tuned since, and starts without history
+                servo = state.servo_gains.controller(CONTROLLER);
+                last_seq = None;
+                rates = (0.0, 0.0);
+                tracking = None;
+            }
+
+            match gimbal.step(dt) {
+                Ok(()) if failing => {
+                    println!("[OK] Gimbal servos responding again");
+                    failing = false;
+                }
+                Ok(()) => {}
+                Err(e) if !failing => {
+                    eprintln!("[ERR] Could not move the gimbal: {}", e);
+                    failing = true;
+                }
+                Err(_) => {}
+            }
+        }
+    });
+}
+
+pub fn routes(gimbal: Arc<Gimbal>) -> Router {
+    Router::new()
+        .route("/gimbal", get(get_status).post(point))
+        .route("/gimbal/mode", post(set_mode))
+        .with_state(gimbal)
+}
+
+async fn get_status(State(gimbal): State<Arc<Gimbal>>) -> Json<GimbalStatus> {
+    Json(gimbal.status())
+}
+
+async fn point(
+    State(gimbal): State<Arc<Gimbal>>,
+    Json(command): Json<GimbalCommand>,
+) -> Result<Json<GimbalStatus>, (StatusCode, String)> {
+    if !(command.pan.is_finite() && command.tilt.is_finite()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "pan and tilt must be finite".to_string(),
+        ));
+    }
+    Ok(Json(gimbal.point(command.pan, command.tilt)))
+}
+
+async fn set_mode(
+    State(gimbal): State<Arc<Gimbal>>,
+    Json(request): Json<GimbalModeRequest>,
+) -> Json<GimbalStatus> {
+    println!("[INFO] Gimbal mode: {:?}", request.mode);
+    Json(gimbal.set_mode(request.mode))
+}
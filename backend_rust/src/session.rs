@@ -0,0 +1,525 @@
+//! Autonomous run sessions and post-run reports.
+//!
+//! Each run gets a folder under `data/sessions/<id>/`; when the run stops a
+//! `report.json` and a printable `report.html` are written there.
+//!
+//! Sessions are started by the operator (`POST /sessions/start`) or by the
+//! run timer as an autonomous run begins, in which case the end of the run
+//! finishes the session too. An autonomous run inside an operator's session
+//! writes the report as it stands when the run ends, and again at the stop.
+
+use crate::blackbox::{BlackboxWriter, Sample};
+use crate::state::AppState;
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::inference::DetectedObject;
+use raspibot_protocol::session::{
+    AutonomousRun, ClassSummary, Fault, RunReport, Split, SplitRequest, StartSessionRequest,
+    StartSessionResponse,
+};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+pub const SESSIONS_DIR: &str = "data/sessions";
+/// Start time and config snapshot, written when a run starts.
+pub const SESSION_META_FILE: &str = "session.json";
+
+struct ActiveRun {
+    id: String,
+    name: String,
+    started: Instant,
+    started_unix: u64,
+    distance_m: f32,
+    detections: BTreeMap<String, ClassSummary>,
+    /// Tracks already counted in `detections`. Every camera's tracker takes
+    /// its ids from the shared re-id gallery, so an id is one object across
+    /// cameras.
+    counted_tracks: HashSet<u64>,
+    faults: Vec<Fault>,
+    battery_start_v: Option<f32>,
+    battery_end_v: Option<f32>,
+    splits: Vec<Split>,
+    autonomous: Vec<AutonomousRun>,
+    blackbox: Option<BlackboxWriter>,
+    /// Opened for an autonomous run, which finishes it when it ends.
+    ends_with_run: bool,
+}
+
+impl ActiveRun {
+    fn log(&mut self, stream: &str, values: Map<String, Value>) {
+        let sample = Sample {
+            t_s: self.started.elapsed().as_secs_f64(),
+            stream: stream.to_string(),
+            values,
+        };
+        if let Some(blackbox) = self.blackbox.as_mut() {
+            if let Err(e) = blackbox.write(&sample) {
+                eprintln!("[ERR] Blackbox write failed, recording stopped: {}", e);
+                self.blackbox = None;
+            }
+        }
+    }
+
+    fn elapsed_s(&self) -> f32 {
+        self.started.elapsed().as_secs_f32()
+    }
+
+    /// The report as the run stands.
+    fn report(&self) -> RunReport {
+        let battery_delta_v = match (self.battery_start_v, self.battery_end_v) {
+            (Some(start), Some(end)) => Some(end - start),
+            _ => None,
+        };
+        RunReport {
+            duration_s: self.elapsed_s(),
+            id: self.id.clone(),
+            name: self.name.clone(),
+            started_unix: self.started_unix,
+            distance_m: self.distance_m,
+            detections: self.detections.clone(),
+            faults: self.faults.clone(),
+            battery_start_v: self.battery_start_v,
+            battery_end_v: self.battery_end_v,
+            battery_delta_v,
+            splits: self.splits.clone(),
+            autonomous: self.autonomous.clone(),
+        }
+    }
+}
+
+pub struct SessionManager {
+    root: PathBuf,
+    active: Mutex<Option<ActiveRun>>,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self {
+            root: PathBuf::from(SESSIONS_DIR),
+            active: Mutex::new(None),
+        }
+    }
+
+    pub fn session_dir(&self, id: &str) -> Option<PathBuf> {
+        // Ids come from URLs; refuse anything that could escape the sessions dir
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| self.root.join(id))
+    }
+
+    pub fn active_id(&self) -> Option<String> {
+        self.active.lock().unwrap().as_ref().map(|r| r.id.clone())
+    }
+
+    /// Starts a run, returning its id. Fails if one is already in progress.
+    /// `config` is a snapshot of the robot's settings, kept with the run as
+    /// `session.json` so exports show what the run was configured with.
+    pub fn start(&self, name: Option<String>, config: Value) -> Result<String, String> {
+        self.open(name, config, false)
+    }
+
+    /// Starts a run that the next autonomous run ends, as `start` does.
+    pub fn start_for_run(&self, name: Option<String>, config: Value) -> Result<String, String> {
+        self.open(name, config, true)
+    }
+
+    fn open(
+        &self,
+        name: Option<String>,
+        config: Value,
+        ends_with_run: bool,
+    ) -> Result<String, String> {
+        let mut active = self.active.lock().unwrap();
+        if let Some(run) = active.as_ref() {
+            return Err(format!("run {} already in progress", run.id));
+        }
+        let started_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let started_unix = started_unix_ms / 1000;
+        // Milliseconds, so runs started within a second get their own folders
+        let id = format!("run-{}", started_unix_ms);
+        let dir = self.root.join(&id);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let meta = serde_json::json!({
+            "id": id,
+            "started_unix_ms": started_unix_ms,
+            "config": config,
+        });
+        if let Err(e) = std::fs::write(
+            dir.join(SESSION_META_FILE),
+            serde_json::to_string_pretty(&meta).unwrap_or_default(),
+        ) {
+            println!(
+                "[WARN] Could not write {} for {}: {}",
+                SESSION_META_FILE, id, e
+            );
+        }
+        let blackbox = match BlackboxWriter::create(&dir) {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                println!("[WARN] Telemetry blackbox disabled for {}: {}", id, e);
+                None
+            }
+        };
+
+        println!("[INFO] Session {} started", id);
+        *active = Some(ActiveRun {
+            name: name.unwrap_or_else(|| id.clone()),
+            id: id.clone(),
+            started: Instant::now(),
+            started_unix,
+            distance_m: 0.0,
+            detections: BTreeMap::new(),
+            counted_tracks: HashSet::new(),
+            faults: Vec::new(),
+            battery_start_v: None,
+            battery_end_v: None,
+            splits: Vec::new(),
+            autonomous: Vec::new(),
+            blackbox,
+            ends_with_run,
+        });
+        Ok(id)
+    }
+
+    /// Ends the active run and writes its report into the session folder.
+    pub fn stop(&self) -> Option<RunReport> {
+        let run = self.active.lock().unwrap().take()?;
+        Some(self.finish(run))
+    }
+
+    fn finish(&self, mut run: ActiveRun) -> RunReport {
+        if let Some(Err(e)) = run.blackbox.as_mut().map(|b| b.flush()) {
+            eprintln!("[ERR] Could not flush blackbox for {}: {}", run.id, e);
+        }
+        let report = run.report();
+        if let Err(e) = self.write_report(&report) {
+            eprintln!("[ERR] Could not write report for {}: {}", report.id, e);
+        }
+        println!(
+            "[OK] Session {} finished after {:.1}s",
+            report.id, report.duration_s
+        );
+        report
+    }
+
+    fn with_run(&self, f: impl FnOnce(&mut ActiveRun)) {
+        if let Some(run) = self.active.lock().unwrap().as_mut() {
+            f(run);
+        }
+    }
+
+    pub fn record_distance(&self, delta_m: f32) {
+        self.with_run(|run| run.distance_m += delta_m.abs());
+    }
+
+    /// Adds a published set to the report's summary: a tracked object
+    /// counts once however long it stays in view; an untracked one, which
+    /// can't be told apart from set to set, only raises the best
+    /// confidence. The sets themselves go to the blackbox separately (see
+    /// `detections`).
+    pub fn record_detections(&self, objects: &[DetectedObject]) {
+        self.with_run(|run| {
+            for object in objects {
+                let new = object
+                    .track_id
+                    .is_some_and(|id| run.counted_tracks.insert(id));
+                let entry = run.detections.entry(object.class.clone()).or_default();
+                if new {
+                    entry.count += 1;
+                }
+                entry.best_confidence = entry.best_confidence.max(object.confidence);
+            }
+        });
+    }
+
+    /// Appends a sample to the run's blackbox under `stream` (e.g. `imu`,
+    /// `drive`); keys become columns in the exported tables.
+    pub fn record_telemetry(&self, stream: &str, values: Map<String, Value>) {
+        self.with_run(|run| run.log(stream, values));
+    }
+
+    pub fn record_fault(&self, message: impl Into<String>) {
+        self.with_run(|run| {
+            let t_s = run.elapsed_s();
+            run.faults.push(Fault {
+                t_s,
+                message: message.into(),
+            });
+        });
+    }
+
+    pub fn record_battery(&self, volts: f32) {
+        self.with_run(|run| {
+            run.battery_start_v.get_or_insert(volts);
+            run.battery_end_v = Some(volts);
+        });
+    }
+
+    pub fn split(&self, label: impl Into<String>) {
+        self.with_run(|run| {
+            let split = Split {
+                label: label.into(),
+                t_s: run.elapsed_s(),
+                distance_m: run.distance_m,
+            };
+            run.splits.push(split);
+        });
+    }
+
+    /// An autonomous stretch that just ended after `duration_s`. Finishes
+    /// the session when it was opened for the run, returning the report;
+    /// otherwise writes the report as it stands.
+    pub fn end_autonomous(
+        &self,
+        mission: &str,
+        duration_s: f32,
+        timed_out: bool,
+    ) -> Option<RunReport> {
+        let mut active = self.active.lock().unwrap();
+        let run = active.as_mut()?;
+        let t_s = (run.elapsed_s() - duration_s).max(0.0);
+        run.autonomous.push(AutonomousRun {
+            mission: mission.to_string(),
+            t_s,
+            duration_s,
+            timed_out,
+        });
+        if run.ends_with_run {
+            let run = active.take()?;
+            drop(active);
+            return Some(self.finish(run));
+        }
+        let report = run.report();
+        drop(active);
+        if let Err(e) = self.write_report(&report) {
+            eprintln!("[ERR] Could not write report for {}: {}", report.id, e);
+        }
+        None
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        let mut ids: Vec<String> = std::fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().is_dir())
+                    .filter_map(|e| e.file_name().into_string().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        ids.sort();
+        ids
+    }
+
+    fn write_report(&self, report: &RunReport) -> std::io::Result<()> {
+        let dir = self.root.join(&report.id);
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("report.json"),
+            serde_json::to_string_pretty(report)?,
+        )?;
+        std::fs::write(dir.join("report.html"), render_html(report))
+    }
+}
+
+/// Notes camera restarts among the active run's faults; the emergency stop
+/// notes its own where it engages.
+pub fn start_fault_recorder(state: AppState) {
+    for camera in state.cameras.iter() {
+        let mut restarts = camera.frames.subscribe_restarts();
+        let sessions = state.sessions.clone();
+        tokio::spawn(async move {
+            loop {
+                match restarts.recv().await {
+                    Ok(restart) => sessions.record_fault(format!(
+                        "camera '{}' restarted ({:?}): {}",
+                        restart.camera, restart.cause, restart.detail
+                    )),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn render_html(r: &RunReport) -> String {
+    let volts = |v: Option<f32>| v.map(|v| format!("{:.2} V", v)).unwrap_or("-".into());
+
+    let detections: String = r
+        .detections
+        .iter()
+        .map(|(class, s)| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.2}</td></tr>",
+                escape(class),
+                s.count,
+                s.best_confidence
+            )
+        })
+        .collect();
+    let splits: String = r
+        .splits
+        .iter()
+        .map(|s| {
+            format!(
+                "<tr><td>{}</td><td>{:.1}s</td><td>{:.2} m</td></tr>",
+                escape(&s.label),
+                s.t_s,
+                s.distance_m
+            )
+        })
+        .collect();
+    let faults: String = r
+        .faults
+        .iter()
+        .map(|f| {
+            format!(
+                "<tr><td>{:.1}s</td><td>{}</td></tr>",
+                f.t_s,
+                escape(&f.message)
+            )
+        })
+        .collect();
+    let autonomous: String = r
+        .autonomous
+        .iter()
+        .map(|a| {
+            let ended_by = if a.timed_out {
+                "time limit"
+            } else {
+                "mode change"
+            };
+            format!(
+                "<tr><td>{}</td><td>{:.1}s</td><td>{:.1}s</td><td>{}</td></tr>",
+                escape(&a.mission),
+                a.t_s,
+                a.duration_s,
+                ended_by
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>Run report {id}</title>
+<style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse;margin-bottom:1.5em}}td,th{{border:1px solid #ccc;padding:4px 10px;text-align:left}}</style>
+</head><body>
+<h1>{name}</h1>
+<table>
+<tr><th>Session</th><td>{id}</td></tr>
+<tr><th>Duration</th><td>{duration:.1}s</td></tr>
+<tr><th>Distance</th><td>{distance:.2} m</td></tr>
+<tr><th>Battery</th><td>{b_start} &rarr; {b_end} ({b_delta})</td></tr>
+</table>
+<h2>Detections</h2>
+<table><tr><th>Class</th><th>Count</th><th>Best confidence</th></tr>{detections}</table>
+<h2>Autonomous</h2>
+<table><tr><th>Mission</th><th>Start</th><th>Duration</th><th>Ended by</th></tr>{autonomous}</table>
+<h2>Splits</h2>
+<table><tr><th>Label</th><th>Time</th><th>Distance</th></tr>{splits}</table>
+<h2>Faults</h2>
+<table><tr><th>Time</th><th>Message</th></tr>{faults}</table>
+</body></html>
+"#,
+        id = escape(&r.id),
+        name = escape(&r.name),
+        duration = r.duration_s,
+        distance = r.distance_m,
+        b_start = volts(r.battery_start_v),
+        b_end = volts(r.battery_end_v),
+        b_delta = volts(r.battery_delta_v),
+        detections = detections,
+        autonomous = autonomous,
+        splits = splits,
+        faults = faults,
+    )
+}
+
+/// Run start/stop also locks and releases camera exposure, so lighting
+/// changes along the course do not trigger AE hunting mid-run.
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/start", post(start_session))
+        .route("/sessions/stop", post(stop_session))
+        .route("/sessions/split", post(split_session))
+        .route("/sessions/{id}/report", get(get_report))
+        .route("/sessions/{id}/report.html", get(get_report_html))
+        .with_state(state)
+}
+
+async fn list_sessions(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(state.sessions.list())
+}
+
+async fn start_session(
+    State(state): State<AppState>,
+    Json(req): Json<StartSessionRequest>,
+) -> Response {
+    match state.sessions.start(req.name, state.config_snapshot()) {
+        Ok(id) => {
+            state.frames.set_exposure_lock(true);
+            Json(StartSessionResponse { id }).into_response()
+        }
+        Err(e) => (StatusCode::CONFLICT, e).into_response(),
+    }
+}
+
+async fn stop_session(State(state): State<AppState>) -> Response {
+    match state.sessions.stop() {
+        Some(report) => {
+            state.frames.set_exposure_lock(false);
+            Json(report).into_response()
+        }
+        None => (StatusCode::CONFLICT, "no run in progress").into_response(),
+    }
+}
+
+async fn split_session(State(state): State<AppState>, Json(req): Json<SplitRequest>) -> StatusCode {
+    if state.sessions.active_id().is_none() {
+        return StatusCode::CONFLICT;
+    }
+    state.sessions.split(req.label);
+    StatusCode::NO_CONTENT
+}
+
+async fn get_report(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    let Some(dir) = state.sessions.session_dir(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match std::fs::read_to_string(dir.join("report.json")) {
+        Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn get_report_html(State(state): State<AppState>, UrlPath(id): UrlPath<String>) -> Response {
+    let Some(dir) = state.sessions.session_dir(&id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match std::fs::read_to_string(dir.join("report.html")) {
+        Ok(html) => Html(html).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
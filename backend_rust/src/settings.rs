@@ -0,0 +1,194 @@
+//! Bulk export/import of the persisted tuning (servo gains, zones, boundary,
+//! virtual bumper, steering mixing, adaptive threshold, image quality
+//! thresholds, viewer limits, privacy masks, unit calibration, class display
+//! names) as one JSON document.
+//!
+//! An import is validated as a whole before anything is written, so a bad
+//! document never leaves the robot half-configured, and applied with the
+//! capture/inference pipeline paused, so no frame is processed with half of
+//! it. `?dry_run=true` only reports which settings would change.
+
+use crate::state::AppState;
+use crate::{
+    adaptive, boundary, bumper, labels, privacy, quality, steering, units, visual_servo, zones,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::settings::{ImportQuery, ImportReport, SettingsDocument};
+
+pub fn export(state: &AppState) -> SettingsDocument {
+    SettingsDocument {
+        servo_gains: Some(state.servo_gains.all()),
+        zones: Some(state.zones.get()),
+        boundary: Some(state.boundary.config()),
+        bumper: Some(state.bumper.config()),
+        steering: Some(state.steering.config()),
+        adaptive_threshold: Some(state.adaptive.config()),
+        image_quality: Some(state.quality.config()),
+        viewer_limits: Some(state.viewers.limits()),
+        privacy_masks: Some(state.frames.masks().get()),
+        units: Some(state.units.get()),
+        class_labels: Some(state.labels.config()),
+    }
+}
+
+/// Every problem in the document, not just the first.
+fn validate(doc: &SettingsDocument) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, gains) in doc.servo_gains.iter().flatten() {
+        if let Err(e) = visual_servo::validate(gains) {
+            errors.push(format!("servo_gains.{}: {}", name, e));
+        }
+    }
+    for zone in doc.zones.iter().flat_map(|z| &z.zones) {
+        if let Err(e) = zones::validate(zone) {
+            errors.push(format!("zones: {}", e));
+        }
+    }
+    if let Some(Err(e)) = doc.boundary.as_ref().map(boundary::validate) {
+        errors.push(format!("boundary: {}", e));
+    }
+    if let Some(Err(e)) = doc.bumper.as_ref().map(bumper::validate) {
+        errors.push(format!("bumper: {}", e));
+    }
+    if let Some(Err(e)) = doc.steering.as_ref().map(steering::validate) {
+        errors.push(format!("steering: {}", e));
+    }
+    if let Some(Err(e)) = doc.adaptive_threshold.as_ref().map(adaptive::validate) {
+        errors.push(format!("adaptive_threshold: {}", e));
+    }
+    if let Some(Err(e)) = doc.image_quality.as_ref().map(quality::validate) {
+        errors.push(format!("image_quality: {}", e));
+    }
+    for mask in doc.privacy_masks.iter().flatten() {
+        if let Err(e) = privacy::validate_mask(mask) {
+            errors.push(format!("privacy_masks: {}", e));
+        }
+    }
+    if let Some(Err(e)) = doc.units.as_ref().map(units::validate) {
+        errors.push(format!("units: {}", e));
+    }
+    if let Some(Err(e)) = doc.class_labels.as_ref().map(labels::validate) {
+        errors.push(format!("class_labels: {}", e));
+    }
+    errors
+}
+
+fn changes(current: &SettingsDocument, doc: &SettingsDocument) -> Vec<String> {
+    let mut changed = Vec::new();
+    let current_gains = current.servo_gains.clone().unwrap_or_default();
+    for (name, gains) in doc.servo_gains.iter().flatten() {
+        if current_gains.get(name) != Some(gains) {
+            changed.push(format!("servo_gains.{}", name));
+        }
+    }
+    if doc.zones.is_some() && doc.zones != current.zones {
+        changed.push("zones".to_string());
+    }
+    if doc.boundary.is_some() && doc.boundary != current.boundary {
+        changed.push("boundary".to_string());
+    }
+    if doc.bumper.is_some() && doc.bumper != current.bumper {
+        changed.push("bumper".to_string());
+    }
+    if doc.steering.is_some() && doc.steering != current.steering {
+        changed.push("steering".to_string());
+    }
+    if doc.adaptive_threshold.is_some() && doc.adaptive_threshold != current.adaptive_threshold {
+        changed.push("adaptive_threshold".to_string());
+    }
+    if doc.image_quality.is_some() && doc.image_quality != current.image_quality {
+        changed.push("image_quality".to_string());
+    }
+    if doc.viewer_limits.is_some() && doc.viewer_limits != current.viewer_limits {
+        changed.push("viewer_limits".to_string());
+    }
+    if doc.privacy_masks.is_some() && doc.privacy_masks != current.privacy_masks {
+        changed.push("privacy_masks".to_string());
+    }
+    if doc.units.is_some() && doc.units != current.units {
+        changed.push("units".to_string());
+    }
+    if doc.class_labels.is_some() && doc.class_labels != current.class_labels {
+        changed.push("class_labels".to_string());
+    }
+    changed
+}
+
+fn apply(state: &AppState, doc: SettingsDocument) -> std::io::Result<()> {
+    let _paused = state.frames.pipeline().pause();
+    for (name, gains) in doc.servo_gains.into_iter().flatten() {
+        state.servo_gains.set(&name, gains)?;
+    }
+    if let Some(config) = doc.zones {
+        state.zones.set(config)?;
+    }
+    if let Some(config) = doc.boundary {
+        state.boundary.set_config(config)?;
+    }
+    if let Some(config) = doc.bumper {
+        state.bumper.set_config(config)?;
+    }
+    if let Some(config) = doc.steering {
+        state.steering.set_config(config)?;
+    }
+    if let Some(config) = doc.adaptive_threshold {
+        adaptive::apply_config(state, config)?;
+    }
+    if let Some(config) = doc.image_quality {
+        state.quality.set_config(config)?;
+    }
+    if let Some(limits) = doc.viewer_limits {
+        state.viewers.set_limits(limits);
+    }
+    if let Some(masks) = doc.privacy_masks {
+        state.frames.masks().set(masks)?;
+    }
+    if let Some(calibration) = doc.units {
+        state.units.set(calibration)?;
+    }
+    if let Some(labels) = doc.class_labels {
+        state.labels.set_config(labels)?;
+    }
+    Ok(())
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/settings/export", get(export_settings))
+        .route("/settings/import", post(import_settings))
+        .with_state(state)
+}
+
+async fn export_settings(State(state): State<AppState>) -> Json<SettingsDocument> {
+    Json(export(&state))
+}
+
+async fn import_settings(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    Json(doc): Json<SettingsDocument>,
+) -> Result<Json<ImportReport>, (StatusCode, String)> {
+    let errors = validate(&doc);
+    if !errors.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, errors.join("; ")));
+    }
+    let changed = changes(&export(&state), &doc);
+    if !query.dry_run {
+        // Pausing waits for the inference pass in flight
+        let applying = state.clone();
+        tokio::task::spawn_blocking(move || apply(&applying, doc))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        println!("[INFO] Settings imported ({} changed)", changed.len());
+    }
+    Ok(Json(ImportReport {
+        dry_run: query.dry_run,
+        changed,
+    }))
+}
@@ -0,0 +1,519 @@
+//! Socket.IO layer for the dashboard.
+//!
+//! On connect the server pushes a `state_snapshot` before anything else, and
+//! clients can ask again at any time with a `sync` event (answered via ack),
+//! so a reconnecting dashboard never has to piece state together from deltas.
+//!
+//! Right after the snapshot the server offers its API versions; until the
+//! client picks one it gets version 0 payloads.
+//!
+//! Two more namespaces carry single streams for clients that want nothing
+//! else: `/frames` (JPEG camera frames) and `/detections`; teleoperation has
+//! its own, `/control` (see [`crate::teleop`]). Every namespace gets
+//! `robot_info` on connect, so any client can check which robot it reached.
+//!
+//! Detection sets carry class display names in the language the client
+//! asked for with `?locale=` on the handshake (see [`crate::labels`]).
+
+use crate::annotate;
+use crate::arbiter::CommandSource;
+use crate::dispatch::Decimation;
+use crate::estop;
+use crate::labels;
+use crate::power;
+use crate::robot;
+use crate::state::AppState;
+use crate::teleop;
+use crate::version::{self, emit_versioned};
+use crate::viewers;
+use axum::extract::ConnectInfo;
+use base64::Engine;
+use opencv::prelude::*;
+use raspibot_protocol::camera::Frame;
+use raspibot_protocol::drive::{DriveCommand, EStopRequest};
+use raspibot_protocol::events;
+use raspibot_protocol::locale::Locale;
+use raspibot_protocol::mission::ApprovalDecision;
+use raspibot_protocol::overlay::OverlayPrimitive;
+use raspibot_protocol::version::{
+    VersionOffer, VersionRequest, VersionSelection, CURRENT, SUPPORTED,
+};
+use socketioxide::{
+    extract::{AckSender, Data, SocketRef, State},
+    layer::SocketIoLayer,
+    SocketIo,
+};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, Notify};
+
+const DEFAULT_FRAME_FPS: f32 = 10.0;
+const DEFAULT_FRAME_QUALITY: i32 = 70;
+
+/// Clients on the frames namespace; nothing is encoded while there are none.
+static FRAME_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn build_layer(state: AppState) -> (SocketIoLayer, SocketIo) {
+    let (layer, io) = SocketIo::builder().with_state(state).build_layer();
+    io.ns("/", on_connect);
+    io.ns(
+        events::FRAMES_NAMESPACE,
+        |socket: SocketRef, State(state): State<AppState>| {
+            FRAME_CLIENTS.fetch_add(1, Ordering::Relaxed);
+            println!("[INFO] Socket.IO frame client connected: {}", socket.id);
+            socket.emit(events::ROBOT_INFO, &robot::info(&state)).ok();
+            socket.on_disconnect(|socket: SocketRef| {
+                FRAME_CLIENTS.fetch_sub(1, Ordering::Relaxed);
+                println!("[INFO] Socket.IO frame client disconnected: {}", socket.id);
+            });
+        },
+    );
+    io.ns(
+        events::DETECTIONS_NAMESPACE,
+        |socket: SocketRef, State(state): State<AppState>| {
+            println!("[INFO] Socket.IO detection client connected: {}", socket.id);
+            socket.join(labels::room(labels::from_handshake(
+                socket.req_parts().uri.query(),
+            )));
+            socket.emit(events::ROBOT_INFO, &robot::info(&state)).ok();
+        },
+    );
+    io.ns(events::CONTROL_NAMESPACE, teleop::on_connect);
+    (layer, io)
+}
+
+/// Pushes server-side changes that every dashboard should see right away.
+pub fn spawn_broadcasts(state: &AppState, io: SocketIo) {
+    let mut mission = state.mission.subscribe();
+    let mission_io = io.clone();
+    tokio::spawn(async move {
+        while let Ok(change) = mission.recv().await {
+            emit_versioned(&mission_io, events::MISSION_STATE, &change).await;
+        }
+    });
+
+    let mut progress = state.executor.subscribe();
+    let progress_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match progress.recv().await {
+                Ok(progress) => {
+                    emit_versioned(&progress_io, events::MISSION_PROGRESS, &progress).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut targets = state.targets.subscribe();
+    let target_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match targets.recv().await {
+                Ok(change) => {
+                    let _ = target_io.emit(events::TARGET, &change).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut power = state.power.subscribe();
+    let power_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match power.recv().await {
+                Ok(status) => {
+                    let _ = power_io.emit(events::POWER, &status).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut devices = state.devices.subscribe();
+    let devices_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match devices.recv().await {
+                Ok(change) => {
+                    let _ = devices_io.emit(events::DEVICE, &change).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut quality = state.quality.subscribe();
+    let quality_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match quality.recv().await {
+                Ok(change) => {
+                    let _ = quality_io.emit(events::IMAGE_QUALITY, &change).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut estop = state.estop.subscribe();
+    let estop_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match estop.recv().await {
+                Ok(status) => {
+                    let _ = estop_io.emit(events::ESTOP_STATE, &status).await;
+                    if let Some(ns) = estop_io.of(events::CONTROL_NAMESPACE) {
+                        let _ = ns.emit(events::ESTOP_STATE, &status).await;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let mut camera_health = state.frames.subscribe_health();
+    let camera_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match camera_health.recv().await {
+                Ok(health) => {
+                    let _ = camera_io.emit(events::CAMERA_HEALTH, &health).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    for camera in state.cameras.iter() {
+        let mut restarts = camera.frames.subscribe_restarts();
+        let restart_io = io.clone();
+        tokio::spawn(async move {
+            loop {
+                match restarts.recv().await {
+                    Ok(restart) => {
+                        let _ = restart_io.emit(events::CAMERA_RESTART, &restart).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    let mut telemetry = state.telemetry.subscribe();
+    let telemetry_io = io.clone();
+    tokio::spawn(async move {
+        loop {
+            match telemetry.recv().await {
+                Ok(aggregate) => {
+                    let _ = telemetry_io.emit(events::TELEMETRY, &aggregate).await;
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    if let Some(thermal) = &state.thermal {
+        let mut frames = thermal.subscribe();
+        let thermal_io = io.clone();
+        tokio::spawn(async move {
+            loop {
+                match frames.recv().await {
+                    Ok(frame) => {
+                        let _ = thermal_io.emit(events::THERMAL, &frame).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    // Tagged on the way out, so the age includes time spent queued here
+    let hub = state.detections.clone();
+    let labels = state.labels.clone();
+    let mut detections = hub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match detections.recv().await {
+                Ok(published) => {
+                    let set = published.to_set(&hub.limits());
+                    let detections_ns = io.of(events::DETECTIONS_NAMESPACE);
+                    for locale in Locale::ALL {
+                        let room = labels::room(locale);
+                        let set = labels.localize(set.clone(), locale);
+                        let _ = io.to(room.clone()).emit(events::DETECTIONS, &set).await;
+                        if let Some(ns) = &detections_ns {
+                            let _ = ns.to(room).emit(events::DETECTIONS, &set).await;
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Streams camera frames on the frames namespace, at most `SOCKET_FRAME_FPS`
+/// per second (default 10) and at JPEG quality `SOCKET_FRAME_QUALITY`
+/// (default 70); faces blurred when face blur is on, annotated when
+/// `stream.annotate` is set.
+pub fn spawn_frame_stream(state: &AppState, io: SocketIo) {
+    let fps = std::env::var("SOCKET_FRAME_FPS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_FRAME_FPS);
+    let quality = std::env::var("SOCKET_FRAME_QUALITY")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_FRAME_QUALITY);
+    let subscription = state.frames.subscribe("socketio", Decimation::MaxFps(fps));
+    let state = state.clone();
+    let annotate = state
+        .stream
+        .annotate
+        .then(|| state.cameras.primary().clone());
+    tokio::spawn(async move {
+        let mut seq = 0;
+        loop {
+            let frame = subscription.recv().await;
+            if FRAME_CLIENTS.load(Ordering::Relaxed) == 0 {
+                continue;
+            }
+            let captured_unix_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            let (width, height) = (frame.cols(), frame.rows());
+            let state = state.clone();
+            let annotate = annotate.clone();
+            let jpeg = match tokio::task::spawn_blocking(move || {
+                // The frame is shared by every client, so labels stay English
+                let annotate = annotate
+                    .as_ref()
+                    .map(|camera| (camera.as_ref(), Locale::En));
+                annotate::publish_jpeg(&state, &frame, quality, annotate)
+            })
+            .await
+            {
+                Ok(Ok(jpeg)) => jpeg,
+                Ok(Err(e)) => {
+                    println!("[WARN] Could not encode frame for Socket.IO: {}", e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            seq += 1;
+            let payload = Frame {
+                seq,
+                width,
+                height,
+                captured_unix_ms,
+                jpeg: base64::engine::general_purpose::STANDARD.encode(jpeg),
+            };
+            if let Some(ns) = io.of(events::FRAMES_NAMESPACE) {
+                let _ = ns.emit(events::FRAME, &payload).await;
+            }
+        }
+    });
+}
+
+/// Approximate wire size of a payload, for per-client bandwidth accounting.
+fn payload_len<T: serde::Serialize>(value: &T) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+/// Sends a snapshot in the version the client picked.
+fn send_snapshot(socket: &SocketRef, state: &AppState, guard: &viewers::ViewerGuard) {
+    let version = state.api_versions.get(&socket.id.to_string());
+    let snapshot = version::convert(&state.snapshot(), version);
+    guard.record_sent(payload_len(&snapshot));
+    if let Err(e) = socket.emit(events::STATE_SNAPSHOT, &snapshot) {
+        println!(
+            "[WARN] Could not send state snapshot to {}: {}",
+            socket.id, e
+        );
+    }
+}
+
+fn on_connect(socket: SocketRef, State(state): State<AppState>) {
+    let addr = socket
+        .req_parts()
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0);
+    let Some(guard) = state.viewers.join(viewers::SOCKET_IO, addr) else {
+        socket.disconnect().ok();
+        return;
+    };
+    let guard = Arc::new(guard);
+    println!(
+        "[INFO] Socket.IO client connected: {} (viewer {})",
+        socket.id,
+        guard.id()
+    );
+
+    socket.join(version::room(0));
+    socket.join(labels::room(labels::from_handshake(
+        socket.req_parts().uri.query(),
+    )));
+    send_snapshot(&socket, &state, &guard);
+    let offer = VersionOffer {
+        supported: SUPPORTED.to_vec(),
+        current: CURRENT,
+    };
+    socket.emit(events::API_VERSIONS, &offer).ok();
+    socket.emit(events::ROBOT_INFO, &robot::info(&state)).ok();
+    socket.emit(events::ESTOP_STATE, &state.estop.status()).ok();
+
+    let select_guard = Arc::clone(&guard);
+    socket.on(
+        events::SELECT_API_VERSION,
+        move |socket: SocketRef,
+              Data(request): Data<VersionRequest>,
+              ack: AckSender,
+              State(state): State<AppState>| {
+            let id = socket.id.to_string();
+            let previous = state.api_versions.get(&id);
+            if !SUPPORTED.contains(&request.version) {
+                ack.send(&VersionSelection {
+                    version: previous,
+                    accepted: false,
+                })
+                .ok();
+                return;
+            }
+            socket.leave(version::room(previous));
+            socket.join(version::room(request.version));
+            state.api_versions.set(&id, request.version);
+            ack.send(&VersionSelection {
+                version: request.version,
+                accepted: true,
+            })
+            .ok();
+            // The snapshot sent on connect was in the old version
+            send_snapshot(&socket, &state, &select_guard);
+        },
+    );
+
+    let sync_guard = Arc::clone(&guard);
+    socket.on(
+        events::SYNC,
+        move |socket: SocketRef, ack: AckSender, State(state): State<AppState>| {
+            let version = state.api_versions.get(&socket.id.to_string());
+            let snapshot = version::convert(&state.snapshot(), version);
+            sync_guard.record_sent(payload_len(&snapshot));
+            ack.send(&snapshot).ok();
+        },
+    );
+
+    socket.on(
+        events::HEARTBEAT,
+        |socket: SocketRef, State(state): State<AppState>| {
+            state.presence.beat(&socket.id.to_string());
+        },
+    );
+
+    socket.on(
+        events::ANNOUNCE,
+        |ack: AckSender, State(state): State<AppState>| {
+            ack.send(&robot::info(&state)).ok();
+            state.robot.announce();
+        },
+    );
+
+    socket.on(events::ACTIVITY, |State(state): State<AppState>| {
+        power::activity(&state, "operator input");
+    });
+
+    socket.on(
+        events::DRIVE,
+        |Data(command): Data<DriveCommand>, State(state): State<AppState>| {
+            power::activity(&state, "operator drive");
+            // Rejected while another source owns the drive; the dashboard
+            // sees who does in the mission state
+            let _ = state.arbiter.submit(CommandSource::Teleop, command);
+        },
+    );
+
+    socket.on(
+        events::ESTOP,
+        |Data(request): Data<serde_json::Value>, ack: AckSender, State(state): State<AppState>| {
+            // Like `POST /estop`, a malformed payload still stops the robot
+            let request: EStopRequest = serde_json::from_value(request).unwrap_or_default();
+            ack.send(&estop::engage(&state, "socket", request.reason))
+                .ok();
+        },
+    );
+    socket.on(
+        events::ESTOP_CLEAR,
+        |ack: AckSender, State(state): State<AppState>| {
+            ack.send(&estop::clear(&state)).ok();
+        },
+    );
+
+    socket.on(
+        events::MISSION_APPROVE,
+        |Data(request): Data<ApprovalDecision>, ack: AckSender, State(state): State<AppState>| {
+            let resolution = state
+                .mission
+                .resolve(request.id, request.action, "operator");
+            ack.send(&resolution).ok();
+        },
+    );
+
+    socket.on(
+        events::OVERLAY_SET,
+        |Data(primitives): Data<Vec<OverlayPrimitive>>, State(state): State<AppState>| {
+            state.overlay.set(primitives);
+        },
+    );
+    socket.on(
+        events::OVERLAY_ADD,
+        |Data(primitive): Data<OverlayPrimitive>, State(state): State<AppState>| {
+            state.overlay.add(primitive);
+        },
+    );
+    socket.on(
+        events::OVERLAY_REMOVE,
+        |Data(id): Data<String>, State(state): State<AppState>| {
+            state.overlay.remove(&id);
+        },
+    );
+    socket.on(events::OVERLAY_CLEAR, |State(state): State<AppState>| {
+        state.overlay.clear();
+    });
+
+    // The guard lives until the socket closes, or an admin kicks it
+    let closed = Arc::new(Notify::new());
+    let closed_tx = Arc::clone(&closed);
+    let kicked_socket = socket.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = guard.kicked() => {
+                kicked_socket.disconnect().ok();
+            }
+            _ = closed.notified() => {}
+        }
+    });
+
+    socket.on_disconnect(move |socket: SocketRef, State(state): State<AppState>| {
+        println!("[INFO] Socket.IO client disconnected: {}", socket.id);
+        state.presence.leave(&socket.id.to_string());
+        state.api_versions.remove(&socket.id.to_string());
+        closed_tx.notify_one();
+    });
+}
@@ -0,0 +1,180 @@
+//! Recorded frame sources standing in for the camera, so the detection
+//! pipeline runs on a laptop: a video file, or a folder of images played in
+//! name order, at the configured rate and looped unless told otherwise.
+//!
+//! Selected with `[camera.playback]` in the config or `CAMERA_PLAYBACK`, and
+//! only taken up where the profile allows simulation (`dev`, not
+//! `competition`); the capture thread then feeds the frames through the
+//! same masks and dispatcher as live ones (see `camera::start_camera_thread`).
+
+use opencv::{core::Mat, imgcodecs, prelude::*, videoio};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "bmp", "tif"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaybackConfig {
+    /// A video file or a folder of images.
+    pub path: PathBuf,
+    /// Playback rate; the video's own, or `camera.video.fps` for images,
+    /// when unset.
+    #[serde(default)]
+    pub fps: Option<f32>,
+    /// Start over at the end instead of stopping.
+    #[serde(default = "default_looped", rename = "loop")]
+    pub looped: bool,
+}
+
+fn default_looped() -> bool {
+    true
+}
+
+impl PlaybackConfig {
+    /// `CAMERA_PLAYBACK`, looped at the source's own rate.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("CAMERA_PLAYBACK").ok()?;
+        let path = path.trim();
+        (!path.is_empty()).then(|| Self {
+            path: PathBuf::from(path),
+            fps: None,
+            looped: true,
+        })
+    }
+}
+
+/// Where frames come from when it isn't the camera.
+pub trait FrameSource: Send {
+    /// The next frame, or `None` once a source that doesn't loop is done.
+    fn next_frame(&mut self) -> opencv::Result<Option<Mat>>;
+
+    /// The rate the source was recorded at, when it knows.
+    fn native_fps(&self) -> Option<f32> {
+        None
+    }
+
+    fn describe(&self) -> String;
+}
+
+pub struct VideoFileSource {
+    path: PathBuf,
+    capture: videoio::VideoCapture,
+    looped: bool,
+}
+
+impl VideoFileSource {
+    pub fn open(path: &Path, looped: bool) -> Result<Self, String> {
+        let capture = videoio::VideoCapture::from_file(&path.to_string_lossy(), videoio::CAP_ANY)
+            .map_err(|e| e.to_string())?;
+        if !capture.is_opened().unwrap_or(false) {
+            return Err(format!("could not open video {}", path.display()));
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            capture,
+            looped,
+        })
+    }
+}
+
+impl FrameSource for VideoFileSource {
+    fn next_frame(&mut self) -> opencv::Result<Option<Mat>> {
+        let mut frame = Mat::default();
+        if self.capture.read(&mut frame)? && !frame.empty() {
+            return Ok(Some(frame));
+        }
+        if !self.looped {
+            return Ok(None);
+        }
+        self.capture.set(videoio::CAP_PROP_POS_FRAMES, 0.0)?;
+        if self.capture.read(&mut frame)? && !frame.empty() {
+            Ok(Some(frame))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn native_fps(&self) -> Option<f32> {
+        let fps = self.capture.get(videoio::CAP_PROP_FPS).ok()? as f32;
+        (fps > 0.0 && fps.is_finite()).then_some(fps)
+    }
+
+    fn describe(&self) -> String {
+        format!("video {}", self.path.display())
+    }
+}
+
+pub struct ImageFolderSource {
+    dir: PathBuf,
+    images: Vec<PathBuf>,
+    next: usize,
+    looped: bool,
+}
+
+impl ImageFolderSource {
+    pub fn open(dir: &Path, looped: bool) -> Result<Self, String> {
+        let mut images: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+            })
+            .collect();
+        if images.is_empty() {
+            return Err(format!("no images in {}", dir.display()));
+        }
+        images.sort();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            images,
+            next: 0,
+            looped,
+        })
+    }
+}
+
+impl FrameSource for ImageFolderSource {
+    fn next_frame(&mut self) -> opencv::Result<Option<Mat>> {
+        // Unreadable files are skipped; a folder of nothing but is done
+        for _ in 0..self.images.len() {
+            if self.next == self.images.len() {
+                if !self.looped {
+                    return Ok(None);
+                }
+                self.next = 0;
+            }
+            let path = &self.images[self.next];
+            self.next += 1;
+            let frame = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+            if !frame.empty() {
+                return Ok(Some(frame));
+            }
+            println!("[WARN] Skipping unreadable image {}", path.display());
+        }
+        Ok(None)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} images in {}", self.images.len(), self.dir.display())
+    }
+}
+
+/// The source `config` points at: a folder plays its images, anything else
+/// is opened as a video.
+pub fn open(config: &PlaybackConfig) -> Result<Box<dyn FrameSource>, String> {
+    if config.path.is_dir() {
+        Ok(Box::new(ImageFolderSource::open(
+            &config.path,
+            config.looped,
+        )?))
+    } else {
+        Ok(Box::new(VideoFileSource::open(
+            &config.path,
+            config.looped,
+        )?))
+    }
+}
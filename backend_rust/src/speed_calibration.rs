@@ -0,0 +1,316 @@
+//! Guided calibration of the duty-to-speed model, for chassis without
+//! encoders: their distance estimates (nudges, anything dead-reckoned from
+//! what the arbiter sent) are only as good as this model.
+//!
+//! The operator marks where the robot stands, has it drive straight at a
+//! given duty for a couple of seconds (`POST /calibration/speed/run`),
+//! measures how far it went and enters that (`.../measure`); `GET
+//! /calibration/speed` says what to do next. Speed against duty is fitted
+//! to a line, giving full-duty speed and deadband, and `.../save` stores
+//! them at the battery voltage of the runs, so repeating the calibration
+//! at another charge level builds up the speed curve.
+
+use crate::arbiter::CommandSource;
+use crate::state::AppState;
+use crate::units;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use raspibot_protocol::drive::DriveCommand;
+use raspibot_protocol::units::{
+    SpeedCalibrationStatus, SpeedFit, SpeedMeasurement, SpeedPoint, SpeedRun, SpeedRunRequest,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Duties the routine asks for, spread over the usable range.
+const DUTIES: [f32; 4] = [0.3, 0.5, 0.7, 0.9];
+/// A run within this of a planned duty counts for it.
+const DUTY_MATCH: f32 = 0.05;
+/// Curve points closer than this in voltage are replaced by a new one.
+const SAME_VOLTAGE_V: f32 = 0.1;
+const MIN_SECONDS: f32 = 0.5;
+const MAX_SECONDS: f32 = 10.0;
+/// How often the duty is resent while running, well inside the drive
+/// watchdog.
+const TICK: Duration = Duration::from_millis(50);
+
+/// The calibration in progress: its runs so far.
+pub struct SpeedCalibration {
+    runs: Mutex<Vec<SpeedRun>>,
+    /// Set while a run drives; runs don't queue.
+    running: AtomicBool,
+}
+
+/// Clears `SpeedCalibration::running` however the run ends.
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl SpeedCalibration {
+    pub fn new() -> Self {
+        Self {
+            runs: Mutex::new(Vec::new()),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// `None` while another run drives.
+    fn start_run(&self) -> Option<RunningGuard<'_>> {
+        (!self.running.swap(true, Ordering::AcqRel)).then_some(RunningGuard(&self.running))
+    }
+}
+
+/// Least-squares line through speed against duty of the measured runs;
+/// `None` with fewer than two duties or when the line makes no sense (no
+/// speed gained with duty, deadband outside 0..1).
+fn fit(runs: &[SpeedRun]) -> Option<SpeedFit> {
+    let measured: Vec<&SpeedRun> = runs.iter().filter(|r| r.distance_m.is_some()).collect();
+    if measured.len() < 2 {
+        return None;
+    }
+    let points: Vec<(f32, f32)> = measured
+        .iter()
+        .filter_map(|r| Some((r.duty, r.distance_m? / r.seconds)))
+        .collect();
+    let n = points.len() as f32;
+    let mean_duty = points.iter().map(|(d, _)| d).sum::<f32>() / n;
+    let mean_speed = points.iter().map(|(_, s)| s).sum::<f32>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (duty, speed) in &points {
+        covariance += (duty - mean_duty) * (speed - mean_speed);
+        variance += (duty - mean_duty).powi(2);
+    }
+    if variance < 1e-4 {
+        return None;
+    }
+    let slope = covariance / variance;
+    let intercept = mean_speed - slope * mean_duty;
+    let deadband = -intercept / slope;
+    if !(slope > 0.0 && (0.0..1.0).contains(&deadband)) {
+        return None;
+    }
+    let volts: Vec<f32> = measured.iter().filter_map(|r| r.battery_v).collect();
+    Some(SpeedFit {
+        max_speed_mps: slope + intercept,
+        deadband,
+        battery_v: (!volts.is_empty()).then(|| volts.iter().sum::<f32>() / volts.len() as f32),
+    })
+}
+
+fn status(state: &AppState) -> SpeedCalibrationStatus {
+    let runs = state.speed_calibration.runs.lock().unwrap().clone();
+    let fit = fit(&runs);
+    let next_duty = DUTIES
+        .into_iter()
+        .find(|duty| !runs.iter().any(|r| (r.duty - duty).abs() <= DUTY_MATCH));
+    let next_step = if runs.last().is_some_and(|r| r.distance_m.is_none()) {
+        "Measure how far the robot drove and enter it".to_string()
+    } else if let Some(duty) = next_duty {
+        format!(
+            "Mark where the robot stands, clear the path ahead and run at duty {:.1}",
+            duty
+        )
+    } else if fit.is_some() {
+        "Save the fit; repeat at another battery level to extend the curve".to_string()
+    } else {
+        "The runs don't fit a line; clear them and start over".to_string()
+    };
+    SpeedCalibrationStatus {
+        runs,
+        fit,
+        next_step,
+        curve: state.units.get().speed_curve,
+    }
+}
+
+/// Drives straight at `request.duty` for `request.seconds` as teleop and
+/// stops, noting the duty that actually went out and the battery voltage.
+/// The run is timed from the first command that goes out, so the duty
+/// averages only time spent driving.
+async fn drive(state: &AppState, request: SpeedRunRequest) -> Result<SpeedRun, String> {
+    // Set by the first command that goes out
+    let mut started: Option<Instant> = None;
+    let mut last = Instant::now();
+    let mut duty_seconds = 0.0f32;
+    let mut sent = DriveCommand::default();
+    let mut volts = Vec::new();
+    let mut interval = tokio::time::interval(TICK);
+    let result = loop {
+        interval.tick().await;
+        let dt = last.elapsed().as_secs_f32();
+        last = Instant::now();
+        if let Some(started) = started {
+            duty_seconds += (sent.left + sent.right) / 2.0 * dt;
+            volts.extend(state.power.battery_v());
+            if started.elapsed().as_secs_f32() >= request.seconds {
+                break Ok(started);
+            }
+        }
+        let command = DriveCommand::new(request.duty, request.duty);
+        match state.arbiter.submit(CommandSource::Teleop, command) {
+            Ok(command) => {
+                sent = command;
+                started.get_or_insert(last);
+            }
+            Err(e) => break Err(e.to_string()),
+        }
+    };
+    if let Err(e) = state
+        .arbiter
+        .submit(CommandSource::Teleop, DriveCommand::default())
+    {
+        eprintln!("[ERR] Could not stop after speed run: {}", e);
+    }
+    let seconds = result?.elapsed().as_secs_f32();
+    Ok(SpeedRun {
+        duty: duty_seconds / seconds,
+        seconds,
+        battery_v: (!volts.is_empty()).then(|| volts.iter().sum::<f32>() / volts.len() as f32),
+        distance_m: None,
+    })
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/calibration/speed", get(get_status).delete(clear))
+        .route("/calibration/speed/run", post(run))
+        .route("/calibration/speed/measure", post(measure))
+        .route("/calibration/speed/save", post(save))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<SpeedCalibrationStatus> {
+    Json(status(&state))
+}
+
+/// Runs detached from the request, so a client hanging up mid-run still
+/// gets the robot stopped and the run recorded.
+async fn run(
+    State(state): State<AppState>,
+    Json(request): Json<SpeedRunRequest>,
+) -> Result<Json<SpeedCalibrationStatus>, (StatusCode, String)> {
+    if !(request.duty > 0.0 && request.duty <= 1.0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "duty must be within 0..=1".to_string(),
+        ));
+    }
+    if !(MIN_SECONDS..=MAX_SECONDS).contains(&request.seconds) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("seconds must be within {}..={}", MIN_SECONDS, MAX_SECONDS),
+        ));
+    }
+    if state
+        .speed_calibration
+        .runs
+        .lock()
+        .unwrap()
+        .last()
+        .is_some_and(|r| r.distance_m.is_none())
+    {
+        return Err((
+            StatusCode::CONFLICT,
+            "measure the last run first".to_string(),
+        ));
+    }
+    let driving = state.clone();
+    tokio::spawn(async move {
+        let calibration = &driving.speed_calibration;
+        let _running = calibration
+            .start_run()
+            .ok_or("a speed run is already driving".to_string())?;
+        let speed_run = drive(&driving, request).await?;
+        println!(
+            "[INFO] Speed run at duty {:.2} for {:.1}s ({})",
+            speed_run.duty,
+            speed_run.seconds,
+            speed_run
+                .battery_v
+                .map_or("no battery voltage".to_string(), |v| format!("{:.2} V", v))
+        );
+        calibration.runs.lock().unwrap().push(speed_run);
+        Ok(())
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e: String| (StatusCode::CONFLICT, e))?;
+    Ok(Json(status(&state)))
+}
+
+async fn measure(
+    State(state): State<AppState>,
+    Json(measurement): Json<SpeedMeasurement>,
+) -> Result<Json<SpeedCalibrationStatus>, (StatusCode, String)> {
+    if !(measurement.distance_m.is_finite() && measurement.distance_m >= 0.0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "distance_m must not be negative".to_string(),
+        ));
+    }
+    {
+        let mut runs = state.speed_calibration.runs.lock().unwrap();
+        let last = runs
+            .last_mut()
+            .filter(|r| r.distance_m.is_none())
+            .ok_or((StatusCode::CONFLICT, "no run to measure".to_string()))?;
+        last.distance_m = Some(measurement.distance_m);
+    }
+    Ok(Json(status(&state)))
+}
+
+/// Stores the fit at the runs' battery voltage, replacing a point at about
+/// the same voltage; without a voltage, as the plain wheel calibration.
+async fn save(
+    State(state): State<AppState>,
+) -> Result<Json<SpeedCalibrationStatus>, (StatusCode, String)> {
+    let fit = fit(&state.speed_calibration.runs.lock().unwrap()).ok_or((
+        StatusCode::CONFLICT,
+        "not enough measured runs to fit".to_string(),
+    ))?;
+    let mut calibration = state.units.get();
+    match fit.battery_v {
+        Some(battery_v) => {
+            calibration
+                .speed_curve
+                .retain(|p| (p.battery_v - battery_v).abs() >= SAME_VOLTAGE_V);
+            calibration.speed_curve.push(SpeedPoint {
+                battery_v,
+                max_speed_mps: fit.max_speed_mps,
+                deadband: fit.deadband,
+            });
+            calibration
+                .speed_curve
+                .sort_by(|a, b| a.battery_v.total_cmp(&b.battery_v));
+        }
+        None => {
+            calibration.wheels.max_speed_mps = fit.max_speed_mps;
+            calibration.wheels.deadband = fit.deadband;
+        }
+    }
+    units::validate(&calibration).map_err(|e| (StatusCode::CONFLICT, e))?;
+    state
+        .units
+        .set(calibration)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[OK] Speed calibration saved: {:.3} m/s at full duty, deadband {:.3}",
+        fit.max_speed_mps, fit.deadband
+    );
+    state.speed_calibration.runs.lock().unwrap().clear();
+    Ok(Json(status(&state)))
+}
+
+async fn clear(State(state): State<AppState>) -> Json<SpeedCalibrationStatus> {
+    state.speed_calibration.runs.lock().unwrap().clear();
+    Json(status(&state))
+}
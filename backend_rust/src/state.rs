@@ -0,0 +1,316 @@
+//! Shared handles to every subsystem, plus the full-state snapshot sent to
+//! dashboards when they (re)connect.
+
+use crate::adaptive::AdaptiveThreshold;
+use crate::arbiter::CommandArbiter;
+use crate::boundary::BoundaryMonitor;
+use crate::bumper::VirtualBumper;
+use crate::camera::{CameraSet, FrameManager};
+use crate::charging::ChargeMonitor;
+use crate::compass::CompassManager;
+use crate::config::Config;
+use crate::crops::CropStore;
+use crate::detections::DetectionHub;
+use crate::devices::DeviceRegistry;
+use crate::estop::EStop;
+use crate::evidence::EvidenceLog;
+use crate::gps::GpsManager;
+use crate::illuminator::IrIlluminator;
+use crate::imu::Imu;
+use crate::labels::ClassLabelStore;
+use crate::mission::executor::MissionExecutor;
+use crate::mission::MissionController;
+use crate::nudge::NudgeLock;
+use crate::overlay::OverlayStore;
+use crate::pose::PoseTracker;
+use crate::power::PowerManager;
+use crate::presence::Presence;
+use crate::privacy::FaceBlur;
+use crate::profile::Profile;
+use crate::quality::QualityMonitor;
+use crate::range::RangeSensors;
+use crate::recorder::Recorder;
+use crate::reid::{ReidGallery, ReidModel};
+use crate::robot::RobotIdentity;
+use crate::run_timer::RunTimer;
+use crate::servo::Gimbal;
+use crate::session::SessionManager;
+use crate::speed_calibration::SpeedCalibration;
+use crate::steering::SteeringMixer;
+use crate::stereo::StereoRig;
+use crate::stream::StreamSettings;
+use crate::target::TargetStore;
+use crate::telemetry::TelemetryDownsampler;
+use crate::teleop::Teleop;
+use crate::thermal::ThermalCamera;
+use crate::threads::CpuMonitor;
+use crate::units::UnitStore;
+use crate::velocity::VelocityController;
+use crate::version::ClientVersions;
+use crate::viewers::ViewerRegistry;
+use crate::visual_servo::ServoGainStore;
+use crate::yolo::ModelSlot;
+use crate::zones::ZoneStore;
+use raspibot_protocol::health::ProfileSettings;
+use raspibot_protocol::rates::RateStats;
+use raspibot_protocol::state::{CameraState, StateSnapshot};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub profile: Profile,
+    pub settings: ProfileSettings,
+    /// MJPEG limits, from `[stream]` or the profile.
+    pub stream: StreamSettings,
+    pub started: Instant,
+    /// Every camera by id; `frames` and `detections` are the primary's.
+    pub cameras: Arc<CameraSet>,
+    pub frames: Arc<FrameManager>,
+    pub sessions: Arc<SessionManager>,
+    pub face_blur: Arc<FaceBlur>,
+    pub viewers: Arc<ViewerRegistry>,
+    pub evidence: Arc<EvidenceLog>,
+    pub detections: Arc<DetectionHub>,
+    /// Crops of tracked objects, for the operator to verify.
+    pub crops: Arc<CropStore>,
+    /// Class display names per language.
+    pub labels: Arc<ClassLabelStore>,
+    pub servo_gains: Arc<ServoGainStore>,
+    pub overlay: Arc<OverlayStore>,
+    pub zones: Arc<ZoneStore>,
+    pub arbiter: Arc<CommandArbiter>,
+    /// Latched emergency stop over the arbiter and every other output.
+    pub estop: Arc<EStop>,
+    pub boundary: Arc<BoundaryMonitor>,
+    pub bumper: Arc<VirtualBumper>,
+    /// Caps turning at speed, and speed while turning.
+    pub steering: Arc<SteeringMixer>,
+    /// Confidence threshold tuned from detection persistence.
+    pub adaptive: Arc<AdaptiveThreshold>,
+    pub mission: Arc<MissionController>,
+    /// Runs mission plans step by step.
+    pub executor: Arc<MissionExecutor>,
+    /// Clock of the autonomous run against the competition's limit.
+    pub run_timer: Arc<RunTimer>,
+    /// Closed-loop drive, holding wheel speeds and heading.
+    pub velocity: Arc<VelocityController>,
+    pub presence: Arc<Presence>,
+    pub power: Arc<PowerManager>,
+    /// Whether the robot is on the charger.
+    pub charging: Arc<ChargeMonitor>,
+    /// CPU use per subsystem, for `/api/health`.
+    pub cpu: Arc<CpuMonitor>,
+    /// Operator-designated visual-servo targets.
+    pub targets: Arc<TargetStore>,
+    /// Deadman switch of the `/control` namespace.
+    pub teleop: Arc<Teleop>,
+    /// One `/drive/nudge` at a time.
+    pub nudge: Arc<NudgeLock>,
+    /// Reduced-rate copy of telemetry for remote clients.
+    pub telemetry: Arc<TelemetryDownsampler>,
+    /// Calibration for physical-unit conversions.
+    pub units: Arc<UnitStore>,
+    /// Runs of the guided speed calibration.
+    pub speed_calibration: Arc<SpeedCalibration>,
+    /// API version each Socket.IO client speaks.
+    pub api_versions: Arc<ClientVersions>,
+    /// Identities shared by every camera's tracker.
+    pub reid_gallery: Arc<ReidGallery>,
+    /// Hot-pluggable devices currently connected.
+    pub devices: Arc<DeviceRegistry>,
+    /// Blur, fog and exposure of the camera feed.
+    pub quality: Arc<QualityMonitor>,
+    /// Operator-started match recordings.
+    pub recorder: Arc<Recorder>,
+    /// Name, team and build, announced as `robot_info`.
+    pub robot: Arc<RobotIdentity>,
+    pub gps: Option<Arc<GpsManager>>,
+    pub compass: Option<Arc<CompassManager>>,
+    pub gimbal: Option<Arc<Gimbal>>,
+    pub imu: Option<Arc<Imu>>,
+    pub range: Option<Arc<RangeSensors>>,
+    pub pose: Option<Arc<PoseTracker>>,
+    pub illuminator: Option<Arc<IrIlluminator>>,
+    pub reid: Option<Arc<ReidModel>>,
+    pub model: Option<Arc<ModelSlot>>,
+    pub stereo: Option<Arc<StereoRig>>,
+    pub thermal: Option<Arc<ThermalCamera>>,
+    revision: Arc<AtomicU64>,
+}
+
+impl AppState {
+    /// Optional subsystems start out absent and are attached by `main` as
+    /// their hardware is found.
+    pub fn new(
+        profile: Profile,
+        config: &Config,
+        cameras: Arc<CameraSet>,
+        sessions: Arc<SessionManager>,
+        face_blur: Arc<FaceBlur>,
+        zones: Arc<ZoneStore>,
+        arbiter: Arc<CommandArbiter>,
+        boundary: Arc<BoundaryMonitor>,
+    ) -> Self {
+        let frames = cameras.primary().frames.clone();
+        let detections = cameras.primary().detections.clone();
+        let bumper = Arc::new(VirtualBumper::load(detections.clone(), frames.clone()));
+        // One store, shared with the zone filter's lens correction
+        let units = zones.units().clone();
+        Self {
+            profile,
+            settings: profile.settings(),
+            stream: StreamSettings::new(&config.stream, &profile.settings()),
+            started: Instant::now(),
+            cameras,
+            frames,
+            sessions,
+            face_blur,
+            viewers: Arc::new(ViewerRegistry::new()),
+            evidence: Arc::new(EvidenceLog::new()),
+            detections,
+            crops: Arc::new(CropStore::new()),
+            labels: Arc::new(ClassLabelStore::load()),
+            servo_gains: Arc::new(ServoGainStore::load()),
+            overlay: Arc::new(OverlayStore::new()),
+            zones,
+            mission: Arc::new(MissionController::new(arbiter.clone())),
+            executor: Arc::new(MissionExecutor::new()),
+            run_timer: Arc::new(RunTimer::new()),
+            velocity: Arc::new(VelocityController::new()),
+            presence: Arc::new(Presence::new(Duration::from_secs_f32(
+                config.presence.timeout_s,
+            ))),
+            power: Arc::new(PowerManager::new(
+                config.power.idle_after_s.map(Duration::from_secs_f32),
+            )),
+            charging: Arc::new(ChargeMonitor::new()),
+            cpu: Arc::new(CpuMonitor::new()),
+            targets: Arc::new(TargetStore::new()),
+            teleop: Arc::new(Teleop::new(Duration::from_millis(config.drive.deadman_ms))),
+            nudge: Arc::new(NudgeLock::new()),
+            telemetry: Arc::new(TelemetryDownsampler::with_default_hz(
+                config.telemetry.remote_hz,
+            )),
+            units,
+            speed_calibration: Arc::new(SpeedCalibration::new()),
+            api_versions: Arc::new(ClientVersions::new()),
+            reid_gallery: Arc::new(ReidGallery::new(Duration::from_secs_f32(
+                config.reid.memory_s,
+            ))),
+            devices: Arc::new(DeviceRegistry::new()),
+            quality: Arc::new(QualityMonitor::load()),
+            recorder: Arc::new(Recorder::new(config.recording.fps)),
+            robot: Arc::new(RobotIdentity::new()),
+            arbiter,
+            estop: Arc::new(EStop::new()),
+            boundary,
+            bumper,
+            steering: Arc::new(SteeringMixer::load()),
+            adaptive: Arc::new(AdaptiveThreshold::load()),
+            gps: None,
+            compass: None,
+            gimbal: None,
+            imu: None,
+            range: None,
+            pose: None,
+            illuminator: None,
+            reid: None,
+            model: None,
+            stereo: None,
+            thermal: None,
+            revision: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Records a telemetry sample at full rate in the active run's blackbox,
+    /// and feeds the downsampled stream sent to remote clients.
+    pub fn record_telemetry(
+        &self,
+        stream: &str,
+        values: serde_json::Map<String, serde_json::Value>,
+    ) {
+        self.telemetry.record(stream, &values);
+        self.sessions.record_telemetry(stream, values);
+    }
+
+    /// Settings worth keeping alongside a run's data.
+    pub fn config_snapshot(&self) -> serde_json::Value {
+        let exposure = self.frames.exposure();
+        let (low_light, grayscale) = self.frames.low_light();
+        serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "profile": self.profile.to_string(),
+            "settings": self.settings,
+            "camera": {
+                "frame_size": self.frames.frame_size(),
+                "exposure_locked": exposure.locked,
+                "low_light": low_light,
+                "grayscale": grayscale,
+                "settings": self.frames.settings(),
+            },
+            "servo_gains": self.servo_gains.all(),
+            "viewer_limits": self.viewers.limits(),
+            "zones": self.zones.get(),
+            "privacy_masks": self.frames.masks().get(),
+            "boundary": self.boundary.config(),
+            "bumper": self.bumper.config(),
+            "steering": self.steering.config(),
+            "class_labels": self.labels.config(),
+            "adaptive_threshold": self.adaptive.config(),
+            "image_quality": self.quality.config(),
+            "detect_classes": self.model.as_ref().and_then(|m| m.classes()),
+            "units": self.units.get(),
+            "thermal": self.thermal.as_ref().map(|t| t.config()),
+        })
+    }
+
+    /// Every rate meter, by loop: `capture`, `consumer.<name>` per frame
+    /// consumer, `inference` and `control` (drive commands), then
+    /// `capture.<id>` and `inference.<id>` for every further camera.
+    pub fn rates(&self) -> BTreeMap<String, RateStats> {
+        let mut rates = BTreeMap::new();
+        rates.insert("capture".to_string(), self.frames.capture_rate());
+        for (name, stats) in self.frames.consumer_rates() {
+            rates.insert(format!("consumer.{}", name), stats);
+        }
+        rates.insert("inference".to_string(), self.detections.rate());
+        for camera in self.cameras.iter().skip(1) {
+            rates.insert(
+                format!("capture.{}", camera.id),
+                camera.frames.capture_rate(),
+            );
+            rates.insert(format!("inference.{}", camera.id), camera.detections.rate());
+        }
+        rates.insert("control".to_string(), self.arbiter.rate());
+        rates
+    }
+
+    pub fn snapshot(&self) -> StateSnapshot {
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        let (width, height) = self.frames.frame_size().unwrap_or((0, 0));
+        StateSnapshot {
+            revision,
+            taken_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            camera: CameraState {
+                streaming: width > 0,
+                width,
+                height,
+                exposure_locked: self.frames.exposure().locked,
+                low_light: self.frames.low_light().0,
+            },
+            active_session: self.sessions.active_id(),
+            gps: self.gps.as_ref().map(|g| g.fix()),
+            compass: self.compass.as_ref().map(|c| c.reading()),
+            imu: self.imu.as_ref().and_then(|i| i.reading()),
+            pose: self.pose.as_ref().map(|p| p.pose()),
+            mission: self.mission.state(),
+            presence: self.presence.status(),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Velocity-scaled steering mixing for high-speed stability.
+//!
+//! At speed a hard turn spins the robot out, and a fast turn with full
+//! forward speed on top slides it wide. The mixer caps the turn by the
+//! forward speed asked for, then the forward speed by the turn that is
+//! left, along curves persisted in `data/` and tuned like every other
+//! setting (`PUT /drive/steering`, or the settings import). It only ever
+//! lowers a command, so the safety constraints after it see no more than
+//! was asked for.
+
+use crate::arbiter::{CommandSource, Constraint};
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::drive::{DriveCommand, SteeringMixConfig};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub const CONFIG_PATH: &str = "data/steering.json";
+
+pub struct SteeringMixer {
+    path: PathBuf,
+    config: Mutex<SteeringMixConfig>,
+}
+
+impl SteeringMixer {
+    pub fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            config: Mutex::new(config),
+        }
+    }
+
+    pub fn config(&self) -> SteeringMixConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: SteeringMixConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+}
+
+/// The limit `curve` sets at `input`, interpolated between its points and
+/// held beyond its ends.
+fn limit(curve: &[[f32; 2]], input: f32) -> f32 {
+    let Some(&[first_input, first_limit]) = curve.first() else {
+        return f32::INFINITY;
+    };
+    if input <= first_input {
+        return first_limit;
+    }
+    for pair in curve.windows(2) {
+        let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+        if input <= x1 {
+            return y0 + (y1 - y0) * (input - x0) / (x1 - x0);
+        }
+    }
+    curve[curve.len() - 1][1]
+}
+
+/// `command` with its turn capped by its forward speed, then its forward
+/// speed capped by the turn left.
+pub fn mix(config: &SteeringMixConfig, command: DriveCommand) -> DriveCommand {
+    let (forward, turn) = command.forward_turn();
+    let max_turn = limit(&config.turn_limit, forward.abs());
+    let turn = turn.clamp(-max_turn, max_turn);
+    let max_forward = limit(&config.forward_limit, turn.abs());
+    let forward = forward.clamp(-max_forward, max_forward);
+    DriveCommand::from_forward_turn(forward, turn)
+}
+
+impl Constraint for SteeringMixer {
+    fn name(&self) -> &'static str {
+        "steering"
+    }
+
+    fn apply(&self, _source: &CommandSource, command: DriveCommand) -> DriveCommand {
+        let config = self.config.lock().unwrap();
+        if !config.enabled {
+            return command;
+        }
+        mix(&config, command)
+    }
+}
+
+fn validate_curve(name: &str, curve: &[[f32; 2]]) -> Result<(), String> {
+    if curve.is_empty() {
+        return Err(format!("{} needs at least one point", name));
+    }
+    if curve.iter().flatten().any(|v| !(0.0..=1.0).contains(v)) {
+        return Err(format!("{} points must be within 0..=1", name));
+    }
+    if curve.windows(2).any(|pair| pair[1][0] <= pair[0][0]) {
+        return Err(format!("{} points must be sorted by input", name));
+    }
+    Ok(())
+}
+
+pub fn validate(config: &SteeringMixConfig) -> Result<(), String> {
+    validate_curve("turn_limit", &config.turn_limit)?;
+    validate_curve("forward_limit", &config.forward_limit)
+}
+
+pub fn routes(mixer: Arc<SteeringMixer>) -> Router {
+    Router::new()
+        .route("/drive/steering", get(get_config).put(set_config))
+        .with_state(mixer)
+}
+
+async fn get_config(State(mixer): State<Arc<SteeringMixer>>) -> Json<SteeringMixConfig> {
+    Json(mixer.config())
+}
+
+async fn set_config(
+    State(mixer): State<Arc<SteeringMixer>>,
+    Json(config): Json<SteeringMixConfig>,
+) -> Result<Json<SteeringMixConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let enabled = config.enabled;
+    mixer
+        .set_config(config.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Steering mixing {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(Json(config))
+}
@@ -0,0 +1,315 @@
+//! Frame-synchronized capture for the dual-camera stereo rig.
+//!
+//! The two sensors free-run, so frames are paired after the fact: each is
+//! timestamped as it is read, and a frame is matched with the nearest one
+//! from the other camera as long as they are at most `STEREO_MAX_SKEW_MS`
+//! apart. Frames that never find a partner are dropped and counted. Pairs go
+//! out through a [`FrameDispatcher`], so depth consumers subscribe with a
+//! declared rate like every other frame consumer.
+//!
+//! Enabled by `STEREO_CAMERAS=left,right`, each a V4L2 index or a libcamera
+//! camera name; the capture mode is the main camera's. Privacy masks are
+//! not painted: these frames are never streamed or recorded.
+
+use crate::camera::CaptureSettings;
+use crate::dispatch::{ConsumerInfo, Decimation, FrameDispatcher, FrameSubscription};
+use crate::faults;
+use crate::rate::RateMeter;
+use crate::state::AppState;
+use crate::threads;
+use axum::{extract::State, routing::get, Json, Router};
+use opencv::{core, prelude::*, videoio};
+use raspibot_protocol::camera::StereoStats;
+use raspibot_protocol::rates::RateStats;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_SKEW_MS: u64 = 8;
+/// Unmatched frames kept per camera while waiting for a partner.
+const PAIR_BUFFER: usize = 4;
+/// Recent pairs the skew statistics are taken over.
+const SKEW_HISTORY: usize = 120;
+const TELEMETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+pub struct StereoConfig {
+    pub left: String,
+    pub right: String,
+    pub max_skew: Duration,
+    pub settings: CaptureSettings,
+}
+
+impl StereoConfig {
+    /// `None` unless `STEREO_CAMERAS` names two cameras.
+    pub fn from_env(settings: CaptureSettings) -> Option<Self> {
+        let value = std::env::var("STEREO_CAMERAS").ok()?;
+        let Some((left, right)) = value
+            .split_once(',')
+            .map(|(l, r)| (l.trim(), r.trim()))
+            .filter(|(l, r)| !l.is_empty() && !r.is_empty())
+        else {
+            println!("[WARN] Ignoring invalid STEREO_CAMERAS='{}'", value);
+            return None;
+        };
+        let max_skew_ms = match std::env::var("STEREO_MAX_SKEW_MS") {
+            Ok(v) => v.trim().parse().unwrap_or_else(|_| {
+                println!("[WARN] Ignoring invalid STEREO_MAX_SKEW_MS='{}'", v);
+                DEFAULT_MAX_SKEW_MS
+            }),
+            Err(_) => DEFAULT_MAX_SKEW_MS,
+        };
+        Some(Self {
+            left: left.to_string(),
+            right: right.to_string(),
+            max_skew: Duration::from_millis(max_skew_ms),
+            settings,
+        })
+    }
+}
+
+/// One camera's frame, stamped when it was read.
+pub struct StereoFrame {
+    pub seq: u64,
+    pub captured: Instant,
+    pub frame: core::Mat,
+}
+
+pub struct StereoPair {
+    pub left: Arc<StereoFrame>,
+    pub right: Arc<StereoFrame>,
+    /// How far apart the two frames were read.
+    pub skew: Duration,
+}
+
+/// Nearest-timestamp matching of the two cameras' frames.
+struct Pairer {
+    max_skew: Duration,
+    left: VecDeque<Arc<StereoFrame>>,
+    right: VecDeque<Arc<StereoFrame>>,
+    unpaired: u64,
+}
+
+impl Pairer {
+    fn new(max_skew: Duration) -> Self {
+        Self {
+            max_skew,
+            left: VecDeque::new(),
+            right: VecDeque::new(),
+            unpaired: 0,
+        }
+    }
+
+    fn skew(a: &StereoFrame, b: &StereoFrame) -> Duration {
+        a.captured
+            .saturating_duration_since(b.captured)
+            .max(b.captured.saturating_duration_since(a.captured))
+    }
+
+    /// Adds a frame; returns a pair when it, or a frame already waiting,
+    /// found a partner within the skew limit.
+    fn push(&mut self, side: Side, frame: Arc<StereoFrame>) -> Option<StereoPair> {
+        let (own, other) = match side {
+            Side::Left => (&mut self.left, &mut self.right),
+            Side::Right => (&mut self.right, &mut self.left),
+        };
+        let nearest = other
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (i, Self::skew(f, &frame)))
+            .min_by_key(|(_, skew)| *skew)
+            .filter(|(_, skew)| *skew <= self.max_skew);
+
+        let Some((index, skew)) = nearest else {
+            own.push_back(frame);
+            if own.len() > PAIR_BUFFER {
+                own.pop_front();
+                self.unpaired += 1;
+            }
+            return None;
+        };
+        // Everything older than the match can only pair worse from now on
+        let matched = other.drain(..=index).last()?;
+        self.unpaired += (index + own.len()) as u64;
+        own.clear();
+        let (left, right) = match side {
+            Side::Left => (frame, matched),
+            Side::Right => (matched, frame),
+        };
+        Some(StereoPair { left, right, skew })
+    }
+}
+
+pub struct StereoRig {
+    pairer: Mutex<Pairer>,
+    dispatcher: FrameDispatcher<StereoPair>,
+    pairs: Mutex<(u64, VecDeque<Duration>)>,
+    rate: RateMeter,
+}
+
+impl StereoRig {
+    fn new(max_skew: Duration) -> Self {
+        Self {
+            pairer: Mutex::new(Pairer::new(max_skew)),
+            dispatcher: FrameDispatcher::new(),
+            pairs: Mutex::new((0, VecDeque::new())),
+            rate: RateMeter::new(),
+        }
+    }
+
+    fn push(&self, side: Side, frame: StereoFrame) {
+        let Some(pair) = self.pairer.lock().unwrap().push(side, Arc::new(frame)) else {
+            return;
+        };
+        {
+            let mut pairs = self.pairs.lock().unwrap();
+            pairs.0 += 1;
+            pairs.1.push_back(pair.skew);
+            if pairs.1.len() > SKEW_HISTORY {
+                pairs.1.pop_front();
+            }
+        }
+        self.rate.tick();
+        self.dispatcher.publish(Arc::new(pair));
+    }
+
+    /// Registers a consumer of synchronized pairs.
+    pub fn subscribe(&self, name: &str, decimation: Decimation) -> FrameSubscription<StereoPair> {
+        self.dispatcher.subscribe(name, decimation)
+    }
+
+    /// Pairs matched per second.
+    pub fn pair_rate(&self) -> RateStats {
+        self.rate.stats()
+    }
+
+    pub fn consumers(&self) -> Vec<ConsumerInfo> {
+        self.dispatcher.consumers()
+    }
+
+    pub fn stats(&self) -> StereoStats {
+        let (max_skew, unpaired) = {
+            let pairer = self.pairer.lock().unwrap();
+            (pairer.max_skew, pairer.unpaired)
+        };
+        let pairs = self.pairs.lock().unwrap();
+        let ms = |d: &Duration| d.as_secs_f32() * 1000.0;
+        StereoStats {
+            pairs: pairs.0,
+            unpaired,
+            max_skew_ms: ms(&max_skew),
+            mean_skew_ms: (!pairs.1.is_empty())
+                .then(|| pairs.1.iter().map(ms).sum::<f32>() / pairs.1.len() as f32),
+            worst_skew_ms: pairs.1.iter().max().map(ms),
+        }
+    }
+}
+
+/// A numeric source is a V4L2 index, anything else a libcamera camera name.
+fn open_camera(source: &str, settings: &CaptureSettings) -> Option<videoio::VideoCapture> {
+    let cap = match source.parse::<i32>() {
+        Ok(index) => {
+            let mut cap = videoio::VideoCapture::new(index, videoio::CAP_V4L2).ok()?;
+            let _ = cap.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64);
+            let _ = cap.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64);
+            let _ = cap.set(videoio::CAP_PROP_FPS, settings.fps as f64);
+            cap
+        }
+        Err(_) => {
+            let pipeline = format!(
+                "libcamerasrc camera-name={} ! video/x-raw, width={}, height={}, framerate={}/1 ! videoconvert ! appsink",
+                source, settings.width, settings.height, settings.fps
+            );
+            videoio::VideoCapture::from_file(&pipeline, videoio::CAP_GSTREAMER).ok()?
+        }
+    };
+    cap.is_opened().unwrap_or(false).then_some(cap)
+}
+
+fn spawn_capture(rig: Arc<StereoRig>, side: Side, mut cap: videoio::VideoCapture) {
+    thread::spawn(move || {
+        threads::label("stereo");
+        let mut seq = 0;
+        loop {
+            if faults::killed("stereo") {
+                return;
+            }
+            let mut frame = core::Mat::default();
+            match cap.read(&mut frame) {
+                Ok(true) => {
+                    let captured = Instant::now();
+                    seq += 1;
+                    rig.push(
+                        side,
+                        StereoFrame {
+                            seq,
+                            captured,
+                            frame,
+                        },
+                    );
+                }
+                _ => thread::sleep(Duration::from_millis(50)),
+            }
+        }
+    });
+}
+
+/// Opens both cameras; `None` (logged) when either is missing.
+pub fn start_stereo_rig(config: StereoConfig) -> Option<Arc<StereoRig>> {
+    let Some(left) = open_camera(&config.left, &config.settings) else {
+        println!("[WARN] Stereo left camera '{}' unavailable", config.left);
+        return None;
+    };
+    let Some(right) = open_camera(&config.right, &config.settings) else {
+        println!("[WARN] Stereo right camera '{}' unavailable", config.right);
+        return None;
+    };
+    println!(
+        "[OK] Stereo rig open ({} / {}, max skew {} ms)",
+        config.left,
+        config.right,
+        config.max_skew.as_millis()
+    );
+    let rig = Arc::new(StereoRig::new(config.max_skew));
+    spawn_capture(rig.clone(), Side::Left, left);
+    spawn_capture(rig.clone(), Side::Right, right);
+    Some(rig)
+}
+
+/// Pairing statistics into the `stereo` telemetry stream, once a second.
+pub fn start_skew_telemetry(state: AppState, rig: Arc<StereoRig>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TELEMETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let stats = rig.stats();
+            let mut values = serde_json::Map::new();
+            values.insert("pairs".into(), stats.pairs.into());
+            values.insert("unpaired".into(), stats.unpaired.into());
+            if let Some(mean) = stats.mean_skew_ms {
+                values.insert("mean_skew_ms".into(), mean.into());
+            }
+            if let Some(worst) = stats.worst_skew_ms {
+                values.insert("worst_skew_ms".into(), worst.into());
+            }
+            state.record_telemetry("stereo", values);
+        }
+    });
+}
+
+pub fn routes(rig: Arc<StereoRig>) -> Router {
+    Router::new()
+        .route("/stereo/stats", get(get_stats))
+        .with_state(rig)
+}
+
+async fn get_stats(State(rig): State<Arc<StereoRig>>) -> Json<StereoStats> {
+    Json(rig.stats())
+}
@@ -0,0 +1,258 @@
+//! MJPEG over HTTP (`multipart/x-mixed-replace`), so the camera can be
+//! watched from any browser without a Socket.IO client.
+//!
+//! Every client gets its own frame subscription at the rate it asked for
+//! (`?fps=`), capped at the profile's `stream_max_fps`, and encoded at its
+//! `stream_jpeg_quality` unless `[stream]` in the config says otherwise; a
+//! slow client skips frames instead of queueing them.
+//! `?annotate=true` draws detections, rates and the overlay onto the frames
+//! (see `annotate`), class names in the language `?locale=` picks; faces are
+//! blurred when face blur is on.
+//! Clients count against the `stream` viewer limit and can be kicked like
+//! any other viewer.
+//!
+//! `/snapshot` returns just the newest frame as one JPEG, for debugging and
+//! the dashboard's capture button; it takes no viewer slot.
+//!
+//! Both serve the primary camera; `/stream/<id>` and `/snapshot/<id>` serve
+//! any camera by its configured id.
+
+use crate::annotate;
+use crate::camera::Camera;
+use crate::config::StreamConfig;
+use crate::dispatch::{Decimation, FrameSubscription};
+use crate::state::AppState;
+use crate::viewers::{self, ViewerGuard};
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use opencv::core::Mat;
+use raspibot_protocol::camera::{SnapshotQuery, StreamQuery};
+use raspibot_protocol::health::ProfileSettings;
+use raspibot_protocol::locale::Locale;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const BOUNDARY: &str = "frame";
+
+#[derive(Debug, Clone, Copy)]
+pub struct StreamSettings {
+    pub max_fps: f32,
+    pub quality: i32,
+    /// Whether MJPEG and Socket.IO frames are annotated unless a client
+    /// asks otherwise.
+    pub annotate: bool,
+}
+
+impl StreamSettings {
+    /// `config`'s limits, the profile's where it sets none.
+    pub fn new(config: &StreamConfig, profile: &ProfileSettings) -> Self {
+        Self {
+            max_fps: config
+                .max_fps
+                .unwrap_or(profile.stream_max_fps.max(1) as f32),
+            quality: config
+                .jpeg_quality
+                .unwrap_or(profile.stream_jpeg_quality)
+                .clamp(1, 100),
+            annotate: config.annotate,
+        }
+    }
+}
+
+/// One client's stream; dropping it unsubscribes and frees the viewer slot.
+struct Client {
+    frames: FrameSubscription<Mat>,
+    guard: ViewerGuard,
+    quality: i32,
+    state: AppState,
+    /// Set when this client gets annotated frames, with the language of
+    /// their labels.
+    annotate: Option<(Arc<Camera>, Locale)>,
+}
+
+impl Client {
+    /// The next multipart part, or `None` once the client was kicked.
+    async fn next_part(&self) -> Option<Bytes> {
+        loop {
+            let frame = tokio::select! {
+                frame = self.frames.recv() => frame,
+                _ = self.guard.kicked() => return None,
+            };
+            let quality = self.quality;
+            let state = self.state.clone();
+            let annotate = self.annotate.clone();
+            let jpeg = match tokio::task::spawn_blocking(move || {
+                let annotate = annotate
+                    .as_ref()
+                    .map(|(camera, locale)| (camera.as_ref(), *locale));
+                annotate::publish_jpeg(&state, &frame, quality, annotate)
+            })
+            .await
+            {
+                Ok(Ok(jpeg)) => jpeg,
+                Ok(Err(e)) => {
+                    println!("[WARN] Could not encode stream frame: {}", e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            let mut part = format!(
+                "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                BOUNDARY,
+                jpeg.len()
+            )
+            .into_bytes();
+            part.extend_from_slice(&jpeg);
+            part.extend_from_slice(b"\r\n");
+            self.guard.record_sent(part.len());
+            return Some(Bytes::from(part));
+        }
+    }
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/stream", get(stream))
+        .route("/stream/{camera}", get(stream_camera))
+        .route("/snapshot", get(snapshot))
+        .route("/snapshot/{camera}", get(snapshot_camera))
+        .with_state(state)
+}
+
+/// Language of the labels a client asked for; English when unset or
+/// unknown.
+fn locale(tag: &Option<String>) -> Locale {
+    tag.as_deref()
+        .and_then(Locale::from_tag)
+        .unwrap_or_default()
+}
+
+fn no_such_camera(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("no camera '{}'", id)).into_response()
+}
+
+async fn stream(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<StreamQuery>,
+) -> Response {
+    let camera = state.cameras.primary().clone();
+    serve_stream(state, camera, addr, query)
+}
+
+async fn stream_camera(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(query): Query<StreamQuery>,
+) -> Response {
+    match state.cameras.get(&id).cloned() {
+        Some(camera) => serve_stream(state, camera, addr, query),
+        None => no_such_camera(&id),
+    }
+}
+
+fn serve_stream(
+    state: AppState,
+    camera: Arc<Camera>,
+    addr: SocketAddr,
+    query: StreamQuery,
+) -> Response {
+    let Some(guard) = state.viewers.join(viewers::STREAM, Some(addr)) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "stream viewer limit reached",
+        )
+            .into_response();
+    };
+    let settings = state.stream;
+    let fps = query
+        .fps
+        .filter(|fps| *fps > 0.0)
+        .map_or(settings.max_fps, |fps| fps.min(settings.max_fps));
+    let frames = camera
+        .frames
+        .subscribe(&format!("stream-{}", guard.id()), Decimation::MaxFps(fps));
+    println!(
+        "[INFO] MJPEG viewer {} of '{}' connected from {} at {} fps",
+        guard.id(),
+        camera.id,
+        addr,
+        fps
+    );
+    let annotated = query.annotate.unwrap_or(state.stream.annotate);
+    let client = Client {
+        frames,
+        guard,
+        quality: settings.quality,
+        annotate: annotated.then(|| (camera, locale(&query.locale))),
+        state,
+    };
+    let parts = futures_util::stream::unfold(client, |client| async move {
+        let part = client.next_part().await?;
+        Some((Ok::<_, std::io::Error>(part), client))
+    });
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                format!("multipart/x-mixed-replace; boundary={}", BOUNDARY),
+            ),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        Body::from_stream(parts),
+    )
+        .into_response()
+}
+
+async fn snapshot(State(state): State<AppState>, Query(query): Query<SnapshotQuery>) -> Response {
+    let camera = state.cameras.primary().clone();
+    serve_snapshot(state, camera, query).await
+}
+
+async fn snapshot_camera(
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    Query(query): Query<SnapshotQuery>,
+) -> Response {
+    match state.cameras.get(&id).cloned() {
+        Some(camera) => serve_snapshot(state, camera, query).await,
+        None => no_such_camera(&id),
+    }
+}
+
+async fn serve_snapshot(state: AppState, camera: Arc<Camera>, query: SnapshotQuery) -> Response {
+    let Some(frame) = camera.frames.get() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "no camera frame yet").into_response();
+    };
+    let quality = query
+        .quality
+        .map_or(state.stream.quality, |q| q.clamp(1, 100));
+    let annotated = query.annotated.unwrap_or(state.stream.annotate);
+    let annotate = annotated.then(|| (camera, locale(&query.locale)));
+    match tokio::task::spawn_blocking(move || {
+        let annotate = annotate
+            .as_ref()
+            .map(|(camera, locale)| (camera.as_ref(), *locale));
+        annotate::publish_jpeg(&state, &frame, quality, annotate)
+    })
+    .await
+    {
+        Ok(Ok(jpeg)) => (
+            [
+                (header::CONTENT_TYPE, "image/jpeg"),
+                (header::CACHE_CONTROL, "no-store"),
+            ],
+            jpeg,
+        )
+            .into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,225 @@
+//! Operator target designation: a click on the dashboard stream picks the
+//! object a visual-servo controller (follow, gimbal, ...) should act on.
+//!
+//! The click is mapped back through the dashboard's scaling, rotation and
+//! mirroring to frame pixels, then matched against the latest detections.
+//! When nothing was detected there, a fixed patch around the point becomes
+//! the target instead.
+
+use crate::state::AppState;
+use crate::visual_servo::CONTROLLERS;
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use raspibot_protocol::inference::{DetectedObject, Freshness};
+use raspibot_protocol::target::{DesignateRequest, Target, TargetChange, TargetKind};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Max distance from a detection's box to count as a click on it, as a
+/// fraction of the frame width.
+const MAX_MISS: f32 = 0.05;
+/// Side of the patch designated when no detection is near the click.
+const POINT_PATCH: i32 = 48;
+
+pub struct TargetStore {
+    targets: Mutex<BTreeMap<String, Target>>,
+    changes: broadcast::Sender<TargetChange>,
+}
+
+impl TargetStore {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(16);
+        Self {
+            targets: Mutex::new(BTreeMap::new()),
+            changes,
+        }
+    }
+
+    pub fn all(&self) -> BTreeMap<String, Target> {
+        self.targets.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, target: Target) {
+        self.targets
+            .lock()
+            .unwrap()
+            .insert(target.controller.clone(), target.clone());
+        let _ = self.changes.send(TargetChange {
+            controller: target.controller.clone(),
+            target: Some(target),
+        });
+    }
+
+    /// Returns whether `controller` had a target.
+    pub fn release(&self, controller: &str) -> bool {
+        let removed = self.targets.lock().unwrap().remove(controller).is_some();
+        if removed {
+            let _ = self.changes.send(TargetChange {
+                controller: controller.to_string(),
+                target: None,
+            });
+        }
+        removed
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TargetChange> {
+        self.changes.subscribe()
+    }
+}
+
+/// Maps a click on the displayed stream to frame pixels, undoing the view's
+/// mirroring, then its clockwise rotation, then its scaling.
+fn click_to_frame(request: &DesignateRequest, width: i32, height: i32) -> (f32, f32) {
+    let mut u = (request.x / request.view_width).clamp(0.0, 1.0);
+    let v = (request.y / request.view_height).clamp(0.0, 1.0);
+    if request.mirrored {
+        u = 1.0 - u;
+    }
+    let (fu, fv) = match request.rotation_deg {
+        90 => (v, 1.0 - u),
+        180 => (1.0 - u, 1.0 - v),
+        270 => (1.0 - v, u),
+        _ => (u, v),
+    };
+    (fu * width as f32, fv * height as f32)
+}
+
+/// Distance from `point` to the box, zero inside it.
+fn distance_to(bbox: &[i32; 4], point: (f32, f32)) -> f32 {
+    let [x, y, w, h] = bbox.map(|v| v as f32);
+    let dx = (x - point.0).max(point.0 - (x + w)).max(0.0);
+    let dy = (y - point.1).max(point.1 - (y + h)).max(0.0);
+    dx.hypot(dy)
+}
+
+/// The detection the click meant: the smallest box containing it, else the
+/// closest one within `max_miss` pixels.
+fn pick<'a>(
+    objects: &'a [DetectedObject],
+    point: (f32, f32),
+    max_miss: f32,
+) -> Option<&'a DetectedObject> {
+    let area = |o: &DetectedObject| o.bbox[2] as i64 * o.bbox[3] as i64;
+    objects
+        .iter()
+        .filter(|o| distance_to(&o.bbox, point) == 0.0)
+        .min_by_key(|o| area(o))
+        .or_else(|| {
+            objects
+                .iter()
+                .map(|o| (o, distance_to(&o.bbox, point)))
+                .filter(|(_, d)| *d <= max_miss)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(o, _)| o)
+        })
+}
+
+fn validate(request: &DesignateRequest) -> Result<(), String> {
+    if !CONTROLLERS.contains(&request.controller.as_str()) {
+        return Err(format!("unknown controller '{}'", request.controller));
+    }
+    if !(request.view_width > 0.0 && request.view_height > 0.0) {
+        return Err("view_width and view_height must be positive".to_string());
+    }
+    if ![0, 90, 180, 270].contains(&request.rotation_deg) {
+        return Err("rotation_deg must be 0, 90, 180 or 270".to_string());
+    }
+    if !(request.x.is_finite() && request.y.is_finite()) {
+        return Err("x and y must be finite".to_string());
+    }
+    Ok(())
+}
+
+fn designate(state: &AppState, request: &DesignateRequest) -> Result<Target, String> {
+    validate(request)?;
+    let (width, height) = state
+        .frames
+        .frame_size()
+        .ok_or_else(|| "no camera frame yet".to_string())?;
+    let point = click_to_frame(request, width, height);
+
+    // Stale detections say nothing about what is under the cursor now
+    let limits = state.detections.limits();
+    let object = state.detections.latest().and_then(|published| {
+        let set = published.to_set(&limits);
+        if set.freshness == Freshness::Stale {
+            return None;
+        }
+        pick(&set.objects, point, MAX_MISS * width as f32).map(|o| (set.seq, o.clone()))
+    });
+
+    let (kind, bbox) = match object {
+        Some((seq, object)) => (
+            TargetKind::Object {
+                class: object.class,
+                confidence: object.confidence,
+                seq,
+                track_id: object.track_id,
+            },
+            object.bbox,
+        ),
+        None => {
+            let half = POINT_PATCH / 2;
+            let x = (point.0 as i32 - half).clamp(0, (width - POINT_PATCH).max(0));
+            let y = (point.1 as i32 - half).clamp(0, (height - POINT_PATCH).max(0));
+            (
+                TargetKind::Point,
+                [x, y, POINT_PATCH.min(width), POINT_PATCH.min(height)],
+            )
+        }
+    };
+    let target = Target {
+        controller: request.controller.clone(),
+        kind,
+        point: [point.0 as i32, point.1 as i32],
+        bbox,
+        designated_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    };
+    state.targets.set(target.clone());
+    Ok(target)
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/targets", get(list_targets))
+        .route("/targets/designate", post(designate_target))
+        .route("/targets/{controller}", delete(release_target))
+        .with_state(state)
+}
+
+async fn list_targets(State(state): State<AppState>) -> Json<BTreeMap<String, Target>> {
+    Json(state.targets.all())
+}
+
+async fn designate_target(
+    State(state): State<AppState>,
+    Json(request): Json<DesignateRequest>,
+) -> Result<Json<Target>, (StatusCode, String)> {
+    let target = designate(&state, &request).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    println!(
+        "[INFO] Target for '{}' designated at ({}, {})",
+        target.controller, target.point[0], target.point[1]
+    );
+    Ok(Json(target))
+}
+
+async fn release_target(
+    State(state): State<AppState>,
+    UrlPath(controller): UrlPath<String>,
+) -> StatusCode {
+    if state.targets.release(&controller) {
+        println!("[INFO] Target for '{}' released", controller);
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
@@ -0,0 +1,260 @@
+//! Downsampling of telemetry for remote clients: a stream recorded at
+//! 100 Hz goes out at, say, 10 Hz as min/mean/max/last per field, which
+//! cuts WiFi load without hiding the extremes needed for debugging.
+
+use crate::clock::{Clock, SystemClock};
+use crate::imu::Imu;
+use crate::range::RangeSensors;
+use crate::serial::McuBridge;
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::telemetry::{DownsampleConfig, FieldAggregate, TelemetryAggregate};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+struct Bucket {
+    started: Instant,
+    samples: usize,
+    sums: BTreeMap<String, (FieldAggregate, f64)>,
+}
+
+impl Bucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            samples: 0,
+            sums: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, values: &Map<String, Value>) {
+        self.samples += 1;
+        for (key, value) in values {
+            let Some(v) = value.as_f64().or_else(|| value.as_bool().map(f64::from)) else {
+                continue;
+            };
+            let (agg, sum) = self.sums.entry(key.clone()).or_insert((
+                FieldAggregate {
+                    min: v,
+                    mean: 0.0,
+                    max: v,
+                    last: v,
+                },
+                0.0,
+            ));
+            agg.min = agg.min.min(v);
+            agg.max = agg.max.max(v);
+            agg.last = v;
+            *sum += v;
+        }
+    }
+}
+
+pub struct TelemetryDownsampler {
+    config: Mutex<DownsampleConfig>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    tx: broadcast::Sender<TelemetryAggregate>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TelemetryDownsampler {
+    /// Sends every stream at `default_hz` until told otherwise.
+    pub fn with_default_hz(default_hz: f32) -> Self {
+        let config = DownsampleConfig {
+            default_hz,
+            ..DownsampleConfig::default()
+        };
+        Self::new(config, Arc::new(SystemClock))
+    }
+
+    /// A downsampler that windows by `clock` instead of the system time.
+    pub fn new(config: DownsampleConfig, clock: Arc<dyn Clock>) -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self {
+            config: Mutex::new(config),
+            buckets: Mutex::new(HashMap::new()),
+            tx,
+            clock,
+        }
+    }
+
+    pub fn config(&self) -> DownsampleConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: DownsampleConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Adds one full-rate sample; an aggregate goes out once the stream's
+    /// period has passed since the previous one.
+    pub fn record(&self, stream: &str, values: &Map<String, Value>) {
+        let hz = {
+            let config = self.config.lock().unwrap();
+            config
+                .streams
+                .get(stream)
+                .copied()
+                .unwrap_or(config.default_hz)
+        };
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(stream.to_string())
+            .or_insert_with(|| Bucket::new(now));
+        bucket.add(values);
+        let due = hz <= 0.0 || now.duration_since(bucket.started).as_secs_f32() * hz >= 1.0;
+        if !due {
+            return;
+        }
+        let bucket = std::mem::replace(bucket, Bucket::new(now));
+        let samples = bucket.samples;
+        let fields = bucket
+            .sums
+            .into_iter()
+            .map(|(key, (mut agg, sum))| {
+                agg.mean = sum / samples as f64;
+                (key, agg)
+            })
+            .collect();
+        let _ = self.tx.send(TelemetryAggregate {
+            stream: stream.to_string(),
+            samples,
+            window_ms: now.duration_since(bucket.started).as_millis() as u64,
+            fields,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TelemetryAggregate> {
+        self.tx.subscribe()
+    }
+}
+
+/// Records every MCU telemetry push as the `mcu` stream: calibrated ADC
+/// channels as volts under their name, anything else as raw counts under
+/// `sensor_<id>`. A `battery` channel also feeds the run's battery figures.
+pub fn forward_mcu(state: AppState, mcu: &McuBridge) {
+    let mut sensors = mcu.telemetry();
+    tokio::spawn(async move {
+        loop {
+            let readings = match sensors.recv().await {
+                Ok(readings) => readings,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let adc = state.units.get().adc;
+            let mut values = Map::new();
+            for reading in &readings {
+                match adc.get(&reading.id) {
+                    Some(channel) => {
+                        let volts = channel.volts(reading.value);
+                        state.charging.record_channel(&channel.name, volts);
+                        if channel.name == "battery" {
+                            state.sessions.record_battery(volts);
+                            state.power.record_battery(volts);
+                        }
+                        values.insert(format!("{}_v", channel.name), Value::from(volts));
+                    }
+                    None => {
+                        values.insert(format!("sensor_{}", reading.id), Value::from(reading.value));
+                    }
+                }
+            }
+            state.record_telemetry("mcu", values);
+        }
+    });
+}
+
+/// Records the MCU's wheel encoder counts as the `odometry` stream.
+pub fn forward_odometry(state: AppState, mcu: &McuBridge) {
+    let mut odometry = mcu.odometry();
+    tokio::spawn(async move {
+        loop {
+            let counts = match odometry.recv().await {
+                Ok(counts) => counts,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let mut values = Map::new();
+            values.insert("left_ticks".to_string(), Value::from(counts.left_ticks));
+            values.insert("right_ticks".to_string(), Value::from(counts.right_ticks));
+            values.insert("mcu_ms".to_string(), Value::from(counts.mcu_ms));
+            state.record_telemetry("odometry", values);
+        }
+    });
+}
+
+/// Records every IMU reading as the `imu` stream.
+pub fn forward_imu(state: AppState, imu: &Imu) {
+    let mut readings = imu.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let reading = match readings.recv().await {
+                Ok(reading) => reading,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let mut values = Map::new();
+            for (axis, (accel, gyro)) in ["x", "y", "z"]
+                .into_iter()
+                .zip(reading.accel_mps2.into_iter().zip(reading.gyro_dps))
+            {
+                values.insert(format!("accel_{}_mps2", axis), Value::from(accel));
+                values.insert(format!("gyro_{}_dps", axis), Value::from(gyro));
+            }
+            values.insert("roll_deg".to_string(), Value::from(reading.roll_deg));
+            values.insert("pitch_deg".to_string(), Value::from(reading.pitch_deg));
+            values.insert("heading_deg".to_string(), Value::from(reading.heading_deg));
+            if let Some(heading) = reading.magnetic_heading_deg {
+                values.insert("magnetic_heading_deg".to_string(), Value::from(heading));
+            }
+            state.record_telemetry("imu", values);
+        }
+    });
+}
+
+/// Records every round of distance readings as the `range` stream, one
+/// `<sensor>_m` field per sensor with something in range.
+pub fn forward_range(state: AppState, sensors: &RangeSensors) {
+    let mut rounds = sensors.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let readings = match rounds.recv().await {
+                Ok(readings) => readings,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let mut values = Map::new();
+            for reading in readings {
+                if let Some(distance) = reading.distance_m {
+                    values.insert(format!("{}_m", reading.sensor), Value::from(distance));
+                }
+            }
+            state.record_telemetry("range", values);
+        }
+    });
+}
+
+pub fn routes(downsampler: Arc<TelemetryDownsampler>) -> Router {
+    Router::new()
+        .route("/telemetry/downsampling", get(get_config).put(set_config))
+        .with_state(downsampler)
+}
+
+async fn get_config(
+    State(downsampler): State<Arc<TelemetryDownsampler>>,
+) -> Json<DownsampleConfig> {
+    Json(downsampler.config())
+}
+
+async fn set_config(
+    State(downsampler): State<Arc<TelemetryDownsampler>>,
+    Json(config): Json<DownsampleConfig>,
+) -> Json<DownsampleConfig> {
+    downsampler.set_config(config.clone());
+    println!("[INFO] Remote telemetry rates updated: {:?}", config);
+    Json(config)
+}
@@ -0,0 +1,158 @@
+//! Teleoperation over the `/control` Socket.IO namespace, the path the
+//! operator drives by. The dashboard sends joystick or keyboard input as
+//! `teleop` at about 20 Hz; each one is mixed onto the two sides and
+//! submitted to the arbiter as teleop, and the ack says what reached the
+//! motors.
+//!
+//! A deadman switch stops the robot once the client driving has been
+//! silent for `drive.deadman_ms` (default 300), well before the general
+//! drive watchdog would, or as soon as it disconnects. Either way the
+//! namespace gets a `deadman` event. It only stops teleop driving; an
+//! autonomous mission that took the drive over is left alone.
+
+use crate::arbiter::CommandSource;
+use crate::power;
+use crate::robot;
+use crate::state::AppState;
+use raspibot_protocol::drive::{DeadmanTrip, TeleopAck, TeleopInput};
+use raspibot_protocol::events;
+use socketioxide::{
+    extract::{AckSender, Data, SocketRef, State},
+    SocketIo,
+};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Driver {
+    socket: String,
+    last_input: Instant,
+    disconnected: bool,
+}
+
+pub struct Teleop {
+    deadman: Duration,
+    /// The client whose input last went to the motors.
+    driver: Mutex<Option<Driver>>,
+}
+
+impl Teleop {
+    pub fn new(deadman: Duration) -> Self {
+        Self {
+            deadman,
+            driver: Mutex::new(None),
+        }
+    }
+
+    fn input(&self, socket: &str) {
+        *self.driver.lock().unwrap() = Some(Driver {
+            socket: socket.to_string(),
+            last_input: Instant::now(),
+            disconnected: false,
+        });
+    }
+
+    /// Lets go of the driving client once it has been silent too long or
+    /// disconnected, returning how long it has been silent.
+    fn expired(&self) -> Option<Duration> {
+        let mut driver = self.driver.lock().unwrap();
+        let current = driver.as_ref()?;
+        let silent = current.last_input.elapsed();
+        if silent < self.deadman && !current.disconnected {
+            return None;
+        }
+        *driver = None;
+        Some(silent)
+    }
+
+    /// Has the deadman trip at once if `socket` was driving.
+    fn disconnected(&self, socket: &str) {
+        if let Some(driver) = self.driver.lock().unwrap().as_mut() {
+            if driver.socket == socket {
+                driver.disconnected = true;
+            }
+        }
+    }
+}
+
+/// Stops the motors if teleop is what is driving them.
+async fn trip(state: &AppState, io: &SocketIo, silent: Duration) {
+    let driving = state
+        .arbiter
+        .last()
+        .is_some_and(|(source, _)| source == CommandSource::Teleop);
+    if !driving {
+        return;
+    }
+    if let Err(e) = state.arbiter.stop() {
+        eprintln!("[ERR] Deadman could not stop motors: {}", e);
+        return;
+    }
+    println!(
+        "[WARN] No teleop input for {} ms, motors stopped",
+        silent.as_millis()
+    );
+    let payload = DeadmanTrip {
+        silent_ms: silent.as_millis() as u64,
+        timeout_ms: state.teleop.deadman.as_millis() as u64,
+    };
+    if let Some(ns) = io.of(events::CONTROL_NAMESPACE) {
+        let _ = ns.emit(events::DEADMAN, &payload).await;
+    }
+}
+
+pub fn on_connect(socket: SocketRef, State(state): State<AppState>) {
+    println!("[INFO] Socket.IO control client connected: {}", socket.id);
+    socket.emit(events::ROBOT_INFO, &robot::info(&state)).ok();
+    socket.emit(events::ESTOP_STATE, &state.estop.status()).ok();
+
+    socket.on(
+        events::TELEOP,
+        |socket: SocketRef,
+         Data(input): Data<TeleopInput>,
+         ack: AckSender,
+         State(state): State<AppState>| {
+            power::activity(&state, "operator drive");
+            state.teleop.input(&socket.id.to_string());
+            let answer = match state
+                .arbiter
+                .submit(CommandSource::Teleop, input.to_command())
+            {
+                Ok(sent) => TeleopAck {
+                    sent: Some(sent),
+                    refused: None,
+                },
+                Err(e) => TeleopAck {
+                    sent: None,
+                    refused: Some(e.to_string()),
+                },
+            };
+            ack.send(&answer).ok();
+        },
+    );
+
+    socket.on_disconnect(|socket: SocketRef, State(state): State<AppState>| {
+        println!(
+            "[INFO] Socket.IO control client disconnected: {}",
+            socket.id
+        );
+        state.teleop.disconnected(&socket.id.to_string());
+    });
+}
+
+/// Watches the driving client and trips the deadman when its input stops
+/// or it disconnects.
+pub fn start_deadman(state: AppState, io: SocketIo) {
+    println!(
+        "[INFO] Teleop deadman: stop after {:?} without input",
+        state.teleop.deadman
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.teleop.deadman / 6);
+        loop {
+            interval.tick().await;
+            if let Some(silent) = state.teleop.expired() {
+                trip(&state, &io, silent).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,706 @@
+//! MLX90640 thermal camera (32 x 24 pixels) over I2C, for the
+//! fire-detection task.
+//!
+//! Every read delivers one of the two subpages the sensor interleaves in a
+//! chess pattern; a frame is published once both are in, four times a
+//! second. Raw readings become temperatures with the per-device calibration
+//! in the sensor's EEPROM, following the Melexis datasheet; pixels the
+//! EEPROM marks as broken or outliers are filled in from their neighbours.
+//!
+//! Frames are streamed as the `thermal` event and served at `/thermal`.
+//! Blobs at or above `hot_threshold_c` join the detector's objects as
+//! `hot_object`, boxed in camera pixels, so they are tracked, zoned and
+//! recorded like anything the model finds; `overlay` blends the heat image
+//! into annotated frames. Sensor and camera are taken to share a center and
+//! orientation, which is close enough when mounted side by side.
+
+use crate::faults;
+use crate::threads;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use opencv::{
+    core::{self, Mat, Rect, Size},
+    imgproc,
+    prelude::*,
+};
+use raspibot_protocol::inference::DetectedObject;
+use raspibot_protocol::thermal::{ThermalConfig, ThermalFrame};
+use raspibot_protocol::units::CameraOptics;
+use rppal::i2c::I2c;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+const I2C_ADDR: u16 = 0x33;
+const REG_STATUS: u16 = 0x8000;
+const REG_CONTROL: u16 = 0x800D;
+const EEPROM: u16 = 0x2400;
+const RAM: u16 = 0x0400;
+/// EEPROM and RAM are both read whole: pixels, then auxiliary words.
+const WORDS: usize = 832;
+const STATUS_DATA_READY: u16 = 0x0008;
+/// Writing this to the status register clears data-ready and keeps
+/// overwriting enabled.
+const STATUS_CLEAR: u16 = 0x0030;
+const CONTROL_CHESS: u16 = 0x1000;
+/// Refresh-rate field of the control register: 8 subpages per second.
+const CONTROL_REFRESH_MASK: u16 = 0x0380;
+const CONTROL_REFRESH_8HZ: u16 = 0b100 << 7;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub const WIDTH: usize = 32;
+pub const HEIGHT: usize = 24;
+const PIXELS: usize = WIDTH * HEIGHT;
+/// The reflected temperature is taken as this much below the sensor's own,
+/// as Melexis recommends in open air.
+const TA_SHIFT: f32 = 8.0;
+/// Frames older than this produce no hot objects and no overlay.
+const FRESH_FOR: Duration = Duration::from_secs(1);
+/// Degrees above the threshold at which a hot object reaches confidence 1.
+const CONFIDENCE_SPAN_C: f32 = 40.0;
+/// Temperature range the overlay spans at least, so a uniform scene isn't
+/// painted as noise.
+const MIN_OVERLAY_SPAN_C: f32 = 5.0;
+
+pub const CONFIG_PATH: &str = "data/thermal.json";
+pub const HOT_CLASS: &str = "hot_object";
+/// Tracker class of hot objects, clear of the detector's class indices.
+pub const HOT_CLASS_ID: i64 = -1;
+
+/// `bits` wide two's complement field.
+fn signed(value: u16, bits: u32) -> i32 {
+    let value = value as i32;
+    if value >= 1 << (bits - 1) {
+        value - (1 << bits)
+    } else {
+        value
+    }
+}
+
+/// The four signed nibbles of each word, low nibble first.
+fn nibbles(words: &[u16]) -> Vec<i32> {
+    words
+        .iter()
+        .flat_map(|w| (0..4).map(move |n| signed((w >> (4 * n)) & 0xF, 4)))
+        .collect()
+}
+
+/// Device constants from the EEPROM (datasheet section 11.1).
+struct Calibration {
+    k_vdd: f32,
+    vdd25: f32,
+    kv_ptat: f32,
+    kt_ptat: f32,
+    v_ptat25: f32,
+    alpha_ptat: f32,
+    gain: f32,
+    tgc: f32,
+    resolution: i32,
+    ks_ta: f32,
+    ks_to: [f32; 5],
+    ct: [f32; 5],
+    cp_offset: [f32; 2],
+    cp_kta: f32,
+    cp_kv: f32,
+    /// Whether the device was calibrated in chess mode; the interleaved
+    /// corrections apply when it runs in the other one.
+    chess_calibrated: bool,
+    il_chess: [f32; 3],
+    cp_alpha: [f32; 2],
+    alpha: Vec<f32>,
+    offset: Vec<f32>,
+    kta: Vec<f32>,
+    kv: Vec<f32>,
+    broken: Vec<bool>,
+}
+
+impl Calibration {
+    fn from_eeprom(ee: &[u16]) -> Self {
+        let tgc = signed(ee[60] & 0xFF, 8) as f32 / 32.0;
+
+        let step = ((ee[63] >> 12) & 0x3) as f32 * 10.0;
+        let ct2 = ((ee[63] >> 4) & 0xF) as f32 * step;
+        let ct3 = ct2 + ((ee[63] >> 8) & 0xF) as f32 * step;
+        let ks_to_scale = (1u32 << ((ee[63] & 0xF) + 8)) as f32;
+
+        let cp_alpha_scale = 2f32.powi(((ee[32] >> 12) + 27) as i32);
+        let cp_alpha0 = signed(ee[57] & 0x3FF, 10) as f32 / cp_alpha_scale;
+        let cp_alpha1 = (1.0 + signed(ee[57] >> 10, 6) as f32 / 128.0) * cp_alpha0;
+        let cp_offset0 = signed(ee[58] & 0x3FF, 10) as f32;
+        let kta_scale1 = 2f32.powi((((ee[56] >> 4) & 0xF) + 8) as i32);
+        let kta_scale2 = ee[56] & 0xF;
+        let kv_scale = 2f32.powi(((ee[56] >> 8) & 0xF) as i32);
+
+        // Pixel constants: a reference, row and column corrections, and the
+        // pixel's own bits, each with its scale
+        let occ_rem_scale = ee[16] & 0xF;
+        let occ_col_scale = (ee[16] >> 4) & 0xF;
+        let occ_row_scale = (ee[16] >> 8) & 0xF;
+        let offset_ref = ee[17] as i16 as i32;
+        let occ_row = nibbles(&ee[18..24]);
+        let occ_col = nibbles(&ee[24..32]);
+        let acc_rem_scale = ee[32] & 0xF;
+        let acc_col_scale = (ee[32] >> 4) & 0xF;
+        let acc_row_scale = (ee[32] >> 8) & 0xF;
+        let alpha_scale = 2f32.powi(((ee[32] >> 12) + 30) as i32);
+        let alpha_ref = ee[33] as i32;
+        let acc_row = nibbles(&ee[34..40]);
+        let acc_col = nibbles(&ee[40..48]);
+        // By row/column parity: odd-odd, odd-even, even-odd, even-even
+        let kta_rc = [
+            signed(ee[54] >> 8, 8),
+            signed(ee[55] >> 8, 8),
+            signed(ee[54] & 0xFF, 8),
+            signed(ee[55] & 0xFF, 8),
+        ];
+        let kv_rc = [
+            signed(ee[52] >> 12, 4),
+            signed((ee[52] >> 4) & 0xF, 4),
+            signed((ee[52] >> 8) & 0xF, 4),
+            signed(ee[52] & 0xF, 4),
+        ];
+
+        let mut alpha = vec![0.0; PIXELS];
+        let mut offset = vec![0.0; PIXELS];
+        let mut kta = vec![0.0; PIXELS];
+        let mut kv = vec![0.0; PIXELS];
+        let mut broken = vec![false; PIXELS];
+        for p in 0..PIXELS {
+            let (row, col) = (p / WIDTH, p % WIDTH);
+            let word = ee[64 + p];
+            offset[p] = (offset_ref
+                + (occ_row[row] << occ_row_scale)
+                + (occ_col[col] << occ_col_scale)
+                + signed(word >> 10, 6) * (1 << occ_rem_scale)) as f32;
+            let a = alpha_ref
+                + (acc_row[row] << acc_row_scale)
+                + (acc_col[col] << acc_col_scale)
+                + signed((word >> 4) & 0x3F, 6) * (1 << acc_rem_scale);
+            alpha[p] = a as f32 / alpha_scale;
+            let parity = 2 * (row % 2) + col % 2;
+            kta[p] = (kta_rc[parity] + signed((word >> 1) & 0x7, 3) * (1 << kta_scale2)) as f32
+                / kta_scale1;
+            kv[p] = kv_rc[parity] as f32 / kv_scale;
+            broken[p] = word == 0 || word & 0x1 != 0;
+        }
+
+        Self {
+            k_vdd: signed(ee[51] >> 8, 8) as f32 * 32.0,
+            vdd25: ((((ee[51] & 0xFF) as i32) - 256) * 32 - 8192) as f32,
+            kv_ptat: signed(ee[50] >> 10, 6) as f32 / 4096.0,
+            kt_ptat: signed(ee[50] & 0x3FF, 10) as f32 / 8.0,
+            v_ptat25: ee[49] as i16 as f32,
+            alpha_ptat: (ee[16] >> 12) as f32 / 4.0 + 8.0,
+            gain: ee[48] as i16 as f32,
+            tgc,
+            resolution: ((ee[56] >> 12) & 0x3) as i32,
+            ks_ta: signed(ee[60] >> 8, 8) as f32 / 8192.0,
+            ks_to: [
+                signed(ee[61] & 0xFF, 8) as f32 / ks_to_scale,
+                signed(ee[61] >> 8, 8) as f32 / ks_to_scale,
+                signed(ee[62] & 0xFF, 8) as f32 / ks_to_scale,
+                signed(ee[62] >> 8, 8) as f32 / ks_to_scale,
+                -0.0002,
+            ],
+            ct: [-40.0, 0.0, ct2, ct3, 400.0],
+            cp_offset: [cp_offset0, cp_offset0 + signed(ee[58] >> 10, 6) as f32],
+            cp_alpha: [cp_alpha0, cp_alpha1],
+            cp_kta: signed(ee[59] & 0xFF, 8) as f32 / kta_scale1,
+            cp_kv: signed(ee[59] >> 8, 8) as f32 / kv_scale,
+            chess_calibrated: ee[10] & 0x0800 == 0,
+            il_chess: [
+                signed(ee[53] & 0x3F, 6) as f32 / 16.0,
+                signed((ee[53] >> 6) & 0x1F, 5) as f32 / 2.0,
+                signed(ee[53] >> 11, 5) as f32 / 8.0,
+            ],
+            alpha,
+            offset,
+            kta,
+            kv,
+            broken,
+        }
+    }
+
+    fn vdd(&self, ram: &[u16], control: u16) -> f32 {
+        let resolution = ((control >> 10) & 0x3) as i32;
+        let correction = 2f32.powi(self.resolution - resolution);
+        (correction * ram[810] as i16 as f32 - self.vdd25) / self.k_vdd + 3.3
+    }
+
+    fn ambient(&self, ram: &[u16], vdd: f32) -> f32 {
+        let ptat = ram[800] as i16 as f32;
+        let ptat_art = ptat / (ptat * self.alpha_ptat + ram[768] as i16 as f32) * 2f32.powi(18);
+        (ptat_art / (1.0 + self.kv_ptat * (vdd - 3.3)) - self.v_ptat25) / self.kt_ptat + 25.0
+    }
+
+    /// Writes the temperatures of `subpage`'s pixels into `out` (the other
+    /// subpage's are left alone) and returns the sensor's own temperature.
+    fn subpage_to(
+        &self,
+        ram: &[u16],
+        control: u16,
+        subpage: usize,
+        emissivity: f32,
+        out: &mut [f32],
+    ) -> f32 {
+        let vdd = self.vdd(ram, control);
+        let ta = self.ambient(ram, vdd);
+        let (dta, dvdd) = (ta - 25.0, vdd - 3.3);
+        let ta4 = (ta + 273.15).powi(4);
+        let tr4 = (ta - TA_SHIFT + 273.15).powi(4);
+        let ta_tr = tr4 - (tr4 - ta4) / emissivity;
+        let corr2 = 1.0 + self.ks_to[1] * self.ct[2];
+        let alpha_corr = [
+            1.0 / (1.0 + self.ks_to[0] * 40.0),
+            1.0,
+            corr2,
+            corr2 * (1.0 + self.ks_to[2] * (self.ct[3] - self.ct[2])),
+        ];
+        let gain = self.gain / ram[778] as i16 as f32;
+        let chess = control & CONTROL_CHESS != 0;
+        let same_mode = chess == self.chess_calibrated;
+
+        let cp_drift = (1.0 + self.cp_kta * dta) * (1.0 + self.cp_kv * dvdd);
+        let cp = if subpage == 0 {
+            ram[776] as i16 as f32 * gain - self.cp_offset[0] * cp_drift
+        } else {
+            let il = if same_mode { 0.0 } else { self.il_chess[0] };
+            ram[808] as i16 as f32 * gain - (self.cp_offset[1] + il) * cp_drift
+        };
+
+        for p in 0..PIXELS {
+            let il_pattern = (p / WIDTH % 2) as i32;
+            let pattern = if chess {
+                il_pattern ^ (p % 2) as i32
+            } else {
+                il_pattern
+            };
+            if pattern != subpage as i32 {
+                continue;
+            }
+            let mut ir = ram[p] as i16 as f32 * gain;
+            ir -= self.offset[p] * (1.0 + self.kta[p] * dta) * (1.0 + self.kv[p] * dvdd);
+            if !same_mode {
+                let n = p as i32;
+                let conversion =
+                    ((n + 2) / 4 - (n + 3) / 4 + (n + 1) / 4 - n / 4) * (1 - 2 * il_pattern);
+                ir += self.il_chess[2] * (2 * il_pattern - 1) as f32
+                    - self.il_chess[1] * conversion as f32;
+            }
+            ir -= self.tgc * cp;
+            ir /= emissivity;
+
+            let alpha =
+                (self.alpha[p] - self.tgc * self.cp_alpha[subpage]) * (1.0 + self.ks_ta * dta);
+            let sx = (alpha.powi(3) * (ir + alpha * ta_tr)).sqrt().sqrt() * self.ks_to[1];
+            let to = (ir / (alpha * (1.0 - self.ks_to[1] * 273.15) + sx) + ta_tr)
+                .sqrt()
+                .sqrt()
+                - 273.15;
+            // Extended ranges get their own sensitivity correction
+            let range = self.ct[1..4].iter().take_while(|ct| to >= **ct).count();
+            out[p] = (ir
+                / (alpha * alpha_corr[range] * (1.0 + self.ks_to[range] * (to - self.ct[range])))
+                + ta_tr)
+                .sqrt()
+                .sqrt()
+                - 273.15;
+        }
+        ta
+    }
+
+    /// Replaces broken pixels with the mean of their working neighbours.
+    fn patch(&self, pixels: &mut [f32]) {
+        for p in (0..PIXELS).filter(|p| self.broken[*p]) {
+            let (row, col) = (p / WIDTH, p % WIDTH);
+            let neighbours: Vec<f32> = [
+                (row > 0, p.wrapping_sub(WIDTH)),
+                (row + 1 < HEIGHT, p + WIDTH),
+                (col > 0, p.wrapping_sub(1)),
+                (col + 1 < WIDTH, p + 1),
+            ]
+            .into_iter()
+            .filter(|(inside, n)| *inside && !self.broken[*n])
+            .map(|(_, n)| pixels[n])
+            .collect();
+            if !neighbours.is_empty() {
+                pixels[p] = neighbours.iter().sum::<f32>() / neighbours.len() as f32;
+            }
+        }
+    }
+}
+
+fn read_words(i2c: &I2c, address: u16, out: &mut [u16]) -> rppal::i2c::Result<()> {
+    let mut bytes = vec![0u8; out.len() * 2];
+    i2c.write_read(&address.to_be_bytes(), &mut bytes)?;
+    for (word, pair) in out.iter_mut().zip(bytes.chunks_exact(2)) {
+        *word = u16::from_be_bytes([pair[0], pair[1]]);
+    }
+    Ok(())
+}
+
+fn write_word(i2c: &I2c, address: u16, value: u16) -> rppal::i2c::Result<()> {
+    let [a0, a1] = address.to_be_bytes();
+    let [v0, v1] = value.to_be_bytes();
+    i2c.write(&[a0, a1, v0, v1])?;
+    Ok(())
+}
+
+/// Reads a new subpage into `ram`, returning the control register and the
+/// subpage number; `None` until one is ready, or if the sensor moved on to
+/// the next one during the read.
+fn read_subpage(i2c: &I2c, ram: &mut [u16]) -> rppal::i2c::Result<Option<(u16, usize)>> {
+    let mut status = [0u16];
+    read_words(i2c, REG_STATUS, &mut status)?;
+    if status[0] & STATUS_DATA_READY == 0 {
+        return Ok(None);
+    }
+    write_word(i2c, REG_STATUS, STATUS_CLEAR)?;
+    read_words(i2c, RAM, ram)?;
+    read_words(i2c, REG_STATUS, &mut status)?;
+    if status[0] & STATUS_DATA_READY != 0 {
+        return Ok(None);
+    }
+    let mut control = [0u16];
+    read_words(i2c, REG_CONTROL, &mut control)?;
+    Ok(Some((control[0], (status[0] & 0x1) as usize)))
+}
+
+pub struct ThermalCamera {
+    path: PathBuf,
+    config: Mutex<ThermalConfig>,
+    latest: Mutex<Option<(Instant, ThermalFrame)>>,
+    seq: AtomicU64,
+    tx: broadcast::Sender<ThermalFrame>,
+}
+
+impl ThermalCamera {
+    fn load() -> Self {
+        let path = PathBuf::from(CONFIG_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        let (tx, _) = broadcast::channel(4);
+        Self {
+            path,
+            config: Mutex::new(config),
+            latest: Mutex::new(None),
+            seq: AtomicU64::new(0),
+            tx,
+        }
+    }
+
+    pub fn config(&self) -> ThermalConfig {
+        *self.config.lock().unwrap()
+    }
+
+    pub fn set_config(&self, config: ThermalConfig) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    pub fn latest(&self) -> Option<ThermalFrame> {
+        self.latest.lock().unwrap().as_ref().map(|(_, f)| f.clone())
+    }
+
+    fn fresh(&self) -> Option<ThermalFrame> {
+        self.latest
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(at, _)| at.elapsed() < FRESH_FOR)
+            .map(|(_, f)| f.clone())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ThermalFrame> {
+        self.tx.subscribe()
+    }
+
+    fn publish(&self, pixels: &[f32], ambient_c: f32) {
+        // The array is read right to left as seen from behind the sensor
+        let pixels: Vec<f32> = pixels
+            .chunks_exact(WIDTH)
+            .flat_map(|row| row.iter().rev().copied())
+            .collect();
+        let frame = ThermalFrame {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            captured_unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            min_c: pixels.iter().copied().fold(f32::MAX, f32::min),
+            max_c: pixels.iter().copied().fold(f32::MIN, f32::max),
+            pixels,
+            ambient_c,
+        };
+        *self.latest.lock().unwrap() = Some((Instant::now(), frame.clone()));
+        let _ = self.tx.send(frame);
+    }
+
+    /// Camera-frame rectangle the sensor's view covers in a `width` x
+    /// `height` frame; larger than the frame for a sensor wider than the
+    /// camera.
+    fn coverage(&self, optics: &CameraOptics, width: i32, height: i32) -> Rect {
+        let config = self.config();
+        let ratio = |sensor: f32, camera: f32| {
+            (sensor / 2.0).to_radians().tan() / (camera / 2.0).to_radians().tan()
+        };
+        let w = (width as f32 * ratio(config.hfov_deg, optics.hfov_deg)).round() as i32;
+        let h = (height as f32 * ratio(config.vfov_deg, optics.vfov_deg)).round() as i32;
+        Rect::new((width - w) / 2, (height - h) / 2, w.max(1), h.max(1))
+    }
+
+    /// Blobs of hot pixels in the latest frame, boxed in the pixels of a
+    /// `width` x `height` camera frame; none once the sensor goes quiet.
+    pub fn hot_objects(
+        &self,
+        optics: &CameraOptics,
+        width: i32,
+        height: i32,
+    ) -> Vec<DetectedObject> {
+        let Some(frame) = self.fresh() else {
+            return Vec::new();
+        };
+        let config = self.config();
+        let area = self.coverage(optics, width, height);
+        let bounds = Rect::new(0, 0, width, height);
+        let (sx, sy) = (
+            area.width as f32 / WIDTH as f32,
+            area.height as f32 / HEIGHT as f32,
+        );
+
+        let mut seen = vec![false; PIXELS];
+        let mut objects = Vec::new();
+        for start in 0..PIXELS {
+            if seen[start] || frame.pixels[start] < config.hot_threshold_c {
+                continue;
+            }
+            // Flood fill over 8-connected hot pixels
+            let (mut min, mut max) = ([WIDTH, HEIGHT], [0, 0]);
+            let (mut count, mut hottest) = (0u32, f32::MIN);
+            let mut stack = vec![start];
+            seen[start] = true;
+            while let Some(p) = stack.pop() {
+                let (col, row) = (p % WIDTH, p / WIDTH);
+                min = [min[0].min(col), min[1].min(row)];
+                max = [max[0].max(col), max[1].max(row)];
+                count += 1;
+                hottest = hottest.max(frame.pixels[p]);
+                for (dc, dr) in [
+                    (-1, -1),
+                    (0, -1),
+                    (1, -1),
+                    (-1, 0),
+                    (1, 0),
+                    (-1, 1),
+                    (0, 1),
+                    (1, 1),
+                ] {
+                    let (c, r) = (col as i32 + dc, row as i32 + dr);
+                    if c < 0 || r < 0 || c >= WIDTH as i32 || r >= HEIGHT as i32 {
+                        continue;
+                    }
+                    let n = r as usize * WIDTH + c as usize;
+                    if !seen[n] && frame.pixels[n] >= config.hot_threshold_c {
+                        seen[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+            if count < config.min_pixels {
+                continue;
+            }
+            let x = area.x + (min[0] as f32 * sx) as i32;
+            let y = area.y + (min[1] as f32 * sy) as i32;
+            let bbox = Rect::new(
+                x,
+                y,
+                (((max[0] + 1) as f32 * sx) as i32 + area.x - x).max(1),
+                (((max[1] + 1) as f32 * sy) as i32 + area.y - y).max(1),
+            ) & bounds;
+            if bbox.width <= 0 || bbox.height <= 0 {
+                continue;
+            }
+            objects.push(DetectedObject {
+                class: HOT_CLASS.to_string(),
+                display_name: None,
+                confidence: 0.5
+                    + 0.5
+                        * ((hottest - config.hot_threshold_c) / CONFIDENCE_SPAN_C).clamp(0.0, 1.0),
+                bbox: [bbox.x, bbox.y, bbox.width, bbox.height],
+                track_id: None,
+            });
+        }
+        objects
+    }
+
+    /// Blends the latest heat image, colorized, into the part of the BGR
+    /// `frame` the sensor sees.
+    pub fn blend(&self, frame: &mut Mat, optics: &CameraOptics) -> opencv::Result<()> {
+        let Some(thermal) = self.fresh() else {
+            return Ok(());
+        };
+        let opacity = self.config().overlay_opacity as f64;
+        let low = thermal.min_c;
+        let span = (thermal.max_c - low).max(MIN_OVERLAY_SPAN_C);
+        let levels: Vec<u8> = thermal
+            .pixels
+            .iter()
+            .map(|t| ((t - low) / span * 255.0).clamp(0.0, 255.0) as u8)
+            .collect();
+        let gray = Mat::new_rows_cols_with_data(HEIGHT as i32, WIDTH as i32, &levels)?;
+        let mut colored = Mat::default();
+        imgproc::apply_color_map(&*gray, &mut colored, imgproc::COLORMAP_INFERNO)?;
+
+        let area = self.coverage(optics, frame.cols(), frame.rows());
+        let mut heat = Mat::default();
+        imgproc::resize(
+            &colored,
+            &mut heat,
+            Size::new(area.width, area.height),
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+        let visible = area & Rect::new(0, 0, frame.cols(), frame.rows());
+        if visible.width <= 0 || visible.height <= 0 {
+            return Ok(());
+        }
+        let heat = Mat::roi(
+            &heat,
+            Rect::new(
+                visible.x - area.x,
+                visible.y - area.y,
+                visible.width,
+                visible.height,
+            ),
+        )?;
+        let region = Mat::roi(frame, visible)?.try_clone()?;
+        let mut blended = Mat::default();
+        core::add_weighted(
+            &region,
+            1.0 - opacity,
+            &*heat,
+            opacity,
+            0.0,
+            &mut blended,
+            -1,
+        )?;
+        let mut target = Mat::roi_mut(frame, visible)?;
+        blended.copy_to(&mut *target)
+    }
+}
+
+pub fn start_thermal_thread() -> Result<Arc<ThermalCamera>, Box<dyn std::error::Error>> {
+    let mut i2c = I2c::new()?;
+    i2c.set_slave_address(I2C_ADDR)?;
+    let mut eeprom = vec![0u16; WORDS];
+    read_words(&i2c, EEPROM, &mut eeprom)?;
+    let calibration = Calibration::from_eeprom(&eeprom);
+    let mut control = [0u16];
+    read_words(&i2c, REG_CONTROL, &mut control)?;
+    write_word(
+        &i2c,
+        REG_CONTROL,
+        (control[0] & !CONTROL_REFRESH_MASK) | CONTROL_REFRESH_8HZ,
+    )?;
+    println!(
+        "[OK] MLX90640 thermal camera ready ({} broken pixels)",
+        calibration.broken.iter().filter(|b| **b).count()
+    );
+
+    let camera = Arc::new(ThermalCamera::load());
+    let camera_clone = Arc::clone(&camera);
+    thread::spawn(move || {
+        threads::label("thermal");
+        let mut ram = vec![0u16; WORDS];
+        let mut pixels = vec![0.0f32; PIXELS];
+        let mut pending = [false; 2];
+        loop {
+            if faults::killed("thermal") {
+                return;
+            }
+            match read_subpage(&i2c, &mut ram) {
+                Ok(Some((control, subpage))) => {
+                    let emissivity = camera_clone.config().emissivity;
+                    let ambient =
+                        calibration.subpage_to(&ram, control, subpage, emissivity, &mut pixels);
+                    pending[subpage] = true;
+                    if pending == [true, true] {
+                        pending = [false, false];
+                        calibration.patch(&mut pixels);
+                        camera_clone.publish(&pixels, ambient);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("[ERR] Thermal camera read failed: {}", e),
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+
+    Ok(camera)
+}
+
+pub fn validate(config: &ThermalConfig) -> Result<(), String> {
+    if !(-40.0..=300.0).contains(&config.hot_threshold_c) {
+        return Err("hot_threshold_c must be within -40..=300".to_string());
+    }
+    if config.min_pixels == 0 {
+        return Err("min_pixels must be at least 1".to_string());
+    }
+    if !(config.emissivity > 0.0 && config.emissivity <= 1.0) {
+        return Err("emissivity must be within 0..=1 and not 0".to_string());
+    }
+    if [config.hfov_deg, config.vfov_deg]
+        .iter()
+        .any(|fov| !(*fov > 0.0 && *fov < 180.0))
+    {
+        return Err("field of view must be within 0..180 degrees".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.overlay_opacity) {
+        return Err("overlay_opacity must be within 0..=1".to_string());
+    }
+    Ok(())
+}
+
+pub fn routes(camera: Arc<ThermalCamera>) -> Router {
+    Router::new()
+        .route("/thermal", get(get_frame))
+        .route("/thermal/config", get(get_config).put(set_config))
+        .with_state(camera)
+}
+
+async fn get_frame(
+    State(camera): State<Arc<ThermalCamera>>,
+) -> Result<Json<ThermalFrame>, (StatusCode, String)> {
+    camera.latest().map(Json).ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "no thermal frame yet".to_string(),
+    ))
+}
+
+async fn get_config(State(camera): State<Arc<ThermalCamera>>) -> Json<ThermalConfig> {
+    Json(camera.config())
+}
+
+async fn set_config(
+    State(camera): State<Arc<ThermalCamera>>,
+    Json(config): Json<ThermalConfig>,
+) -> Result<Json<ThermalConfig>, (StatusCode, String)> {
+    validate(&config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    camera
+        .set_config(config)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("[INFO] Thermal camera config updated");
+    Ok(Json(config))
+}
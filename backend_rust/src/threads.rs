@@ -0,0 +1,236 @@
+//! Per-thread CPU accounting from `/proc/self/task`, reported in
+//! `/api/health`, to tell which subsystem eats the CPU when the frame rate
+//! drops.
+//!
+//! A thread belongs to the subsystem it is named after: long-running
+//! threads name themselves with [`label`], and threads they start (rayon's
+//! pool, ONNX Runtime's, see [`spawning_as`]) inherit the name from the
+//! kernel. Work that runs on shared threads, like JPEG encoding on the
+//! runtime's blocking pool, is timed with [`attribute`] and charged to its
+//! subsystem instead of the thread's.
+
+use crate::state::AppState;
+use raspibot_protocol::health::{CpuReport, SubsystemCpu, ThreadCpu};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// `/proc/<pid>/stat` times are in clock ticks; USER_HZ is 100 on every
+/// kernel we deploy on.
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+/// The kernel keeps 15 bytes of a thread name.
+const NAME_MAX: usize = 15;
+/// What tokio names its worker and blocking threads, cut to `NAME_MAX`.
+const RUNTIME_THREAD: &str = "tokio-runtime-w";
+
+/// CPU time run by `attribute`, by thread and subsystem, since the last
+/// sample.
+static ATTRIBUTED: Mutex<BTreeMap<(u32, &'static str), u64>> = Mutex::new(BTreeMap::new());
+
+/// Names the calling thread after `subsystem`. Best effort: where the name
+/// can't be set, the thread counts as `main`.
+pub fn label(subsystem: &str) {
+    let mut end = subsystem.len().min(NAME_MAX);
+    while !subsystem.is_char_boundary(end) {
+        end -= 1;
+    }
+    let _ = std::fs::write("/proc/thread-self/comm", &subsystem[..end]);
+}
+
+/// Runs `f` with the calling thread named after `subsystem`, so the threads
+/// it starts are too.
+pub fn spawning_as<T>(subsystem: &str, f: impl FnOnce() -> T) -> T {
+    let previous = std::fs::read_to_string("/proc/thread-self/comm").ok();
+    label(subsystem);
+    let result = f();
+    if let Some(previous) = previous {
+        label(previous.trim_end());
+    }
+    result
+}
+
+/// Runs `f`, charging the CPU time it takes to `subsystem` instead of to
+/// the calling thread's.
+pub fn attribute<T>(subsystem: &'static str, f: impl FnOnce() -> T) -> T {
+    let task = Path::new("/proc/thread-self");
+    let start = cpu_ns(task);
+    let result = f();
+    if let (Some(start), Some(end), Some(tid)) = (start, cpu_ns(task), thread_id()) {
+        *ATTRIBUTED
+            .lock()
+            .unwrap()
+            .entry((tid, subsystem))
+            .or_default() += end.saturating_sub(start);
+    }
+    result
+}
+
+fn thread_id() -> Option<u32> {
+    std::fs::read_link("/proc/thread-self")
+        .ok()?
+        .file_name()?
+        .to_str()?
+        .parse()
+        .ok()
+}
+
+/// CPU time of a task in nanoseconds: from `schedstat` where the kernel
+/// keeps it, from `stat`'s user and system ticks otherwise.
+fn cpu_ns(task: &Path) -> Option<u64> {
+    let schedstat = std::fs::read_to_string(task.join("schedstat"))
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse().ok());
+    if schedstat.is_some() {
+        return schedstat;
+    }
+    let stat = std::fs::read_to_string(task.join("stat")).ok()?;
+    // The name may contain spaces, so split after its closing paren
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some((utime + stime) * 1_000_000_000 / CLOCK_TICKS_PER_SEC)
+}
+
+struct Sample {
+    at: Instant,
+    /// Name and CPU time by thread id.
+    tasks: HashMap<u32, (String, u64)>,
+}
+
+impl Sample {
+    fn take() -> Option<Self> {
+        let mut tasks = HashMap::new();
+        for entry in std::fs::read_dir("/proc/self/task").ok()?.flatten() {
+            let Some(tid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+            let path = entry.path();
+            // Threads that exited since the directory was read are skipped
+            let (Ok(name), Some(ns)) = (std::fs::read_to_string(path.join("comm")), cpu_ns(&path))
+            else {
+                continue;
+            };
+            tasks.insert(tid, (name.trim_end().to_string(), ns));
+        }
+        Some(Self {
+            at: Instant::now(),
+            tasks,
+        })
+    }
+}
+
+/// The subsystem a thread named `name` works for.
+fn subsystem(name: &str, process: &str) -> String {
+    if name == RUNTIME_THREAD {
+        "runtime".to_string()
+    } else if name == process {
+        "main".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn report(
+    previous: &Sample,
+    current: &Sample,
+    attributed: BTreeMap<(u32, &'static str), u64>,
+) -> CpuReport {
+    let window_ns = current.at.duration_since(previous.at).as_nanos().max(1) as f64;
+    let percent = |ns: u64| (ns as f64 / window_ns * 100.0) as f32;
+    let process = current
+        .tasks
+        .get(&std::process::id())
+        .map(|(name, _)| name.clone())
+        .unwrap_or_default();
+
+    let mut total = 0;
+    let mut threads = Vec::new();
+    // CPU time and threads by subsystem
+    let mut subsystems: BTreeMap<String, (u64, usize)> = BTreeMap::new();
+    for (tid, (name, ns)) in &current.tasks {
+        // A thread started during the window ran all of its time in it
+        let ran = ns.saturating_sub(previous.tasks.get(tid).map_or(0, |(_, ns)| *ns));
+        total += ran;
+        let charged: u64 = attributed
+            .iter()
+            .filter(|((t, _), _)| t == tid)
+            .map(|(_, ns)| *ns)
+            .sum();
+        let own = ran.saturating_sub(charged);
+        let subsystem = subsystem(name, &process);
+        if own > 0 {
+            let entry = subsystems.entry(subsystem.clone()).or_default();
+            entry.0 += own;
+            entry.1 += 1;
+        }
+        threads.push(ThreadCpu {
+            tid: *tid,
+            name: name.clone(),
+            subsystem,
+            percent: percent(own),
+        });
+    }
+    for ((_, name), ns) in attributed {
+        let entry = subsystems.entry(name.to_string()).or_default();
+        entry.0 += ns;
+        entry.1 += 1;
+    }
+
+    let mut subsystems: Vec<SubsystemCpu> = subsystems
+        .into_iter()
+        .map(|(name, (ns, threads))| SubsystemCpu {
+            name,
+            percent: percent(ns),
+            threads,
+        })
+        .collect();
+    subsystems.sort_by(|a, b| b.percent.total_cmp(&a.percent));
+    threads.sort_by(|a, b| b.percent.total_cmp(&a.percent));
+    CpuReport {
+        window_s: (window_ns / 1e9) as f32,
+        cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        total_percent: percent(total),
+        subsystems,
+        threads,
+    }
+}
+
+pub struct CpuMonitor {
+    last: Mutex<Option<CpuReport>>,
+}
+
+impl CpuMonitor {
+    pub fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn report(&self) -> Option<CpuReport> {
+        self.last.lock().unwrap().clone()
+    }
+}
+
+/// Samples every thread's CPU time every couple of seconds; does nothing
+/// where `/proc/self/task` can't be read.
+pub fn start_cpu_monitor(state: AppState) {
+    let Some(mut previous) = Sample::take() else {
+        println!("[WARN] No per-thread CPU times, CPU report disabled");
+        return;
+    };
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let Some(current) = Sample::take() else {
+                continue;
+            };
+            let attributed = std::mem::take(&mut *ATTRIBUTED.lock().unwrap());
+            *state.cpu.last.lock().unwrap() = Some(report(&previous, &current, attributed));
+            previous = current;
+        }
+    });
+}
@@ -0,0 +1,81 @@
+//! Optional HTTPS/WSS for the dashboard. Browsers only expose the Gamepad
+//! API and friends to secure contexts, so the operator laptop needs TLS even
+//! on the robot's own network.
+//!
+//! A `[server.tls]` section in the config (or `TLS_ENABLED=1`) turns it on;
+//! `cert` / `key` (`TLS_CERT` / `TLS_KEY`) point at PEM files, by default in
+//! `data/tls/`. If they don't exist yet, a self-signed certificate
+//! for the hostname and current interface addresses is generated on first
+//! boot; accept it once in the browser.
+
+use axum_server::tls_rustls::RustlsConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CERT: &str = "data/tls/cert.pem";
+pub const DEFAULT_KEY: &str = "data/tls/key.pem";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSettings {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Default for TlsSettings {
+    fn default() -> Self {
+        Self {
+            cert: DEFAULT_CERT.into(),
+            key: DEFAULT_KEY.into(),
+        }
+    }
+}
+
+impl TlsSettings {
+    /// Loads the certificate pair, generating a self-signed one if missing.
+    pub async fn load(&self) -> Result<RustlsConfig, Box<dyn std::error::Error>> {
+        if !self.cert.exists() || !self.key.exists() {
+            generate_self_signed(&self.cert, &self.key)?;
+        }
+        let config = RustlsConfig::from_pem_file(&self.cert, &self.key).await?;
+        println!("[OK] TLS certificate loaded from {}", self.cert.display());
+        Ok(config)
+    }
+}
+
+fn generate_self_signed(
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut names = vec!["localhost".to_string()];
+    if let Ok(hostname) = std::fs::read_to_string("/etc/hostname") {
+        let hostname = hostname.trim();
+        if !hostname.is_empty() {
+            names.push(hostname.to_string());
+            names.push(format!("{}.local", hostname));
+        }
+    }
+    // rcgen turns IP literals into IP SANs
+    for interface in if_addrs::get_if_addrs().unwrap_or_default() {
+        names.push(interface.ip().to_string());
+    }
+
+    let rcgen::CertifiedKey { cert, key_pair } = rcgen::generate_simple_self_signed(names.clone())?;
+    for path in [cert_path, key_path] {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    std::fs::write(cert_path, cert.pem())?;
+    std::fs::write(key_path, key_pair.serialize_pem())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(key_path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    println!(
+        "[OK] Generated self-signed TLS certificate for {}",
+        names.join(", ")
+    );
+    Ok(())
+}
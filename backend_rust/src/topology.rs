@@ -0,0 +1,113 @@
+//! The processing graph as it is wired right now, served at `GET /pipeline`:
+//! each camera, who subscribes to its frames and how often they are handed
+//! one, what inference publishes and how far behind its receivers are, and
+//! the stereo pairs with their consumers.
+//!
+//! Built from the dispatchers' and channels' own registries on every
+//! request, so a consumer that never subscribed, or one stuck with a frame
+//! it never takes, shows as it is.
+
+use crate::detections;
+use crate::dispatch::ConsumerInfo;
+use crate::state::AppState;
+use axum::{extract::State, routing::get, Json, Router};
+use raspibot_protocol::rates::{NodeKind, PipelineEdge, PipelineGraph, PipelineNode, RateStats};
+
+/// The frame consumer that runs the detector and tracker and publishes
+/// the camera's detections.
+const INFERENCE_CONSUMER: &str = "inference";
+
+fn node(id: String, kind: NodeKind, rate: RateStats) -> PipelineNode {
+    PipelineNode {
+        id,
+        kind,
+        rate,
+        queued: 0,
+        capacity: 0,
+        receivers: None,
+        capture: None,
+    }
+}
+
+/// Adds the subscribers of a frame dispatcher below `source`.
+fn add_consumers(
+    graph: &mut PipelineGraph,
+    source: &str,
+    consumers: Vec<ConsumerInfo>,
+    stage: Option<&str>,
+) {
+    for consumer in consumers {
+        let id = format!("{}.{}", source, consumer.name);
+        let kind = if stage == Some(consumer.name.as_str()) {
+            NodeKind::Stage
+        } else {
+            NodeKind::Consumer
+        };
+        graph.nodes.push(PipelineNode {
+            queued: consumer.pending as usize,
+            capacity: 1,
+            ..node(id.clone(), kind, consumer.rate)
+        });
+        graph.edges.push(PipelineEdge {
+            from: source.to_string(),
+            to: id,
+            decimation: Some(consumer.decimation.to_string()),
+        });
+    }
+}
+
+pub fn graph(state: &AppState) -> PipelineGraph {
+    let mut graph = PipelineGraph::default();
+    for camera in state.cameras.iter() {
+        let source = format!("camera.{}", camera.id);
+        graph.nodes.push(PipelineNode {
+            capture: Some(camera.frames.health().state),
+            ..node(
+                source.clone(),
+                NodeKind::Source,
+                camera.frames.capture_rate(),
+            )
+        });
+        let consumers = camera.frames.consumers();
+        let inference = consumers
+            .iter()
+            .any(|c| c.name == INFERENCE_CONSUMER)
+            .then(|| format!("{}.{}", source, INFERENCE_CONSUMER));
+        add_consumers(&mut graph, &source, consumers, Some(INFERENCE_CONSUMER));
+
+        let (receivers, queued) = camera.detections.backlog();
+        let results = format!("detections.{}", camera.id);
+        graph.nodes.push(PipelineNode {
+            queued,
+            capacity: detections::CHANNEL_CAPACITY,
+            receivers: Some(receivers),
+            ..node(results.clone(), NodeKind::Stage, camera.detections.rate())
+        });
+        if let Some(inference) = inference {
+            graph.edges.push(PipelineEdge {
+                from: inference,
+                to: results,
+                decimation: None,
+            });
+        }
+    }
+    if let Some(rig) = &state.stereo {
+        graph.nodes.push(node(
+            "stereo".to_string(),
+            NodeKind::Source,
+            rig.pair_rate(),
+        ));
+        add_consumers(&mut graph, "stereo", rig.consumers(), None);
+    }
+    graph
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/pipeline", get(get_graph))
+        .with_state(state)
+}
+
+async fn get_graph(State(state): State<AppState>) -> Json<PipelineGraph> {
+    Json(graph(&state))
+}
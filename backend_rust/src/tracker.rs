@@ -0,0 +1,531 @@
+//! Multi-object tracker: stable ids for per-frame detections.
+//!
+//! Tracks are associated by IoU against the prediction of a constant-velocity
+//! Kalman filter on their center, SORT-style. As in ByteTrack, confident
+//! detections are matched first and the rest may only continue tracks still
+//! unmatched, so an object whose score dips for a frame keeps its id without
+//! low-scoring noise ever starting tracks of its own.
+//!
+//! A track that loses its detection is not dropped straight away: it is kept
+//! as occluded, coasting on its last velocity, for up to `memory_s`. If a
+//! detection of the same class shows up near the predicted position with a
+//! similar color histogram, it takes over the old id, so behaviours following
+//! a target survive it passing behind a pillar.
+//!
+//! With a re-id model loaded, observations also carry an appearance embedding.
+//! It is preferred over the histogram when both sides have one, and a tracker
+//! built [`Tracker::with_gallery`] looks new detections up in the shared
+//! [`ReidGallery`], recovering ids after longer gaps or from other cameras.
+
+use crate::nms::{iou, Detection};
+use crate::reid::{self, ReidGallery};
+use crate::transform::BoxF;
+use opencv::{
+    core::{self, Mat, Vector},
+    imgproc,
+    prelude::*,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Hue x saturation bins of the appearance histogram.
+const HIST_BINS: [i32; 2] = [16, 8];
+/// Weight of the newest observation in the running appearance.
+const SMOOTHING: f32 = 0.3;
+/// Detector box jitter, as a fraction of the box size.
+const MEASUREMENT_NOISE: f32 = 0.05;
+/// How fast a track may change velocity, in box sizes per second squared.
+const ACCELERATION_NOISE: f32 = 2.0;
+/// Uncertainty of a new track's (zero) velocity, in box sizes per second.
+const INITIAL_VELOCITY_NOISE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackerConfig {
+    /// Minimum IoU between a prediction and a detection to continue a track.
+    pub iou_threshold: f32,
+    /// Detections scoring at least this are matched first and may start
+    /// tracks; weaker ones can only continue an existing track.
+    pub high_confidence: f32,
+    /// How long an occluded track is remembered.
+    pub memory_s: f32,
+    /// Minimum histogram similarity (Bhattacharyya coefficient, 0..=1) to
+    /// re-associate an occluded track.
+    pub min_similarity: f32,
+    /// Minimum embedding cosine similarity, used instead of the histogram
+    /// when both the track and the detection have an embedding.
+    pub min_embedding_similarity: f32,
+    /// Re-association gate: max center distance, in box diagonals, per
+    /// second of occlusion (plus one diagonal).
+    pub gate_per_s: f32,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            iou_threshold: 0.3,
+            high_confidence: 0.5,
+            memory_s: 2.0,
+            min_similarity: 0.6,
+            min_embedding_similarity: 0.7,
+            gate_per_s: 2.0,
+        }
+    }
+}
+
+impl TrackerConfig {
+    /// Defaults, with `TRACK_MEMORY_S` overriding the occlusion memory and
+    /// `TRACK_HIGH_CONFIDENCE` the score needed to start a track.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(value) = std::env::var("TRACK_MEMORY_S") {
+            match value.trim().parse::<f32>() {
+                Ok(s) if s >= 0.0 => config.memory_s = s,
+                _ => println!("[WARN] Ignoring invalid TRACK_MEMORY_S '{}'", value),
+            }
+        }
+        if let Ok(value) = std::env::var("TRACK_HIGH_CONFIDENCE") {
+            match value.trim().parse::<f32>() {
+                Ok(c) if (0.0..=1.0).contains(&c) => config.high_confidence = c,
+                _ => println!("[WARN] Ignoring invalid TRACK_HIGH_CONFIDENCE '{}'", value),
+            }
+        }
+        config
+    }
+}
+
+/// Appearance cues computed from a detection's crop.
+#[derive(Debug, Clone, Default)]
+pub struct Appearance {
+    /// L1-normalized hue/saturation histogram, see [`color_histogram`].
+    pub histogram: Option<Vec<f32>>,
+    /// L2-normalized re-id embedding, see [`reid::ReidModel::embed`].
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl Appearance {
+    /// Similarity to another appearance and the threshold that applies to it:
+    /// embeddings when both have one, else histograms.
+    fn score(&self, other: &Appearance, config: &TrackerConfig) -> Option<(f32, f32)> {
+        if let (Some(a), Some(b)) = (&self.embedding, &other.embedding) {
+            return Some((reid::cosine(a, b), config.min_embedding_similarity));
+        }
+        if let (Some(a), Some(b)) = (&self.histogram, &other.histogram) {
+            return Some((similarity(a, b), config.min_similarity));
+        }
+        None
+    }
+}
+
+/// Constant-velocity Kalman filter of one center coordinate. Noise scales
+/// with the box size, so near and far objects are filtered alike.
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    position: f32,
+    velocity: f32,
+    /// Covariance of (position, velocity).
+    p: [[f32; 2]; 2],
+}
+
+impl Axis {
+    fn new(position: f32, size: f32) -> Self {
+        Self {
+            position,
+            velocity: 0.0,
+            p: [
+                [(MEASUREMENT_NOISE * size).powi(2), 0.0],
+                [0.0, (INITIAL_VELOCITY_NOISE * size).powi(2)],
+            ],
+        }
+    }
+
+    fn at(&self, dt: f32) -> f32 {
+        self.position + self.velocity * dt
+    }
+
+    /// Moves the state `dt` seconds ahead, growing its uncertainty by
+    /// white-noise acceleration.
+    fn predict(&mut self, dt: f32, size: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        self.position = self.at(dt);
+        let q = (ACCELERATION_NOISE * size).powi(2);
+        let [[a, b], [c, d]] = self.p;
+        self.p = [
+            [
+                a + dt * (b + c) + dt * dt * d + q * dt.powi(4) / 4.0,
+                b + dt * d + q * dt.powi(3) / 2.0,
+            ],
+            [c + dt * d + q * dt.powi(3) / 2.0, d + q * dt * dt],
+        ];
+    }
+
+    fn correct(&mut self, measured: f32, size: f32) {
+        let r = (MEASUREMENT_NOISE * size).powi(2);
+        let [[a, b], [c, d]] = self.p;
+        let (k0, k1) = (a / (a + r), c / (a + r));
+        let innovation = measured - self.position;
+        self.position += k0 * innovation;
+        self.velocity += k1 * innovation;
+        self.p = [[(1.0 - k0) * a, (1.0 - k0) * b], [c - k1 * a, d - k1 * b]];
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub detection: Detection,
+    pub appearance: Appearance,
+}
+
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: u64,
+    pub class: i64,
+    pub confidence: f32,
+    /// Filtered center with the last measured size, or the prediction
+    /// while occluded.
+    pub bbox: BoxF,
+    /// Center velocity in pixels per second.
+    pub velocity: (f32, f32),
+    pub appearance: Appearance,
+    pub hits: u32,
+    /// Set while the track has no matching detection.
+    pub lost_since: Option<Instant>,
+    /// Index of the observation the latest update matched to this track.
+    pub observation: Option<usize>,
+    /// Kalman state of the center, x then y.
+    motion: [Axis; 2],
+    last_update: Instant,
+}
+
+impl Track {
+    pub fn occluded(&self) -> bool {
+        self.lost_since.is_some()
+    }
+
+    fn start(id: u64, observation: &Observation, index: usize, now: Instant) -> Self {
+        let (bbox, confidence, class) = observation.detection;
+        let (cx, cy) = center(&bbox);
+        Self {
+            id,
+            class,
+            confidence,
+            bbox,
+            velocity: (0.0, 0.0),
+            appearance: observation.appearance.clone(),
+            hits: 1,
+            lost_since: None,
+            observation: Some(index),
+            motion: [Axis::new(cx, bbox.w), Axis::new(cy, bbox.h)],
+            last_update: now,
+        }
+    }
+
+    fn predicted(&self, now: Instant) -> BoxF {
+        let dt = now.duration_since(self.last_update).as_secs_f32();
+        BoxF {
+            x: self.motion[0].at(dt) - self.bbox.w / 2.0,
+            y: self.motion[1].at(dt) - self.bbox.h / 2.0,
+            ..self.bbox
+        }
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let dt = now.duration_since(self.last_update).as_secs_f32();
+        self.motion[0].predict(dt, self.bbox.w);
+        self.motion[1].predict(dt, self.bbox.h);
+        self.last_update = now;
+    }
+
+    /// Box and velocity from the filter's current state.
+    fn sync(&mut self) {
+        self.bbox.x = self.motion[0].position - self.bbox.w / 2.0;
+        self.bbox.y = self.motion[1].position - self.bbox.h / 2.0;
+        self.velocity = (self.motion[0].velocity, self.motion[1].velocity);
+    }
+
+    fn correct(&mut self, observation: &Observation, index: usize, now: Instant) {
+        let (b, confidence, _) = observation.detection;
+        self.advance(now);
+        let (cx, cy) = center(&b);
+        self.motion[0].correct(cx, b.w);
+        self.motion[1].correct(cy, b.h);
+        self.bbox.w = b.w;
+        self.bbox.h = b.h;
+        self.sync();
+        self.confidence = confidence;
+        self.hits += 1;
+        self.lost_since = None;
+        self.observation = Some(index);
+        if let Some(new) = &observation.appearance.histogram {
+            self.appearance.histogram = Some(match self.appearance.histogram.take() {
+                Some(old) if old.len() == new.len() => old
+                    .iter()
+                    .zip(new)
+                    .map(|(o, n)| o + SMOOTHING * (n - o))
+                    .collect(),
+                _ => new.clone(),
+            });
+        }
+        if let Some(new) = &observation.appearance.embedding {
+            self.appearance.embedding = Some(match &self.appearance.embedding {
+                Some(old) => reid::blend(old, new, SMOOTHING),
+                None => new.clone(),
+            });
+        }
+    }
+
+    /// Moves an occluded track along its prediction.
+    fn coast(&mut self, now: Instant) {
+        self.advance(now);
+        self.sync();
+    }
+}
+
+fn center(b: &BoxF) -> (f32, f32) {
+    (b.x + b.w / 2.0, b.y + b.h / 2.0)
+}
+
+/// Bhattacharyya coefficient of two L1-normalized histograms (1.0 = identical).
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter().zip(b).map(|(x, y)| (x * y).max(0.0).sqrt()).sum()
+}
+
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+    gallery: Option<Arc<ReidGallery>>,
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+            gallery: None,
+        }
+    }
+
+    /// A tracker that draws ids from, and re-identifies against, `gallery`.
+    /// Trackers sharing one gallery never hand out the same id.
+    pub fn with_gallery(config: TrackerConfig, gallery: Arc<ReidGallery>) -> Self {
+        Self {
+            gallery: Some(gallery),
+            ..Self::new(config)
+        }
+    }
+
+    /// All live tracks, including occluded ones at their predicted position.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.id == id)
+    }
+
+    /// Feeds one frame's detections; `now` is the frame's capture time.
+    /// Each returned track that a detection was matched to carries its
+    /// index in [`Track::observation`].
+    pub fn update(&mut self, observations: &[Observation], now: Instant) -> &[Track] {
+        let mut matched_track = vec![false; self.tracks.len()];
+        let mut matched_obs = vec![false; observations.len()];
+        for track in &mut self.tracks {
+            track.observation = None;
+        }
+        let high_confidence = self.config.high_confidence;
+        let confident = |obs: &Observation| obs.detection.1 >= high_confidence;
+
+        // 1. Visible tracks: greedy IoU against the prediction, best pairs
+        //    first; confident detections pick first, weak ones get the rest
+        for high in [true, false] {
+            let mut pairs = Vec::new();
+            for (ti, track) in self.tracks.iter().enumerate() {
+                if track.occluded() || matched_track[ti] {
+                    continue;
+                }
+                let predicted = track.predicted(now);
+                for (oi, obs) in observations.iter().enumerate() {
+                    let (b, _, class) = obs.detection;
+                    if class != track.class || confident(obs) != high {
+                        continue;
+                    }
+                    let overlap = iou(&predicted, &b);
+                    if overlap >= self.config.iou_threshold {
+                        pairs.push((overlap, ti, oi));
+                    }
+                }
+            }
+            self.assign(
+                pairs,
+                &mut matched_track,
+                &mut matched_obs,
+                observations,
+                now,
+            );
+        }
+
+        // 2. Occluded tracks: appearance within a gate that widens over time
+        let mut pairs = Vec::new();
+        for (ti, track) in self.tracks.iter().enumerate() {
+            let Some(lost_since) = track.lost_since else {
+                continue;
+            };
+            let predicted = track.predicted(now);
+            let (px, py) = center(&predicted);
+            let diagonal = (predicted.w.powi(2) + predicted.h.powi(2)).sqrt();
+            let lost_s = now.duration_since(lost_since).as_secs_f32();
+            let gate = diagonal * (1.0 + self.config.gate_per_s * lost_s);
+            for (oi, obs) in observations.iter().enumerate() {
+                let (b, _, class) = obs.detection;
+                if matched_obs[oi] || class != track.class || !confident(obs) {
+                    continue;
+                }
+                let (ox, oy) = center(&b);
+                if ((ox - px).powi(2) + (oy - py).powi(2)).sqrt() > gate {
+                    continue;
+                }
+                let Some((score, threshold)) =
+                    track.appearance.score(&obs.appearance, &self.config)
+                else {
+                    continue;
+                };
+                if score >= threshold {
+                    pairs.push((score, ti, oi));
+                }
+            }
+        }
+        self.assign(
+            pairs,
+            &mut matched_track,
+            &mut matched_obs,
+            observations,
+            now,
+        );
+
+        // 3. Unmatched tracks become (or stay) occluded until memory runs out
+        let memory = self.config.memory_s;
+        for (track, matched) in self.tracks.iter_mut().zip(&matched_track) {
+            if !matched {
+                track.lost_since.get_or_insert(now);
+                track.coast(now);
+            }
+        }
+        self.tracks.retain(|t| {
+            t.lost_since
+                .is_none_or(|since| now.duration_since(since).as_secs_f32() <= memory)
+        });
+
+        // 4. Leftover confident detections start new tracks, or resume a
+        //    remembered identity from the gallery
+        for (oi, obs) in observations.iter().enumerate() {
+            if matched_obs[oi] || !confident(obs) {
+                continue;
+            }
+            let id = self
+                .reidentify(obs.detection.2, &obs.appearance, now)
+                .unwrap_or_else(|| self.allocate_id());
+            self.tracks.push(Track::start(id, obs, oi, now));
+        }
+
+        if let Some(gallery) = &self.gallery {
+            for track in self.tracks.iter().filter(|t| !t.occluded()) {
+                if let Some(embedding) = &track.appearance.embedding {
+                    gallery.update(track.id, track.class, embedding, now);
+                }
+            }
+        }
+        &self.tracks
+    }
+
+    fn reidentify(&self, class: i64, appearance: &Appearance, now: Instant) -> Option<u64> {
+        let gallery = self.gallery.as_ref()?;
+        let embedding = appearance.embedding.as_ref()?;
+        let live: Vec<u64> = self.tracks.iter().map(|t| t.id).collect();
+        let id = gallery.identify(class, embedding, &live, now)?;
+        println!("[INFO] Track {} re-identified", id);
+        Some(id)
+    }
+
+    fn allocate_id(&mut self) -> u64 {
+        match &self.gallery {
+            Some(gallery) => gallery.next_id(),
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                id
+            }
+        }
+    }
+
+    fn assign(
+        &mut self,
+        mut pairs: Vec<(f32, usize, usize)>,
+        matched_track: &mut [bool],
+        matched_obs: &mut [bool],
+        observations: &[Observation],
+        now: Instant,
+    ) {
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, ti, oi) in pairs {
+            if matched_track[ti] || matched_obs[oi] {
+                continue;
+            }
+            matched_track[ti] = true;
+            matched_obs[oi] = true;
+            if self.tracks[ti].occluded() {
+                println!(
+                    "[INFO] Track {} re-acquired after occlusion",
+                    self.tracks[ti].id
+                );
+            }
+            self.tracks[ti].correct(&observations[oi], oi, now);
+        }
+    }
+}
+
+/// Hue/saturation histogram of `rect` in a BGR frame, L1-normalized so
+/// crops of different sizes compare directly.
+pub fn color_histogram(frame: &Mat, rect: core::Rect) -> opencv::Result<Vec<f32>> {
+    let x1 = rect.x.max(0);
+    let y1 = rect.y.max(0);
+    let x2 = (rect.x + rect.width).min(frame.cols());
+    let y2 = (rect.y + rect.height).min(frame.rows());
+    if x2 <= x1 || y2 <= y1 {
+        return Ok(Vec::new());
+    }
+    let rect = core::Rect::new(x1, y1, x2 - x1, y2 - y1);
+    let crop = Mat::roi(frame, rect)?;
+    let mut hsv = Mat::default();
+    imgproc::cvt_color_def(&*crop, &mut hsv, imgproc::COLOR_BGR2HSV)?;
+
+    let mut images = Vector::<Mat>::new();
+    images.push(hsv);
+    let mut hist = Mat::default();
+    imgproc::calc_hist(
+        &images,
+        &Vector::<i32>::from_slice(&[0, 1]),
+        &core::no_array(),
+        &mut hist,
+        &Vector::<i32>::from_slice(&HIST_BINS),
+        &Vector::<f32>::from_slice(&[0.0, 180.0, 0.0, 256.0]),
+        false,
+    )?;
+
+    let mut values = Vec::with_capacity((HIST_BINS[0] * HIST_BINS[1]) as usize);
+    for h in 0..HIST_BINS[0] {
+        for s in 0..HIST_BINS[1] {
+            values.push(*hist.at_2d::<f32>(h, s)?);
+        }
+    }
+    let total: f32 = values.iter().sum();
+    if total > 0.0 {
+        values.iter_mut().for_each(|v| *v /= total);
+    }
+    Ok(values)
+}
@@ -0,0 +1,169 @@
+//! Geometry for fitting capture frames (e.g. 1280x720) into the square model
+//! input, and for mapping detections back to full-frame coordinates.
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleStrategy {
+    /// Keep the whole frame, pad the short side (gray bars).
+    Letterbox,
+    /// Crop the centered square of the frame; the sides are not seen.
+    CenterCrop,
+    /// Squash the frame to the input size, distorting aspect ratio.
+    Stretch,
+}
+
+impl FromStr for ScaleStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "letterbox" => Ok(ScaleStrategy::Letterbox),
+            "center_crop" | "crop" => Ok(ScaleStrategy::CenterCrop),
+            "stretch" => Ok(ScaleStrategy::Stretch),
+            other => Err(format!("unknown scale strategy '{}'", other)),
+        }
+    }
+}
+
+/// Axis-aligned box in pixels, `(x, y)` is the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoxF {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+/// Source frame -> model input mapping:
+/// crop `crop` from the source, scale by `(scale_x, scale_y)`, then offset by
+/// `(pad_x, pad_y)` inside a `dst_w` x `dst_h` canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputTransform {
+    pub src_w: i32,
+    pub src_h: i32,
+    pub dst_w: i32,
+    pub dst_h: i32,
+    pub crop: (i32, i32, i32, i32),
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub pad_x: i32,
+    pub pad_y: i32,
+}
+
+impl InputTransform {
+    pub fn new(src_w: i32, src_h: i32, dst_w: i32, dst_h: i32, strategy: ScaleStrategy) -> Self {
+        let full = (0, 0, src_w, src_h);
+        let (crop, scale_x, scale_y, pad_x, pad_y) = match strategy {
+            ScaleStrategy::Stretch => (
+                full,
+                dst_w as f32 / src_w as f32,
+                dst_h as f32 / src_h as f32,
+                0,
+                0,
+            ),
+            ScaleStrategy::Letterbox => {
+                let scale = (dst_w as f32 / src_w as f32).min(dst_h as f32 / src_h as f32);
+                let new_w = (src_w as f32 * scale).round() as i32;
+                let new_h = (src_h as f32 * scale).round() as i32;
+                (full, scale, scale, (dst_w - new_w) / 2, (dst_h - new_h) / 2)
+            }
+            ScaleStrategy::CenterCrop => {
+                // Largest centered region with the destination's aspect ratio
+                let dst_aspect = dst_w as f32 / dst_h as f32;
+                let (crop_w, crop_h) = if src_w as f32 / src_h as f32 > dst_aspect {
+                    ((src_h as f32 * dst_aspect).round() as i32, src_h)
+                } else {
+                    (src_w, (src_w as f32 / dst_aspect).round() as i32)
+                };
+                let crop = ((src_w - crop_w) / 2, (src_h - crop_h) / 2, crop_w, crop_h);
+                (
+                    crop,
+                    dst_w as f32 / crop_w as f32,
+                    dst_h as f32 / crop_h as f32,
+                    0,
+                    0,
+                )
+            }
+        };
+        Self {
+            src_w,
+            src_h,
+            dst_w,
+            dst_h,
+            crop,
+            scale_x,
+            scale_y,
+            pad_x,
+            pad_y,
+        }
+    }
+
+    /// Size of the scaled (unpadded) image inside the destination canvas.
+    pub fn scaled_size(&self) -> (i32, i32) {
+        (
+            (self.crop.2 as f32 * self.scale_x).round() as i32,
+            (self.crop.3 as f32 * self.scale_y).round() as i32,
+        )
+    }
+
+    /// Maps a box from model-input space back to source-frame pixels,
+    /// clipped to the frame.
+    pub fn map_to_source(&self, b: BoxF) -> BoxF {
+        let x1 = (b.x - self.pad_x as f32) / self.scale_x + self.crop.0 as f32;
+        let y1 = (b.y - self.pad_y as f32) / self.scale_y + self.crop.1 as f32;
+        let x2 = (b.x + b.w - self.pad_x as f32) / self.scale_x + self.crop.0 as f32;
+        let y2 = (b.y + b.h - self.pad_y as f32) / self.scale_y + self.crop.1 as f32;
+
+        let x1 = x1.clamp(0.0, self.src_w as f32);
+        let y1 = y1.clamp(0.0, self.src_h as f32);
+        let x2 = x2.clamp(0.0, self.src_w as f32);
+        let y2 = y2.clamp(0.0, self.src_h as f32);
+        BoxF {
+            x: x1,
+            y: y1,
+            w: x2 - x1,
+            h: y2 - y1,
+        }
+    }
+}
+
+/// Splits a `src_w` x `src_h` frame into a `cols` x `rows` grid of tiles
+/// that overlap their neighbours by `overlap` (fraction of the tile size),
+/// so objects on a seam are fully inside at least one tile.
+pub fn tiles(
+    src_w: i32,
+    src_h: i32,
+    cols: i32,
+    rows: i32,
+    overlap: f32,
+) -> Vec<(i32, i32, i32, i32)> {
+    let cols = cols.max(1);
+    let rows = rows.max(1);
+    let overlap = overlap.clamp(0.0, 0.9);
+    // Tile size such that `n` tiles with the given overlap span the frame exactly
+    let tile_w = (src_w as f32 / (cols as f32 - (cols - 1) as f32 * overlap)).ceil() as i32;
+    let tile_h = (src_h as f32 / (rows as f32 - (rows - 1) as f32 * overlap)).ceil() as i32;
+    let tile_w = tile_w.min(src_w);
+    let tile_h = tile_h.min(src_h);
+
+    let offset = |i: i32, n: i32, tile: i32, full: i32| {
+        if n == 1 {
+            0
+        } else {
+            (full - tile) * i / (n - 1)
+        }
+    };
+    let mut out = Vec::with_capacity((cols * rows) as usize);
+    for r in 0..rows {
+        for c in 0..cols {
+            out.push((
+                offset(c, cols, tile_w, src_w),
+                offset(r, rows, tile_h, src_h),
+                tile_w,
+                tile_h,
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,145 @@
+//! Calibration constants for converting raw hardware units to physical ones
+//! (drive duty to m/s, pixels to degrees, ADC counts to volts).
+//!
+//! The conversions themselves live on the wire types so clients use the same
+//! math; this module persists the constants and serves them.
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::units::{CameraOptics, UnitCalibration};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+pub const CALIBRATION_PATH: &str = "data/unit_calibration.json";
+
+pub struct UnitStore {
+    path: PathBuf,
+    calibration: RwLock<UnitCalibration>,
+}
+
+impl UnitStore {
+    pub fn load() -> Self {
+        let path = PathBuf::from(CALIBRATION_PATH);
+        let calibration = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            calibration: RwLock::new(calibration),
+        }
+    }
+
+    pub fn get(&self) -> UnitCalibration {
+        self.calibration.read().unwrap().clone()
+    }
+
+    /// Just the camera part, for per-frame use without cloning the rest.
+    pub fn camera(&self) -> CameraOptics {
+        self.calibration.read().unwrap().camera
+    }
+
+    pub fn set(&self, calibration: UnitCalibration) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&calibration)?)?;
+        *self.calibration.write().unwrap() = calibration;
+        Ok(())
+    }
+}
+
+pub fn validate(calibration: &UnitCalibration) -> Result<(), String> {
+    let wheels = calibration.wheels;
+    if !(wheels.max_speed_mps.is_finite() && wheels.max_speed_mps > 0.0) {
+        return Err("wheels.max_speed_mps must be positive".to_string());
+    }
+    if !(0.0..1.0).contains(&wheels.deadband) {
+        return Err("wheels.deadband must be within 0..1".to_string());
+    }
+    if !(wheels.track_width_m.is_finite() && wheels.track_width_m > 0.0) {
+        return Err("wheels.track_width_m must be positive".to_string());
+    }
+    if !(wheels.ticks_per_m.is_finite() && wheels.ticks_per_m > 0.0) {
+        return Err("wheels.ticks_per_m must be positive".to_string());
+    }
+    for (i, point) in calibration.speed_curve.iter().enumerate() {
+        if !(point.battery_v.is_finite() && point.battery_v > 0.0) {
+            return Err(format!("speed_curve.{}: battery_v must be positive", i));
+        }
+        if !(point.max_speed_mps.is_finite() && point.max_speed_mps > 0.0) {
+            return Err(format!("speed_curve.{}: max_speed_mps must be positive", i));
+        }
+        if !(0.0..1.0).contains(&point.deadband) {
+            return Err(format!("speed_curve.{}: deadband must be within 0..1", i));
+        }
+    }
+    if calibration
+        .speed_curve
+        .windows(2)
+        .any(|pair| pair[0].battery_v >= pair[1].battery_v)
+    {
+        return Err("speed_curve must be ordered by rising battery_v".to_string());
+    }
+    let camera = calibration.camera;
+    if [camera.hfov_deg, camera.vfov_deg]
+        .iter()
+        .any(|fov| !(*fov > 0.0 && *fov < 180.0))
+    {
+        return Err("camera field of view must be within 0..180 degrees".to_string());
+    }
+    if let Some(lens) = camera.lens {
+        if lens.width <= 0 || lens.height <= 0 {
+            return Err("camera.lens: calibration resolution must be positive".to_string());
+        }
+        if !(lens.fx > 0.0 && lens.fy > 0.0) {
+            return Err("camera.lens: focal lengths must be positive".to_string());
+        }
+        if ![lens.cx, lens.cy]
+            .iter()
+            .chain(&lens.coefficients)
+            .all(|v| v.is_finite())
+        {
+            return Err("camera.lens: principal point and coefficients must be finite".to_string());
+        }
+    }
+    for (id, channel) in &calibration.adc {
+        if channel.name.is_empty() {
+            return Err(format!("adc.{}: name is required", id));
+        }
+        if !channel.volts_per_count.is_finite()
+            || channel.volts_per_count == 0.0
+            || !channel.offset_v.is_finite()
+        {
+            return Err(format!(
+                "adc.{}: volts_per_count must be finite and non-zero",
+                id
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn routes(store: Arc<UnitStore>) -> Router {
+    Router::new()
+        .route(
+            "/calibration/units",
+            get(get_calibration).put(set_calibration),
+        )
+        .with_state(store)
+}
+
+async fn get_calibration(State(store): State<Arc<UnitStore>>) -> Json<UnitCalibration> {
+    Json(store.get())
+}
+
+async fn set_calibration(
+    State(store): State<Arc<UnitStore>>,
+    Json(calibration): Json<UnitCalibration>,
+) -> Result<Json<UnitCalibration>, (StatusCode, String)> {
+    validate(&calibration).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    store
+        .set(calibration.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("[INFO] Unit calibration updated");
+    Ok(Json(calibration))
+}
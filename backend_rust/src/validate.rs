@@ -0,0 +1,197 @@
+//! `validate` subcommand: a dry run of the detection pipeline before a match.
+//!
+//! Loads the configured model, runs it on a test image and compares the
+//! decoded boxes with an expected fixture. Exits nonzero on any mismatch:
+//!
+//! ```text
+//! backend_rust validate [--image test.jpg] [--fixture expected.json] [--write-fixture]
+//! ```
+
+use crate::config::ModelConfig;
+use crate::yolo::YoloModel;
+use opencv::imgcodecs;
+use raspibot_protocol::inference::SessionOptions;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const DEFAULT_IMAGE: &str = "data/validate/test.jpg";
+pub const DEFAULT_FIXTURE: &str = "data/validate/expected.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExpectedBox {
+    class: String,
+    score: f32,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fixture {
+    /// A detection matches an expected box at or above this IoU.
+    #[serde(default = "default_min_iou")]
+    min_iou: f32,
+    /// Allowed absolute difference in confidence.
+    #[serde(default = "default_score_tolerance")]
+    score_tolerance: f32,
+    boxes: Vec<ExpectedBox>,
+}
+
+fn default_min_iou() -> f32 {
+    0.7
+}
+
+fn default_score_tolerance() -> f32 {
+    0.1
+}
+
+pub struct ValidateArgs {
+    pub image: PathBuf,
+    pub fixture: PathBuf,
+    /// Record the current output as the fixture instead of checking it.
+    pub write_fixture: bool,
+}
+
+impl ValidateArgs {
+    /// Parses the arguments after `validate`; `--profile` is taken out
+    /// beforehand.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self {
+            image: PathBuf::from(DEFAULT_IMAGE),
+            fixture: PathBuf::from(DEFAULT_FIXTURE),
+            write_fixture: false,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--image" => {
+                    parsed.image = args.next().ok_or("--image needs a path")?.into();
+                }
+                "--fixture" => {
+                    parsed.fixture = args.next().ok_or("--fixture needs a path")?.into();
+                }
+                "--write-fixture" => parsed.write_fixture = true,
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn iou(a: &ExpectedBox, b: &ExpectedBox) -> f32 {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.w).min(b.x + b.w);
+    let y2 = (a.y + a.h).min(b.y + b.h);
+    let inter = ((x2 - x1).max(0) * (y2 - y1).max(0)) as f32;
+    let union = (a.w * a.h + b.w * b.h) as f32 - inter;
+    if union > 0.0 {
+        inter / union
+    } else {
+        0.0
+    }
+}
+
+/// Greedily pairs each expected box with its best unused same-class
+/// detection; returns one message per mismatch.
+fn compare(fixture: &Fixture, detected: &[ExpectedBox]) -> Vec<String> {
+    let mut used = vec![false; detected.len()];
+    let mut errors = Vec::new();
+    for expected in &fixture.boxes {
+        let best = detected
+            .iter()
+            .enumerate()
+            .filter(|(i, d)| !used[*i] && d.class == expected.class)
+            .map(|(i, d)| (i, iou(expected, d)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((i, overlap)) if overlap >= fixture.min_iou => {
+                used[i] = true;
+                let diff = (detected[i].score - expected.score).abs();
+                if diff > fixture.score_tolerance {
+                    errors.push(format!(
+                        "{} at ({}, {}): score {:.2}, expected {:.2}",
+                        expected.class, expected.x, expected.y, detected[i].score, expected.score
+                    ));
+                }
+            }
+            Some((_, overlap)) => errors.push(format!(
+                "{} at ({}, {}): best IoU {:.2} below {:.2}",
+                expected.class, expected.x, expected.y, overlap, fixture.min_iou
+            )),
+            None => errors.push(format!(
+                "{} at ({}, {}): not detected",
+                expected.class, expected.x, expected.y
+            )),
+        }
+    }
+    for (d, _) in detected.iter().zip(&used).filter(|(_, used)| !**used) {
+        errors.push(format!(
+            "unexpected {} ({:.2}) at ({}, {})",
+            d.class, d.score, d.x, d.y
+        ));
+    }
+    errors
+}
+
+/// Returns `Ok(true)` when the pipeline output matches the fixture.
+pub fn run(
+    args: &ValidateArgs,
+    config: &ModelConfig,
+    options: &SessionOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut model = YoloModel::new(config, options)?;
+    let image = imgcodecs::imread(&args.image.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+    if image.empty() {
+        return Err(format!("could not read test image {}", args.image.display()).into());
+    }
+
+    let detected: Vec<ExpectedBox> = model
+        .predict(image)?
+        .into_iter()
+        .map(|(rect, score, class)| ExpectedBox {
+            class: model.label(class).to_string(),
+            score,
+            x: rect.x,
+            y: rect.y,
+            w: rect.width,
+            h: rect.height,
+        })
+        .collect();
+    println!(
+        "[INFO] {} boxes decoded from {}",
+        detected.len(),
+        args.image.display()
+    );
+
+    if args.write_fixture {
+        let fixture = Fixture {
+            min_iou: default_min_iou(),
+            score_tolerance: default_score_tolerance(),
+            boxes: detected,
+        };
+        if let Some(dir) = args.fixture.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&args.fixture, serde_json::to_string_pretty(&fixture)?)?;
+        println!("[OK] Fixture written to {}", args.fixture.display());
+        return Ok(true);
+    }
+
+    let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(&args.fixture)?)?;
+    let errors = compare(&fixture, &detected);
+    if errors.is_empty() {
+        println!(
+            "[OK] Validation passed: {} boxes match {}",
+            fixture.boxes.len(),
+            args.fixture.display()
+        );
+        return Ok(true);
+    }
+    for error in &errors {
+        eprintln!("[ERR] {}", error);
+    }
+    eprintln!("[ERR] Validation failed with {} mismatches", errors.len());
+    Ok(false)
+}
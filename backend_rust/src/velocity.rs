@@ -0,0 +1,234 @@
+//! Closed-loop drive: `POST /drive/velocity {linear, angular}` holds wheel
+//! speeds instead of duties, so the robot drives the same on a full battery
+//! and a flat one. Each wheel gets the speed model's duty (see
+//! `speed_calibration`) plus a PID correction from the encoder speeds the
+//! MCU reports; without current encoder counts the model drives alone.
+//! While the setpoint doesn't turn, the IMU's heading is held too, so
+//! straight stays straight.
+//!
+//! Setpoints go to the arbiter as teleop and, like raw drive commands, have
+//! to keep coming: after `velocity.timeout_ms` without one the robot stops.
+
+use crate::arbiter::CommandSource;
+use crate::config::VelocityConfig;
+use crate::nudge::angle_between;
+use crate::pid::Pid;
+use crate::serial::{McuBridge, Odometry};
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use raspibot_protocol::drive::{DriveCommand, VelocityCommand, VelocityStatus};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Encoder speeds older than this don't steer the wheels.
+const ENCODER_STALE: Duration = Duration::from_millis(200);
+
+struct Control {
+    setpoint: Option<(VelocityCommand, Instant)>,
+    left: Pid,
+    right: Pid,
+    heading: Pid,
+    /// IMU heading held while the setpoint doesn't turn.
+    holding: Option<f32>,
+    sent: Option<DriveCommand>,
+}
+
+impl Control {
+    fn new(config: &VelocityConfig) -> Self {
+        Self {
+            setpoint: None,
+            left: Pid::new(config.wheel),
+            right: Pid::new(config.wheel),
+            heading: Pid::new(config.heading),
+            holding: None,
+            sent: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+        self.heading.reset();
+        self.holding = None;
+    }
+}
+
+pub struct VelocityController {
+    config: Mutex<VelocityConfig>,
+    control: Mutex<Control>,
+    /// Wheel speeds `[left, right]` from the last two odometry pushes.
+    encoders: Mutex<Option<([f32; 2], Instant)>>,
+}
+
+impl VelocityController {
+    pub fn new() -> Self {
+        let config = VelocityConfig::default();
+        Self {
+            control: Mutex::new(Control::new(&config)),
+            config: Mutex::new(config),
+            encoders: Mutex::new(None),
+        }
+    }
+
+    /// Takes the gains from the startup config.
+    pub fn configure(&self, config: VelocityConfig) {
+        *self.control.lock().unwrap() = Control::new(&config);
+        *self.config.lock().unwrap() = config;
+    }
+
+    pub fn set(&self, setpoint: VelocityCommand) {
+        let mut control = self.control.lock().unwrap();
+        if control.setpoint.is_none() {
+            control.reset();
+        }
+        control.setpoint = Some((setpoint, Instant::now()));
+    }
+
+    fn measured(&self) -> Option<[f32; 2]> {
+        self.encoders
+            .lock()
+            .unwrap()
+            .filter(|(_, at)| at.elapsed() < ENCODER_STALE)
+            .map(|(speeds, _)| speeds)
+    }
+
+    pub fn status(&self) -> VelocityStatus {
+        let control = self.control.lock().unwrap();
+        VelocityStatus {
+            setpoint: control.setpoint.map(|(setpoint, _)| setpoint),
+            measured_mps: self.measured(),
+            holding_heading_deg: control.holding,
+            sent: control.sent,
+        }
+    }
+
+    /// One control step, `dt` seconds after the last; does nothing without
+    /// a setpoint.
+    fn step(&self, state: &AppState, dt: f32) {
+        let timeout = Duration::from_millis(self.config.lock().unwrap().timeout_ms);
+        let mut control = self.control.lock().unwrap();
+        let Some((setpoint, at)) = control.setpoint else {
+            return;
+        };
+        let command = if at.elapsed() > timeout {
+            println!("[WARN] No velocity setpoint for {:?}, stopping", timeout);
+            control.setpoint = None;
+            control.reset();
+            DriveCommand::default()
+        } else if setpoint == VelocityCommand::default() {
+            control.reset();
+            DriveCommand::default()
+        } else {
+            let wheels = state.units.get().wheels_at(state.power.battery_v());
+            let mut angular = setpoint.angular;
+            let heading = state
+                .imu
+                .as_ref()
+                .and_then(|imu| imu.reading())
+                .map(|r| r.heading_deg);
+            match heading {
+                Some(now) if setpoint.angular == 0.0 => {
+                    let hold = *control.holding.get_or_insert(now);
+                    angular += control.heading.update(angle_between(hold, now), dt);
+                }
+                _ => {
+                    control.holding = None;
+                    control.heading.reset();
+                }
+            }
+            // Turning clockwise, the left wheel runs faster
+            let turn = angular.to_radians() * wheels.track_width_m / 2.0;
+            let targets = [setpoint.linear + turn, setpoint.linear - turn];
+            let mut duty = targets.map(|mps| wheels.duty_for(mps));
+            match self.measured() {
+                Some([left, right]) => {
+                    duty[0] += control.left.update(targets[0] - left, dt);
+                    duty[1] += control.right.update(targets[1] - right, dt);
+                }
+                None => {
+                    control.left.reset();
+                    control.right.reset();
+                }
+            }
+            DriveCommand::new(duty[0].clamp(-1.0, 1.0), duty[1].clamp(-1.0, 1.0))
+        };
+        match state.arbiter.submit(CommandSource::Teleop, command) {
+            Ok(sent) => control.sent = Some(sent),
+            Err(e) => {
+                println!("[WARN] Closed-loop drive refused, stopping: {}", e);
+                control.setpoint = None;
+                control.reset();
+                control.sent = None;
+            }
+        }
+    }
+}
+
+/// Runs the control loop at `velocity.rate_hz`.
+pub fn start_velocity_control(state: AppState) {
+    let rate_hz = state.velocity.config.lock().unwrap().rate_hz;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs_f32(1.0 / rate_hz));
+        let mut last = Instant::now();
+        loop {
+            interval.tick().await;
+            let dt = last.elapsed().as_secs_f32();
+            last = Instant::now();
+            state.velocity.step(&state, dt);
+        }
+    });
+}
+
+/// Turns the MCU's encoder counts into wheel speeds for the control loop.
+pub fn track_encoders(state: AppState, mcu: &McuBridge) {
+    let mut odometry = mcu.odometry();
+    tokio::spawn(async move {
+        let mut previous: Option<Odometry> = None;
+        loop {
+            let counts = match odometry.recv().await {
+                Ok(counts) => counts,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            // A restarted MCU counts from scratch; its first push is the new
+            // baseline
+            if let Some(last) = previous.filter(|last| counts.mcu_ms > last.mcu_ms) {
+                let ticks_per_m = state.units.get().wheels.ticks_per_m;
+                let seconds = (counts.mcu_ms - last.mcu_ms) as f32 / 1000.0;
+                let speed =
+                    |now: i32, then: i32| now.wrapping_sub(then) as f32 / ticks_per_m / seconds;
+                let speeds = [
+                    speed(counts.left_ticks, last.left_ticks),
+                    speed(counts.right_ticks, last.right_ticks),
+                ];
+                *state.velocity.encoders.lock().unwrap() = Some((speeds, Instant::now()));
+            }
+            previous = Some(counts);
+        }
+    });
+}
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/drive/velocity", get(get_status).post(set_velocity))
+        .with_state(state)
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<VelocityStatus> {
+    Json(state.velocity.status())
+}
+
+async fn set_velocity(
+    State(state): State<AppState>,
+    Json(setpoint): Json<VelocityCommand>,
+) -> Result<Json<VelocityStatus>, (StatusCode, String)> {
+    if !(setpoint.linear.is_finite() && setpoint.angular.is_finite()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "linear and angular must be finite".to_string(),
+        ));
+    }
+    state.velocity.set(setpoint);
+    Ok(Json(state.velocity.status()))
+}
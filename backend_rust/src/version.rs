@@ -0,0 +1,115 @@
+//! Conversion shims between API versions (see `raspibot_protocol::version`).
+//!
+//! Handlers always produce the current schema; payloads for older clients are
+//! rewritten on the way out, so a dashboard and the backend can be updated
+//! independently.
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use raspibot_protocol::version::SUPPORTED;
+use serde::Serialize;
+use serde_json::Value;
+use socketioxide::SocketIo;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The version each Socket.IO client picked, by client id.
+pub struct ClientVersions {
+    versions: Mutex<HashMap<String, u32>>,
+}
+
+impl ClientVersions {
+    pub fn new() -> Self {
+        Self {
+            versions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Version 0 until the client picks one.
+    pub fn get(&self, client: &str) -> u32 {
+        self.versions
+            .lock()
+            .unwrap()
+            .get(client)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn set(&self, client: &str, version: u32) {
+        self.versions
+            .lock()
+            .unwrap()
+            .insert(client.to_string(), version);
+    }
+
+    pub fn remove(&self, client: &str) {
+        self.versions.lock().unwrap().remove(client);
+    }
+}
+
+/// Socket.IO room holding the clients that speak `version`.
+pub fn room(version: u32) -> String {
+    format!("api-v{}", version)
+}
+
+/// Rewrites a current-schema payload into `version`'s shape.
+pub fn convert<T: Serialize>(payload: &T, version: u32) -> Value {
+    let mut value = serde_json::to_value(payload).unwrap_or(Value::Null);
+    if version < 1 {
+        downgrade_to_v0(&mut value);
+    }
+    value
+}
+
+/// Version 0 has no `paused` mission mode; a paused robot reads as idle.
+fn downgrade_to_v0(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if map.get("mode").and_then(Value::as_str) == Some("paused") {
+                map.insert("mode".to_string(), Value::from("idle"));
+                map.remove("resume");
+            }
+            map.values_mut().for_each(downgrade_to_v0);
+        }
+        Value::Array(items) => items.iter_mut().for_each(downgrade_to_v0),
+        _ => {}
+    }
+}
+
+/// Middleware for the unversioned `/api` routes: JSON responses are
+/// converted to version 0.
+pub async fn legacy_shim(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => Body::from(convert(&value, 0).to_string()),
+        Err(_) => Body::from(bytes),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body)
+}
+
+/// Emits `payload` to every client in the version it picked.
+pub async fn emit_versioned<T: Serialize>(io: &SocketIo, event: &'static str, payload: &T) {
+    for version in SUPPORTED {
+        let _ = io
+            .to(room(version))
+            .emit(event, &convert(payload, version))
+            .await;
+    }
+}
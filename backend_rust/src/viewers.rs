@@ -0,0 +1,208 @@
+//! Connected-client accounting: per-endpoint viewer limits, bandwidth per
+//! client, and an admin kick so a stray viewer can't starve the operator link.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use raspibot_protocol::viewers::{ViewerInfo, ViewerLimits};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Notify;
+
+pub const STREAM: &str = "stream";
+pub const SOCKET_IO: &str = "socket.io";
+
+fn default_limits() -> ViewerLimits {
+    [(STREAM.to_string(), 3), (SOCKET_IO.to_string(), 8)]
+        .into_iter()
+        .collect()
+}
+
+struct Viewer {
+    endpoint: String,
+    addr: Option<SocketAddr>,
+    connected: Instant,
+    bytes_sent: AtomicU64,
+    kicked: AtomicBool,
+    kick: Notify,
+}
+
+pub struct ViewerRegistry {
+    limits: Mutex<ViewerLimits>,
+    viewers: Mutex<HashMap<u64, Arc<Viewer>>>,
+    next_id: AtomicU64,
+}
+
+impl ViewerRegistry {
+    pub fn new() -> Self {
+        Self {
+            limits: Mutex::new(default_limits()),
+            viewers: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn limits(&self) -> ViewerLimits {
+        self.limits.lock().unwrap().clone()
+    }
+
+    /// Only affects new connections; existing viewers are never dropped by
+    /// lowering a limit.
+    pub fn set_limits(&self, limits: ViewerLimits) {
+        *self.limits.lock().unwrap() = limits;
+    }
+
+    /// Registers a viewer on `endpoint`, or returns `None` if the endpoint is
+    /// full. Endpoints without a configured limit are unlimited.
+    pub fn join(self: &Arc<Self>, endpoint: &str, addr: Option<SocketAddr>) -> Option<ViewerGuard> {
+        let limit = self.limits.lock().unwrap().get(endpoint).copied();
+        let mut viewers = self.viewers.lock().unwrap();
+        let current = viewers.values().filter(|v| v.endpoint == endpoint).count();
+        if limit.is_some_and(|limit| current >= limit) {
+            println!(
+                "[WARN] Rejected {} viewer from {:?}: limit {} reached",
+                endpoint,
+                addr,
+                limit.unwrap_or_default()
+            );
+            return None;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let viewer = Arc::new(Viewer {
+            endpoint: endpoint.to_string(),
+            addr,
+            connected: Instant::now(),
+            bytes_sent: AtomicU64::new(0),
+            kicked: AtomicBool::new(false),
+            kick: Notify::new(),
+        });
+        viewers.insert(id, Arc::clone(&viewer));
+        Some(ViewerGuard {
+            id,
+            viewer,
+            registry: Arc::clone(self),
+        })
+    }
+
+    pub fn list(&self) -> Vec<ViewerInfo> {
+        let mut list: Vec<ViewerInfo> = self
+            .viewers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, v)| {
+                let connected_s = v.connected.elapsed().as_secs_f64();
+                let bytes_sent = v.bytes_sent.load(Ordering::Relaxed);
+                ViewerInfo {
+                    id,
+                    endpoint: v.endpoint.clone(),
+                    addr: v.addr.map(|a| a.to_string()),
+                    connected_s,
+                    bytes_sent,
+                    kbps: if connected_s > 0.0 {
+                        bytes_sent as f64 * 8.0 / 1000.0 / connected_s
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        list.sort_by_key(|v| v.id);
+        list
+    }
+
+    /// Signals the viewer's connection to close; the guard is removed once
+    /// the connection actually drops it.
+    pub fn kick(&self, id: u64) -> bool {
+        let Some(viewer) = self.viewers.lock().unwrap().get(&id).cloned() else {
+            return false;
+        };
+        println!(
+            "[INFO] Kicking {} viewer {} ({:?})",
+            viewer.endpoint, id, viewer.addr
+        );
+        viewer.kicked.store(true, Ordering::Relaxed);
+        viewer.kick.notify_waiters();
+        true
+    }
+}
+
+/// Held by a connection for as long as it is served.
+pub struct ViewerGuard {
+    id: u64,
+    viewer: Arc<Viewer>,
+    registry: Arc<ViewerRegistry>,
+}
+
+impl ViewerGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.viewer
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn is_kicked(&self) -> bool {
+        self.viewer.kicked.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once an admin kicks this viewer.
+    pub async fn kicked(&self) {
+        let notified = self.viewer.kick.notified();
+        if self.is_kicked() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+impl Drop for ViewerGuard {
+    fn drop(&mut self) {
+        self.registry.viewers.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub fn routes(viewers: Arc<ViewerRegistry>) -> Router {
+    Router::new()
+        .route("/admin/clients", get(list_clients))
+        .route("/admin/clients/{id}", delete(kick_client))
+        .route("/admin/viewer-limits", get(get_limits).put(set_limits))
+        .with_state(viewers)
+}
+
+async fn list_clients(State(viewers): State<Arc<ViewerRegistry>>) -> Json<Vec<ViewerInfo>> {
+    Json(viewers.list())
+}
+
+async fn kick_client(
+    State(viewers): State<Arc<ViewerRegistry>>,
+    Path(id): Path<u64>,
+) -> StatusCode {
+    if viewers.kick(id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn get_limits(State(viewers): State<Arc<ViewerRegistry>>) -> Json<ViewerLimits> {
+    Json(viewers.limits())
+}
+
+async fn set_limits(
+    State(viewers): State<Arc<ViewerRegistry>>,
+    Json(limits): Json<ViewerLimits>,
+) -> Json<ViewerLimits> {
+    viewers.set_limits(limits);
+    Json(viewers.limits())
+}
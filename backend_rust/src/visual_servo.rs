@@ -0,0 +1,173 @@
+//! Image-space visual servoing: turns a target's pixel position into rate
+//! commands that center it. Shared by follow, gimbal centering, docking and
+//! arm alignment, each with its own gains stored in `data/servo_gains.json`.
+
+use crate::pid::Pid;
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use raspibot_protocol::inference::StalenessLimits;
+use raspibot_protocol::servo::ServoGains;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const GAINS_PATH: &str = "data/servo_gains.json";
+
+/// Controllers with stored gains; unknown names start from the defaults.
+pub const CONTROLLERS: [&str; 4] = ["follow", "gimbal", "docking", "arm"];
+
+pub struct VisualServo {
+    x: Pid,
+    y: Pid,
+    staleness: StalenessLimits,
+}
+
+impl VisualServo {
+    pub fn new(gains: ServoGains) -> Self {
+        Self {
+            x: Pid::new(gains.x),
+            y: Pid::new(gains.y),
+            staleness: gains.staleness,
+        }
+    }
+
+    /// Rate commands `(x, y)` that move `target` (pixels) toward the center
+    /// of a `width` x `height` image. Positive x means the target is right of
+    /// center, positive y below it.
+    pub fn update(&mut self, target: (f32, f32), width: i32, height: i32, dt: f32) -> (f32, f32) {
+        let ex = (target.0 - width as f32 / 2.0) / (width as f32 / 2.0);
+        let ey = (target.1 - height as f32 / 2.0) / (height as f32 / 2.0);
+        (
+            self.x.update(ex.clamp(-1.0, 1.0), dt),
+            self.y.update(ey.clamp(-1.0, 1.0), dt),
+        )
+    }
+
+    /// Like [`update`](Self::update) for a target measured `age` ago: output
+    /// fades as the measurement ages and `None` means it is too old to act
+    /// on, so the caller should stop (the controller is reset).
+    pub fn update_aged(
+        &mut self,
+        target: (f32, f32),
+        width: i32,
+        height: i32,
+        dt: f32,
+        age: Duration,
+    ) -> Option<(f32, f32)> {
+        let scale = self.staleness.gain_scale(age.as_millis() as u64);
+        if scale <= 0.0 {
+            self.reset();
+            return None;
+        }
+        let (x, y) = self.update(target, width, height, dt);
+        Some((x * scale, y * scale))
+    }
+
+    /// Call when the target is lost so a stale derivative/integral is not
+    /// applied once it reappears.
+    pub fn reset(&mut self) {
+        self.x.reset();
+        self.y.reset();
+    }
+}
+
+/// Named gain sets, persisted so tuning survives restarts.
+pub struct ServoGainStore {
+    path: PathBuf,
+    gains: Mutex<BTreeMap<String, ServoGains>>,
+}
+
+impl ServoGainStore {
+    pub fn load() -> Self {
+        let path = PathBuf::from(GAINS_PATH);
+        let mut gains: BTreeMap<String, ServoGains> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        for name in CONTROLLERS {
+            gains.entry(name.to_string()).or_default();
+        }
+        Self {
+            path,
+            gains: Mutex::new(gains),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> ServoGains {
+        self.gains
+            .lock()
+            .unwrap()
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn all(&self) -> BTreeMap<String, ServoGains> {
+        self.gains.lock().unwrap().clone()
+    }
+
+    /// A controller for `name` with its current gains.
+    pub fn controller(&self, name: &str) -> VisualServo {
+        VisualServo::new(self.get(name))
+    }
+
+    pub fn set(&self, name: &str, gains: ServoGains) -> std::io::Result<()> {
+        let mut all = self.gains.lock().unwrap();
+        all.insert(name.to_string(), gains);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*all)?)
+    }
+}
+
+pub fn validate(gains: &ServoGains) -> Result<(), String> {
+    for (axis, g) in [("x", gains.x), ("y", gains.y)] {
+        let values = [g.kp, g.ki, g.kd, g.deadband, g.max_rate];
+        if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+            return Err(format!("{} gains must be finite and non-negative", axis));
+        }
+    }
+    if gains.staleness.fresh_ms > gains.staleness.stale_ms {
+        return Err("staleness fresh_ms must not exceed stale_ms".to_string());
+    }
+    Ok(())
+}
+
+pub fn routes(store: Arc<ServoGainStore>) -> Router {
+    Router::new()
+        .route("/servo/gains", get(list_gains))
+        .route("/servo/gains/{name}", get(get_gains).put(set_gains))
+        .with_state(store)
+}
+
+async fn list_gains(
+    State(store): State<Arc<ServoGainStore>>,
+) -> Json<BTreeMap<String, ServoGains>> {
+    Json(store.all())
+}
+
+async fn get_gains(
+    State(store): State<Arc<ServoGainStore>>,
+    UrlPath(name): UrlPath<String>,
+) -> Json<ServoGains> {
+    Json(store.get(&name))
+}
+
+async fn set_gains(
+    State(store): State<Arc<ServoGainStore>>,
+    UrlPath(name): UrlPath<String>,
+    Json(gains): Json<ServoGains>,
+) -> Result<Json<ServoGains>, (StatusCode, String)> {
+    validate(&gains).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    store
+        .set(&name, gains)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("[INFO] Servo gains for '{}' updated", name);
+    Ok(Json(gains))
+}
@@ -1,46 +1,907 @@
-use ort::{GraphOptimizationLevel, Session, SessionBuilder};
-use ndarray::{Array, Array4, Axis};
+use crate::config::ModelConfig;
+use crate::nms::{nms, Detection};
+use crate::threads;
+use crate::transform::{tiles, BoxF, InputTransform, ScaleStrategy};
+use crate::zones::ZoneStore;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
 use opencv::{
-    core::{self, Mat, Point, Scalar, Size},
-    imgproc, prelude::*,
+    core::{self, Mat, Scalar, Size},
+    imgproc,
+    prelude::*,
 };
-use std::sync::Arc;
+use ort::memory::{AllocationDevice, AllocatorType, MemoryInfo, MemoryType};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Tensor;
+use raspibot_protocol::inference::{
+    ClassPrompt, DetectedObject, DetectionClasses, InferenceSessionInfo, SessionOptions,
+};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_MODEL_PATH: &str = "../backend/models/yolov8s-worldv2.onnx";
+
+/// Square input size used when the model carries no `imgsz` metadata.
+pub const DEFAULT_INPUT_SIZE: i32 = 320;
+/// Fallback class list next to the model, one name per line.
+pub const LABELS_FILE: &str = "labels.txt";
+/// The operator's class prompt, kept across restarts.
+pub const CLASSES_PATH: &str = "data/detect_classes.json";
+
+/// IoU above which overlapping same-class boxes from different passes merge.
+const NMS_IOU_THRESHOLD: f32 = 0.45;
+const DEFAULT_CONFIDENCE: f32 = 0.25;
+const DEFAULT_IOU: f32 = 0.45;
+/// Gray of the letterbox padding, as in Ultralytics' training pipeline.
+const PAD_VALUE: f64 = 114.0;
+const DEFAULT_TILE_OVERLAP: f32 = 0.2;
+/// Anchors from which decoding is split across the rayon pool: a 320 px
+/// input (2100 anchors) and up. A starting point rather than a measurement;
+/// `bench-postprocess` suggests the cutoffs for the machine at hand.
+const DEFAULT_PARALLEL_MIN_ANCHORS: usize = 2048;
+/// Candidate boxes from which NMS runs class by class in parallel; a
+/// starting point as well.
+const DEFAULT_PARALLEL_MIN_BOXES: usize = 256;
+/// Anchors per rayon task while decoding.
+const DECODE_CHUNK: usize = 256;
+/// Initial capacity of the per-model detection buffer; it grows if a frame
+/// ever needs more and keeps that size.
+const SCRATCH_CAPACITY: usize = 256;
+
+/// Grid for tiled inference, on top of the regular full-frame pass.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileConfig {
+    pub cols: i32,
+    pub rows: i32,
+    /// Fraction of a tile shared with its neighbour.
+    #[serde(default = "default_tile_overlap")]
+    pub overlap: f32,
+}
+
+fn default_tile_overlap() -> f32 {
+    DEFAULT_TILE_OVERLAP
+}
+
+/// Per-pass decoding thresholds; zones can raise the confidence further.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Best class score a box needs to be decoded at all.
+    pub confidence: f32,
+    /// IoU above which same-class boxes of one pass are suppressed.
+    pub iou: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            confidence: DEFAULT_CONFIDENCE,
+            iou: DEFAULT_IOU,
+        }
+    }
+}
+
+/// Sizes from which postprocessing goes parallel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parallelism {
+    /// Decode on the rayon pool from this many anchors.
+    pub min_anchors: usize,
+    /// Run NMS per class on the rayon pool from this many candidate boxes.
+    pub min_boxes: usize,
+}
+
+impl Default for Parallelism {
+    fn default() -> Self {
+        Self {
+            min_anchors: DEFAULT_PARALLEL_MIN_ANCHORS,
+            min_boxes: DEFAULT_PARALLEL_MIN_BOXES,
+        }
+    }
+}
 
 pub struct YoloModel {
-    session: Session,
+    /// `run` needs exclusive access; one pass at a time per model anyway.
+    session: Mutex<Session>,
+    input_size: i32,
+    strategy: ScaleStrategy,
+    /// Interned, so labelling a detection is a reference-count bump.
+    labels: Vec<Arc<str>>,
+    /// Names made up for ids beyond `labels`, interned the same way.
+    unknown_labels: Mutex<HashMap<i64, Arc<str>>>,
+    tiling: Option<TileConfig>,
+    thresholds: Thresholds,
+    /// Class ids decoding picks from; all of them when `None`.
+    class_filter: Option<Vec<usize>>,
+    parallelism: Parallelism,
+    zones: Option<Arc<ZoneStore>>,
+    /// Decoded boxes of the frame in progress, reused across frames.
+    scratch: Mutex<Vec<Detection>>,
 }
 
 impl YoloModel {
-    pub fn new(model_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let session = SessionBuilder::new()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)?;
-
-        println!("[OK] Loaded YOLO ONNX model from {}", model_path);
-        Ok(Self { session })
-    }
-
-    pub fn predict(&self, mut frame: Mat) -> Result<Vec<(core::Rect, f32, i64)>, Box<dyn std::error::Error>> {
-        // Simple placeholder for now: resize, normalize, infer, parse output
-        // YOLOv8 output parsing in Rust can be complex, so this is a simplified stub
-        // showing how ort connects to opencv.
-        
-        let mut resized_frame = Mat::default();
+    /// Loads `config.path`, decoding with `config`'s thresholds.
+    pub fn new(
+        config: &ModelConfig,
+        options: &SessionOptions,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let model_path = config.path.as_str();
+        let allocator = if options.memory_arena {
+            AllocatorType::Arena
+        } else {
+            AllocatorType::Device
+        };
+        // ONNX Runtime starts its thread pools here; they count as inference
+        let session = threads::spawning_as("inference", || {
+            Session::builder()?
+                .with_optimization_level(GraphOptimizationLevel::Level3)?
+                .with_intra_threads(options.intra_threads)?
+                .with_inter_threads(options.inter_threads)?
+                .with_parallel_execution(options.parallel_execution)?
+                .with_memory_pattern(options.memory_pattern)?
+                .with_allocator(MemoryInfo::new(
+                    AllocationDevice::CPU,
+                    0,
+                    allocator,
+                    MemoryType::Default,
+                )?)?
+                .commit_from_file(model_path)
+        })?;
+
+        // Ultralytics export stores `names` and `imgsz` as custom metadata, so
+        // the class mapping always matches the deployed model
+        let metadata = session.metadata()?;
+        let input_size = metadata
+            .custom("imgsz")
+            .and_then(|v| parse_imgsz(&v))
+            .unwrap_or(DEFAULT_INPUT_SIZE);
+        let labels = match metadata.custom("names").map(|v| parse_names(&v)) {
+            Some(names) if !names.is_empty() => names,
+            _ => {
+                let path = Path::new(model_path)
+                    .parent()
+                    .unwrap_or(Path::new("."))
+                    .join(LABELS_FILE);
+                let names = load_labels(&path);
+                if names.is_empty() {
+                    println!(
+                        "[WARN] No class names in model metadata or {}",
+                        path.display()
+                    );
+                } else {
+                    println!("[INFO] Class names loaded from {}", path.display());
+                }
+                names
+            }
+        };
+        drop(metadata);
+
+        println!(
+            "[OK] Loaded YOLO ONNX model from {} ({} classes, {}px input)",
+            model_path,
+            labels.len(),
+            input_size
+        );
+        Ok(Self {
+            session: Mutex::new(session),
+            input_size,
+            strategy: ScaleStrategy::Letterbox,
+            labels: labels.into_iter().map(Arc::from).collect(),
+            unknown_labels: Mutex::new(HashMap::new()),
+            tiling: None,
+            thresholds: config.thresholds(),
+            class_filter: None,
+            parallelism: parallelism_from_env(),
+            zones: None,
+            scratch: Mutex::new(Vec::with_capacity(SCRATCH_CAPACITY)),
+        })
+    }
+
+    pub fn labels(&self) -> &[Arc<str>] {
+        &self.labels
+    }
+
+    /// Class name for a predicted id, `"class_<id>"` if the model has none.
+    pub fn label(&self, class_id: i64) -> Arc<str> {
+        if let Some(name) = usize::try_from(class_id)
+            .ok()
+            .and_then(|i| self.labels.get(i))
+        {
+            return Arc::clone(name);
+        }
+        Arc::clone(
+            self.unknown_labels
+                .lock()
+                .unwrap()
+                .entry(class_id)
+                .or_insert_with(|| Arc::from(format!("class_{}", class_id))),
+        )
+    }
+
+    /// How non-square frames (e.g. 1280x720) are fitted into the model input.
+    pub fn with_strategy(mut self, strategy: ScaleStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Enables (or with `None` disables) tiled inference; each tile costs a
+    /// full model pass, so only turn it on when throughput allows.
+    pub fn set_tiling(&mut self, tiling: Option<TileConfig>) {
+        self.tiling = tiling;
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// Vocabulary ids of `names`, matched case-insensitively; the names
+    /// the model has no class for are the error.
+    pub fn class_ids(&self, names: &[String]) -> Result<Vec<usize>, Vec<String>> {
+        let mut ids = Vec::new();
+        let mut unknown = Vec::new();
+        for name in names {
+            match self
+                .labels
+                .iter()
+                .position(|l| l.eq_ignore_ascii_case(name.trim()))
+            {
+                Some(id) => ids.push(id),
+                None => unknown.push(name.clone()),
+            }
+        }
+        if unknown.is_empty() {
+            Ok(ids)
+        } else {
+            Err(unknown)
+        }
+    }
+
+    /// Restricts decoding to the classes with these ids, or with `None`
+    /// scores the whole vocabulary again. YOLO-World scores every class
+    /// against its own text embedding, so this detects exactly what a model
+    /// exported with just these classes would.
+    pub fn set_class_filter(&mut self, ids: Option<Vec<usize>>) {
+        self.class_filter = ids;
+    }
+
+    /// Applies per-zone thresholds and class filters to every prediction.
+    pub fn set_zones(&mut self, zones: Arc<ZoneStore>) {
+        self.zones = Some(zones);
+    }
+
+    /// Crops/scales/pads `frame` into the model input according to the strategy,
+    /// returning the input image and the transform needed to map boxes back.
+    pub fn prepare_input(
+        &self,
+        frame: &Mat,
+    ) -> Result<(Mat, InputTransform), Box<dyn std::error::Error>> {
+        let size = frame.size()?;
+        let transform = InputTransform::new(
+            size.width,
+            size.height,
+            self.input_size,
+            self.input_size,
+            self.strategy,
+        );
+
+        let (cx, cy, cw, ch) = transform.crop;
+        let cropped = Mat::roi(frame, core::Rect::new(cx, cy, cw, ch))?;
+        let (scaled_w, scaled_h) = transform.scaled_size();
+        let mut resized = Mat::default();
         imgproc::resize(
-            &frame,
-            &mut resized_frame,
-            Size::new(320, 320),
+            &*cropped,
+            &mut resized,
+            Size::new(scaled_w, scaled_h),
             0.0,
             0.0,
             imgproc::INTER_LINEAR,
         )?;
 
-        // Convert HWC to CHW / f32 normalizations could follow here
-        // ...
-        
-        // This is a stub returning empty results to allow compilation
-        // Full NMS and processing would be added here in full implementation.
-        Ok(vec![])
+        if transform.pad_x == 0 && transform.pad_y == 0 {
+            return Ok((resized, transform));
+        }
+        let mut padded = Mat::default();
+        core::copy_make_border(
+            &resized,
+            &mut padded,
+            transform.pad_y,
+            self.input_size - scaled_h - transform.pad_y,
+            transform.pad_x,
+            self.input_size - scaled_w - transform.pad_x,
+            core::BORDER_CONSTANT,
+            Scalar::all(PAD_VALUE),
+        )?;
+        Ok((padded, transform))
+    }
+
+    /// BGR HWC bytes to the RGB CHW `[1, 3, size, size]` tensor the model
+    /// takes, scaled to 0..=1.
+    fn to_tensor(&self, input: &Mat) -> Result<Tensor<f32>, Box<dyn std::error::Error>> {
+        let size = self.input_size as usize;
+        let plane = size * size;
+        let owned;
+        let input = if input.is_continuous() {
+            input
+        } else {
+            owned = input.try_clone()?;
+            &owned
+        };
+        let pixels = input.data_bytes()?;
+        if pixels.len() != plane * 3 {
+            return Err(format!(
+                "model input is {} bytes, expected {}x{}x3",
+                pixels.len(),
+                size,
+                size
+            )
+            .into());
+        }
+        let mut chw = vec![0.0f32; plane * 3];
+        for (i, bgr) in pixels.chunks_exact(3).enumerate() {
+            chw[i] = bgr[2] as f32 / 255.0;
+            chw[plane + i] = bgr[1] as f32 / 255.0;
+            chw[2 * plane + i] = bgr[0] as f32 / 255.0;
+        }
+        Ok(Tensor::from_array(([1usize, 3, size, size], chw))?)
+    }
+
+    /// Runs one model pass over `region` of `frame`, appending its boxes to
+    /// `out` in full-frame pixels.
+    fn detect_region(
+        &self,
+        frame: &Mat,
+        region: (i32, i32, i32, i32),
+        out: &mut Vec<Detection>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (rx, ry, rw, rh) = region;
+        let view = Mat::roi(frame, core::Rect::new(rx, ry, rw, rh))?;
+        let (input, transform) = self.prepare_input(&view)?;
+        let tensor = self.to_tensor(&input)?;
+
+        // Boxes come back in model-input space; suppress within the pass
+        // before mapping, so every pass leaves only its best boxes
+        let first = out.len();
+        {
+            let mut session = self.session.lock().unwrap();
+            let outputs = session.run(ort::inputs![tensor])?;
+            let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
+            decode_output(
+                shape,
+                data,
+                self.thresholds.confidence,
+                self.class_filter.as_deref(),
+                self.parallelism.min_anchors,
+                out,
+            )?;
+        }
+        let mut decoded = out.split_off(first);
+        let parallel = decoded.len() >= self.parallelism.min_boxes;
+        nms(&mut decoded, self.thresholds.iou, parallel);
+
+        out.extend(decoded.into_iter().map(|(b, score, class)| {
+            let b = transform.map_to_source(b);
+            let b = BoxF {
+                x: b.x + rx as f32,
+                y: b.y + ry as f32,
+                ..b
+            };
+            (b, score, class)
+        }));
+        Ok(())
+    }
+
+    /// Returns boxes in full-frame pixel coordinates, whatever the capture size,
+    /// already filtered by the zone rules when zones are set.
+    pub fn predict(
+        &self,
+        frame: Mat,
+    ) -> Result<Vec<(core::Rect, f32, i64)>, Box<dyn std::error::Error>> {
+        let mut out = Vec::new();
+        self.predict_into(&frame, &mut out)?;
+        Ok(out)
+    }
+
+    /// Like [`predict`](Self::predict), but fills `out` (cleared first), so a
+    /// frame loop that keeps its buffer does no per-frame allocation.
+    pub fn predict_into(
+        &self,
+        frame: &Mat,
+        out: &mut Vec<(core::Rect, f32, i64)>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let size = frame.size()?;
+        let mut detections = self.scratch.lock().unwrap();
+        detections.clear();
+        self.detect_region(frame, (0, 0, size.width, size.height), &mut detections)?;
+
+        // Pyramid mode: the full-frame pass catches large objects, the tiles
+        // see small distant ones at a higher effective resolution
+        if let Some(tiling) = self.tiling {
+            for tile in tiles(
+                size.width,
+                size.height,
+                tiling.cols,
+                tiling.rows,
+                tiling.overlap,
+            ) {
+                self.detect_region(frame, tile, &mut detections)?;
+            }
+            let parallel = detections.len() >= self.parallelism.min_boxes;
+            nms(&mut detections, NMS_IOU_THRESHOLD, parallel);
+        }
+
+        out.clear();
+        out.extend(detections.iter().map(|&(b, score, class)| {
+            let rect = core::Rect::new(
+                b.x.round() as i32,
+                b.y.round() as i32,
+                b.w.round() as i32,
+                b.h.round() as i32,
+            );
+            (rect, score, class)
+        }));
+        if let Some(zones) = &self.zones {
+            zones.filter(out, size.width, size.height, |c| self.label(c));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a YOLOv8 head, `[1, 4 + classes, anchors]` (or transposed),
+/// appending every anchor whose best class scores at least `confidence`.
+/// With `classes`, only those class ids compete for an anchor. Boxes are
+/// `cx, cy, w, h` in model-input pixels. Heads with at least
+/// `parallel_min_anchors` anchors are scanned in chunks on the rayon pool;
+/// the output is the same either way.
+pub fn decode_output(
+    shape: &[i64],
+    data: &[f32],
+    confidence: f32,
+    classes: Option<&[usize]>,
+    parallel_min_anchors: usize,
+    out: &mut Vec<Detection>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let [_, a, b] = shape else {
+        return Err(format!("unexpected YOLO output shape {:?}", shape).into());
+    };
+    let (a, b) = (*a as usize, *b as usize);
+    // There are always far more anchors than channels
+    let (channels, anchors, transposed) = if a <= b { (a, b, false) } else { (b, a, true) };
+    if channels <= 4 || data.len() < channels * anchors {
+        return Err(format!("unexpected YOLO output shape {:?}", shape).into());
+    }
+    let at = |anchor: usize, channel: usize| {
+        if transposed {
+            data[anchor * channels + channel]
+        } else {
+            data[channel * anchors + anchor]
+        }
+    };
+    let decode = |anchor: usize| -> Option<Detection> {
+        let scored = |class: usize| (class, at(anchor, 4 + class));
+        let (class, score) = match classes {
+            Some(classes) => classes
+                .iter()
+                .filter(|&&c| c < channels - 4)
+                .map(|&c| scored(c))
+                .max_by(|x, y| x.1.total_cmp(&y.1))?,
+            None => (0..channels - 4)
+                .map(scored)
+                .max_by(|x, y| x.1.total_cmp(&y.1))?,
+        };
+        if score < confidence {
+            return None;
+        }
+        let (cx, cy, w, h) = (at(anchor, 0), at(anchor, 1), at(anchor, 2), at(anchor, 3));
+        Some((
+            BoxF {
+                x: cx - w / 2.0,
+                y: cy - h / 2.0,
+                w,
+                h,
+            },
+            score,
+            class as i64,
+        ))
+    };
+    if anchors >= parallel_min_anchors {
+        // Indexed, so `collect` keeps anchor order
+        let decoded: Vec<Detection> = (0..anchors)
+            .into_par_iter()
+            .with_min_len(DECODE_CHUNK)
+            .filter_map(decode)
+            .collect();
+        out.extend(decoded);
+    } else {
+        out.extend((0..anchors).filter_map(decode));
+    }
+    Ok(())
+}
+
+/// `thresholds` with `YOLO_CONFIDENCE` / `YOLO_IOU` applied.
+pub fn thresholds_with_env(mut thresholds: Thresholds) -> Thresholds {
+    let parse = |name: &str| {
+        let value = std::env::var(name).ok()?;
+        match value.trim().parse::<f32>() {
+            Ok(v) if (0.0..=1.0).contains(&v) => Some(v),
+            _ => {
+                println!("[WARN] Ignoring invalid {}='{}'", name, value);
+                None
+            }
+        }
+    };
+    if let Some(v) = parse("YOLO_CONFIDENCE") {
+        thresholds.confidence = v;
+    }
+    if let Some(v) = parse("YOLO_IOU") {
+        thresholds.iou = v;
+    }
+    thresholds
+}
+
+/// `YOLO_PARALLEL_MIN_ANCHORS` / `YOLO_PARALLEL_MIN_BOXES` override where
+/// postprocessing goes parallel, e.g. with figures from `bench-postprocess`;
+/// `RAYON_NUM_THREADS` caps the pool.
+pub fn parallelism_from_env() -> Parallelism {
+    let mut parallelism = Parallelism::default();
+    let parse = |name: &str| {
+        let value = std::env::var(name).ok()?;
+        match value.trim().parse::<usize>() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                println!("[WARN] Ignoring invalid {}='{}'", name, value);
+                None
+            }
+        }
+    };
+    if let Some(v) = parse("YOLO_PARALLEL_MIN_ANCHORS") {
+        parallelism.min_anchors = v;
+    }
+    if let Some(v) = parse("YOLO_PARALLEL_MIN_BOXES") {
+        parallelism.min_boxes = v;
+    }
+    parallelism
+}
+
+/// The detector, droppable to give its memory and threads back while the
+/// robot idles and rebuilt (tiling, zones and class prompt included) on wake.
+pub struct ModelSlot {
+    config: ModelConfig,
+    options: SessionOptions,
+    zones: Arc<ZoneStore>,
+    model: Mutex<Option<YoloModel>>,
+    /// Set by the adaptive threshold; kept across unload/reload.
+    confidence: Mutex<Option<f32>>,
+    /// The operator's class prompt; `None` scores the whole vocabulary.
+    classes: Mutex<Option<Vec<String>>>,
+}
+
+impl ModelSlot {
+    /// Loads right away; a model that fails to load is logged and the slot
+    /// left empty.
+    pub fn load(config: ModelConfig, options: SessionOptions, zones: Arc<ZoneStore>) -> Self {
+        let slot = Self {
+            config,
+            options,
+            zones,
+            model: Mutex::new(None),
+            confidence: Mutex::new(None),
+            classes: Mutex::new(
+                std::fs::read_to_string(CLASSES_PATH)
+                    .ok()
+                    .and_then(|text| serde_json::from_str(&text).ok())
+                    .flatten(),
+            ),
+        };
+        if let Err(e) = slot.reload() {
+            println!("[WARN] YOLO model unavailable: {}", e);
+        }
+        slot
+    }
+
+    pub fn loaded(&self) -> bool {
+        self.model.lock().unwrap().is_some()
+    }
+
+    /// Builds the session if it is not loaded; takes seconds on the Pi.
+    pub fn reload(&self) -> Result<(), String> {
+        let mut model = self.model.lock().unwrap();
+        if model.is_some() {
+            return Ok(());
+        }
+        let mut loaded = YoloModel::new(&self.config, &self.options).map_err(|e| e.to_string())?;
+        loaded.set_tiling(self.config.tiles);
+        loaded.set_thresholds(self.thresholds());
+        loaded.set_zones(self.zones.clone());
+        if let Some(classes) = self.classes.lock().unwrap().as_deref() {
+            match loaded.class_ids(classes) {
+                Ok(ids) => loaded.set_class_filter(Some(ids)),
+                Err(unknown) => println!(
+                    "[WARN] Model has no class {}; detecting its full vocabulary",
+                    unknown.join(", ")
+                ),
+            }
+        }
+        *model = Some(loaded);
+        Ok(())
+    }
+
+    pub fn unload(&self) {
+        self.model.lock().unwrap().take();
+    }
+
+    /// Runs one frame through the loaded model; `Ok(None)` while unloaded.
+    pub fn detect(
+        &self,
+        frame: &Mat,
+        out: &mut Vec<(core::Rect, f32, i64)>,
+    ) -> Result<Option<Vec<DetectedObject>>, String> {
+        let model = self.model.lock().unwrap();
+        let Some(model) = model.as_ref() else {
+            return Ok(None);
+        };
+        model.predict_into(frame, out).map_err(|e| e.to_string())?;
+        Ok(Some(
+            out.iter()
+                .map(|(rect, confidence, class)| DetectedObject {
+                    class: model.label(*class).to_string(),
+                    display_name: None,
+                    confidence: *confidence,
+                    bbox: [rect.x, rect.y, rect.width, rect.height],
+                    track_id: None,
+                })
+                .collect(),
+        ))
+    }
+
+    /// The configured thresholds, without the override.
+    pub fn base_thresholds(&self) -> Thresholds {
+        self.config.thresholds()
+    }
+
+    /// The configured thresholds, with the confidence override if any.
+    pub fn thresholds(&self) -> Thresholds {
+        let mut thresholds = self.base_thresholds();
+        if let Some(confidence) = *self.confidence.lock().unwrap() {
+            thresholds.confidence = confidence;
+        }
+        thresholds
+    }
+
+    /// Overrides the decoding confidence, or with `None` goes back to
+    /// the configured one.
+    pub fn set_confidence(&self, confidence: Option<f32>) {
+        *self.confidence.lock().unwrap() = confidence;
+        let thresholds = self.thresholds();
+        if let Some(model) = self.model.lock().unwrap().as_mut() {
+            model.set_thresholds(thresholds);
+        }
+    }
+
+    pub fn classes(&self) -> Option<Vec<String>> {
+        self.classes.lock().unwrap().clone()
+    }
+
+    pub fn prompt(&self) -> DetectionClasses {
+        DetectionClasses {
+            classes: self.classes(),
+            vocabulary: self
+                .model
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|m| m.labels().iter().map(|l| l.to_string()).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Trims and de-duplicates a prompt and spells it as the model does.
+    /// The text embeddings are baked in at export, so names outside the
+    /// model's vocabulary are an error; while unloaded the names are taken
+    /// as given and checked on reload.
+    pub fn resolve_classes(&self, classes: &[String]) -> Result<Vec<String>, String> {
+        let mut names: Vec<String> = Vec::new();
+        for name in classes.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name.to_string());
+            }
+        }
+        if names.is_empty() {
+            return Err("no classes given".to_string());
+        }
+        let model = self.model.lock().unwrap();
+        let Some(model) = model.as_ref() else {
+            return Ok(names);
+        };
+        let ids = model.class_ids(&names).map_err(|unknown| {
+            format!(
+                "not in the model's vocabulary: {} (re-export the model with them)",
+                unknown.join(", ")
+            )
+        })?;
+        Ok(ids
+            .into_iter()
+            .map(|id| model.labels()[id].to_string())
+            .collect())
+    }
+
+    /// Saves the prompt and restricts detection to it from the next pass
+    /// on; `None` goes back to the whole vocabulary. Pass names through
+    /// [`resolve_classes`](Self::resolve_classes) first.
+    pub fn set_classes(&self, classes: Option<Vec<String>>) -> std::io::Result<()> {
+        let path = Path::new(CLASSES_PATH);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&classes)?)?;
+        *self.classes.lock().unwrap() = classes.clone();
+        if let Some(model) = self.model.lock().unwrap().as_mut() {
+            let ids = classes.and_then(|c| model.class_ids(&c).ok());
+            model.set_class_filter(ids);
+        }
+        Ok(())
+    }
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = std::env::var(name).ok()?;
+    let parsed = value.trim().parse().ok();
+    if parsed.is_none() {
+        println!("[WARN] Ignoring invalid {}='{}'", name, value);
+    }
+    parsed
+}
+
+/// `tiling` with `YOLO_TILES` (e.g. `2x2`, turns tiled inference on) and
+/// `YOLO_TILE_OVERLAP` (the fraction shared between neighbouring tiles)
+/// applied.
+pub fn tiling_with_env(mut tiling: Option<TileConfig>) -> Option<TileConfig> {
+    if let Ok(value) = std::env::var("YOLO_TILES") {
+        match value
+            .split_once('x')
+            .and_then(|(c, r)| Some((c.trim().parse().ok()?, r.trim().parse().ok()?)))
+        {
+            Some((cols, rows)) => {
+                tiling = Some(TileConfig {
+                    cols,
+                    rows,
+                    overlap: tiling.map_or(DEFAULT_TILE_OVERLAP, |t| t.overlap),
+                })
+            }
+            None => println!("[WARN] Ignoring invalid YOLO_TILES '{}'", value),
+        }
+    }
+    if let (Some(tiling), Some(overlap)) = (tiling.as_mut(), env_var("YOLO_TILE_OVERLAP")) {
+        tiling.overlap = overlap;
+    }
+    tiling
+}
+
+/// `defaults` with `YOLO_INTRA_THREADS`, `YOLO_INTER_THREADS`,
+/// `YOLO_PARALLEL`, `YOLO_MEMORY_ARENA` and `YOLO_MEMORY_PATTERN` applied;
+/// invalid values are ignored.
+pub fn session_options_with_env(defaults: SessionOptions) -> SessionOptions {
+    SessionOptions {
+        intra_threads: env_var("YOLO_INTRA_THREADS").unwrap_or(defaults.intra_threads),
+        inter_threads: env_var("YOLO_INTER_THREADS").unwrap_or(defaults.inter_threads),
+        parallel_execution: env_var("YOLO_PARALLEL").unwrap_or(defaults.parallel_execution),
+        memory_arena: env_var("YOLO_MEMORY_ARENA").unwrap_or(defaults.memory_arena),
+        memory_pattern: env_var("YOLO_MEMORY_PATTERN").unwrap_or(defaults.memory_pattern),
+    }
+}
+
+pub fn routes(info: InferenceSessionInfo) -> Router {
+    Router::new()
+        .route("/inference/session", get(get_session_info))
+        .with_state(info)
+}
+
+async fn get_session_info(State(info): State<InferenceSessionInfo>) -> Json<InferenceSessionInfo> {
+    Json(info)
+}
+
+/// The open-vocabulary class prompt: `POST` a list of names to detect only
+/// those, `DELETE` to detect everything the model knows again.
+pub fn class_routes(model: Arc<ModelSlot>) -> Router {
+    Router::new()
+        .route(
+            "/detect/classes",
+            get(get_classes).post(set_classes).delete(reset_classes),
+        )
+        .with_state(model)
+}
+
+async fn get_classes(State(model): State<Arc<ModelSlot>>) -> Json<DetectionClasses> {
+    Json(model.prompt())
+}
+
+async fn set_classes(
+    State(model): State<Arc<ModelSlot>>,
+    Json(prompt): Json<ClassPrompt>,
+) -> Result<Json<DetectionClasses>, (StatusCode, String)> {
+    let classes = model
+        .resolve_classes(&prompt.classes)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    println!("[INFO] Detection classes set to {}", classes.join(", "));
+    model
+        .set_classes(Some(classes))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(model.prompt()))
+}
+
+async fn reset_classes(
+    State(model): State<Arc<ModelSlot>>,
+) -> Result<Json<DetectionClasses>, (StatusCode, String)> {
+    model
+        .set_classes(None)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!("[INFO] Detection classes reset to the full vocabulary");
+    Ok(Json(model.prompt()))
+}
+
+/// Parses Ultralytics' `names` metadata, a Python dict literal such as
+/// `{0: 'person', 1: "o'clock sign"}`, into names indexed by class id; ids
+/// the dict skips are named `class_<id>`.
+fn parse_names(value: &str) -> Vec<String> {
+    let mut names = BTreeMap::new();
+    let mut rest = value.trim().trim_start_matches('{');
+    while let Some((key, after)) = rest.split_once(':') {
+        let Ok(id) = key.trim().trim_start_matches(',').trim().parse::<usize>() else {
+            break;
+        };
+        let after = after.trim_start();
+        let Some(quote) = after.chars().next().filter(|c| *c == '\'' || *c == '"') else {
+            break;
+        };
+        let Some(end) = after[1..].find(quote) else {
+            break;
+        };
+        names.insert(id, after[1..1 + end].to_string());
+        rest = &after[end + 2..];
+    }
+    let count = names.last_key_value().map_or(0, |(id, _)| id + 1);
+    (0..count)
+        .map(|id| names.remove(&id).unwrap_or_else(|| format!("class_{}", id)))
+        .collect()
+}
+
+/// `imgsz` is stored as `[h, w]`; the model input is square, so take the larger.
+fn parse_imgsz(value: &str) -> Option<i32> {
+    value
+        .trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|v| v.trim().parse::<i32>().ok())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .max()
+}
+
+fn load_labels(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .map(|text| {
+            text.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_in_id_order() {
+        assert_eq!(
+            parse_names(r#"{0: 'person', 1: "o'clock sign", 2: 'car'}"#),
+            ["person", "o'clock sign", "car"]
+        );
+    }
+
+    #[test]
+    fn keeps_sparse_ids_in_place() {
+        assert_eq!(parse_names("{2: 'c', 0: 'a'}"), ["a", "class_1", "c"]);
     }
 }
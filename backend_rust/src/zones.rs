@@ -0,0 +1,166 @@
+//! Image zones with per-zone detection rules, e.g. a stricter threshold
+//! near the scoring area. Persisted in `data/zones.json`.
+//!
+//! With a lens model in the unit calibration, boxes are undistorted before
+//! the zone test, so polygons are drawn in rectified image coordinates.
+
+use crate::units::UnitStore;
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use opencv::core::Rect;
+use raspibot_protocol::units::CameraOptics;
+use raspibot_protocol::zones::{Zone, ZoneConfig};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub const ZONES_PATH: &str = "data/zones.json";
+
+pub struct ZoneStore {
+    path: PathBuf,
+    config: Mutex<ZoneConfig>,
+    units: Arc<UnitStore>,
+}
+
+impl ZoneStore {
+    pub fn load(units: Arc<UnitStore>) -> Self {
+        let path = PathBuf::from(ZONES_PATH);
+        let config = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            config: Mutex::new(config),
+            units,
+        }
+    }
+
+    /// Calibration used to undistort boxes.
+    pub fn units(&self) -> &Arc<UnitStore> {
+        &self.units
+    }
+
+    pub fn get(&self) -> ZoneConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, config: ZoneConfig) -> std::io::Result<()> {
+        let mut current = self.config.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&config)?)?;
+        *current = config;
+        Ok(())
+    }
+
+    /// Drops detections that fail the rules of the zone their box center
+    /// falls in (or the default threshold outside every zone).
+    pub fn filter(
+        &self,
+        detections: &mut Vec<(Rect, f32, i64)>,
+        frame_w: i32,
+        frame_h: i32,
+        label: impl Fn(i64) -> Arc<str>,
+    ) {
+        let optics = self.units.camera();
+        let config = self.config.lock().unwrap();
+        detections.retain(|(rect, score, class)| {
+            let center = box_center(
+                &optics,
+                [rect.x, rect.y, rect.width, rect.height],
+                frame_w,
+                frame_h,
+            );
+            let zone = config.zones.iter().find(|z| contains(&z.polygon, center));
+            let threshold = zone
+                .and_then(|z| z.min_confidence)
+                .unwrap_or(config.default_min_confidence);
+            if *score < threshold {
+                return false;
+            }
+            match zone.and_then(|z| z.classes.as_ref()) {
+                Some(classes) => {
+                    let name = label(*class);
+                    classes.iter().any(|c| **c == *name)
+                }
+                None => true,
+            }
+        });
+    }
+
+    /// Name of the zone the center of `bbox` (`[x, y, w, h]` in frame
+    /// pixels) falls in, matched in order as the filter does.
+    pub fn zone_of(&self, bbox: [i32; 4], frame_w: i32, frame_h: i32) -> Option<String> {
+        let center = box_center(&self.units.camera(), bbox, frame_w, frame_h);
+        let config = self.config.lock().unwrap();
+        config
+            .zones
+            .iter()
+            .find(|z| contains(&z.polygon, center))
+            .map(|z| z.name.clone())
+    }
+}
+
+/// Normalized center of a frame-pixel box, undistorted first.
+fn box_center(optics: &CameraOptics, bbox: [i32; 4], frame_w: i32, frame_h: i32) -> [f32; 2] {
+    let [x, y, w, h] = optics.undistort_box(bbox.map(|v| v as f32), frame_w, frame_h);
+    [
+        (x + w / 2.0) / frame_w.max(1) as f32,
+        (y + h / 2.0) / frame_h.max(1) as f32,
+    ]
+}
+
+/// Even-odd point-in-polygon test.
+fn contains(polygon: &[[f32; 2]], point: [f32; 2]) -> bool {
+    let [px, py] = point;
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &[xi, yi]) in polygon.iter().enumerate() {
+        let [xj, yj] = polygon[j];
+        if (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+pub fn validate(zone: &Zone) -> Result<(), String> {
+    if zone.polygon.len() < 3 {
+        return Err(format!("zone '{}' needs at least 3 points", zone.name));
+    }
+    if zone
+        .min_confidence
+        .is_some_and(|c| !(0.0..=1.0).contains(&c))
+    {
+        return Err(format!("zone '{}' min_confidence must be 0..=1", zone.name));
+    }
+    Ok(())
+}
+
+pub fn routes(store: Arc<ZoneStore>) -> Router {
+    Router::new()
+        .route("/zones", get(get_zones).put(set_zones))
+        .with_state(store)
+}
+
+async fn get_zones(State(store): State<Arc<ZoneStore>>) -> Json<ZoneConfig> {
+    Json(store.get())
+}
+
+async fn set_zones(
+    State(store): State<Arc<ZoneStore>>,
+    Json(config): Json<ZoneConfig>,
+) -> Result<Json<ZoneConfig>, (StatusCode, String)> {
+    for zone in &config.zones {
+        validate(zone).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    store
+        .set(config.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    println!(
+        "[INFO] Detection zones updated ({} zones)",
+        config.zones.len()
+    );
+    Ok(Json(config))
+}